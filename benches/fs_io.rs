@@ -0,0 +1,149 @@
+//! Throughput of object reads, writes and listings of the fs storage
+//!
+//! `cargo bench --bench fs_io [--features rt-tokio,rt-uring,pread]`
+//!
+//! The runtime of file operations is chosen at compile time,
+//! so `async-fs` and `tokio::fs` are compared by running with and without `rt-tokio`.
+//! With `pread`, positional reads are compared with the loop of 4096-byte reads.
+
+use s3_server::dto::{ByteStream, GetObjectRequest, ListObjectsV2Request, PutObjectRequest};
+use s3_server::storages::fs::{FileSystem, FileSystemConfig};
+use s3_server::S3Storage;
+
+use std::time::{Duration, Instant};
+
+use futures::{StreamExt, TryStreamExt};
+use hyper::body::Bytes;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// max number of keys of a listed page
+const LIST_PAGE_SIZE: i64 = 100;
+
+/// runtime of file operations
+#[cfg(not(feature = "rt-tokio"))]
+const RUNTIME: &str = "async-fs";
+
+/// runtime of file operations
+#[cfg(feature = "rt-tokio")]
+const RUNTIME: &str = "tokio";
+
+/// (name, object size, number of objects)
+const WORKLOADS: [(&str, usize, usize); 3] = [
+    ("large", 64 * 1024 * 1024, 8),
+    ("medium", 256 * 1024, 512),
+    ("small", 16 * 1024, 2048),
+];
+
+/// size of the reads of the read loop
+const READ_BUF_SIZE: usize = 4096;
+
+fn throughput(object_size: usize, count: usize, total: Duration) -> f64 {
+    let bytes = (object_size * count) as f64;
+    bytes / total.as_secs_f64() / 1024.0 / 1024.0
+}
+
+/// lists the keys under a prefix page by page, returns the number of keys
+async fn list_all(fs: &FileSystem, prefix: &str) -> usize {
+    let mut count = 0;
+    let mut continuation_token = None;
+    loop {
+        let input = ListObjectsV2Request {
+            bucket: "bench".into(),
+            prefix: Some(prefix.into()),
+            max_keys: Some(LIST_PAGE_SIZE),
+            continuation_token,
+            ..ListObjectsV2Request::default()
+        };
+        let output = fs.list_objects_v2(input).await.unwrap();
+        count += output.contents.map_or(0, |contents| contents.len());
+        continuation_token = output.next_continuation_token;
+        if continuation_token.is_none() {
+            return count;
+        }
+    }
+}
+
+async fn run(name: &str, fs: &FileSystem) {
+    for (workload, object_size, count) in WORKLOADS {
+        let chunk = Bytes::from(vec![b'a'; CHUNK_SIZE.min(object_size)]);
+        let mut put_total = Duration::ZERO;
+        let mut get_total = Duration::ZERO;
+
+        for i in 0..count {
+            let key = format!("{name}-{workload}-{i}");
+            let chunks = vec![chunk.clone(); object_size / chunk.len()];
+            let input = PutObjectRequest {
+                bucket: "bench".into(),
+                key: key.clone(),
+                body: Some(ByteStream::new(futures::stream::iter(chunks).map(Ok))),
+                ..PutObjectRequest::default()
+            };
+            let t0 = Instant::now();
+            let _ = fs.put_object(input).await.unwrap();
+            put_total += t0.elapsed();
+
+            let input = GetObjectRequest {
+                bucket: "bench".into(),
+                key,
+                ..GetObjectRequest::default()
+            };
+            let t0 = Instant::now();
+            let output = fs.get_object(input).await.unwrap();
+            let size = output
+                .body
+                .unwrap()
+                .try_fold(0, |acc, bytes| async move { Ok(acc + bytes.len()) })
+                .await
+                .unwrap();
+            get_total += t0.elapsed();
+            assert_eq!(size, object_size);
+        }
+
+        let t0 = Instant::now();
+        let listed = list_all(fs, &format!("{name}-{workload}-")).await;
+        let list_total = t0.elapsed();
+        assert_eq!(listed, count);
+
+        let put = throughput(object_size, count, put_total);
+        let get = throughput(object_size, count, get_total);
+        let list = listed as f64 / list_total.as_secs_f64();
+        println!("{name:<8} {workload:<8} put  {put:>10.2} MiB/s");
+        println!("{name:<8} {workload:<8} get  {get:>10.2} MiB/s");
+        println!("{name:<8} {workload:<8} list {list:>10.2} keys/s");
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let root = std::env::temp_dir().join("s3-server-bench-fs-io");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("bench")).unwrap();
+
+    let config = || {
+        let mut config = FileSystemConfig::default();
+        config.read_buf_size = READ_BUF_SIZE;
+        config
+    };
+
+    let fs = FileSystem::new_with_config(&root, config()).unwrap();
+    run(RUNTIME, &fs).await;
+
+    #[cfg(all(feature = "rt-uring", target_os = "linux"))]
+    {
+        let mut fs = FileSystem::new(&root).unwrap();
+        match fs.enable_io_uring() {
+            Ok(()) => run("io_uring", &fs).await,
+            Err(e) => println!("io_uring is not available: {e}"),
+        }
+    }
+
+    #[cfg(feature = "pread")]
+    {
+        let mut fs = FileSystem::new_with_config(&root, config()).unwrap();
+        fs.set_pread_threshold(Some(1024 * 1024));
+        run("pread", &fs).await;
+    }
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
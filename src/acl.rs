@@ -0,0 +1,235 @@
+//! Network access control
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::str::FromStr;
+
+/// An IP network, such as `10.0.0.0/8`, `2001:db8::/32` or a single address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpCidr {
+    /// network address
+    addr: IpAddr,
+    /// prefix length
+    prefix_len: u8,
+}
+
+/// An error which can be returned when parsing an [`IpCidr`]
+#[allow(missing_copy_implementations)] // see `ParseS3PathError`
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("invalid CIDR block")]
+pub struct ParseIpCidrError {
+    /// private place holder
+    _priv: (),
+}
+
+impl IpCidr {
+    /// Constructs a network, returns `None` if the prefix length is too large
+    #[must_use]
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Option<Self> {
+        (prefix_len <= max_prefix_len(addr)).then_some(Self { addr, prefix_len })
+    }
+
+    /// Checks whether the network contains the address
+    #[must_use]
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32_u32.wrapping_sub(self.prefix_len.into()));
+                let mask = mask.unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128_u32.wrapping_sub(self.prefix_len.into()));
+                let mask = mask.unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            (IpAddr::V4(_), IpAddr::V6(ip)) => match ip.segments() {
+                // IPv4-mapped address
+                [0, 0, 0, 0, 0, 0xffff, hi, lo] => {
+                    let v4 = Ipv4Addr::from((u32::from(hi) << 16_u32) | u32::from(lo));
+                    self.contains(IpAddr::V4(v4))
+                }
+                _ => false,
+            },
+            (IpAddr::V6(_), IpAddr::V4(_)) => false,
+        }
+    }
+}
+
+/// returns the max prefix length of the address family
+const fn max_prefix_len(addr: IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = ParseIpCidrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseIpCidrError { _priv: () };
+        let (addr, len) = s.split_once('/').map_or((s, None), |(a, l)| (a, Some(l)));
+        let addr: IpAddr = addr.parse().ok().ok_or_else(err)?;
+        let prefix_len = match len {
+            Some(len) => len.parse::<u8>().ok().ok_or_else(err)?,
+            None => max_prefix_len(addr),
+        };
+        Self::new(addr, prefix_len).ok_or_else(err)
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Allowed and denied networks
+///
+/// An address is denied if it is in any denied network,
+/// or if there are allowed networks and it is in none of them.
+#[derive(Debug, Clone, Default)]
+pub struct IpRules {
+    /// allowed networks
+    allow: Vec<IpCidr>,
+    /// denied networks
+    deny: Vec<IpCidr>,
+}
+
+impl IpRules {
+    /// Constructs empty rules which allow all addresses
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows a network, which turns the rules into an allowlist
+    pub fn allow(&mut self, cidr: IpCidr) {
+        self.allow.push(cidr);
+    }
+
+    /// Denies a network
+    pub fn deny(&mut self, cidr: IpCidr) {
+        self.deny.push(cidr);
+    }
+
+    /// Checks whether the address is allowed
+    ///
+    /// An unknown address is only allowed if there is no allowlist.
+    #[must_use]
+    pub fn is_allowed(&self, ip: Option<IpAddr>) -> bool {
+        match ip {
+            Some(ip) => {
+                let denied = self.deny.iter().any(|cidr| cidr.contains(ip));
+                let allowed =
+                    self.allow.is_empty() || self.allow.iter().any(|cidr| cidr.contains(ip));
+                allowed && !denied
+            }
+            None => self.allow.is_empty(),
+        }
+    }
+}
+
+/// Network access control of the service and its buckets
+///
+/// A request must be allowed by both the global rules and the rules of each bucket it accesses,
+/// including the source bucket of `CopyObject` and the new bucket of `RenameBucket`.
+///
+/// ```
+/// use s3_server::NetworkAcl;
+///
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut acl = NetworkAcl::new();
+/// acl.global_mut().deny("192.0.2.0/24".parse()?);
+/// acl.bucket_mut("internal").allow("10.0.0.0/8".parse()?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct NetworkAcl {
+    /// rules of all requests
+    global: IpRules,
+    /// rules of buckets
+    buckets: HashMap<String, IpRules>,
+}
+
+impl NetworkAcl {
+    /// Constructs an acl which allows all addresses
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the rules of all requests
+    pub fn global_mut(&mut self) -> &mut IpRules {
+        &mut self.global
+    }
+
+    /// Returns the rules of a bucket
+    pub fn bucket_mut(&mut self, bucket: &str) -> &mut IpRules {
+        self.buckets.entry(bucket.to_owned()).or_default()
+    }
+
+    /// Checks whether the address can access the bucket, or the root path if `bucket` is `None`
+    #[must_use]
+    pub fn is_allowed(&self, bucket: Option<&str>, ip: Option<IpAddr>) -> bool {
+        if !self.global.is_allowed(ip) {
+            return false;
+        }
+        match bucket.and_then(|bucket| self.buckets.get(bucket)) {
+            Some(rules) => rules.is_allowed(ip),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cidr() {
+        let net: IpCidr = "10.1.0.0/16".parse().unwrap();
+        assert!(net.contains("10.1.2.3".parse().unwrap()));
+        assert!(!net.contains("10.2.0.1".parse().unwrap()));
+        assert!(net.contains("::ffff:10.1.0.1".parse().unwrap()));
+
+        let net6: IpCidr = "2001:db8::/32".parse().unwrap();
+        assert!(net6.contains("2001:db8:1::1".parse().unwrap()));
+        assert!(!net6.contains("2001:db9::1".parse().unwrap()));
+        assert!(!net6.contains("10.1.2.3".parse().unwrap()));
+
+        let any: IpCidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("203.0.113.1".parse().unwrap()));
+
+        let host: IpCidr = "192.0.2.1".parse().unwrap();
+        assert_eq!(host.to_string(), "192.0.2.1/32");
+
+        for s in ["10.0.0.0/33", "::/129", "10.0.0.0/", "example.com"] {
+            assert!(s.parse::<IpCidr>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn acl() {
+        let mut acl = NetworkAcl::new();
+        acl.global_mut().deny("192.0.2.0/24".parse().unwrap());
+        let internal = acl.bucket_mut("internal");
+        internal.allow("10.0.0.0/8".parse().unwrap());
+        internal.deny("10.0.0.1".parse().unwrap());
+
+        let ip = |s: &str| Some(s.parse::<IpAddr>().unwrap());
+
+        assert!(acl.is_allowed(None, ip("203.0.113.1")));
+        assert!(!acl.is_allowed(None, ip("192.0.2.1")));
+        assert!(!acl.is_allowed(Some("public"), ip("192.0.2.1")));
+        assert!(acl.is_allowed(Some("public"), None));
+
+        assert!(acl.is_allowed(Some("internal"), ip("10.1.2.3")));
+        assert!(!acl.is_allowed(Some("internal"), ip("10.0.0.1")));
+        assert!(!acl.is_allowed(Some("internal"), ip("203.0.113.1")));
+        assert!(!acl.is_allowed(Some("internal"), None));
+    }
+}
@@ -0,0 +1,236 @@
+//! Admin endpoints
+
+use crate::dto::{BucketStatsOutput, BucketStatsRequest, ListBucketsRequest};
+use crate::errors::S3Result;
+use crate::output::S3Output;
+use crate::service::SharedS3Service;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{Body, BoxStdError, Method, Request, Response, StatusCode};
+
+use std::convert::Infallible;
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use hyper::body::Bytes;
+use tracing::{debug, error};
+
+/// content type of the Prometheus text format
+const PROMETHEUS_TEXT: &str = "text/plain; version=0.0.4";
+
+/// Admin endpoints of a [`SharedS3Service`], created by [`SharedS3Service::admin_service`]
+///
+/// + `GET /metrics` returns the stats of all buckets in the Prometheus text format.
+/// + `GET /buckets/{bucket}/stats` returns the stats of a bucket in JSON.
+///
+/// The stats come from [`S3Storage::bucket_stats`].
+/// The endpoints are not authenticated, so they should be served on a private address.
+#[derive(Debug, Clone)]
+pub struct AdminService {
+    /// inner service
+    inner: SharedS3Service,
+}
+
+impl AdminService {
+    /// Constructs the admin endpoints of a service
+    pub(crate) const fn new(inner: SharedS3Service) -> Self {
+        Self { inner }
+    }
+
+    /// serves a request
+    fn serve(&self, req: Request) -> BoxFuture<'static, Result<Response, Infallible>> {
+        let service = self.inner.clone();
+        Box::pin(async move {
+            let storage = service.storage();
+            let ret = match handle(&req, storage).await {
+                Ok(res) => Ok(res),
+                Err(err) => err.into_xml_response().try_into_response(),
+            };
+            match ret {
+                Ok(res) => Ok(res),
+                Err(err) => {
+                    error!(%err, "failed to respond");
+                    Ok(Response::new_with_status(
+                        Body::empty(),
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                    ))
+                }
+            }
+        })
+    }
+}
+
+impl<B> hyper::service::Service<http::Request<B>> for AdminService
+where
+    B: http_body::Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxStdError>,
+{
+    type Response = Response;
+
+    type Error = Infallible;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn call(&self, req: http::Request<B>) -> Self::Future {
+        self.serve(req.map(Body::new))
+    }
+}
+
+impl<B> tower_service::Service<http::Request<B>> for AdminService
+where
+    B: http_body::Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxStdError>,
+{
+    type Response = Response;
+
+    type Error = Infallible;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        self.serve(req.map(Body::new))
+    }
+}
+
+/// routes an admin request
+async fn handle(req: &Request, storage: &(dyn S3Storage + Send + Sync)) -> S3Result<Response> {
+    if req.method() != Method::GET {
+        let res = Response::new_with_status(Body::empty(), StatusCode::METHOD_NOT_ALLOWED);
+        return Ok(res);
+    }
+
+    let path = req.uri().path();
+    if path == "/metrics" {
+        return metrics(storage).await;
+    }
+    if let Some(bucket) = path
+        .strip_prefix("/buckets/")
+        .and_then(|s| s.strip_suffix("/stats"))
+    {
+        let input = BucketStatsRequest {
+            bucket: bucket.into(),
+        };
+        return storage.bucket_stats(input).await.try_into_response();
+    }
+
+    Ok(Response::new_with_status(
+        Body::empty(),
+        StatusCode::NOT_FOUND,
+    ))
+}
+
+impl S3Output for BucketStatsOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        let body = serde_json::json!({
+            "objectCount": self.object_count,
+            "size": self.size,
+        })
+        .to_string();
+        let mut res = Response::new(Body::from(body));
+        res.set_mime(&mime::APPLICATION_JSON)
+            .map_err(|e| internal_error!(e))?;
+        Ok(res)
+    }
+}
+
+/// returns the stats of all buckets in the Prometheus text format
+async fn metrics(storage: &(dyn S3Storage + Send + Sync)) -> S3Result<Response> {
+    let buckets = storage
+        .list_buckets(ListBucketsRequest)
+        .await
+        .map_err(|e| internal_error!(e))?
+        .buckets
+        .unwrap_or_default();
+
+    let mut stats = Vec::with_capacity(buckets.len());
+    for name in buckets.into_iter().filter_map(|b| b.name) {
+        let input = BucketStatsRequest {
+            bucket: name.clone(),
+        };
+        match storage.bucket_stats(input).await {
+            Ok(output) => stats.push((name, output)),
+            // deleted after listing, or not supported by the storage
+            Err(err) => debug!(bucket = %name, %err, "failed to get bucket stats"),
+        }
+    }
+
+    let body = format_metrics(&stats);
+    let mut res = Response::new(Body::from(body));
+    let content_type = PROMETHEUS_TEXT.parse::<mime::Mime>();
+    res.set_mime(&content_type.map_err(|e| internal_error!(e))?)
+        .map_err(|e| internal_error!(e))?;
+    Ok(res)
+}
+
+/// formats bucket stats as gauges
+fn format_metrics(stats: &[(String, BucketStatsOutput)]) -> String {
+    let mut lines = Vec::new();
+    push_gauge(
+        &mut lines,
+        ("s3_bucket_objects", "Number of objects in the bucket"),
+        stats,
+        |s| s.object_count,
+    );
+    push_gauge(
+        &mut lines,
+        (
+            "s3_bucket_size_bytes",
+            "Total size of objects in the bucket",
+        ),
+        stats,
+        |s| s.size,
+    );
+    lines.concat()
+}
+
+/// pushes the lines of a gauge labeled by buckets
+fn push_gauge(
+    lines: &mut Vec<String>,
+    (name, help): (&str, &str),
+    stats: &[(String, BucketStatsOutput)],
+    value: impl Fn(&BucketStatsOutput) -> u64,
+) {
+    lines.push(format!("# HELP {name} {help}\n"));
+    lines.push(format!("# TYPE {name} gauge\n"));
+    for &(ref bucket, ref output) in stats {
+        let bucket = escape_label_value(bucket);
+        lines.push(format!("{name}{{bucket=\"{bucket}\"}} {}\n", value(output)));
+    }
+}
+
+/// escapes a label value of the Prometheus text format
+fn escape_label_value(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_format() {
+        let stats = [(
+            "asd".to_owned(),
+            BucketStatsOutput {
+                object_count: 2,
+                size: 11,
+            },
+        )];
+        let expected = concat!(
+            "# HELP s3_bucket_objects Number of objects in the bucket\n",
+            "# TYPE s3_bucket_objects gauge\n",
+            "s3_bucket_objects{bucket=\"asd\"} 2\n",
+            "# HELP s3_bucket_size_bytes Total size of objects in the bucket\n",
+            "# TYPE s3_bucket_size_bytes gauge\n",
+            "s3_bucket_size_bytes{bucket=\"asd\"} 11\n",
+        );
+        assert_eq!(format_metrics(&stats), expected);
+        assert_eq!(escape_label_value("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
@@ -1,6 +1,11 @@
 //! S3 Authentication
 
+use crate::data_structures::OrderedQs;
+use crate::dto::Owner;
 use crate::errors::S3AuthError;
+use crate::ops::OperationKind;
+use crate::path::S3Path;
+use crate::{Method, StatusCode};
 
 use std::collections::HashMap;
 
@@ -11,6 +16,268 @@ use async_trait::async_trait;
 pub trait S3Auth {
     /// lookup `secret_access_key` by `access_key_id`
     async fn get_secret_access_key(&self, access_key_id: &str) -> Result<String, S3AuthError>;
+
+    /// Maps the client certificate of a mutual TLS connection to an access key id
+    ///
+    /// It is called for requests which carry a [`ClientCertificate`] and no signature.
+    /// A mapped request is served as if it were signed by the access key,
+    /// while an unmapped request falls back to the anonymous policy.
+    /// Returns `Ok(None)` by default, so that certificates identify nobody.
+    async fn identify_client_cert(
+        &self,
+        _cert: &ClientCertificate,
+    ) -> Result<Option<String>, S3AuthError> {
+        Ok(None)
+    }
+
+    /// Maps the bearer token of `Authorization: Bearer <token>` to an access key id
+    ///
+    /// A mapped request is served as if it were signed by the access key,
+    /// while an unmapped request is denied.
+    /// Returns `Ok(None)` by default. See [`JwtAuth`](crate::jwt::JwtAuth) with the feature `jwt`.
+    async fn identify_bearer_token(&self, _token: &str) -> Result<Option<String>, S3AuthError> {
+        Ok(None)
+    }
+
+    /// Authorizes an operation of an identified access key, after the request is routed
+    ///
+    /// `resources` lists what the operation reads or writes, such as both objects of `CopyObject`.
+    /// A denied request fails with `AccessDenied`. Anonymous requests are not authorized here.
+    /// Returns `Ok(true)` by default, so that an access key can access everything.
+    /// See [`ScopedAuth`](crate::ScopedAuth).
+    async fn authorize(
+        &self,
+        _access_key_id: &str,
+        _operation: OperationKind,
+        _resources: &[ResourceAccess<'_>],
+    ) -> Result<bool, S3AuthError> {
+        Ok(true)
+    }
+
+    /// Resolves the owner of the objects in a bucket
+    ///
+    /// It is called for `ListObjectsV2` requests with `fetch-owner=true`,
+    /// and the owner is listed with each object. A multi-tenant provider may map the bucket to its tenant.
+    /// Returns `Ok(None)` by default, so that no owner is listed.
+    async fn get_owner(&self, _bucket: &str) -> Result<Option<Owner>, S3AuthError> {
+        Ok(None)
+    }
+
+    /// Records the usage of a finished request, such as for billing or metering
+    ///
+    /// It is called once the response body has been sent or dropped. Does nothing by default.
+    fn record_usage(&self, usage: RequestUsage) {
+        drop(usage);
+    }
+}
+
+/// A resource accessed by an operation, see [`S3Auth::authorize`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ResourceAccess<'a> {
+    /// the bucket, `None` for `ListBuckets`
+    pub bucket: Option<&'a str>,
+    /// the object key or key prefix, `None` for the bucket itself or all of its objects
+    pub key: Option<&'a str>,
+    /// whether `key` is a prefix of the accessed keys, such as the prefix of a listing
+    pub is_prefix: bool,
+    /// whether the resource is modified
+    pub write: bool,
+}
+
+/// Byte counts of a finished request, see [`S3Auth::record_usage`]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RequestUsage {
+    /// access key id of a signed request, `None` for anonymous requests
+    pub access_key_id: Option<String>,
+    /// bucket of the request path
+    pub bucket: Option<String>,
+    /// request method
+    pub method: Method,
+    /// operation of the request, `None` if it is not routed to any operation
+    pub operation: Option<OperationKind>,
+    /// response status
+    pub status: StatusCode,
+    /// size of the request body received by the service
+    pub request_bytes: u64,
+    /// size of the response body sent by the service
+    pub response_bytes: u64,
+}
+
+/// The verified client certificate of a mutual TLS connection, as a request extension
+///
+/// The TLS acceptor verifies the certificate chain and passes the subject names,
+/// for example by [`SharedS3Service::with_client_cert`](crate::SharedS3Service::with_client_cert).
+/// See [`S3Auth::identify_client_cert`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ClientCertificate {
+    /// common name (CN) of the subject
+    pub common_name: Option<String>,
+    /// DNS names and URIs of the subject alternative names (SAN)
+    pub subject_alt_names: Vec<String>,
+}
+
+impl ClientCertificate {
+    /// Constructs a `ClientCertificate`
+    pub fn new(
+        common_name: Option<String>,
+        subject_alt_names: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            common_name,
+            subject_alt_names: subject_alt_names.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Iterates over the subject alternative names and then the common name
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.subject_alt_names
+            .iter()
+            .chain(self.common_name.iter())
+            .map(String::as_str)
+    }
+}
+
+/// How to handle requests without any signature
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AnonymousPolicy {
+    /// deny all anonymous requests
+    #[default]
+    Deny,
+    /// allow anonymous `GET` and `HEAD` requests
+    AllowRead,
+    /// allow all anonymous requests
+    AllowAll,
+}
+
+impl AnonymousPolicy {
+    /// Checks whether an anonymous request with the method is allowed
+    #[must_use]
+    pub fn is_allowed(self, method: &Method) -> bool {
+        match self {
+            Self::Deny => false,
+            Self::AllowRead => *method == Method::GET || *method == Method::HEAD,
+            Self::AllowAll => true,
+        }
+    }
+}
+
+/// How to report the server-computed signing inputs of mismatched signatures
+///
+/// The canonical request and the string to sign can be compared with the signing debug output
+/// of a client SDK, to find the header or query string which breaks the canonicalization.
+/// They reveal the signed headers of requests, so it is a debug option.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SignatureDebug {
+    /// report nothing
+    #[default]
+    Disabled,
+    /// log them at the `INFO` level
+    Log,
+    /// log them and return them base64-encoded in the headers
+    /// `x-s3-server-canonical-request` and `x-s3-server-string-to-sign` of the error response
+    Respond,
+}
+
+/// query strings which anonymous object reads may carry
+const PUBLIC_OBJECT_QUERIES: &[&str] = &["versionId", "partNumber"];
+
+/// query strings which anonymous bucket listings may carry
+const PUBLIC_BUCKET_QUERIES: &[&str] = &[
+    "list-type",
+    "prefix",
+    "delimiter",
+    "marker",
+    "max-keys",
+    "continuation-token",
+    "start-after",
+    "fetch-owner",
+    "encoding-type",
+];
+
+/// Buckets and key prefixes which anonymous requests may read
+///
+/// Anonymous `GET` and `HEAD` requests of objects under a public prefix are allowed,
+/// and so are listings of a bucket which is public as a whole.
+/// Writes and subresources such as `?acl` are still denied.
+#[derive(Debug, Clone, Default)]
+pub struct PublicRead {
+    /// (bucket, key prefix)
+    prefixes: Vec<(String, String)>,
+}
+
+impl PublicRead {
+    /// Constructs an empty `PublicRead`, which makes nothing public
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes all objects of a bucket public
+    pub fn add_bucket(&mut self, bucket: impl Into<String>) {
+        self.add_prefix(bucket, "");
+    }
+
+    /// Makes the objects under a key prefix of a bucket public
+    pub fn add_prefix(&mut self, bucket: impl Into<String>, prefix: impl Into<String>) {
+        self.prefixes.push((bucket.into(), prefix.into()));
+    }
+
+    /// Checks whether an object is public
+    #[must_use]
+    pub fn is_object_public(&self, bucket: &str, key: &str) -> bool {
+        self.prefixes
+            .iter()
+            .any(|&(ref b, ref p)| b == bucket && key.starts_with(p.as_str()))
+    }
+
+    /// Checks whether a bucket is public as a whole
+    #[must_use]
+    pub fn is_bucket_public(&self, bucket: &str) -> bool {
+        self.prefixes
+            .iter()
+            .any(|&(ref b, ref p)| b == bucket && p.is_empty())
+    }
+}
+
+/// Decides whether an anonymous request is allowed
+///
+/// The policy applies to every resource. Otherwise only public reads are allowed.
+pub fn is_anonymous_allowed(
+    policy: AnonymousPolicy,
+    public_read: &PublicRead,
+    method: &Method,
+    path: &S3Path<'_>,
+    qs: Option<&OrderedQs>,
+) -> bool {
+    if policy.is_allowed(method) {
+        return true;
+    }
+    if *method != Method::GET && *method != Method::HEAD {
+        return false;
+    }
+
+    let only_queries = |allowed: &[&str]| {
+        qs.map_or(true, |qs| {
+            qs.as_ref().iter().all(|&(ref name, _)| {
+                allowed.contains(&name.as_str()) || name.starts_with("response-")
+            })
+        })
+    };
+
+    match *path {
+        S3Path::Root => false,
+        S3Path::Bucket { bucket } => {
+            public_read.is_bucket_public(bucket) && only_queries(PUBLIC_BUCKET_QUERIES)
+        }
+        S3Path::Object { bucket, key } => {
+            public_read.is_object_public(bucket, key) && only_queries(PUBLIC_OBJECT_QUERIES)
+        }
+    }
 }
 
 /// A simple authentication provider
@@ -18,6 +285,10 @@ pub trait S3Auth {
 pub struct SimpleAuth {
     /// key map
     map: HashMap<String, String>,
+    /// certificate subject names to access keys
+    client_certs: HashMap<String, String>,
+    /// owner of all buckets
+    owner: Option<Owner>,
 }
 
 impl SimpleAuth {
@@ -26,6 +297,8 @@ impl SimpleAuth {
     pub fn new() -> Self {
         Self {
             map: HashMap::new(),
+            client_certs: HashMap::new(),
+            owner: None,
         }
     }
 
@@ -34,6 +307,19 @@ impl SimpleAuth {
         let _prev = self.map.insert(access_key, secret_key);
     }
 
+    /// register a certificate subject name, either a SAN or the CN, of an access key
+    pub fn register_client_cert(&mut self, subject_name: String, access_key: String) {
+        let _prev = self.client_certs.insert(subject_name, access_key);
+    }
+
+    /// set the owner of all buckets, which is listed by `ListObjectsV2` with `fetch-owner=true`
+    pub fn set_owner(&mut self, id: String, display_name: String) {
+        self.owner = Some(Owner {
+            id: Some(id),
+            display_name: Some(display_name),
+        });
+    }
+
     /// lookup a credential
     #[must_use]
     pub fn lookup(&self, access_key: &str) -> Option<&str> {
@@ -49,4 +335,72 @@ impl S3Auth for SimpleAuth {
             Some(s) => Ok(s.to_owned()),
         }
     }
+
+    async fn identify_client_cert(
+        &self,
+        cert: &ClientCertificate,
+    ) -> Result<Option<String>, S3AuthError> {
+        Ok(cert
+            .names()
+            .find_map(|name| self.client_certs.get(name))
+            .cloned())
+    }
+
+    async fn get_owner(&self, _bucket: &str) -> Result<Option<Owner>, S3AuthError> {
+        Ok(self.owner.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn public_read() {
+        let mut public_read = PublicRead::new();
+        public_read.add_bucket("public");
+        public_read.add_prefix("asd", "pub/");
+
+        let allowed = |method: &Method, path: &S3Path<'_>, query: &str| {
+            let qs = (!query.is_empty()).then(|| OrderedQs::from_query(query).unwrap());
+            is_anonymous_allowed(
+                AnonymousPolicy::Deny,
+                &public_read,
+                method,
+                path,
+                qs.as_ref(),
+            )
+        };
+        let object = |bucket, key| S3Path::Object { bucket, key };
+        let bucket = |bucket| S3Path::Bucket { bucket };
+
+        assert!(allowed(&Method::GET, &object("public", "a"), ""));
+        assert!(allowed(&Method::HEAD, &object("asd", "pub/a"), ""));
+        assert!(allowed(
+            &Method::GET,
+            &object("asd", "pub/a"),
+            "response-content-type=a"
+        ));
+        assert!(allowed(
+            &Method::GET,
+            &bucket("public"),
+            "list-type=2&prefix=a"
+        ));
+
+        assert!(!allowed(&Method::PUT, &object("public", "a"), ""));
+        assert!(!allowed(&Method::DELETE, &object("asd", "pub/a"), ""));
+        assert!(!allowed(&Method::GET, &object("asd", "a"), ""));
+        assert!(!allowed(&Method::GET, &object("public", "a"), "acl"));
+        assert!(!allowed(&Method::GET, &bucket("asd"), ""));
+        assert!(!allowed(&Method::GET, &bucket("public"), "policy"));
+        assert!(!allowed(&Method::GET, &S3Path::Root, ""));
+
+        assert!(is_anonymous_allowed(
+            AnonymousPolicy::AllowAll,
+            &public_read,
+            &Method::PUT,
+            &object("asd", "a"),
+            None,
+        ));
+    }
 }
@@ -12,25 +12,63 @@
 //!         --fs-root <fs-root>           [default: .]
 //!         --host <host>                 [default: localhost]
 //!         --port <port>                 [default: 8014]
+//!         --admin-port <admin-port>
+//!         --delete-concurrency <delete-concurrency>
+//!         --file-body
+//!         --read-buf-size <read-buf-size>
+//!         --write-buf-size <write-buf-size>
+//!         --fsync
+//!         --gzip-uploads <gzip-uploads>    [possible values: record, decode]
+//!         --temp-dir <temp-dir>
+//!         --xattr-metadata
+//!         --etag <etag>    [default: md5]
+//!         --path-prefix <path-prefix>
+//!         --trusted-proxy-hops <trusted-proxy-hops>    [default: 0]
+//!         --proxy-protocol
+//!         --max-requests-per-connection <max-requests-per-connection>
+//!         --scrub-interval <scrub-interval>
+//!         --scrub-replica <scrub-replica>
+//!         --inventory-interval <inventory-interval>
+//!         --inventory-bucket <inventory-bucket>
+//!         --trash-retention <trash-retention>
+//!         --recover-max-age <recover-max-age>
+//!         --max-presigned-expires <max-presigned-expires>
+//!         --signature-debug <signature-debug>    [possible values: log, respond]
+//!         --print-openapi
+//!         --dev
 //!         --access-key <access-key>    
 //!         --secret-key <secret-key>
+//!         --credentials-file <credentials-file>
 //! ```
 
 #![forbid(unsafe_code)]
 
-use s3_server::storages::fs::FileSystem;
-use s3_server::S3Service;
-use s3_server::SimpleAuth;
+use s3_server::dto::ListBucketsRequest;
+use s3_server::secrets::{FileSecretSource, SecretAuth};
+use s3_server::storages::fs::{
+    ContentEncodingPolicy, ETagStrategy, FileSystem, FileSystemConfig, FsyncPolicy, InventoryConfig,
+};
 
-use std::net::TcpListener;
+#[cfg(all(feature = "xattr", unix))]
+use s3_server::storages::fs::MetadataBackend;
+use s3_server::{
+    AdminService, AnonymousPolicy, PublicRead, S3Service, S3Storage, SharedS3Service,
+    SignatureDebug, SimpleAuth,
+};
+
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::time::Duration;
 
-use anyhow::Result;
-use futures::future;
-use hyper::server::Server;
-use hyper::service::make_service_fn;
+use anyhow::{bail, ensure, Context, Result};
+use hyper::server::conn::http1;
+use hyper_util::rt::TokioIo;
 use structopt::StructOpt;
-use tracing::{debug, info};
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, error, info};
+use uuid::Uuid;
 
 #[derive(StructOpt)]
 struct Args {
@@ -43,11 +81,121 @@ struct Args {
     #[structopt(long, default_value = "8014")]
     port: u16,
 
+    /// Serve admin endpoints, such as `/metrics`, on this port of the host
+    #[structopt(long)]
+    admin_port: Option<u16>,
+
+    #[structopt(long)]
+    delete_concurrency: Option<NonZeroUsize>,
+
+    /// Serve whole objects by file-backed bodies
+    #[structopt(long)]
+    file_body: bool,
+
+    /// Size of the chunks read from object files in bytes
+    #[structopt(long)]
+    read_buf_size: Option<usize>,
+
+    /// Size of the buffer coalescing writes to object files in bytes
+    #[structopt(long)]
+    write_buf_size: Option<usize>,
+
+    /// Sync object files to the disk before responding to writes
+    #[structopt(long)]
+    fsync: bool,
+
+    /// Record the encoding of gzip uploads, or store them decompressed
+    #[structopt(long, possible_values = &["record", "decode"])]
+    gzip_uploads: Option<String>,
+
+    /// Store the files of multipart uploads in this directory instead of the root
+    #[structopt(long)]
+    temp_dir: Option<PathBuf>,
+
+    /// Store the metadata of objects in extended attributes of their files
+    #[cfg(all(feature = "xattr", unix))]
+    #[structopt(long)]
+    xattr_metadata: bool,
+
+    /// How the ETags of written objects are computed, `md5` or `blake3`
+    ///
+    /// BLAKE3 ETags are faster for large objects, but tools which compare ETags with MD5 sums
+    /// see every object as modified.
+    #[structopt(long, default_value = "md5")]
+    etag: ETagStrategy,
+
+    #[structopt(long)]
+    path_prefix: Option<String>,
+
+    /// Number of trusted proxies which append X-Forwarded-For or Forwarded headers
+    #[structopt(long, default_value = "0")]
+    trusted_proxy_hops: usize,
+
+    /// Read the client address from a PROXY protocol v1 header of each connection
+    #[structopt(long)]
+    proxy_protocol: bool,
+
+    /// Reject requests beyond this number of in-flight requests of a connection with SlowDown
+    #[structopt(long)]
+    max_requests_per_connection: Option<NonZeroUsize>,
+
+    /// Verify the checksums of all objects every N seconds
+    #[structopt(long)]
+    scrub_interval: Option<u64>,
+
+    /// Restore corrupted objects from a replica root with the same layout
+    #[structopt(long, requires("scrub-interval"))]
+    scrub_replica: Option<PathBuf>,
+
+    /// Write inventory reports of all buckets every N seconds
+    #[structopt(long, requires("inventory-bucket"))]
+    inventory_interval: Option<u64>,
+
+    /// The bucket where inventory reports are written
+    #[structopt(long, requires("inventory-interval"))]
+    inventory_bucket: Option<String>,
+
+    /// Move deleted objects to a trash, where they can be undeleted for N seconds
+    #[structopt(long)]
+    trash_retention: Option<u64>,
+
+    /// Remove multipart uploads and temporary files older than N seconds at startup
+    #[structopt(long)]
+    recover_max_age: Option<u64>,
+
+    /// Allow anonymous reads of a bucket or a key prefix, such as `bucket` or `bucket/prefix/`
+    #[structopt(long)]
+    public_read: Vec<String>,
+
+    /// Reject overwrites and deletes of existing objects in a bucket
+    #[structopt(long)]
+    worm: Vec<String>,
+
+    /// Reject presigned urls which are valid for more than N seconds, at most 7 days
+    #[structopt(long)]
+    max_presigned_expires: Option<u64>,
+
+    /// Log the canonical request of mismatched signatures, or also return it in response headers
+    #[structopt(long, possible_values = &["log", "respond"])]
+    signature_debug: Option<String>,
+
+    /// Print an OpenAPI description of the supported operations in JSON and exit
+    #[structopt(long)]
+    print_openapi: bool,
+
+    /// Accept any credentials and log each request, for local development only
+    #[structopt(long, conflicts_with_all(&["access-key", "secret-key", "credentials-file"]))]
+    dev: bool,
+
     #[structopt(long, requires("secret-key"), display_order = 1000)]
     access_key: Option<String>,
 
     #[structopt(long, requires("access-key"), display_order = 1000)]
     secret_key: Option<String>,
+
+    /// Read credentials from a file of `access_key:secret_key` lines, which is reloaded every minute
+    #[structopt(long, conflicts_with("access-key"), display_order = 1000)]
+    credentials_file: Option<PathBuf>,
 }
 
 pub fn setup_tracing() {
@@ -69,34 +217,315 @@ pub fn setup_tracing() {
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv::dotenv().ok();
-    setup_tracing();
-
     let args: Args = Args::from_args();
 
+    if args.print_openapi {
+        // the operations do not depend on the configuration
+        let service = S3Service::new(FileSystem::new(&args.fs_root)?);
+        println!("{}", service.openapi_json());
+        return Ok(());
+    }
+
+    setup_tracing();
+
     // setup the storage
-    let fs = FileSystem::new(&args.fs_root)?;
+    let mut config = FileSystemConfig::default();
+    if let Some(n) = args.read_buf_size {
+        config.read_buf_size = n;
+    }
+    if let Some(n) = args.write_buf_size {
+        config.write_buf_size = n;
+    }
+    if args.fsync {
+        config.fsync = FsyncPolicy::Always;
+    }
+    match args.gzip_uploads.as_deref() {
+        Some("record") => config.content_encoding = ContentEncodingPolicy::Record,
+        Some("decode") => config.content_encoding = ContentEncodingPolicy::Decode,
+        _ => {}
+    }
+    config.temp_dir = args.temp_dir;
+    config.etag = args.etag;
+    #[cfg(all(feature = "xattr", unix))]
+    if args.xattr_metadata {
+        config.metadata = MetadataBackend::Xattr;
+    }
+    let mut fs = FileSystem::new_with_config(&args.fs_root, config)?;
+    if let Some(n) = args.delete_concurrency {
+        fs.set_delete_concurrency(n);
+    }
+    fs.set_file_body(args.file_body);
+    if let Some(secs) = args.trash_retention {
+        fs.set_trash_retention(Some(Duration::from_secs(secs)));
+    }
     debug!(?fs);
 
+    if let Some(secs) = args.recover_max_age {
+        let report = fs.recover(Duration::from_secs(secs)).await?;
+        info!(
+            removed_uploads = report.removed_uploads,
+            kept_uploads = report.kept_uploads,
+            removed_files = report.removed_files.len(),
+            removed_bytes = report.removed_bytes,
+            "recovery finished",
+        );
+    }
+
+    if let Some(secs) = args.scrub_interval {
+        let fs = FileSystem::new(&args.fs_root)?;
+        let replica = args.scrub_replica.map(FileSystem::new).transpose()?;
+        drop(tokio::spawn(run_scrubber(
+            fs,
+            replica,
+            Duration::from_secs(secs),
+        )));
+    }
+
+    if let (Some(secs), Some(bucket)) = (args.inventory_interval, args.inventory_bucket) {
+        let fs = FileSystem::new(&args.fs_root)?;
+        drop(tokio::spawn(run_inventory(
+            fs,
+            bucket,
+            Duration::from_secs(secs),
+        )));
+    }
+
+    if let Some(secs) = args.trash_retention {
+        let mut fs = FileSystem::new(&args.fs_root)?;
+        fs.set_trash_retention(Some(Duration::from_secs(secs)));
+        drop(tokio::spawn(run_trash_purger(fs)));
+    }
+
     // setup the service
-    let mut service = S3Service::new(fs);
+    let mut service = if args.dev {
+        S3Service::insecure_dev(fs)
+    } else {
+        S3Service::new(fs)
+    };
+
+    if let Some(prefix) = args.path_prefix {
+        service.set_path_prefix(prefix);
+    }
+    service.set_trusted_proxy_hops(args.trusted_proxy_hops);
+    if let Some(n) = args.max_requests_per_connection {
+        service.set_max_requests_per_connection(n);
+    }
 
-    if let (Some(access_key), Some(secret_key)) = (args.access_key, args.secret_key) {
+    let has_auth = if let Some(path) = args.credentials_file {
+        let mut auth = SecretAuth::new(FileSecretSource::new(path));
+        auth.set_ttl(Duration::from_secs(60));
+        debug!(?auth);
+        service.set_auth(auth);
+        true
+    } else if let (Some(access_key), Some(secret_key)) = (args.access_key, args.secret_key) {
         let mut auth = SimpleAuth::new();
         auth.register(access_key, secret_key);
         debug!(?auth);
         service.set_auth(auth);
+        true
+    } else {
+        false
+    };
+
+    if has_auth {
+        let mut public_read = PublicRead::new();
+        for s in args.public_read {
+            match s.split_once('/') {
+                Some((bucket, prefix)) => public_read.add_prefix(bucket, prefix),
+                None => public_read.add_bucket(s),
+            }
+        }
+        service.set_public_read(public_read);
+    } else if !args.dev {
+        // no credentials, serve everyone
+        service.set_anonymous_policy(AnonymousPolicy::AllowAll);
     }
 
-    let server = {
-        let service = service.into_shared();
-        let listener = TcpListener::bind((args.host.as_str(), args.port))?;
-        let make_service: _ =
-            make_service_fn(move |_| future::ready(Ok::<_, anyhow::Error>(service.clone())));
-        Server::from_tcp(listener)?.serve(make_service)
-    };
+    service.set_worm_buckets(args.worm);
+
+    if let Some(secs) = args.max_presigned_expires {
+        service.set_max_presigned_expires(Duration::from_secs(secs));
+    }
+
+    match args.signature_debug.as_deref() {
+        Some("log") => service.set_signature_debug(SignatureDebug::Log),
+        Some("respond") => service.set_signature_debug(SignatureDebug::Respond),
+        _ => {}
+    }
+
+    let service = service.into_shared();
+    let listener = TcpListener::bind((args.host.as_str(), args.port)).await?;
+
+    if let Some(port) = args.admin_port {
+        let admin_listener = TcpListener::bind((args.host.as_str(), port)).await?;
+        info!("admin endpoints are at http://{}:{}/", args.host, port);
+        drop(tokio::spawn(serve_admin(
+            admin_listener,
+            service.admin_service(),
+        )));
+    }
 
     info!("server is running at http://{}:{}/", args.host, args.port);
-    server.await?;
+    if args.dev {
+        print_dev_credentials(&args.host, args.port);
+    }
+
+    loop {
+        let (stream, peer_addr) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(err) => {
+                // such as running out of file descriptors
+                error!(%err, "failed to accept connection");
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let service = service.clone();
+        let proxy_protocol = args.proxy_protocol;
+        drop(tokio::spawn(async move {
+            if let Err(err) = serve_connection(stream, peer_addr, service, proxy_protocol).await {
+                debug!(%err, %peer_addr, "connection error");
+            }
+        }));
+    }
+}
+
+/// prints generated credentials as environment variables of aws-cli
+///
+/// Any credentials are accepted in the dev mode, the generated ones are just ready to copy.
+fn print_dev_credentials(host: &str, port: u16) {
+    let id = Uuid::new_v4().simple().to_string().to_uppercase();
+    let secret = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    println!("# the server accepts any credentials, such as");
+    println!("export AWS_ACCESS_KEY_ID=AKDEV{}", &id[..15]);
+    println!("export AWS_SECRET_ACCESS_KEY={}", &secret[..40]);
+    println!("export AWS_DEFAULT_REGION=us-east-1");
+    println!("export AWS_ENDPOINT_URL=http://{host}:{port}");
+}
 
+/// verifies the checksums of all objects periodically
+async fn run_scrubber(fs: FileSystem, replica: Option<FileSystem>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        match fs.scrub(replica.as_ref()).await {
+            Ok(report) => info!(
+                scanned = report.scanned,
+                unverified = report.unverified,
+                corrupted = report.corrupted.len(),
+                healthy = report.is_healthy(),
+                "scrub finished",
+            ),
+            Err(err) => error!(%err, "scrub failed"),
+        }
+    }
+}
+
+/// writes inventory reports of all buckets periodically
+async fn run_inventory(fs: FileSystem, destination: String, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        if let Err(err) = write_inventories(&fs, &destination).await {
+            error!(%err, "inventory failed");
+        }
+    }
+}
+
+/// purges expired objects from the trash hourly
+async fn run_trash_purger(fs: FileSystem) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(3600)).await;
+        match fs.purge_trash().await {
+            Ok(purged) => info!(purged, "trash purged"),
+            Err(err) => error!(%err, "trash purge failed"),
+        }
+    }
+}
+
+/// writes inventory reports of all buckets except the destination
+async fn write_inventories(fs: &FileSystem, destination: &str) -> Result<()> {
+    let config = InventoryConfig::new("inventory", destination);
+    let output = fs.list_buckets(ListBucketsRequest).await?;
+    for bucket in output.buckets.into_iter().flatten() {
+        let name = bucket.name.unwrap_or_default();
+        if name == destination {
+            continue;
+        }
+        let inventory = fs.write_inventory(&name, &config).await?;
+        info!(
+            bucket = %name,
+            manifest = %inventory.manifest_key,
+            objects = inventory.object_count,
+            size = inventory.total_size,
+            "inventory written",
+        );
+    }
     Ok(())
 }
+
+/// serves admin endpoints
+async fn serve_admin(listener: TcpListener, service: AdminService) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                error!(%err, "failed to accept admin connection");
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let service = service.clone();
+        drop(tokio::spawn(async move {
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!(%err, "admin connection error");
+            }
+        }));
+    }
+}
+
+/// serves http requests of a connection
+async fn serve_connection(
+    mut stream: TcpStream,
+    peer_addr: SocketAddr,
+    service: SharedS3Service,
+    proxy_protocol: bool,
+) -> Result<()> {
+    let remote_addr = if proxy_protocol {
+        read_proxy_header(&mut stream).await?.unwrap_or(peer_addr)
+    } else {
+        peer_addr
+    };
+    http1::Builder::new()
+        .serve_connection(TokioIo::new(stream), service.with_remote_addr(remote_addr))
+        .await?;
+    Ok(())
+}
+
+/// reads a PROXY protocol v1 header, returns the source address unless it is unknown
+///
+/// See <https://www.haproxy.org/download/2.8/doc/proxy-protocol.txt>
+async fn read_proxy_header(stream: &mut TcpStream) -> Result<Option<SocketAddr>> {
+    /// max length of a v1 header, including CRLF
+    const MAX_LEN: usize = 107;
+
+    let mut line = Vec::with_capacity(MAX_LEN);
+    while !line.ends_with(b"\r\n") {
+        ensure!(line.len() < MAX_LEN, "PROXY header is too long");
+        line.push(stream.read_u8().await?);
+    }
+    let line = std::str::from_utf8(&line)?.trim_end();
+
+    let mut fields = line.split(' ');
+    ensure!(fields.next() == Some("PROXY"), "invalid PROXY header");
+    match fields.next() {
+        Some("TCP4" | "TCP6") => {}
+        Some("UNKNOWN") => return Ok(None),
+        _ => bail!("invalid PROXY header: {line:?}"),
+    }
+    let src_ip: IpAddr = fields.next().context("missing source address")?.parse()?;
+    let _dst_ip = fields.next();
+    let src_port: u16 = fields.next().context("missing source port")?.parse()?;
+    Ok(Some(SocketAddr::new(src_ip, src_port)))
+}
@@ -0,0 +1,217 @@
+//! http body of requests and responses
+
+use crate::BoxStdError;
+
+use std::any::Any;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Mutex, PoisonError};
+use std::task::{Context, Poll};
+
+use futures::stream::{Stream, TryStreamExt};
+use http_body::{Frame, SizeHint};
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, StreamBody};
+use hyper::body::Bytes;
+
+/// The body of requests and responses of [`S3Service`](crate::S3Service)
+///
+/// It implements [`http_body::Body`], so a response can be served by hyper 1.x
+/// or any framework built on it. A body of another type, such as
+/// [`hyper::body::Incoming`], is converted by [`Body::new`].
+/// It is also a [`Stream`] of data chunks, which skips trailers.
+#[derive(Default)]
+pub struct Body {
+    /// inner body
+    kind: Kind,
+}
+
+/// the kinds of bodies
+#[derive(Default)]
+enum Kind {
+    /// no data
+    #[default]
+    Empty,
+    /// a single chunk, which is taken when it is polled
+    Full(Option<Bytes>),
+    /// any other body, which is only polled by `&mut` so the mutex is never contended
+    ///
+    /// The mutex makes `Body` `Sync` while the inner body is only `Send`, such as a stream
+    /// holding a boxed future, so a `&Body` can be held across an `.await` of a `Send` future.
+    Boxed(Mutex<UnsyncBoxBody<Bytes, BoxStdError>>),
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            Kind::Empty => f.write_str("Body(Empty)"),
+            Kind::Full(ref bytes) => {
+                let len = bytes.as_ref().map_or(0, Bytes::len);
+                write!(f, "Body(Full({len} bytes))")
+            }
+            Kind::Boxed(_) => f.write_str("Body(Streaming)"),
+        }
+    }
+}
+
+impl Body {
+    /// Constructs an empty body
+    #[must_use]
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Wraps another body, such as [`hyper::body::Incoming`]
+    ///
+    /// A [`Body`] is returned as is, without boxing it again.
+    pub fn new<B>(body: B) -> Self
+    where
+        B: http_body::Body<Data = Bytes> + Send + 'static,
+        B::Error: Into<BoxStdError>,
+    {
+        let mut slot = Some(body);
+        let any: &mut dyn Any = &mut slot;
+        if let Some(this) = any.downcast_mut::<Option<Self>>() {
+            return this.take().unwrap_or_default();
+        }
+
+        match slot {
+            Some(inner) => Self {
+                kind: Kind::Boxed(Mutex::new(inner.map_err(Into::into).boxed_unsync())),
+            },
+            None => Self::empty(),
+        }
+    }
+
+    /// Wraps a stream of data chunks
+    pub fn wrap_stream<S, D, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<D, E>> + Send + 'static,
+        D: Into<Bytes> + 'static,
+        E: Into<BoxStdError> + 'static,
+    {
+        let stream = stream.map_ok(|data| Frame::data(data.into()));
+        Self::new(StreamBody::new(stream.map_err(Into::into)))
+    }
+
+    /// Receives the whole body
+    /// # Errors
+    /// Returns an `Err` if the body can not be received
+    pub async fn into_bytes(self) -> Result<Bytes, BoxStdError> {
+        match self.kind {
+            Kind::Empty => Ok(Bytes::new()),
+            Kind::Full(bytes) => Ok(bytes.unwrap_or_default()),
+            Kind::Boxed(body) => {
+                let body = body.into_inner().unwrap_or_else(PoisonError::into_inner);
+                Ok(body.collect().await?.to_bytes())
+            }
+        }
+    }
+}
+
+/// locks a boxed body for a method taking `&self`
+fn lock(
+    body: &Mutex<UnsyncBoxBody<Bytes, BoxStdError>>,
+) -> std::sync::MutexGuard<'_, UnsyncBoxBody<Bytes, BoxStdError>> {
+    body.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl From<Bytes> for Body {
+    fn from(bytes: Bytes) -> Self {
+        Self {
+            kind: Kind::Full(Some(bytes)),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::from(Bytes::from(bytes))
+    }
+}
+
+impl From<String> for Body {
+    fn from(s: String) -> Self {
+        Self::from(Bytes::from(s))
+    }
+}
+
+impl From<&'static str> for Body {
+    fn from(s: &'static str) -> Self {
+        Self::from(Bytes::from_static(s.as_bytes()))
+    }
+}
+
+impl From<&'static [u8]> for Body {
+    fn from(bytes: &'static [u8]) -> Self {
+        Self::from(Bytes::from_static(bytes))
+    }
+}
+
+impl http_body::Body for Body {
+    type Data = Bytes;
+    type Error = BoxStdError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        match self.kind {
+            Kind::Empty => Poll::Ready(None),
+            Kind::Full(ref mut bytes) => Poll::Ready(bytes.take().map(|b| Ok(Frame::data(b)))),
+            Kind::Boxed(ref mut body) => {
+                let body = body.get_mut().unwrap_or_else(PoisonError::into_inner);
+                Pin::new(body).poll_frame(cx)
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        match self.kind {
+            Kind::Empty => true,
+            Kind::Full(ref bytes) => bytes.is_none(),
+            Kind::Boxed(ref body) => lock(body).is_end_stream(),
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match self.kind {
+            Kind::Empty => SizeHint::with_exact(0),
+            Kind::Full(ref bytes) => {
+                let len = bytes.as_ref().map_or(0, Bytes::len);
+                SizeHint::with_exact(u64::try_from(len).unwrap_or(u64::MAX))
+            }
+            Kind::Boxed(ref body) => lock(body).size_hint(),
+        }
+    }
+}
+
+impl Stream for Body {
+    type Item = Result<Bytes, BoxStdError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let frame = match http_body::Body::poll_frame(self.as_mut(), cx) {
+                Poll::Ready(Some(Ok(frame))) => frame,
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+            // trailers are skipped
+            if let Ok(data) = frame.into_data() {
+                return Poll::Ready(Some(Ok(data)));
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.kind {
+            Kind::Empty => (0, Some(0)),
+            Kind::Full(ref bytes) => {
+                let n = usize::from(bytes.is_some());
+                (n, Some(n))
+            }
+            Kind::Boxed(_) => (0, None),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Time source
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A source of the current time
+///
+/// The service reads the time from a clock when it checks request dates and presigned urls.
+pub trait Clock {
+    /// Returns the current time
+    fn now(&self) -> SystemTime;
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> SystemTime {
+        C::now(self)
+    }
+}
+
+/// The system clock
+#[derive(Debug, Clone, Copy, Default)]
+#[allow(clippy::exhaustive_structs)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock which only moves when it is told to, with second resolution
+///
+/// Share it by `Arc<ManualClock>` to freeze or advance the time in tests.
+#[derive(Debug)]
+pub struct ManualClock {
+    /// seconds since unix epoch
+    secs: AtomicU64,
+}
+
+impl ManualClock {
+    /// Constructs a clock frozen at `time`
+    #[must_use]
+    pub fn new(time: SystemTime) -> Self {
+        Self {
+            secs: AtomicU64::new(to_secs(time)),
+        }
+    }
+
+    /// Sets the current time
+    pub fn set(&self, time: SystemTime) {
+        self.secs.store(to_secs(time), Ordering::SeqCst);
+    }
+
+    /// Moves the current time forward
+    pub fn advance(&self, duration: Duration) {
+        let _prev = self.secs.fetch_add(duration.as_secs(), Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        let secs = self.secs.load(Ordering::SeqCst);
+        UNIX_EPOCH
+            .checked_add(Duration::from_secs(secs))
+            .unwrap_or_else(|| panic!("time overflow: secs = {secs}"))
+    }
+}
+
+/// converts `SystemTime` to seconds since unix epoch, saturating at the epoch
+fn to_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_clock() {
+        let t0 = UNIX_EPOCH + Duration::from_secs(1_369_353_600);
+        let clock = Arc::new(ManualClock::new(t0));
+        assert_eq!(clock.now(), t0);
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(Clock::now(&clock), t0 + Duration::from_secs(60));
+
+        clock.set(t0);
+        assert_eq!(clock.now(), t0);
+    }
+}
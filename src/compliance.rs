@@ -0,0 +1,489 @@
+//! Semantic checks against any [`S3Storage`] implementation
+//!
+//! This module is enabled by the feature `compliance`.
+//!
+//! [`run`] creates buckets named `compliance-*` in the storage,
+//! calls the storage directly and reports which checks hold.
+//! It should be run against an empty storage.
+//! The multipart checks upload small parts, so the min part size should be disabled.
+//!
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use s3_server::storages::fs::{FileSystem, FileSystemConfig};
+//!
+//! let mut config = FileSystemConfig::default();
+//! config.limits.min_part_size = 0;
+//! let storage = FileSystem::new_with_config("target/s3-compliance", config)?;
+//! let report = s3_server::compliance::run(&storage).await;
+//! println!("{report}");
+//! assert!(report.is_success());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::dto::{
+    ByteStream, CompleteMultipartUploadRequest, CompletedMultipartUpload, CompletedPart,
+    CreateBucketRequest, CreateMultipartUploadRequest, DeleteObjectRequest, GetObjectRequest,
+    HeadBucketRequest, HeadObjectRequest, ListObjectsRequest, ListObjectsV2Request,
+    PutObjectRequest, UploadPartRequest,
+};
+use crate::errors::{S3Error, S3StorageError, S3StorageResult};
+use crate::storage::S3Storage;
+
+use std::fmt::{self, Display};
+
+use futures::stream::TryStreamExt;
+
+/// The outcome of a check
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct CheckResult {
+    /// name of the check
+    pub name: &'static str,
+    /// `Err` contains the reason of the failure
+    pub outcome: Result<(), String>,
+}
+
+/// The outcomes of all checks
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Report {
+    /// outcomes in the order of execution
+    pub checks: Vec<CheckResult>,
+}
+
+impl Report {
+    /// Returns `true` if all checks passed
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.checks.iter().all(|c| c.outcome.is_ok())
+    }
+
+    /// Returns the failed checks
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> + '_ {
+        self.checks.iter().filter(|c| c.outcome.is_err())
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            match check.outcome {
+                Ok(()) => writeln!(f, "PASS {}", check.name)?,
+                Err(ref reason) => writeln!(f, "FAIL {}: {}", check.name, reason)?,
+            }
+        }
+        let passed = self.checks.iter().filter(|c| c.outcome.is_ok()).count();
+        write!(f, "{}/{} checks passed", passed, self.checks.len())
+    }
+}
+
+/// Runs all checks against the storage
+pub async fn run(storage: &(dyn S3Storage + Send + Sync)) -> Report {
+    /// helper macro
+    macro_rules! checks {
+        [$($name:ident,)+] => {
+            vec![$(CheckResult {
+                name: stringify!($name),
+                outcome: $name(storage, concat!("compliance-", stringify!($name))).await,
+            },)+]
+        }
+    }
+
+    let checks = checks![
+        bucket_errors,
+        object_errors,
+        etag_format,
+        list_ordering,
+        multipart_rules,
+    ];
+    Report { checks }
+}
+
+/// the result of a check
+type CheckOutcome = Result<(), String>;
+
+/// fails the check if the condition does not hold
+macro_rules! ensure {
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            return Err(format!($($arg)+));
+        }
+    };
+}
+
+/// unwraps a storage result or fails the check
+macro_rules! require {
+    ($ret:expr, $op:literal) => {
+        match $ret {
+            Ok(output) => output,
+            Err(err) => return Err(format!("{} failed: {}", $op, error_code(err))),
+        }
+    };
+}
+
+/// returns the error code of a storage error
+fn error_code<E: Into<S3Error>>(err: S3StorageError<E>) -> &'static str {
+    let err: S3Error = match err {
+        S3StorageError::Operation(e) => e.into(),
+        S3StorageError::Other(e) => e,
+    };
+    err.code().as_static_str()
+}
+
+/// checks that an operation fails with the error code
+fn expect_error<T, E: Into<S3Error>>(
+    ret: S3StorageResult<T, E>,
+    op: &str,
+    code: &str,
+) -> CheckOutcome {
+    match ret {
+        Ok(_) => Err(format!("{op} succeeded, expected {code}")),
+        Err(err) => {
+            let actual = error_code(err);
+            ensure!(actual == code, "{op} failed with {actual}, expected {code}");
+            Ok(())
+        }
+    }
+}
+
+/// creates a bucket
+async fn create_bucket(storage: &(dyn S3Storage + Send + Sync), bucket: &str) -> CheckOutcome {
+    let input = CreateBucketRequest {
+        bucket: bucket.into(),
+        ..CreateBucketRequest::default()
+    };
+    let _output = require!(storage.create_bucket(input).await, "CreateBucket");
+    Ok(())
+}
+
+/// puts an object and returns its `ETag`
+async fn put_object(
+    storage: &(dyn S3Storage + Send + Sync),
+    bucket: &str,
+    key: &str,
+    content: &'static [u8],
+) -> Result<Option<String>, String> {
+    let input = PutObjectRequest {
+        bucket: bucket.into(),
+        key: key.into(),
+        body: Some(ByteStream::from(content.to_vec())),
+        content_length: i64::try_from(content.len()).ok(),
+        ..PutObjectRequest::default()
+    };
+    let output = require!(storage.put_object(input).await, "PutObject");
+    Ok(output.e_tag)
+}
+
+/// gets the content of an object
+async fn get_content(
+    storage: &(dyn S3Storage + Send + Sync),
+    bucket: &str,
+    key: &str,
+) -> Result<Vec<u8>, String> {
+    let input = GetObjectRequest {
+        bucket: bucket.into(),
+        key: key.into(),
+        ..GetObjectRequest::default()
+    };
+    let output = require!(storage.get_object(input).await, "GetObject");
+    let body = output.body.ok_or("GetObject returned no body")?;
+    body.map_ok(|bytes| bytes.to_vec())
+        .try_concat()
+        .await
+        .map_err(|err| format!("failed to read the body of GetObject: {err}"))
+}
+
+/// checks whether an `ETag` is a quoted md5 digest, optionally with a part count
+fn is_valid_etag(e_tag: &str) -> bool {
+    let digest = match e_tag.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(s) => s,
+        None => return false,
+    };
+    let (hex, parts) = match digest.split_once('-') {
+        Some((hex, parts)) => (hex, Some(parts)),
+        None => (digest, None),
+    };
+    let is_hex = hex.len() == 32
+        && hex
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+    let is_count = parts.map_or(true, |s| {
+        !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+    });
+    is_hex && is_count
+}
+
+/// buckets can not be created twice, and missing buckets are reported
+#[allow(clippy::shadow_unrelated)]
+async fn bucket_errors(storage: &(dyn S3Storage + Send + Sync), bucket: &str) -> CheckOutcome {
+    create_bucket(storage, bucket).await?;
+
+    let input = HeadBucketRequest {
+        bucket: bucket.into(),
+        ..HeadBucketRequest::default()
+    };
+    let _output = require!(storage.head_bucket(input).await, "HeadBucket");
+
+    let input = CreateBucketRequest {
+        bucket: bucket.into(),
+        ..CreateBucketRequest::default()
+    };
+    match storage.create_bucket(input).await {
+        Ok(_) => return Err("CreateBucket succeeded on an existing bucket".into()),
+        Err(err) => {
+            let code = error_code(err);
+            ensure!(
+                code == "BucketAlreadyExists" || code == "BucketAlreadyOwnedByYou",
+                "CreateBucket on an existing bucket failed with {code}"
+            );
+        }
+    }
+
+    let input = HeadBucketRequest {
+        bucket: format!("{bucket}-missing"),
+        ..HeadBucketRequest::default()
+    };
+    expect_error(
+        storage.head_bucket(input).await,
+        "HeadBucket",
+        "NoSuchBucket",
+    )
+}
+
+/// missing objects are reported, and deleting a missing object succeeds
+#[allow(clippy::shadow_unrelated)]
+async fn object_errors(storage: &(dyn S3Storage + Send + Sync), bucket: &str) -> CheckOutcome {
+    create_bucket(storage, bucket).await?;
+
+    let input = GetObjectRequest {
+        bucket: bucket.into(),
+        key: "missing".into(),
+        ..GetObjectRequest::default()
+    };
+    expect_error(storage.get_object(input).await, "GetObject", "NoSuchKey")?;
+
+    let input = HeadObjectRequest {
+        bucket: bucket.into(),
+        key: "missing".into(),
+        ..HeadObjectRequest::default()
+    };
+    expect_error(storage.head_object(input).await, "HeadObject", "NoSuchKey")?;
+
+    let _e_tag = put_object(storage, bucket, "deleted", b"deleted").await?;
+    for _ in 0..2_u8 {
+        let input = DeleteObjectRequest {
+            bucket: bucket.into(),
+            key: "deleted".into(),
+            ..DeleteObjectRequest::default()
+        };
+        let _output = require!(storage.delete_object(input).await, "DeleteObject");
+    }
+
+    let input = GetObjectRequest {
+        bucket: bucket.into(),
+        key: "deleted".into(),
+        ..GetObjectRequest::default()
+    };
+    expect_error(storage.get_object(input).await, "GetObject", "NoSuchKey")
+}
+
+/// `ETag`s are quoted md5 digests and stay the same in all responses
+#[allow(clippy::shadow_unrelated)]
+async fn etag_format(storage: &(dyn S3Storage + Send + Sync), bucket: &str) -> CheckOutcome {
+    create_bucket(storage, bucket).await?;
+
+    let e_tag = put_object(storage, bucket, "qwe", b"Hello World!")
+        .await?
+        .ok_or("PutObject returned no ETag")?;
+    ensure!(
+        e_tag == "\"ed076287532e86365e841e92bfc50d8c\"",
+        "PutObject returned {e_tag}, expected the quoted md5 digest of the content"
+    );
+
+    let input = HeadObjectRequest {
+        bucket: bucket.into(),
+        key: "qwe".into(),
+        ..HeadObjectRequest::default()
+    };
+    let output = require!(storage.head_object(input).await, "HeadObject");
+    ensure!(
+        output.e_tag.as_deref() == Some(e_tag.as_str()),
+        "HeadObject returned {:?}, expected {e_tag}",
+        output.e_tag
+    );
+
+    let input = GetObjectRequest {
+        bucket: bucket.into(),
+        key: "qwe".into(),
+        ..GetObjectRequest::default()
+    };
+    let output = require!(storage.get_object(input).await, "GetObject");
+    ensure!(
+        output.e_tag.as_deref() == Some(e_tag.as_str()),
+        "GetObject returned {:?}, expected {e_tag}",
+        output.e_tag
+    );
+    Ok(())
+}
+
+/// listings are sorted by key in binary order
+#[allow(clippy::shadow_unrelated)]
+async fn list_ordering(storage: &(dyn S3Storage + Send + Sync), bucket: &str) -> CheckOutcome {
+    create_bucket(storage, bucket).await?;
+
+    let keys = ["b", "a", "a-", "c", "B", "a0"];
+    for key in keys {
+        let _e_tag = put_object(storage, bucket, key, b"").await?;
+    }
+    let mut expected: Vec<&str> = keys.to_vec();
+    expected.sort_unstable();
+
+    let input = ListObjectsRequest {
+        bucket: bucket.into(),
+        ..ListObjectsRequest::default()
+    };
+    let output = require!(storage.list_objects(input).await, "ListObjects");
+    let listed: Vec<String> = output
+        .contents
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|o| o.key)
+        .collect();
+    ensure!(
+        listed == expected,
+        "ListObjects returned {listed:?}, expected {expected:?}"
+    );
+
+    let input = ListObjectsV2Request {
+        bucket: bucket.into(),
+        ..ListObjectsV2Request::default()
+    };
+    let output = require!(storage.list_objects_v2(input).await, "ListObjectsV2");
+    let listed: Vec<String> = output
+        .contents
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|o| o.key)
+        .collect();
+    ensure!(
+        listed == expected,
+        "ListObjectsV2 returned {listed:?}, expected {expected:?}"
+    );
+    Ok(())
+}
+
+/// completes a multipart upload with the parts in the order
+async fn complete_upload(
+    storage: &(dyn S3Storage + Send + Sync),
+    bucket: &str,
+    upload_id: &str,
+    parts: &[i64],
+) -> S3StorageResult<
+    crate::dto::CompleteMultipartUploadOutput,
+    crate::dto::CompleteMultipartUploadError,
+> {
+    let parts = parts
+        .iter()
+        .map(|&n| CompletedPart {
+            part_number: Some(n),
+            e_tag: None,
+        })
+        .collect();
+    let input = CompleteMultipartUploadRequest {
+        bucket: bucket.into(),
+        key: "multipart".into(),
+        upload_id: upload_id.into(),
+        multipart_upload: Some(CompletedMultipartUpload { parts: Some(parts) }),
+        ..CompleteMultipartUploadRequest::default()
+    };
+    storage.complete_multipart_upload(input).await
+}
+
+/// parts are concatenated in ascending order, and invalid uploads are rejected
+#[allow(clippy::shadow_unrelated)]
+async fn multipart_rules(storage: &(dyn S3Storage + Send + Sync), bucket: &str) -> CheckOutcome {
+    create_bucket(storage, bucket).await?;
+
+    let input = CreateMultipartUploadRequest {
+        bucket: bucket.into(),
+        key: "multipart".into(),
+        ..CreateMultipartUploadRequest::default()
+    };
+    let output = require!(
+        storage.create_multipart_upload(input).await,
+        "CreateMultipartUpload"
+    );
+    let upload_id = output
+        .upload_id
+        .ok_or("CreateMultipartUpload returned no upload id")?;
+
+    for (part_number, content) in [(2, "World!"), (1, "Hello ")] {
+        let input = UploadPartRequest {
+            bucket: bucket.into(),
+            key: "multipart".into(),
+            upload_id: upload_id.clone(),
+            part_number,
+            body: Some(ByteStream::from(content.as_bytes().to_vec())),
+            ..UploadPartRequest::default()
+        };
+        let output = require!(storage.upload_part(input).await, "UploadPart");
+        let e_tag = output.e_tag.unwrap_or_default();
+        ensure!(
+            is_valid_etag(&e_tag),
+            "UploadPart returned an invalid ETag {e_tag:?}"
+        );
+    }
+
+    expect_error(
+        complete_upload(storage, bucket, &upload_id, &[2, 1]).await,
+        "CompleteMultipartUpload with descending parts",
+        "InvalidPartOrder",
+    )?;
+
+    let output = require!(
+        complete_upload(storage, bucket, &upload_id, &[1, 2]).await,
+        "CompleteMultipartUpload"
+    );
+    let e_tag = output.e_tag.unwrap_or_default();
+    ensure!(
+        is_valid_etag(&e_tag),
+        "CompleteMultipartUpload returned an invalid ETag {e_tag:?}"
+    );
+
+    let content = get_content(storage, bucket, "multipart").await?;
+    ensure!(
+        content == b"Hello World!",
+        "the completed object contains {:?}",
+        String::from_utf8_lossy(&content)
+    );
+
+    let input = CreateMultipartUploadRequest {
+        bucket: bucket.into(),
+        key: "multipart".into(),
+        ..CreateMultipartUploadRequest::default()
+    };
+    let _output = require!(
+        storage.create_multipart_upload(input).await,
+        "CreateMultipartUpload"
+    );
+    expect_error(
+        complete_upload(storage, bucket, "no-such-upload", &[1]).await,
+        "CompleteMultipartUpload with an unknown upload id",
+        "NoSuchUpload",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn etag() {
+        assert!(is_valid_etag("\"ed076287532e86365e841e92bfc50d8c\""));
+        assert!(is_valid_etag("\"ed076287532e86365e841e92bfc50d8c-2\""));
+        assert!(!is_valid_etag("ed076287532e86365e841e92bfc50d8c"));
+        assert!(!is_valid_etag("\"ED076287532E86365E841E92BFC50D8C\""));
+        assert!(!is_valid_etag("\"ed076287532e86365e841e92bfc50d8c-\""));
+    }
+}
@@ -0,0 +1,116 @@
+//! Content types of objects guessed by the extensions of their keys
+
+use std::collections::HashMap;
+
+/// Content types of objects guessed by the extensions of their keys
+///
+/// [`S3Service`](crate::S3Service) applies it to `PutObject` requests without `Content-Type`,
+/// and to `GetObject` and `HeadObject` responses whose storage records no content type,
+/// so that browsers render `.html` and `.png` objects instead of downloading them.
+///
+/// Extensions are matched case-insensitively. Mappings of a bucket take precedence over
+/// the mappings of all buckets, which take precedence over the builtin table of
+/// `mime_guess` with the feature `mime_guess`.
+///
+/// ```
+/// use s3_server::ContentTypes;
+///
+/// let mut content_types = ContentTypes::new();
+/// content_types.insert("md", "text/markdown");
+/// content_types.insert_for_bucket("logs", "s3log", "text/plain");
+///
+/// assert_eq!(content_types.guess("asd", "README.MD").as_deref(), Some("text/markdown"));
+/// assert_eq!(content_types.guess("logs", "a/b.s3log").as_deref(), Some("text/plain"));
+/// assert_eq!(content_types.guess("asd", "a/b.s3log"), None);
+/// assert_eq!(content_types.guess("asd", "a.b/c"), None);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ContentTypes {
+    /// extensions to content types of all buckets
+    extensions: HashMap<String, String>,
+    /// extensions to content types of each bucket
+    buckets: HashMap<String, HashMap<String, String>>,
+}
+
+impl ContentTypes {
+    /// Constructs a mapping without custom extensions
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// maps an extension without the leading dot to a content type in all buckets
+    pub fn insert(&mut self, extension: &str, content_type: impl Into<String>) {
+        let _prev = self
+            .extensions
+            .insert(extension.to_ascii_lowercase(), content_type.into());
+    }
+
+    /// maps an extension without the leading dot to a content type in a bucket
+    pub fn insert_for_bucket(
+        &mut self,
+        bucket: impl Into<String>,
+        extension: &str,
+        content_type: impl Into<String>,
+    ) {
+        let _prev = self
+            .buckets
+            .entry(bucket.into())
+            .or_default()
+            .insert(extension.to_ascii_lowercase(), content_type.into());
+    }
+
+    /// guesses the content type of an object, returns `None` for unknown extensions
+    #[must_use]
+    pub fn guess(&self, bucket: &str, key: &str) -> Option<String> {
+        let name = key.rsplit('/').next().unwrap_or(key);
+        let (_, extension) = name.rsplit_once('.')?;
+        let extension = extension.to_ascii_lowercase();
+
+        let mapped = self
+            .buckets
+            .get(bucket)
+            .and_then(|m| m.get(&extension))
+            .or_else(|| self.extensions.get(&extension));
+        if let Some(content_type) = mapped {
+            return Some(content_type.clone());
+        }
+        builtin(&extension)
+    }
+}
+
+/// looks up the builtin table
+#[cfg(feature = "mime_guess")]
+fn builtin(extension: &str) -> Option<String> {
+    mime_guess::from_ext(extension)
+        .first_raw()
+        .map(str::to_owned)
+}
+
+/// there is no builtin table without the feature `mime_guess`
+#[cfg(not(feature = "mime_guess"))]
+#[allow(clippy::missing_const_for_fn)]
+fn builtin(_: &str) -> Option<String> {
+    None
+}
+
+#[cfg(all(test, feature = "mime_guess"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_table() {
+        let mut content_types = ContentTypes::new();
+        content_types.insert_for_bucket("asd", "png", "application/x-custom");
+
+        let guess = |bucket, key| content_types.guess(bucket, key);
+        assert_eq!(guess("qwe", "index.html").as_deref(), Some("text/html"));
+        assert_eq!(guess("qwe", "a/b/c.PNG").as_deref(), Some("image/png"));
+        assert_eq!(
+            guess("asd", "c.png").as_deref(),
+            Some("application/x-custom")
+        );
+        assert_eq!(guess("qwe", "c.unknown-extension"), None);
+        assert_eq!(guess("qwe", "no-extension"), None);
+    }
+}
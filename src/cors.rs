@@ -0,0 +1,224 @@
+//! Cross-origin resource sharing
+//!
+//! See <https://docs.aws.amazon.com/AmazonS3/latest/userguide/cors.html>
+
+use crate::Method;
+
+use std::collections::HashMap;
+
+/// request headers which select the CORS response, listed by `Vary`
+pub const VARY_HEADERS: &str =
+    "Origin, Access-Control-Request-Headers, Access-Control-Request-Method";
+
+/// A CORS rule, like a `CORSRule` of a bucket CORS configuration
+///
+/// Origins and allowed headers may contain one `*` wildcard.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct CorsRule {
+    /// origins which are allowed, such as `https://*.example.com` or `*`
+    pub allowed_origins: Vec<String>,
+    /// methods which are allowed
+    pub allowed_methods: Vec<Method>,
+    /// request headers which are allowed in preflight requests, such as `x-amz-*`
+    pub allowed_headers: Vec<String>,
+    /// response headers which browsers may expose to scripts
+    pub expose_headers: Vec<String>,
+    /// how long browsers may cache a preflight response, in seconds
+    pub max_age_seconds: Option<u32>,
+    /// whether credentialed requests, such as requests with cookies, are allowed
+    pub allow_credentials: bool,
+}
+
+impl CorsRule {
+    /// Constructs a rule which allows the methods from the origins
+    pub fn new(
+        allowed_origins: impl IntoIterator<Item = impl Into<String>>,
+        allowed_methods: impl IntoIterator<Item = Method>,
+    ) -> Self {
+        Self {
+            allowed_origins: allowed_origins.into_iter().map(Into::into).collect(),
+            allowed_methods: allowed_methods.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// checks whether the rule matches a request
+    fn is_match(&self, origin: &str, method: &Method, request_headers: &[&str]) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|p| wildcard_match(p, origin))
+            && self.allowed_methods.contains(method)
+            && request_headers.iter().all(|h| {
+                self.allowed_headers
+                    .iter()
+                    .any(|p| wildcard_match(&p.to_ascii_lowercase(), &h.to_ascii_lowercase()))
+            })
+    }
+}
+
+/// CORS rules of buckets
+///
+/// ```
+/// use s3_server::{CorsConfig, CorsRule};
+/// use hyper::Method;
+///
+/// let mut rule = CorsRule::new(["https://*.example.com"], [Method::GET, Method::PUT]);
+/// rule.allowed_headers.push("x-amz-*".into());
+/// rule.max_age_seconds = Some(3600);
+///
+/// let mut cors = CorsConfig::new();
+/// cors.add_rule("asd", rule);
+///
+/// let headers = cors
+///     .match_request("asd", "https://www.example.com", &Method::PUT, &["X-Amz-Date"])
+///     .unwrap();
+/// assert_eq!(headers.allow_origin, "https://www.example.com");
+/// assert_eq!(headers.max_age, Some(3600));
+///
+/// assert!(cors
+///     .match_request("asd", "https://example.org", &Method::GET, &[])
+///     .is_none());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CorsConfig {
+    /// rules of each bucket
+    buckets: HashMap<String, Vec<CorsRule>>,
+}
+
+/// The response headers of a request which matches a [`CorsRule`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CorsHeaders {
+    /// `Access-Control-Allow-Origin`
+    pub allow_origin: String,
+    /// `Access-Control-Allow-Credentials: true`
+    pub allow_credentials: bool,
+    /// `Access-Control-Allow-Methods`
+    pub allow_methods: String,
+    /// `Access-Control-Allow-Headers`, which echoes the requested headers
+    pub allow_headers: Option<String>,
+    /// `Access-Control-Expose-Headers`
+    pub expose_headers: Option<String>,
+    /// `Access-Control-Max-Age`
+    pub max_age: Option<u32>,
+}
+
+impl CorsConfig {
+    /// Constructs an empty config, which allows no cross-origin requests
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a rule of a bucket, rules are matched in order
+    pub fn add_rule(&mut self, bucket: impl Into<String>, rule: CorsRule) {
+        self.buckets.entry(bucket.into()).or_default().push(rule);
+    }
+
+    /// Matches a request against the rules of a bucket
+    ///
+    /// `method` and `request_headers` come from `Access-Control-Request-Method` and
+    /// `Access-Control-Request-Headers` of a preflight request,
+    /// or from the method of an actual request with no headers.
+    ///
+    /// The origin is echoed unless the rule allows any origin without credentials,
+    /// because browsers reject `*` in responses to credentialed requests.
+    #[must_use]
+    pub fn match_request(
+        &self,
+        bucket: &str,
+        origin: &str,
+        method: &Method,
+        request_headers: &[&str],
+    ) -> Option<CorsHeaders> {
+        let rule = self
+            .buckets
+            .get(bucket)?
+            .iter()
+            .find(|r| r.is_match(origin, method, request_headers))?;
+
+        let any_origin = rule.allowed_origins.iter().any(|o| o == "*");
+        let allow_origin = if any_origin && !rule.allow_credentials {
+            "*".to_owned()
+        } else {
+            origin.to_owned()
+        };
+        let join = |v: &[&str]| (!v.is_empty()).then(|| v.join(", "));
+        let methods: Vec<&str> = rule.allowed_methods.iter().map(Method::as_str).collect();
+        let expose_headers: Vec<&str> = rule.expose_headers.iter().map(String::as_str).collect();
+
+        Some(CorsHeaders {
+            allow_origin,
+            allow_credentials: rule.allow_credentials,
+            allow_methods: methods.join(", "),
+            allow_headers: join(request_headers),
+            expose_headers: join(&expose_headers),
+            max_age: rule.max_age_seconds,
+        })
+    }
+}
+
+/// matches a string against a pattern with at most one `*`
+fn wildcard_match(pattern: &str, s: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == s,
+        Some((prefix, suffix)) => {
+            s.len() >= prefix.len().saturating_add(suffix.len())
+                && s.starts_with(prefix)
+                && s.ends_with(suffix)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard() {
+        assert!(wildcard_match("*", "https://a.com"));
+        assert!(wildcard_match("https://*.a.com", "https://b.a.com"));
+        assert!(!wildcard_match("https://*.a.com", "https://a.com"));
+        assert!(wildcard_match("x-amz-*", "x-amz-date"));
+        assert!(!wildcard_match("x-amz-*", "content-type"));
+        assert!(wildcard_match("https://a.com", "https://a.com"));
+    }
+
+    #[test]
+    #[allow(clippy::shadow_unrelated)]
+    fn credentials() {
+        let mut cors = CorsConfig::new();
+        let mut rule = CorsRule::new(["*"], [Method::GET]);
+        rule.expose_headers = vec!["ETag".into(), "x-amz-request-id".into()];
+        cors.add_rule("public", rule.clone());
+        rule.allow_credentials = true;
+        cors.add_rule("private", rule);
+
+        let headers = cors
+            .match_request("public", "https://a.com", &Method::GET, &[])
+            .unwrap();
+        assert_eq!(headers.allow_origin, "*");
+        assert!(!headers.allow_credentials);
+        assert_eq!(
+            headers.expose_headers.as_deref(),
+            Some("ETag, x-amz-request-id")
+        );
+
+        let headers = cors
+            .match_request("private", "https://a.com", &Method::GET, &[])
+            .unwrap();
+        assert_eq!(headers.allow_origin, "https://a.com");
+        assert!(headers.allow_credentials);
+
+        assert!(cors
+            .match_request("public", "https://a.com", &Method::PUT, &[])
+            .is_none());
+        assert!(cors
+            .match_request("public", "https://a.com", &Method::GET, &["range"])
+            .is_none());
+        assert!(cors
+            .match_request("other", "https://a.com", &Method::GET, &[])
+            .is_none());
+    }
+}
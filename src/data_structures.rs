@@ -1,9 +1,11 @@
 //! Internal data structures
 
 mod bytes_stream;
+mod lru_cache;
 mod ordered_headers;
 mod ordered_qs;
 
 pub use self::bytes_stream::BytesStream;
+pub use self::lru_cache::LruCache;
 pub use self::ordered_headers::OrderedHeaders;
 pub use self::ordered_qs::OrderedQs;
@@ -0,0 +1,103 @@
+//! Small LRU cache
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A small LRU cache
+///
+/// Eviction scans all entries, so the capacity should be small.
+#[derive(Debug)]
+pub struct LruCache<K, V> {
+    /// entries with their last access time
+    map: HashMap<K, (V, u64)>,
+    /// logical clock
+    clock: u64,
+    /// max number of entries
+    capacity: usize,
+}
+
+impl<K: Eq + Hash + Clone, V> LruCache<K, V> {
+    /// Constructs an empty `LruCache`
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be positive");
+        Self {
+            map: HashMap::with_capacity(capacity),
+            clock: 0,
+            capacity,
+        }
+    }
+
+    /// advances the logical clock
+    fn tick(&mut self) -> u64 {
+        self.clock = self.clock.wrapping_add(1);
+        self.clock
+    }
+
+    /// Gets a value and marks it as recently used
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let now = self.tick();
+        let &mut (ref value, ref mut last_used) = self.map.get_mut(key)?;
+        *last_used = now;
+        Some(value)
+    }
+
+    /// Inserts a value, evicting the least recently used entry if the cache is full
+    pub fn insert(&mut self, key: K, value: V) {
+        let now = self.tick();
+        if self.map.len() >= self.capacity && !self.map.contains_key(&key) {
+            self.evict();
+        }
+        drop(self.map.insert(key, (value, now)));
+    }
+
+    /// removes the least recently used entry
+    fn evict(&mut self) {
+        #[allow(clippy::iter_over_hash_type)] // the order does not matter
+        let oldest = self
+            .map
+            .iter()
+            .min_by_key(|&(_, &(_, last_used))| last_used)
+            .map(|(k, _)| k.clone());
+
+        if let Some(ref k) = oldest {
+            drop(self.map.remove(k));
+        }
+    }
+
+    /// Returns the number of entries
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evict_least_recently_used() {
+        let mut cache: LruCache<&str, u32> = LruCache::new(2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        assert_eq!(cache.get("a"), Some(&1));
+
+        cache.insert("c", 3);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(&1));
+        assert_eq!(cache.get("c"), Some(&3));
+
+        cache.insert("c", 4);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("c"), Some(&4));
+    }
+}
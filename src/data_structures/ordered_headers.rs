@@ -7,10 +7,32 @@ use std::str::FromStr;
 use hyper::header::{AsHeaderName, ToStrError};
 use smallvec::SmallVec;
 
+/// max number of headers of a request
+const MAX_HEADERS: usize = 128;
+
+/// max total size of header names and values of a request
+const MAX_HEADERS_SIZE: usize = 64 * 1024;
+
+/// An error which can be returned when collecting headers from a request
+#[derive(Debug, thiserror::Error)]
+pub enum HeadersError {
+    /// A header value is not visible ASCII
+    #[error(transparent)]
+    ToStr(#[from] ToStrError),
+    /// There are more than 128 headers
+    #[error("too many headers")]
+    TooMany,
+    /// Header names and values exceed 64 KiB
+    #[error("headers are too large")]
+    TooLarge,
+}
+
 /// Immutable http header container
+///
+/// Repeated headers are kept in the order they are received.
 #[derive(Debug)]
 pub struct OrderedHeaders<'a> {
-    /// Ascending headers (header names are lowercase)
+    /// Headers in ascending order of names (header names are lowercase)
     headers: SmallVec<[(&'a str, &'a str); 16]>,
 }
 
@@ -23,19 +45,33 @@ impl<'a> OrderedHeaders<'a> {
     pub fn from_slice_unchecked(slice: &[(&'a str, &'a str)]) -> Self {
         let mut headers = SmallVec::new();
         headers.extend_from_slice(slice);
-        headers.sort_unstable();
+        headers.sort_by_key(|&(name, _)| name);
         Self { headers }
     }
 
     /// Constructs `OrderedHeaders<'a>` from `&'a Request`
-    pub fn from_req(req: &'a Request) -> Result<Self, ToStrError> {
-        let mut headers: SmallVec<[(&'a str, &'a str); 16]> =
-            SmallVec::with_capacity(req.headers().len());
+    ///
+    /// The number and the total size of headers are bounded,
+    /// which are checked before allocating.
+    pub fn from_req(req: &'a Request) -> Result<Self, HeadersError> {
+        let len = req.headers().len();
+        if len > MAX_HEADERS {
+            return Err(HeadersError::TooMany);
+        }
+        let size = req.headers().iter().fold(0_usize, |acc, (name, value)| {
+            acc.saturating_add(name.as_str().len())
+                .saturating_add(value.len())
+        });
+        if size > MAX_HEADERS_SIZE {
+            return Err(HeadersError::TooLarge);
+        }
 
-        for (name, value) in req.headers().iter() {
+        let mut headers: SmallVec<[(&'a str, &'a str); 16]> = SmallVec::with_capacity(len);
+        for (name, value) in req.headers() {
             headers.push((name.as_str(), value.to_str()?));
         }
-        headers.sort_unstable();
+        // stable sorting keeps the order of repeated headers
+        headers.sort_by_key(|&(name, _)| name);
 
         Ok(Self { headers })
     }
@@ -43,7 +79,7 @@ impl<'a> OrderedHeaders<'a> {
     /// + Signed headers must be sorted
     pub fn map_signed_headers(&self, signed_headers: &[impl AsRef<str>]) -> Self {
         let mut headers: SmallVec<[(&'a str, &'a str); 16]> = SmallVec::new();
-        for &(name, value) in self.headers.iter() {
+        for &(name, value) in &self.headers {
             if signed_headers
                 .binary_search_by(|probe| probe.as_ref().cmp(name))
                 .is_ok()
@@ -54,15 +90,23 @@ impl<'a> OrderedHeaders<'a> {
         Self { headers }
     }
 
-    /// Gets header value by name. Time `O(logn)`
+    /// Gets the first header value by name. Time `O(logn)`
     pub fn get(&self, name: impl AsHeaderName) -> Option<&'a str> {
+        self.get_all(name).next()
+    }
+
+    /// Gets all header values by name in the order they are received. Time `O(logn)`
+    pub fn get_all(&self, name: impl AsHeaderName) -> impl Iterator<Item = &'a str> + '_ {
         let headers = self.headers.as_slice();
-        let ans = match headers.binary_search_by_key(&name.as_str(), |&(n, _)| n) {
-            Ok(idx) => headers.get(idx).map(|&(_, v)| v),
-            Err(_) => None,
-        };
+        let start = headers.partition_point(|&(n, _)| n < name.as_str());
+        let matched = headers.get(start..).unwrap_or_default();
+        let end = matched.partition_point(|&(n, _)| n == name.as_str());
         drop(name);
-        ans
+        matched
+            .get(..end)
+            .unwrap_or_default()
+            .iter()
+            .map(|&(_, v)| v)
     }
 
     /// Assigns value from optional header
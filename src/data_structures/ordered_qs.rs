@@ -6,10 +6,32 @@ use std::str::FromStr;
 
 use smallvec::SmallVec;
 
+/// max number of query pairs of a request
+const MAX_QUERY_PAIRS: usize = 128;
+
+/// max size of the query of a request
+const MAX_QUERY_SIZE: usize = 16 * 1024;
+
+/// An error which can be returned when parsing query strings
+#[derive(Debug, thiserror::Error)]
+pub enum QsError {
+    /// The query is not url-encoded
+    #[error(transparent)]
+    Decode(#[from] serde_urlencoded::de::Error),
+    /// There are more than 128 pairs
+    #[error("too many query pairs")]
+    TooMany,
+    /// The query exceeds 16 KiB
+    #[error("query is too large")]
+    TooLarge,
+}
+
 /// Immutable query string container
+///
+/// Repeated parameters are kept in the order they are received.
 #[derive(Debug)]
 pub struct OrderedQs {
-    /// Ascending query strings
+    /// Query strings in ascending order of names
     qs: SmallVec<[(String, String); 16]>,
 }
 
@@ -19,24 +41,43 @@ impl OrderedQs {
     /// + strings must be url-decoded
     #[cfg(test)]
     pub fn from_vec_unchecked(mut v: Vec<(String, String)>) -> Self {
-        v.sort();
+        v.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
         Self { qs: v.into() }
     }
 
     /// Parses `OrderedQs` from query
-    pub fn from_query(query: &str) -> Result<Self, serde_urlencoded::de::Error> {
+    ///
+    /// The size of the query and the number of pairs are bounded,
+    /// which are checked before decoding.
+    pub fn from_query(query: &str) -> Result<Self, QsError> {
+        if query.len() > MAX_QUERY_SIZE {
+            return Err(QsError::TooLarge);
+        }
+        if query.split('&').filter(|s| !s.is_empty()).count() > MAX_QUERY_PAIRS {
+            return Err(QsError::TooMany);
+        }
         serde_urlencoded::from_str::<Vec<(String, String)>>(query)?
-            .also(|v| v.sort())
+            // stable sorting keeps the order of repeated parameters
+            .also(|v| v.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0)))
             .apply(|qs| Ok(Self { qs: qs.into() }))
     }
 
-    /// Gets query value by name. Time `O(logn)`
+    /// Gets the first query value by name. Time `O(logn)`
     pub fn get(&self, name: &str) -> Option<&str> {
-        let qs = self.qs.as_ref();
-        match qs.binary_search_by_key(&name, |&(ref n, _)| n.as_str()) {
-            Ok(idx) => qs.get(idx).map(|&(_, ref v)| v.as_str()),
-            Err(_) => None,
-        }
+        self.get_all(name).next()
+    }
+
+    /// Gets all query values by name in the order they are received. Time `O(logn)`
+    pub fn get_all<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a str> {
+        let qs: &[(String, String)] = self.qs.as_ref();
+        let start = qs.partition_point(|&(ref n, _)| n.as_str() < name);
+        let matched = qs.get(start..).unwrap_or_default();
+        let end = matched.partition_point(|&(ref n, _)| n == name);
+        matched
+            .get(..end)
+            .unwrap_or_default()
+            .iter()
+            .map(|&(_, ref v)| v.as_str())
     }
 
     /// Assigns value from optional query
@@ -61,3 +102,17 @@ impl AsRef<[(String, String)]> for OrderedQs {
         self.qs.as_ref()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_params() {
+        let qs = OrderedQs::from_query("b=2&a=1&b=1&c&b=3").unwrap();
+        assert_eq!(qs.get("b"), Some("2"));
+        assert_eq!(qs.get_all("b").collect::<Vec<_>>(), ["2", "1", "3"]);
+        assert_eq!(qs.get("c"), Some(""));
+        assert_eq!(qs.get_all("d").count(), 0);
+    }
+}
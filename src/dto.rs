@@ -2,26 +2,193 @@
 
 pub use rusoto_core::ByteStream;
 pub use rusoto_s3::{
-    Bucket, CompleteMultipartUploadError, CompleteMultipartUploadOutput,
-    CompleteMultipartUploadRequest, CompletedMultipartUpload, CompletedPart, CopyObjectError,
-    CopyObjectOutput, CopyObjectRequest, CopyObjectResult, CreateBucketConfiguration,
-    CreateBucketError, CreateBucketOutput, CreateBucketRequest, CreateMultipartUploadError,
-    CreateMultipartUploadOutput, CreateMultipartUploadRequest, Delete, DeleteBucketError,
-    DeleteBucketRequest, DeleteObjectError, DeleteObjectOutput, DeleteObjectRequest,
-    DeleteObjectsError, DeleteObjectsOutput, DeleteObjectsRequest, DeletedObject,
-    GetBucketLocationError, GetBucketLocationOutput, GetBucketLocationRequest, GetObjectError,
-    GetObjectOutput, GetObjectRequest, HeadBucketError, HeadBucketRequest, HeadObjectError,
-    HeadObjectOutput, HeadObjectRequest, ListBucketsError, ListBucketsOutput, ListObjectsError,
+    AccelerateConfiguration, Bucket, CommonPrefix, CompleteMultipartUploadError,
+    CompleteMultipartUploadOutput, CompleteMultipartUploadRequest, CompletedMultipartUpload,
+    CompletedPart, CopyObjectError, CopyObjectOutput, CopyObjectRequest, CopyObjectResult,
+    CreateBucketConfiguration, CreateBucketError, CreateBucketOutput, CreateBucketRequest,
+    CreateMultipartUploadError, CreateMultipartUploadOutput, CreateMultipartUploadRequest, Delete,
+    DeleteBucketError, DeleteBucketRequest, DeleteObjectError, DeleteObjectOutput,
+    DeleteObjectRequest, DeleteObjectsError, DeleteObjectsOutput, DeleteObjectsRequest,
+    DeletedObject, GetBucketAccelerateConfigurationError, GetBucketAccelerateConfigurationOutput,
+    GetBucketAccelerateConfigurationRequest, GetBucketLocationError, GetBucketLocationOutput,
+    GetBucketLocationRequest, GetBucketRequestPaymentError, GetBucketRequestPaymentOutput,
+    GetBucketRequestPaymentRequest, GetObjectError, GetObjectOutput, GetObjectRequest,
+    HeadBucketError, HeadBucketRequest, HeadObjectError, HeadObjectOutput, HeadObjectRequest,
+    ListBucketsError, ListBucketsOutput, ListObjectVersionsOutput, ListObjectsError,
     ListObjectsOutput, ListObjectsRequest, ListObjectsV2Error, ListObjectsV2Output,
-    ListObjectsV2Request, Object, ObjectIdentifier, Owner, PutObjectError, PutObjectOutput,
-    PutObjectRequest, UploadPartError, UploadPartOutput, UploadPartRequest,
+    ListObjectsV2Request, Object, ObjectIdentifier, ObjectVersion, Owner,
+    PutBucketAccelerateConfigurationError, PutBucketAccelerateConfigurationRequest,
+    PutBucketRequestPaymentError, PutBucketRequestPaymentRequest, PutObjectError, PutObjectOutput,
+    PutObjectRequest, RequestPaymentConfiguration, S3Error, UploadPartError, UploadPartOutput,
+    UploadPartRequest,
 };
 
+/// `AppendObjectRequest`
+///
+/// A `PutObject` request with `x-amz-write-offset-bytes`, which appends data to an existing object
+#[derive(Debug, Default)]
+#[allow(clippy::exhaustive_structs)]
+pub struct AppendObjectRequest {
+    /// the put request
+    pub input: PutObjectRequest,
+    /// the current size of the object, where the data is appended
+    pub write_offset_bytes: u64,
+}
+
+/// `ListObjectsFilter`
+///
+/// Conditions on listed objects, which are vendor extensions of `ListObjectsV2`
+#[cfg(feature = "extensions")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct ListObjectsFilter {
+    /// `x-mtime-after`, lists objects modified after the time
+    pub modified_after: Option<std::time::SystemTime>,
+    /// `x-mtime-before`, lists objects modified before the time
+    pub modified_before: Option<std::time::SystemTime>,
+    /// `x-min-size`, lists objects of at least the size in bytes
+    pub min_size: Option<u64>,
+    /// `x-max-size`, lists objects of at most the size in bytes
+    pub max_size: Option<u64>,
+}
+
+#[cfg(feature = "extensions")]
+impl ListObjectsFilter {
+    /// Checks whether an object of the modification time and size passes the filter
+    #[must_use]
+    pub fn is_match(&self, last_modified: std::time::SystemTime, size: u64) -> bool {
+        self.modified_after.map_or(true, |t| last_modified > t)
+            && self.modified_before.map_or(true, |t| last_modified < t)
+            && self.min_size.map_or(true, |s| size >= s)
+            && self.max_size.map_or(true, |s| size <= s)
+    }
+}
+
+/// `FilteredListObjectsV2Request`
+///
+/// A `ListObjectsV2` request with vendor-specific filters
+#[cfg(feature = "extensions")]
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::exhaustive_structs)]
+pub struct FilteredListObjectsV2Request {
+    /// the list request
+    pub input: ListObjectsV2Request,
+    /// conditions on listed objects
+    pub filter: ListObjectsFilter,
+}
+
+/// `BucketStatsRequest`
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::exhaustive_structs)]
+pub struct BucketStatsRequest {
+    /// bucket name
+    pub bucket: String,
+}
+
+/// `BucketStatsOutput`
+///
+/// The usage of a bucket, which may be cached by the storage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct BucketStatsOutput {
+    /// number of objects
+    pub object_count: u64,
+    /// total size of objects in bytes
+    pub size: u64,
+}
+
+/// `HeadObjectsRequest`
+///
+/// Reads the attributes of many objects of a bucket at once
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::exhaustive_structs)]
+pub struct HeadObjectsRequest {
+    /// bucket name
+    pub bucket: String,
+    /// object keys
+    pub keys: Vec<String>,
+}
+
+/// `HeadObjectsOutput`
+#[derive(Debug, Default)]
+#[allow(clippy::exhaustive_structs)]
+pub struct HeadObjectsOutput {
+    /// attributes of the objects in the order of the keys, `None` for missing objects
+    pub objects: Vec<Option<HeadObjectOutput>>,
+}
+
 /// `DeleteBucketOutput`
 #[derive(Debug, Clone, Copy)]
 #[allow(clippy::exhaustive_structs)]
 pub struct DeleteBucketOutput;
 
+/// `UndeleteObjectRequest`
+///
+/// Restores the latest deleted version of an object from the trash
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::exhaustive_structs)]
+pub struct UndeleteObjectRequest {
+    /// bucket name
+    pub bucket: String,
+    /// object key
+    pub key: String,
+}
+
+/// `UndeleteObjectOutput`
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::exhaustive_structs)]
+pub struct UndeleteObjectOutput;
+
+/// `RenameBucketRequest`
+///
+/// Renames a bucket on the server side
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::exhaustive_structs)]
+pub struct RenameBucketRequest {
+    /// bucket name
+    pub bucket: String,
+    /// new bucket name
+    pub new_bucket: String,
+}
+
+/// `RenameBucketOutput`
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::exhaustive_structs)]
+pub struct RenameBucketOutput;
+
+/// `RenameObjectRequest`
+///
+/// Renames an object on the server side,
+/// or all objects under a prefix if the key ends with `/`
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::exhaustive_structs)]
+pub struct RenameObjectRequest {
+    /// bucket name
+    pub bucket: String,
+    /// object key or prefix
+    pub key: String,
+    /// new object key or prefix
+    pub new_key: String,
+}
+
+/// `RenameObjectOutput`
+#[derive(Debug, Clone, Copy, Default)]
+#[allow(clippy::exhaustive_structs)]
+pub struct RenameObjectOutput {
+    /// number of renamed objects
+    pub renamed_count: u64,
+}
+
+/// `PutBucketAccelerateConfigurationOutput`
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::exhaustive_structs)]
+pub struct PutBucketAccelerateConfigurationOutput;
+
+/// `PutBucketRequestPaymentOutput`
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::exhaustive_structs)]
+pub struct PutBucketRequestPaymentOutput;
+
 /// `HeadBucketOutput`
 #[derive(Debug, Clone, Copy)]
 #[allow(clippy::exhaustive_structs)]
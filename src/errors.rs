@@ -5,6 +5,7 @@ use crate::{BoxStdError, StatusCode};
 
 use std::error::Error;
 use std::fmt::{self, Debug, Display};
+use std::io;
 
 use backtrace::Backtrace;
 use tracing_error::SpanTrace;
@@ -12,11 +13,13 @@ use tracing_error::SpanTrace;
 /// Type representing an error response
 pub(crate) struct XmlErrorResponse {
     /// code
-    pub(crate) code: S3ErrorCode,
+    pub code: S3ErrorCode,
     /// message
-    pub(crate) message: Option<String>,
-    // resource: Option<String>, // unimplemented
-    // request_id: Option<String>, // unimplemented
+    pub message: Option<String>,
+    /// path of the request
+    pub resource: Option<String>,
+    /// id of the request
+    pub request_id: Option<String>,
 }
 
 /// `S3ErrorInner`
@@ -32,8 +35,6 @@ struct S3ErrorInner {
     span_trace: Option<SpanTrace>,
     /// stack trace
     backtrace: Option<Backtrace>,
-    // resource: Option<String>, // unimplemented
-    // request_id: Option<String>, // unimplemented
 }
 
 // `S3Error` uses `Box` to avoid moving too much bytes.
@@ -96,11 +97,46 @@ impl S3Error {
         .apply(|e| S3ErrorBuilder(Box::new(e)))
     }
 
+    /// error code
+    #[must_use]
+    pub fn code(&self) -> S3ErrorCode {
+        self.0.code
+    }
+
+    /// error message
+    #[must_use]
+    pub fn message(&self) -> Option<&str> {
+        self.0.message.as_deref()
+    }
+
+    /// status code of the error response, `500` if the code has no status code
+    #[must_use]
+    pub fn status_code(&self) -> StatusCode {
+        self.0
+            .code
+            .as_status_code()
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    /// whether the error is caused by the client, such as `NoSuchKey`
+    #[must_use]
+    pub fn is_client_error(&self) -> bool {
+        self.status_code().is_client_error()
+    }
+
+    /// whether the error is caused by the server, such as `InternalError`
+    #[must_use]
+    pub fn is_server_error(&self) -> bool {
+        self.status_code().is_server_error()
+    }
+
     /// consume the error and return an xml response
     pub(crate) fn into_xml_response(self) -> XmlErrorResponse {
         XmlErrorResponse {
             code: self.0.code,
             message: self.0.message,
+            resource: None,
+            request_id: None,
         }
     }
 
@@ -112,6 +148,62 @@ impl S3Error {
     }
 }
 
+/// Maps an io error to the closest error code
+///
+/// `NotFound` is mapped to `NoSuchKey`, as storages usually fail to find objects.
+/// Errors without a closer code are internal errors with captured traces.
+impl From<io::Error> for S3Error {
+    fn from(e: io::Error) -> Self {
+        let kind = e.kind();
+        let (code, msg) = if kind == io::ErrorKind::NotFound {
+            (S3ErrorCode::NoSuchKey, "The specified key does not exist.")
+        } else if kind == io::ErrorKind::PermissionDenied {
+            (S3ErrorCode::AccessDenied, "Access Denied")
+        } else if kind == io::ErrorKind::InvalidInput {
+            (S3ErrorCode::InvalidArgument, "Invalid Argument")
+        } else if kind == io::ErrorKind::TimedOut {
+            (
+                S3ErrorCode::RequestTimeout,
+                "Your socket connection to the server was not read from or written to within the timeout period.",
+            )
+        } else if kind == io::ErrorKind::UnexpectedEof {
+            (
+                S3ErrorCode::IncompleteBody,
+                "You did not provide the number of bytes specified by the Content-Length HTTP header.",
+            )
+        } else {
+            return internal_error!(e);
+        };
+        code_error!(code = code, msg, e)
+    }
+}
+
+/// Maps a hyper error, which usually occurs while receiving a request body
+///
+/// Errors without a closer code are internal errors with captured traces.
+impl From<hyper::Error> for S3Error {
+    fn from(e: hyper::Error) -> Self {
+        if e.is_timeout() {
+            return code_error!(
+                RequestTimeout,
+                "Your socket connection to the server was not read from or written to within the timeout period.",
+                e
+            );
+        }
+        if e.is_incomplete_message() || e.is_canceled() || e.is_closed() {
+            return code_error!(
+                IncompleteBody,
+                "You did not provide the number of bytes specified by the Content-Length HTTP header.",
+                e
+            );
+        }
+        if e.is_parse() {
+            return invalid_request!("The request is malformed.", e);
+        }
+        internal_error!(e)
+    }
+}
+
 /// The builder of `S3Error`
 #[derive(Debug)]
 pub struct S3ErrorBuilder(Box<S3ErrorInner>);
@@ -189,6 +281,18 @@ impl<E> From<S3Error> for S3StorageError<E> {
     }
 }
 
+impl<E> From<io::Error> for S3StorageError<E> {
+    fn from(e: io::Error) -> Self {
+        Self::Other(e.into())
+    }
+}
+
+impl<E> From<hyper::Error> for S3StorageError<E> {
+    fn from(e: hyper::Error) -> Self {
+        Self::Other(e.into())
+    }
+}
+
 /// Result carrying a generic `S3StorageError<E>`
 pub type S3StorageResult<T, E> = Result<T, S3StorageError<E>>;
 
@@ -245,6 +349,9 @@ pub enum S3ErrorCode {
     /// The authorization header you provided is invalid.
     AuthorizationHeaderMalformed,
 
+    /// The authorization query parameters of a presigned url are invalid.
+    AuthorizationQueryParametersError,
+
     /// The Content-MD5 you specified did not match what we received.
     BadDigest,
 
@@ -273,7 +380,7 @@ pub enum S3ErrorCode {
     /// The provided token has expired.
     ExpiredToken,
 
-    /// Indicates that you are attempting to access a bucket from a different region than where the bucket exists. To avoid this error, use the <code class=\"code\">--region</code> option. For example: <code class=\"code\">aws s3 cp awsexample.txt s3://testbucket/ --region ap-east-1</code>.
+    /// Indicates that you are attempting to access a bucket from a different region than where the bucket exists. To avoid this error, use the <code class=\"code\">--region</code> option. For example: `aws s3 cp awsexample.txt s3://testbucket/ --region ap-east-1`.
     IllegalLocationConstraintException,
 
     /// Indicates that the versioning configuration specified in the request is invalid.
@@ -326,6 +433,9 @@ pub enum S3ErrorCode {
     /// The list of parts was not in ascending order. Parts list must be specified in order by part number.
     InvalidPartOrder,
 
+    /// The requested partnumber is not satisfiable.
+    InvalidPartNumber,
+
     /// All access to this object has been disabled.
     InvalidPayer,
 
@@ -365,6 +475,9 @@ pub enum S3ErrorCode {
     /// Couldn't parse the specified URI.
     InvalidURI,
 
+    /// The write offset doesn't match the current size of the object.
+    InvalidWriteOffset,
+
     /// Your key is too long.
     KeyTooLongError,
 
@@ -502,7 +615,7 @@ pub enum S3ErrorCode {
 
 impl Display for S3ErrorCode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        <Self as fmt::Debug>::fmt(self, f)
+        <Self as Debug>::fmt(self, f)
     }
 }
 
@@ -532,7 +645,7 @@ macro_rules! roundtrip_variant_and_str
         /// Returns a corresponding error code from a string
         /// # Errors
         /// Returns an `Err` if parsing failed
-        pub fn parse_from_str<'a>(s: &'a str) -> Result<Self, ParseS3ErrorCodeError<'a>> {
+        pub fn parse_from_str(s: &str) -> Result<Self, ParseS3ErrorCodeError<'_>> {
             Ok(match s {
                 $(
                    stringify!($v) =>  Self::$v,
@@ -556,6 +669,7 @@ impl S3ErrorCode {
             Self::AllAccessDisabled => Some(StatusCode::FORBIDDEN),
             Self::AmbiguousGrantByEmailAddress => Some(StatusCode::BAD_REQUEST),
             Self::AuthorizationHeaderMalformed => Some(StatusCode::BAD_REQUEST),
+            Self::AuthorizationQueryParametersError => Some(StatusCode::BAD_REQUEST),
             Self::BadDigest => Some(StatusCode::BAD_REQUEST),
             Self::BucketAlreadyExists => Some(StatusCode::CONFLICT),
             Self::BucketAlreadyOwnedByYou => Some(StatusCode::CONFLICT),
@@ -582,6 +696,7 @@ impl S3ErrorCode {
             Self::InvalidObjectState => Some(StatusCode::FORBIDDEN),
             Self::InvalidPart => Some(StatusCode::BAD_REQUEST),
             Self::InvalidPartOrder => Some(StatusCode::BAD_REQUEST),
+            Self::InvalidPartNumber => Some(StatusCode::RANGE_NOT_SATISFIABLE),
             Self::InvalidPayer => Some(StatusCode::FORBIDDEN),
             Self::InvalidPolicyDocument => Some(StatusCode::BAD_REQUEST),
             Self::InvalidRange => Some(StatusCode::RANGE_NOT_SATISFIABLE),
@@ -592,6 +707,7 @@ impl S3ErrorCode {
             Self::InvalidTargetBucketForLogging => Some(StatusCode::BAD_REQUEST),
             Self::InvalidToken => Some(StatusCode::BAD_REQUEST),
             Self::InvalidURI => Some(StatusCode::BAD_REQUEST),
+            Self::InvalidWriteOffset => Some(StatusCode::BAD_REQUEST),
             Self::KeyTooLongError => Some(StatusCode::BAD_REQUEST),
             Self::MalformedACLError => Some(StatusCode::BAD_REQUEST),
             Self::MalformedPOSTRequest => Some(StatusCode::BAD_REQUEST),
@@ -645,6 +761,7 @@ impl S3ErrorCode {
         AllAccessDisabled,
         AmbiguousGrantByEmailAddress,
         AuthorizationHeaderMalformed,
+        AuthorizationQueryParametersError,
         BadDigest,
         BucketAlreadyExists,
         BucketAlreadyOwnedByYou,
@@ -671,6 +788,7 @@ impl S3ErrorCode {
         InvalidObjectState,
         InvalidPart,
         InvalidPartOrder,
+        InvalidPartNumber,
         InvalidPayer,
         InvalidPolicyDocument,
         InvalidRange,
@@ -681,6 +799,7 @@ impl S3ErrorCode {
         InvalidTargetBucketForLogging,
         InvalidToken,
         InvalidURI,
+        InvalidWriteOffset,
         KeyTooLongError,
         MalformedACLError,
         MalformedPOSTRequest,
@@ -727,3 +846,30 @@ impl S3ErrorCode {
         XAmzContentSHA256Mismatch,
     ];
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::shadow_unrelated)]
+    fn from_io_error() {
+        let err = S3Error::from(io::Error::from(io::ErrorKind::NotFound));
+        assert!(matches!(err.code(), S3ErrorCode::NoSuchKey));
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+        assert!(err.is_client_error() && !err.is_server_error());
+        assert!(err.source().is_some());
+
+        let err = S3Error::from(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(matches!(err.code(), S3ErrorCode::AccessDenied));
+
+        let err = S3Error::from(io::Error::new(io::ErrorKind::Other, "disk failure"));
+        assert!(matches!(err.code(), S3ErrorCode::InternalError));
+        assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(err.is_server_error());
+        assert!(err.span_trace().is_some());
+
+        let err: S3StorageError<()> = io::Error::from(io::ErrorKind::UnexpectedEof).into();
+        assert!(matches!(err, S3StorageError::Other(ref e) if e.is_client_error()));
+    }
+}
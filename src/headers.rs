@@ -5,13 +5,17 @@ mod amz_content_sha256;
 mod amz_copy_source;
 mod amz_date;
 mod authorization_v4;
+mod forwarded;
 mod range;
+mod traceparent;
 
 pub use self::amz_content_sha256::AmzContentSha256;
 pub use self::amz_copy_source::AmzCopySource;
 pub use self::amz_date::AmzDate;
 pub use self::authorization_v4::{AuthorizationV4, CredentialV4};
+pub use self::forwarded::ForwardedFor;
 pub use self::range::Range;
+pub use self::traceparent::TraceParent;
 
 pub use hyper::header::*;
 
@@ -52,6 +56,9 @@ declare_header_name! {
     /// x-amz-request-charged
     X_AMZ_REQUEST_CHARGED: "x-amz-request-charged";
 
+    /// x-amz-request-id
+    X_AMZ_REQUEST_ID: "x-amz-request-id";
+
     /// x-amz-acl
     X_AMZ_ACL: "x-amz-acl";
 
@@ -172,6 +179,9 @@ declare_header_name! {
     /// x-amz-content-sha256
     X_AMZ_CONTENT_SHA256: "x-amz-content-sha256";
 
+    /// x-amz-decoded-content-length
+    X_AMZ_DECODED_CONTENT_LENGTH: "x-amz-decoded-content-length";
+
     /// x-amz-abort-date
     X_AMZ_ABORT_DATE: "x-amz-abort-date";
 
@@ -180,4 +190,22 @@ declare_header_name! {
 
     /// x-amz-expected-bucket-owner
     X_AMZ_EXPECTED_BUCKET_OWNER: "x-amz-expected-bucket-owner";
+
+    /// x-amz-write-offset-bytes
+    X_AMZ_WRITE_OFFSET_BYTES: "x-amz-write-offset-bytes";
+
+    /// x-forwarded-for
+    X_FORWARDED_FOR: "x-forwarded-for";
+
+    /// traceparent
+    TRACEPARENT: "traceparent";
+
+    /// tracestate
+    TRACESTATE: "tracestate";
+
+    /// x-s3-server-canonical-request
+    X_S3_SERVER_CANONICAL_REQUEST: "x-s3-server-canonical-request";
+
+    /// x-s3-server-string-to-sign
+    X_S3_SERVER_STRING_TO_SIGN: "x-s3-server-string-to-sign";
 }
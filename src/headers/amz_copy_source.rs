@@ -81,7 +81,7 @@ impl<'a> AmzCopySource<'a> {
                     return Err(ParseAmzCopySourceError::InvalidBucketName);
                 }
 
-                if !S3Path::check_key(key) {
+                if !S3Path::check_key(key) || !S3Path::is_safe_key(key) {
                     return Err(ParseAmzCopySourceError::InvalidKey);
                 }
 
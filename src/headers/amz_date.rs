@@ -1,5 +1,10 @@
 //! x-amz-date
 
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::NaiveDate;
+
 /// x-amz-date
 #[derive(Debug, Clone, Copy)]
 pub struct AmzDate {
@@ -101,6 +106,35 @@ impl AmzDate {
     /// `YYYYMMDD`
     #[must_use]
     pub fn to_date(&self) -> String {
-        format!("{:04}{:02}{:02}", self.year, self.month, self.day,)
+        format!("{:04}{:02}{:02}", self.year, self.month, self.day)
+    }
+
+    /// Converts to `SystemTime`, returns `None` if the date is invalid
+    #[must_use]
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        let year = i32::try_from(self.year).ok()?;
+        let time = NaiveDate::from_ymd_opt(year, self.month, self.day)?.and_hms_opt(
+            self.hour,
+            self.minute,
+            self.second,
+        )?;
+        let epoch = NaiveDate::from_ymd_opt(1970, 1, 1)?.and_hms_opt(0, 0, 0)?;
+        let secs = u64::try_from(time.signed_duration_since(epoch).num_seconds()).ok()?;
+        UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_time() {
+        let date = AmzDate::from_header_str("20130524T000000Z").unwrap();
+        let expected = UNIX_EPOCH + Duration::from_secs(1_369_353_600);
+        assert_eq!(date.to_system_time(), Some(expected));
+
+        let invalid = AmzDate::from_header_str("20130230T000000Z").unwrap();
+        assert_eq!(invalid.to_system_time(), None);
     }
 }
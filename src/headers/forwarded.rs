@@ -0,0 +1,98 @@
+//! Forwarded and X-Forwarded-For
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Client addresses recorded by proxies, ordered from the client to the nearest proxy
+///
+/// An address is `None` if it is unknown, obfuscated or malformed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForwardedFor {
+    /// addresses
+    addrs: Vec<Option<IpAddr>>,
+}
+
+impl ForwardedFor {
+    /// Parses the `for` parameters of a `Forwarded` header value (RFC 7239)
+    #[must_use]
+    pub fn from_forwarded(header: &str) -> Self {
+        let addrs = header
+            .split(',')
+            .filter_map(|element| {
+                element.split(';').find_map(|pair| {
+                    let (name, value) = pair.split_once('=')?;
+                    name.trim()
+                        .eq_ignore_ascii_case("for")
+                        .then(|| parse_node(value))
+                })
+            })
+            .collect();
+        Self { addrs }
+    }
+
+    /// Parses an `X-Forwarded-For` header value
+    #[must_use]
+    pub fn from_x_forwarded_for(header: &str) -> Self {
+        let addrs = header.split(',').map(parse_node).collect();
+        Self { addrs }
+    }
+
+    /// Appends the addresses of another header value
+    pub fn extend(&mut self, other: Self) {
+        self.addrs.extend(other.addrs);
+    }
+
+    /// Returns the addresses, ordered from the client to the nearest proxy
+    #[must_use]
+    pub fn addrs(&self) -> &[Option<IpAddr>] {
+        &self.addrs
+    }
+}
+
+/// parses a node, such as `192.0.2.43:47011` or `"[2001:db8:cafe::17]:4711"`
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        let (ip, _) = rest.split_once(']')?;
+        return ip.parse::<Ipv6Addr>().ok().map(IpAddr::V6);
+    }
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    let (ip, _port) = node.rsplit_once(':')?;
+    ip.parse::<Ipv4Addr>().ok().map(IpAddr::V4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwarded() {
+        let header = r#"for=192.0.2.60;proto=http;by=203.0.113.43, For="[2001:db8:cafe::17]:4711", for=unknown, for=_hidden, proto=https"#;
+        let ans = ForwardedFor::from_forwarded(header);
+        assert_eq!(
+            ans.addrs(),
+            [
+                Some("192.0.2.60".parse().unwrap()),
+                Some("2001:db8:cafe::17".parse().unwrap()),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn x_forwarded_for() {
+        let header = "203.0.113.195, 2001:db8:85a3::8a2e:370:7334, 198.51.100.1:8080, garbage";
+        let ans = ForwardedFor::from_x_forwarded_for(header);
+        assert_eq!(
+            ans.addrs(),
+            [
+                Some("203.0.113.195".parse().unwrap()),
+                Some("2001:db8:85a3::8a2e:370:7334".parse().unwrap()),
+                Some("198.51.100.1".parse().unwrap()),
+                None,
+            ]
+        );
+    }
+}
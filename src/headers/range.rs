@@ -81,6 +81,32 @@ impl Range {
             Ok((_, ans)) => Ok(ans),
         }
     }
+
+    /// Parses a `Range` header of one or more comma-separated ranges, such as `bytes=0-9, -10`
+    /// # Errors
+    /// Returns an error if the header or any range is invalid
+    pub fn from_header_str_multi(header: &str) -> Result<Vec<Self>, ParseRangeError> {
+        let specs = header
+            .strip_prefix("bytes=")
+            .ok_or(ParseRangeError { _priv: () })?;
+        specs
+            .split(',')
+            .map(|spec| Self::from_header_str(&format!("bytes={}", spec.trim())))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Range {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Normal { first, last: None } => write!(f, "bytes={first}-"),
+            Self::Normal {
+                first,
+                last: Some(last),
+            } => write!(f, "bytes={first}-{last}"),
+            Self::Suffix { last } => write!(f, "bytes=-{last}"),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -137,4 +163,14 @@ mod tests {
             assert!(result.is_err());
         }
     }
+
+    #[test]
+    fn multiple_ranges() {
+        let ranges = Range::from_header_str_multi("bytes=0-9, 20-,-5").unwrap();
+        let strs: Vec<String> = ranges.iter().map(ToString::to_string).collect();
+        assert_eq!(strs, ["bytes=0-9", "bytes=20-", "bytes=-5"]);
+
+        assert!(Range::from_header_str_multi("bytes=0-9,").is_err());
+        assert!(Range::from_header_str_multi("0-9, 20-").is_err());
+    }
 }
@@ -0,0 +1,102 @@
+//! traceparent
+
+/// `traceparent`
+///
+/// See [W3C Trace Context](https://www.w3.org/TR/trace-context/#traceparent-header)
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceParent<'a> {
+    /// trace id, 32 lowercase hex digits
+    pub trace_id: &'a str,
+    /// span id of the caller, 16 lowercase hex digits
+    pub parent_id: &'a str,
+    /// trace flags, 2 lowercase hex digits
+    pub trace_flags: &'a str,
+}
+
+/// `ParseTraceParentError`
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("ParseTraceParentError")]
+pub struct ParseTraceParentError {
+    /// priv place holder
+    _priv: (),
+}
+
+/// checks whether a field consists of `len` lowercase hex digits
+fn is_hex_field(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+impl<'a> TraceParent<'a> {
+    /// parse `TraceParent` from `traceparent` header
+    ///
+    /// Versions after `00` are parsed by the fields of `00`, ignoring the fields after them.
+    /// # Errors
+    /// Returns an `Err` if the header is invalid
+    pub fn from_header_str(header: &'a str) -> Result<Self, ParseTraceParentError> {
+        let err = ParseTraceParentError { _priv: () };
+
+        let mut fields = header.trim().split('-');
+        let version = fields.next().ok_or(err)?;
+        let trace_id = fields.next().ok_or(err)?;
+        let parent_id = fields.next().ok_or(err)?;
+        let trace_flags = fields.next().ok_or(err)?;
+
+        if !is_hex_field(version, 2) || version == "ff" {
+            return Err(err);
+        }
+        if version == "00" && fields.next().is_some() {
+            return Err(err);
+        }
+        if !is_hex_field(trace_id, 32) || trace_id.bytes().all(|b| b == b'0') {
+            return Err(err);
+        }
+        if !is_hex_field(parent_id, 16) || parent_id.bytes().all(|b| b == b'0') {
+            return Err(err);
+        }
+        if !is_hex_field(trace_flags, 2) {
+            return Err(err);
+        }
+
+        Ok(Self {
+            trace_id,
+            parent_id,
+            trace_flags,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let ans = TraceParent::from_header_str(header).unwrap();
+        assert_eq!(ans.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(ans.parent_id, "00f067aa0ba902b7");
+        assert_eq!(ans.trace_flags, "01");
+
+        let future = "cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-what-the-future";
+        assert!(TraceParent::from_header_str(future).is_ok());
+
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-00",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1",
+        ] {
+            assert!(
+                TraceParent::from_header_str(invalid).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+}
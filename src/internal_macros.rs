@@ -118,10 +118,11 @@ macro_rules! invalid_request {
 
 /// Create a `SignatureDoesNotMatch` error
 macro_rules! signature_mismatch {
-    () => {{
+    ($($source:expr)?) => {{
         code_error!(
             SignatureDoesNotMatch,
             "The request signature we calculated does not match the signature you provided."
+            $(, $source)?
         )
     }};
 }
@@ -0,0 +1,302 @@
+//! Bearer-token authentication with JSON Web Tokens (JWT) of OIDC identity providers
+//!
+//! This module is enabled by the feature `jwt`.
+//!
+//! [`JwtAuth`] wraps another [`S3Auth`] and accepts `Authorization: Bearer <jwt>`
+//! besides signed requests. A token is verified against the keys of a [`JwksSource`],
+//! and one of its claims becomes the access key id of the request.
+//!
+//! ```no_run
+//! use s3_server::jwt::{HttpJwksSource, JwtAuth};
+//! use s3_server::{S3Service, SimpleAuth};
+//! # use s3_server::storages::fs::FileSystem;
+//!
+//! # fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! # let mut service = S3Service::new(FileSystem::new(".")?);
+//! let source = HttpJwksSource::new("http://idp.internal/.well-known/jwks.json".parse()?);
+//! let mut auth = JwtAuth::new(SimpleAuth::new(), source);
+//! auth.set_issuer(["http://idp.internal"]);
+//! auth.set_audience(["s3"]);
+//! service.set_auth(auth);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::auth::{ClientCertificate, RequestUsage, ResourceAccess, S3Auth};
+use crate::errors::{S3AuthError, S3Error};
+use crate::ops::OperationKind;
+use crate::utils::http_client;
+
+use std::io;
+use std::sync::{Mutex, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use hyper::Uri;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{DecodingKey, Validation};
+use serde_json::{Map, Value};
+use tracing::debug;
+
+pub use jsonwebtoken::jwk::JwkSet;
+pub use jsonwebtoken::Algorithm;
+
+/// min interval between refreshes of the key set, which are triggered by unknown key ids
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A source of the JSON Web Key Set (JWKS) of an identity provider
+#[async_trait]
+pub trait JwksSource {
+    /// Fetches the current key set
+    async fn fetch_jwks(&self) -> io::Result<JwkSet>;
+}
+
+/// A static key set, which is never rotated
+#[async_trait]
+impl JwksSource for JwkSet {
+    async fn fetch_jwks(&self) -> io::Result<Self> {
+        Ok(self.clone())
+    }
+}
+
+/// Fetches a key set by `GET` from a JWKS endpoint
+///
+/// Only `http` is supported. An `https` endpoint can be reached
+/// through a TLS-terminating proxy, or by implementing [`JwksSource`] with another client.
+#[derive(Debug)]
+pub struct HttpJwksSource {
+    /// JWKS endpoint
+    uri: Uri,
+}
+
+impl HttpJwksSource {
+    /// Constructs a `HttpJwksSource` of the endpoint
+    #[must_use]
+    pub const fn new(uri: Uri) -> Self {
+        Self { uri }
+    }
+}
+
+#[async_trait]
+impl JwksSource for HttpJwksSource {
+    async fn fetch_jwks(&self) -> io::Result<JwkSet> {
+        // a connection for each fetch, which is rare
+        let (status, body) = http_client::get(&self.uri, &[]).await?;
+        if !status.is_success() {
+            let msg = format!("JWKS endpoint responded {status}");
+            return Err(io::Error::new(io::ErrorKind::Other, msg));
+        }
+        serde_json::from_slice(&body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// An authentication provider which accepts JWT bearer tokens
+///
+/// Other requests are authenticated by the inner provider.
+pub struct JwtAuth<A> {
+    /// inner provider
+    inner: A,
+    /// key set source
+    source: Box<dyn JwksSource + Send + Sync + 'static>,
+    /// cached key set
+    keys: RwLock<Option<JwkSet>>,
+    /// time of the last refresh
+    last_refresh: Mutex<Option<Instant>>,
+    /// accepted signature algorithms
+    algorithms: Vec<Algorithm>,
+    /// accepted issuers, any issuer if empty
+    issuers: Vec<String>,
+    /// accepted audiences, any audience if empty
+    audiences: Vec<String>,
+    /// the claim which becomes the access key id
+    identity_claim: String,
+}
+
+impl<A> std::fmt::Debug for JwtAuth<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtAuth")
+            .field("algorithms", &self.algorithms)
+            .field("issuers", &self.issuers)
+            .field("audiences", &self.audiences)
+            .field("identity_claim", &self.identity_claim)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<A: Send + Sync> JwtAuth<A> {
+    /// Constructs a `JwtAuth` which verifies tokens with the keys of the source
+    ///
+    /// By default, tokens signed by asymmetric algorithms are accepted
+    /// and the `sub` claim becomes the access key id.
+    pub fn new(inner: A, source: impl JwksSource + Send + Sync + 'static) -> Self {
+        Self {
+            inner,
+            source: Box::new(source),
+            keys: RwLock::new(None),
+            last_refresh: Mutex::new(None),
+            algorithms: vec![
+                Algorithm::RS256,
+                Algorithm::RS384,
+                Algorithm::RS512,
+                Algorithm::PS256,
+                Algorithm::PS384,
+                Algorithm::PS512,
+                Algorithm::ES256,
+                Algorithm::ES384,
+                Algorithm::EdDSA,
+            ],
+            issuers: Vec::new(),
+            audiences: Vec::new(),
+            identity_claim: "sub".into(),
+        }
+    }
+
+    /// Sets the accepted signature algorithms
+    pub fn set_algorithms(&mut self, algorithms: impl IntoIterator<Item = Algorithm>) {
+        self.algorithms = algorithms.into_iter().collect();
+    }
+
+    /// Sets the accepted issuers (`iss`)
+    pub fn set_issuer(&mut self, issuers: impl IntoIterator<Item = impl Into<String>>) {
+        self.issuers = issuers.into_iter().map(Into::into).collect();
+    }
+
+    /// Sets the accepted audiences (`aud`)
+    pub fn set_audience(&mut self, audiences: impl IntoIterator<Item = impl Into<String>>) {
+        self.audiences = audiences.into_iter().map(Into::into).collect();
+    }
+
+    /// Sets the string claim which becomes the access key id, such as `email`
+    pub fn set_identity_claim(&mut self, claim: impl Into<String>) {
+        self.identity_claim = claim.into();
+    }
+
+    /// finds the decoding key of a key id, refreshing the key set if it is unknown
+    async fn find_key(&self, kid: Option<&str>) -> Result<Option<DecodingKey>, S3Error> {
+        let find = |keys: &JwkSet| {
+            let jwk = match kid {
+                Some(kid) => keys.find(kid),
+                None => keys.keys.first(),
+            };
+            jwk.map(DecodingKey::from_jwk)
+        };
+
+        let cached = self
+            .keys
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .and_then(find);
+        if let Some(key) = cached {
+            return key.map(Some).map_err(|e| invalid_token(&e));
+        }
+
+        {
+            let mut last_refresh = self
+                .last_refresh
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let now = Instant::now();
+            if last_refresh.map_or(false, |t| {
+                now.saturating_duration_since(t) < MIN_REFRESH_INTERVAL
+            }) {
+                return Ok(None);
+            }
+            *last_refresh = Some(now);
+        }
+
+        let keys = trace_try!(self.source.fetch_jwks().await);
+        debug!(count = keys.keys.len(), "refreshed JWKS");
+        let ans = find(&keys).transpose().map_err(|e| invalid_token(&e));
+        *self.keys.write().unwrap_or_else(PoisonError::into_inner) = Some(keys);
+        ans
+    }
+
+    /// verifies a token and returns its identity claim
+    async fn verify(&self, token: &str) -> Result<String, S3Error> {
+        let header = jsonwebtoken::decode_header(token).map_err(|e| invalid_token(&e))?;
+        if !self.algorithms.contains(&header.alg) {
+            return Err(code_error!(
+                InvalidToken,
+                "The provided token is malformed or otherwise invalid."
+            ));
+        }
+        let key = self.find_key(header.kid.as_deref()).await?.ok_or_else(|| {
+            code_error!(
+                InvalidToken,
+                "The provided token is signed by an unknown key."
+            )
+        })?;
+
+        let mut validation = Validation::new(header.alg);
+        if !self.issuers.is_empty() {
+            validation.set_issuer(&self.issuers);
+        }
+        if !self.audiences.is_empty() {
+            validation.set_audience(&self.audiences);
+        }
+        let claims = jsonwebtoken::decode::<Map<String, Value>>(token, &key, &validation)
+            .map_err(|e| invalid_token(&e))?
+            .claims;
+
+        match claims.get(&self.identity_claim) {
+            Some(&Value::String(ref identity)) => Ok(identity.clone()),
+            _ => Err(code_error!(
+                InvalidToken,
+                "The provided token has no identity claim."
+            )),
+        }
+    }
+}
+
+/// converts a verification error
+fn invalid_token(err: &jsonwebtoken::errors::Error) -> S3Error {
+    debug!(%err, "invalid bearer token");
+    if *err.kind() == ErrorKind::ExpiredSignature {
+        code_error!(ExpiredToken, "The provided token has expired.")
+    } else {
+        code_error!(
+            InvalidToken,
+            "The provided token is malformed or otherwise invalid."
+        )
+    }
+}
+
+#[async_trait]
+impl<A> S3Auth for JwtAuth<A>
+where
+    A: S3Auth + Send + Sync,
+{
+    async fn get_secret_access_key(&self, access_key_id: &str) -> Result<String, S3AuthError> {
+        self.inner.get_secret_access_key(access_key_id).await
+    }
+
+    async fn identify_client_cert(
+        &self,
+        cert: &ClientCertificate,
+    ) -> Result<Option<String>, S3AuthError> {
+        self.inner.identify_client_cert(cert).await
+    }
+
+    async fn identify_bearer_token(&self, token: &str) -> Result<Option<String>, S3AuthError> {
+        self.verify(token)
+            .await
+            .map(Some)
+            .map_err(S3AuthError::Other)
+    }
+
+    async fn authorize(
+        &self,
+        access_key_id: &str,
+        operation: OperationKind,
+        resources: &[ResourceAccess<'_>],
+    ) -> Result<bool, S3AuthError> {
+        self.inner
+            .authorize(access_key_id, operation, resources)
+            .await
+    }
+
+    fn record_usage(&self, usage: RequestUsage) {
+        self.inner.record_usage(usage);
+    }
+}
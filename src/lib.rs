@@ -8,12 +8,22 @@
 //!
 //! [`S3Service`] contains internal handlers, a storage and a optional auth provider.
 //!
-//! [`hyper::service::Service<Request>`] is implemented for [`SharedS3Service`].
+//! Both [`hyper::service::Service`] and `tower_service::Service` are implemented for [`SharedS3Service`],
+//! accepting requests of any body type, such as [`hyper::body::Incoming`].
+//! Responses carry a [`Body`].
 //!
 //! When a new tcp stream connects, the server should clone [`SharedS3Service`], then handle each http request by `S3Service::hyper_call` or `S3Service::handle`.
 //!
 //! An [`S3Service`] instance can be integrated into a [`hyper`] application.
 //!
+//! [`SharedS3Service::into_make_service`] creates a service for each connection of a tower server.
+//! [`SharedS3Service::mount`] serves the S3 API under a path prefix,
+//! which can be embedded into an existing web application as a tower service.
+//!
+//! The request body is not read until the request is authenticated and the storage accepts it.
+//! Since hyper sends the interim `100 Continue` response when the body is first read,
+//! a client sending `Expect: 100-continue` receives the final error without uploading the body.
+//!
 //! See `src/bin/s3-server.rs` for how to setup an [`S3Service`].
 //!
 //! ### Trait: `S3Storage`
@@ -65,6 +75,7 @@
 //! All types in `src/headers` are http headers which may occur in an S3 http request.
 //!
 //! All types in `src/streams` are http body streams which may occur in an S3 http request.
+//! The multipart/form-data parser in `streams::multipart` is public.
 
 #![forbid(unsafe_code)]
 #![deny(
@@ -115,37 +126,94 @@
     clippy::assertions_on_result_states, // It is a common pattern in tests
     clippy::self_named_module_files, // Prefer self named module files as the new standard
     clippy::missing_trait_methods, // Recommended to disable globally
+    clippy::question_mark_used, // The `?` operator is idiomatic Rust code
+    clippy::min_ident_chars, // Short names like `f`, `s`, `x` are common in small scopes
+    clippy::single_call_fn, // Splitting logic into small functions is fine
+    clippy::arbitrary_source_item_ordering, // Items are ordered by relevance, not alphabetically
+    clippy::doc_paragraphs_missing_punctuation, // Short doc comments are noun phrases
+    clippy::unused_trait_names, // Conflicts with rustfmt import style
+    clippy::ref_patterns, // It is a common pattern
+    clippy::absolute_paths, // Fully qualified paths are clearer in some places
+    clippy::impl_trait_in_params, // It is a common pattern
+    clippy::pub_with_shorthand, // Conflicts with `clippy::pub_without_shorthand`
+    clippy::semicolon_outside_block, // Conflicts with `clippy::semicolon_inside_block`
+    clippy::multiple_crate_versions, // Dependency tree is out of our control
+    clippy::needless_borrowed_reference, // Conflicts with `clippy::pattern_type_mismatch`
 )]
 #![cfg_attr(test, allow(
     clippy::unwrap_used, // Tests need [`unwrap`]
     clippy::indexing_slicing, // Fail fast
-    clippy::expect_used
+    clippy::arithmetic_side_effects, // Overflow panics are acceptable in tests
+    clippy::default_numeric_fallback, // Literals in tests are self-explanatory
+    clippy::let_underscore_untyped, // Outputs of setup steps are ignored
+    clippy::tests_outside_test_module, // Tests generated by macros
 ))]
 
 #[macro_use]
 mod internal_macros;
 
+#[macro_use]
 pub(crate) mod utils;
 
+mod body;
 mod data_structures;
 mod ops;
 mod output;
+mod post_policy;
 mod signature_v4;
-mod streams;
 
+mod acl;
+mod admin;
+mod audit;
 mod auth;
+mod clock;
+mod content_types;
+mod cors;
+mod limits;
+mod replay;
+mod scope;
 mod service;
 mod storage;
 
-pub use self::auth::{S3Auth, SimpleAuth};
-pub use self::service::{S3Service, SharedS3Service};
+pub use self::acl::{IpCidr, IpRules, NetworkAcl, ParseIpCidrError};
+pub use self::admin::AdminService;
+pub use self::audit::{
+    verify_audit_chain, AuditChainError, AuditLog, AuditRecord, AuditSink, BucketAuditSink,
+    FileAuditSink,
+};
+pub use self::auth::{
+    AnonymousPolicy, ClientCertificate, PublicRead, RequestUsage, ResourceAccess, S3Auth,
+    SignatureDebug, SimpleAuth,
+};
+pub use self::body::Body;
+pub use self::clock::{Clock, ManualClock, SystemClock};
+pub use self::content_types::ContentTypes;
+pub use self::cors::{CorsConfig, CorsHeaders, CorsRule};
+pub use self::limits::ObjectLimits;
+pub use self::ops::{OperationDoc, OperationKind, Resource, Route};
+pub use self::replay::ReplayCache;
+pub use self::scope::{Permission, ScopedAuth};
+pub use self::service::{
+    MakeSharedS3Service, MountedS3Service, RemoteAddr, S3Service, SharedS3Service,
+};
 pub use self::storage::S3Storage;
 
 pub mod dto;
 pub mod errors;
 pub mod headers;
 pub mod path;
+pub mod secrets;
 pub mod storages;
+pub mod streams;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[cfg(feature = "compliance")]
+pub mod compliance;
+
+#[cfg(feature = "jwt")]
+pub mod jwt;
 
 /// Request type
 pub(crate) type Request = hyper::Request<Body>;
@@ -157,5 +225,5 @@ pub(crate) type Response = hyper::Response<Body>;
 pub(crate) type BoxStdError = Box<dyn std::error::Error + Send + Sync + 'static>;
 
 pub(crate) use async_trait::async_trait;
-pub(crate) use hyper::{Body, Method, StatusCode};
+pub(crate) use hyper::{Method, StatusCode};
 pub(crate) use mime::Mime;
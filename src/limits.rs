@@ -0,0 +1,35 @@
+//! size limits of objects and multipart uploads
+
+/// Size limits of objects and multipart uploads, which are the limits of S3 by default
+///
+/// See <https://docs.aws.amazon.com/AmazonS3/latest/userguide/qfacts.html>
+///
+/// [`S3Service`](crate::S3Service) checks the sizes declared by requests and the part numbers.
+/// The sizes of uploaded parts are only known to the storage,
+/// so `min_part_size` and `max_multipart_object_size` are checked by the storage
+/// when the parts are assembled, such as by
+/// [`FileSystem`](crate::storages::fs::FileSystem) with
+/// [`FileSystemConfig::limits`](crate::storages::fs::FileSystemConfig::limits).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ObjectLimits {
+    /// max size of an object uploaded by a single request, and of a part, 5 GiB by default
+    pub max_object_size: u64,
+    /// max size of an object assembled from parts, 5 TiB by default
+    pub max_multipart_object_size: u64,
+    /// max number of parts of a multipart upload, 10000 by default
+    pub max_parts: u32,
+    /// min size of each part except the last one, 5 MiB by default
+    pub min_part_size: u64,
+}
+
+impl Default for ObjectLimits {
+    fn default() -> Self {
+        Self {
+            max_object_size: 5 * 1024 * 1024 * 1024,
+            max_multipart_object_size: 5 * 1024 * 1024 * 1024 * 1024,
+            max_parts: 10000,
+            min_part_size: 5 * 1024 * 1024,
+        }
+    }
+}
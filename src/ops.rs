@@ -2,6 +2,9 @@
 
 #![allow(clippy::unnecessary_wraps, clippy::panic_in_result_fn)]
 
+#[macro_use]
+mod header_fields;
+
 mod complete_multipart_upload;
 mod copy_object;
 mod create_bucket;
@@ -9,25 +12,48 @@ mod create_multipart_upload;
 mod delete_bucket;
 mod delete_object;
 mod delete_objects;
+mod get_bucket_accelerate_configuration;
 mod get_bucket_location;
+mod get_bucket_request_payment;
 mod get_object;
 mod head_bucket;
 mod head_object;
 mod list_buckets;
+mod list_object_versions;
 mod list_objects;
 mod list_objects_v2;
+mod put_bucket_accelerate_configuration;
+mod put_bucket_request_payment;
 mod put_object;
+mod rename_bucket;
+mod rename_object;
+mod undelete_object;
 mod upload_part;
 
+mod api_doc;
+mod operation_kind;
+mod validation;
+
+pub use self::api_doc::{openapi, OperationDoc, Resource, Route};
+pub use self::operation_kind::OperationKind;
+
+use crate::auth::ResourceAccess;
+use crate::content_types::ContentTypes;
 use crate::data_structures::{OrderedHeaders, OrderedQs};
+use crate::dto::{CommonPrefix, HeadObjectRequest, Object, Owner};
 use crate::errors::S3Result;
+use crate::headers::{CONTENT_LENGTH, IF_NONE_MATCH, RANGE, X_AMZ_DECODED_CONTENT_LENGTH};
+use crate::limits::ObjectLimits;
 use crate::path::S3Path;
-use crate::storage::S3Storage;
+use crate::storage::{stat_or_missing, S3Storage};
 use crate::streams::multipart::Multipart;
+use crate::utils::is_xml_char;
 use crate::{async_trait, Body, BoxStdError, Mime, Request, Response};
 
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::mem;
+use std::sync::Arc;
 
 use hyper::header::AsHeaderName;
 
@@ -46,14 +72,22 @@ pub fn setup_handlers() -> Vec<Box<dyn S3Handler + Send + Sync + 'static>> {
         delete_bucket,
         delete_object,
         delete_objects,
+        get_bucket_accelerate_configuration,
         get_bucket_location,
+        get_bucket_request_payment,
         get_object,
         head_bucket,
         head_object,
         list_buckets,
+        list_object_versions,
         list_objects,
         list_objects_v2,
+        put_bucket_accelerate_configuration,
+        put_bucket_request_payment,
         put_object,
+        rename_bucket,
+        rename_object,
+        undelete_object,
         upload_part,
     ]
 }
@@ -61,6 +95,12 @@ pub fn setup_handlers() -> Vec<Box<dyn S3Handler + Send + Sync + 'static>> {
 /// S3 operation handler
 #[async_trait]
 pub trait S3Handler {
+    /// the operation of the handler
+    fn kind(&self) -> OperationKind;
+
+    /// the documentation of the operation, listing the forms of requests matched by the handler
+    fn doc(&self) -> OperationDoc;
+
     /// determine if the handler matches current request
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool;
 
@@ -77,6 +117,8 @@ pub trait S3Handler {
 pub struct ReqContext<'a> {
     /// req
     pub req: &'a Request,
+    /// decoded uri path
+    pub uri_path: &'a str,
     /// ordered headers
     pub headers: OrderedHeaders<'a>,
     /// query strings
@@ -89,6 +131,20 @@ pub struct ReqContext<'a> {
     pub mime: Option<Mime>,
     /// multipart/form-data
     pub multipart: Option<Multipart>,
+    /// allowed file size of POST Object, from the `content-length-range` condition of the policy
+    pub content_length_range: Option<(u64, u64)>,
+    /// access key id of the verified signature
+    pub access_key_id: Option<String>,
+    /// whether the bucket is write-once-read-many
+    pub worm: bool,
+    /// size limits of objects and multipart uploads
+    pub limits: ObjectLimits,
+    /// content types guessed by the extensions of keys
+    pub content_types: Option<Arc<ContentTypes>>,
+    /// max number of keys in a page of listings
+    pub max_keys: i64,
+    /// owner of the objects in the bucket, resolved by the auth provider for `fetch-owner=true`
+    pub owner: Option<Owner>,
 }
 
 impl<'a> ReqContext<'a> {
@@ -117,6 +173,23 @@ impl<'a> ReqContext<'a> {
         }
     }
 
+    /// guesses the content type of an object by its key
+    fn guess_content_type(&self, bucket: &str, key: &str) -> Option<String> {
+        self.content_types.as_ref()?.guess(bucket, key)
+    }
+
+    /// the content type of an object whose storage records none,
+    /// which is guessed by its key or `application/octet-stream`
+    fn default_content_type(&self, bucket: &str, key: &str) -> String {
+        self.guess_content_type(bucket, key)
+            .unwrap_or_else(|| mime::APPLICATION_OCTET_STREAM.as_ref().to_owned())
+    }
+
+    /// lowers `max-keys` of a listing to the limit of the service
+    fn clamp_max_keys(&self, max_keys: &mut Option<i64>) {
+        *max_keys = Some(max_keys.map_or(self.max_keys, |n| n.min(self.max_keys)));
+    }
+
     /// get query string
     fn unwrap_qs(&self, name: &str) -> &str {
         match self.query_strings.as_ref().and_then(|qs| qs.get(name)) {
@@ -125,6 +198,84 @@ impl<'a> ReqContext<'a> {
         }
     }
 
+    /// lists the resources accessed by an operation, see [`S3Auth::authorize`](crate::S3Auth::authorize)
+    ///
+    /// `copy_source` is the decoded source object of `CopyObject`.
+    pub fn resource_accesses<'c>(
+        &'c self,
+        kind: OperationKind,
+        copy_source: Option<(&'c str, &'c str)>,
+    ) -> Vec<ResourceAccess<'c>> {
+        use OperationKind as K;
+
+        let (bucket, key) = match self.path {
+            S3Path::Root => (None, None),
+            S3Path::Bucket { bucket } => (Some(bucket), None),
+            S3Path::Object { bucket, key } => (Some(bucket), Some(key)),
+        };
+        let qs = |name: &str| -> Option<&str> { self.query_strings.as_ref()?.get(name) };
+        let access = |b, k, is_prefix, write| ResourceAccess {
+            bucket: b,
+            key: k,
+            is_prefix,
+            write,
+        };
+
+        match kind {
+            K::ListBuckets
+            | K::HeadBucket
+            | K::GetBucketLocation
+            | K::GetBucketAccelerateConfiguration
+            | K::GetBucketRequestPayment => vec![access(bucket, None, false, false)],
+            K::CreateBucket
+            | K::DeleteBucket
+            | K::DeleteObjects
+            | K::PutBucketAccelerateConfiguration
+            | K::PutBucketRequestPayment => vec![access(bucket, None, false, true)],
+            K::RenameBucket => vec![
+                access(bucket, None, false, true),
+                access(qs("rename"), None, false, true),
+            ],
+            K::ListObjects | K::ListObjectsV2 | K::ListObjectVersions => {
+                vec![access(
+                    bucket,
+                    Some(qs("prefix").unwrap_or("")),
+                    true,
+                    false,
+                )]
+            }
+            K::GetObject | K::HeadObject => vec![access(bucket, key, false, false)],
+            K::PutObject if self.path.is_bucket() => {
+                let form_key = self
+                    .multipart
+                    .as_ref()
+                    .and_then(|m| m.find_field_value("key"));
+                vec![access(bucket, form_key, false, true)]
+            }
+            K::PutObject
+            | K::CreateMultipartUpload
+            | K::UploadPart
+            | K::CompleteMultipartUpload
+            | K::DeleteObject
+            | K::UndeleteObject => vec![access(bucket, key, false, true)],
+            K::CopyObject => {
+                let mut ans = vec![access(bucket, key, false, true)];
+                if let Some((src_bucket, src_key)) = copy_source {
+                    ans.push(access(Some(src_bucket), Some(src_key), false, false));
+                }
+                ans
+            }
+            K::RenameObject => {
+                // a key ending with a slash renames all keys under the prefix
+                let is_prefix = key.map_or(false, |k| k.ends_with('/'));
+                vec![
+                    access(bucket, key, is_prefix, true),
+                    access(bucket, qs("rename"), is_prefix, true),
+                ]
+            }
+        }
+    }
+
     /// get header
     fn unwrap_header(&self, name: impl AsHeaderName + Debug) -> &str {
         let s = match self.headers.get(name.as_str()) {
@@ -136,6 +287,154 @@ impl<'a> ReqContext<'a> {
     }
 }
 
+/// prefix of user-defined metadata headers and form fields
+const META_PREFIX: &str = "x-amz-meta-";
+
+/// collects user-defined metadata from lowercase `x-amz-meta-*` headers or form fields
+///
+/// The values of a repeated key are joined by commas.
+fn collect_metadata<'f>(
+    fields: impl IntoIterator<Item = (&'f str, &'f str)>,
+) -> Option<HashMap<String, String>> {
+    let mut metadata: HashMap<String, String> = HashMap::new();
+    for (name, value) in fields {
+        let key = match name.strip_prefix(META_PREFIX) {
+            Some(key) if !key.is_empty() => validation::sanitize_metadata_key(key),
+            _ => continue,
+        };
+        let _value = metadata
+            .entry(key)
+            .and_modify(|v| {
+                v.push(',');
+                v.push_str(value);
+            })
+            .or_insert_with(|| value.to_owned());
+    }
+    (!metadata.is_empty()).then_some(metadata)
+}
+
+/// url-encodes keys in listings if `encoding-type=url` is requested
+///
+/// Without it, keys with chars which are not allowed in xml 1.0 are rejected.
+fn listed_key_encoder(
+    encoding_type: Option<&str>,
+) -> impl Fn(Option<String>) -> S3Result<Option<String>> {
+    let is_url = encoding_type == Some("url");
+    move |s| {
+        if is_url {
+            return Ok(s.map(|s| urlencoding::encode(&s).into_owned()));
+        }
+        if s.as_deref().map_or(false, |s| !s.chars().all(is_xml_char)) {
+            return Err(code_error!(
+                InvalidArgument,
+                "The listing contains characters which are not allowed in XML 1.0, use encoding-type=url."
+            ));
+        }
+        Ok(s)
+    }
+}
+
+impl_xml_element! {
+    Owner {
+        "DisplayName" => display_name,
+        "ID" => id,
+    }
+    Object {
+        "Key" => key,
+        "LastModified" => last_modified,
+        "ETag" => e_tag,
+        "Size" => size,
+        "StorageClass" => storage_class,
+        "Owner" => owner,
+    }
+    CommonPrefix {
+        "Prefix" => prefix,
+    }
+}
+
+/// returns whether a write must create the object, which is checked by the storage on commit
+///
+/// Objects of a WORM bucket are never overwritten. `If-None-Match` only supports `*`,
+/// which means the write succeeds only if the key does not exist.
+/// See [`S3Storage::put_object_if_absent`].
+fn is_create_only(ctx: &ReqContext<'_>) -> S3Result<bool> {
+    let value = match ctx.headers.get(IF_NONE_MATCH) {
+        Some(value) => value,
+        None => return Ok(ctx.worm),
+    };
+    if value.trim() != "*" {
+        return Err(code_error!(
+            NotImplemented,
+            "Only `If-None-Match: *` is supported for writes."
+        ));
+    }
+    Ok(true)
+}
+
+/// checks whether an object exists
+async fn object_exists(
+    storage: &(dyn S3Storage + Send + Sync),
+    bucket: &str,
+    key: &str,
+) -> S3Result<bool> {
+    let input = HeadObjectRequest {
+        bucket: bucket.into(),
+        key: key.into(),
+        ..HeadObjectRequest::default()
+    };
+    let output = stat_or_missing(storage.stat_object(input).await)?;
+    Ok(output.is_some())
+}
+
+/// checks the declared body size of an upload against `max_object_size`
+///
+/// The size of an `aws-chunked` body is `x-amz-decoded-content-length`.
+fn check_upload_size(ctx: &ReqContext<'_>) -> S3Result<()> {
+    let value = match ctx
+        .headers
+        .get(X_AMZ_DECODED_CONTENT_LENGTH)
+        .or_else(|| ctx.headers.get(CONTENT_LENGTH))
+    {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+    let size = value
+        .parse::<u64>()
+        .map_err(|err| invalid_request!("Invalid header: content-length", err))?;
+    if size > ctx.limits.max_object_size {
+        return Err(code_error!(
+            EntityTooLarge,
+            "Your proposed upload exceeds the maximum allowed size."
+        ));
+    }
+    Ok(())
+}
+
+/// extracts the `partNumber` query of `GetObject` and `HeadObject`
+fn extract_part_number(ctx: &ReqContext<'_>) -> S3Result<Option<i64>> {
+    let value = match ctx
+        .query_strings
+        .as_ref()
+        .and_then(|qs| qs.get("partNumber"))
+    {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let part_number = value.parse::<i64>().map_err(|err| {
+        code_error!(
+            InvalidArgument,
+            "Part number must be an integer between 1 and 10000, inclusive",
+            err
+        )
+    })?;
+    if ctx.headers.get(RANGE).is_some() {
+        return Err(invalid_request!(
+            "Cannot specify both Range header and partNumber query parameter"
+        ));
+    }
+    Ok(Some(part_number))
+}
+
 /// wrap any error as an internal error
 fn wrap_internal_error(
     f: impl FnOnce(&mut Response) -> Result<(), BoxStdError>,
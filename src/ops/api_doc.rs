@@ -0,0 +1,178 @@
+//! API documentation of the supported S3 operations
+
+use super::OperationKind;
+
+use crate::Method;
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Map, Value};
+
+/// The resource addressed by the path of a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Resource {
+    /// `/`
+    Service,
+    /// `/{Bucket}`
+    Bucket,
+    /// `/{Bucket}/{Key+}`
+    Object,
+}
+
+impl Resource {
+    /// Returns the path template, such as `"/{Bucket}/{Key+}"`
+    #[must_use]
+    pub const fn path_template(self) -> &'static str {
+        match self {
+            Self::Service => "/",
+            Self::Bucket => "/{Bucket}",
+            Self::Object => "/{Bucket}/{Key+}",
+        }
+    }
+}
+
+/// A form of requests routed to a handler
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone)]
+pub struct Route {
+    /// HTTP method
+    pub method: Method,
+    /// the addressed resource
+    pub resource: Resource,
+    /// required query parameters, as `name` or `name=value`
+    pub query: &'static [&'static str],
+    /// required headers
+    pub headers: &'static [&'static str],
+}
+
+impl Route {
+    /// Constructs a route without required query parameters or headers
+    #[must_use]
+    pub const fn new(method: Method, resource: Resource) -> Self {
+        Self {
+            method,
+            resource,
+            query: &[],
+            headers: &[],
+        }
+    }
+
+    /// Requires query parameters, as `name` or `name=value`
+    #[must_use]
+    pub const fn query(mut self, query: &'static [&'static str]) -> Self {
+        self.query = query;
+        self
+    }
+
+    /// Requires headers
+    #[must_use]
+    pub const fn headers(mut self, headers: &'static [&'static str]) -> Self {
+        self.headers = headers;
+        self
+    }
+}
+
+/// Documentation of an operation, attached to its handler
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone)]
+pub struct OperationDoc {
+    /// the operation
+    pub kind: OperationKind,
+    /// forms of requests routed to the operation
+    pub routes: Vec<Route>,
+    /// reference of the S3 API, `None` for custom operations
+    pub reference: Option<&'static str>,
+}
+
+/// generates an `OpenAPI 3.0` description of operations
+///
+/// Like the `OpenAPI` models of the AWS SDKs, query parameters selecting subresources
+/// are part of the path keys, and operations sharing a path are told apart by `x-id`.
+pub fn openapi(title: &str, version: &str, operations: &[OperationDoc]) -> Value {
+    let mut paths: BTreeMap<String, Map<String, Value>> = BTreeMap::new();
+
+    // a request without the required headers of another route is routed by path and method,
+    // so those routes keep the plain path keys
+    let mut routes: Vec<_> = operations
+        .iter()
+        .flat_map(|doc| doc.routes.iter().map(move |route| (doc, route)))
+        .collect();
+    routes.sort_by_key(|&(_, route)| !route.headers.is_empty());
+
+    for (doc, route) in routes {
+        let template = route.resource.path_template();
+        let method = route.method.as_str().to_ascii_lowercase();
+
+        let mut key = template.to_owned();
+        if !route.query.is_empty() {
+            key.push('?');
+            key.push_str(&route.query.join("&"));
+        }
+        if paths
+            .get(&key)
+            .map_or(false, |item| item.contains_key(&method))
+        {
+            key.push(if route.query.is_empty() { '?' } else { '&' });
+            key.push_str("x-id=");
+            key.push_str(doc.kind.as_str());
+        }
+
+        let mut parameters = Vec::new();
+        if route.resource != Resource::Service {
+            parameters.push(path_parameter("Bucket"));
+        }
+        if route.resource == Resource::Object {
+            parameters.push(path_parameter("Key"));
+        }
+        for q in route.query {
+            let name = q.split_once('=').map_or(*q, |(name, _)| name);
+            parameters.push(json!({
+                "name": name,
+                "in": "query",
+                "required": true,
+                "allowEmptyValue": !q.contains('='),
+                "schema": { "type": "string" },
+            }));
+        }
+        for h in route.headers {
+            parameters.push(json!({
+                "name": h,
+                "in": "header",
+                "required": true,
+                "schema": { "type": "string" },
+            }));
+        }
+
+        let mut operation = json!({
+            "operationId": doc.kind.as_str(),
+            "parameters": parameters,
+            "responses": {
+                "200": { "description": "Success" },
+                "default": { "description": "S3 error response" },
+            },
+        });
+        if let (Some(url), Some(fields)) = (doc.reference, operation.as_object_mut()) {
+            let _prev_docs = fields.insert("externalDocs".into(), json!({ "url": url }));
+        }
+
+        let item = paths.entry(key).or_default();
+        let _prev = item.insert(method, operation);
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": { "title": title, "version": version },
+        "paths": paths,
+    })
+}
+
+/// a required path parameter
+fn path_parameter(name: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "schema": { "type": "string" },
+    })
+}
@@ -1,6 +1,9 @@
 //! [`CompleteMultipartUpload`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CompleteMultipartUpload.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{
+    is_create_only, wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource, Route,
+    S3Handler,
+};
 
 use crate::dto::{
     CompleteMultipartUploadError, CompleteMultipartUploadOutput, CompleteMultipartUploadRequest,
@@ -8,13 +11,13 @@ use crate::dto::{
 };
 use crate::errors::{S3Error, S3Result};
 use crate::headers::{
-    X_AMZ_EXPIRATION, X_AMZ_REQUEST_CHARGED, X_AMZ_REQUEST_PAYER, X_AMZ_SERVER_SIDE_ENCRYPTION,
+    ETAG, X_AMZ_EXPIRATION, X_AMZ_REQUEST_CHARGED, X_AMZ_SERVER_SIDE_ENCRYPTION,
     X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID, X_AMZ_VERSION_ID,
 };
 use crate::output::S3Output;
 use crate::storage::S3Storage;
 use crate::utils::body::deserialize_xml_body;
-use crate::utils::{ResponseExt, XmlWriterExt};
+use crate::utils::ResponseExt;
 use crate::{async_trait, Response};
 
 use hyper::Method;
@@ -24,6 +27,20 @@ pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::CompleteMultipartUpload
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::POST, Resource::Object).query(&["uploadId"])],
+            reference: Some(
+                "https://docs.aws.amazon.com/AmazonS3/latest/API/API_CompleteMultipartUpload.html",
+            ),
+        }
+    }
+
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
         bool_try!(ctx.req.method() == Method::POST);
         bool_try!(ctx.path.is_object());
@@ -37,14 +54,29 @@ impl S3Handler for Handler {
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
         let input = extract(ctx).await?;
-        let output = storage.complete_multipart_upload(input).await;
+        let parts_count = input
+            .multipart_upload
+            .as_ref()
+            .and_then(|upload| upload.parts.as_ref())
+            .map_or(0, Vec::len);
+        if u32::try_from(parts_count).map_or(true, |n| n > ctx.limits.max_parts) {
+            return Err(code_error!(
+                InvalidArgument,
+                "The number of parts exceeds the maximum number of parts."
+            ));
+        }
+        let output = if is_create_only(ctx)? {
+            storage.complete_multipart_upload_if_absent(input).await
+        } else {
+            storage.complete_multipart_upload(input).await
+        };
         output.try_into_response()
     }
 }
 
 /// extract operation request
 async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<CompleteMultipartUploadRequest> {
-    let multipart_upload: Option<self::xml::CompletedMultipartUpload> =
+    let multipart_upload: Option<xml::CompletedMultipartUpload> =
         deserialize_xml_body(ctx.take_body())
             .await
             .map_err(|err| invalid_request!("Invalid xml format", err))?;
@@ -60,8 +92,9 @@ async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<CompleteMultipartUploadRe
         ..CompleteMultipartUploadRequest::default()
     };
 
-    let h = &ctx.headers;
-    h.assign_str(X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+    assign_headers!(ctx.headers => input {
+        X_AMZ_REQUEST_PAYER => request_payer,
+    });
 
     Ok(input)
 }
@@ -73,7 +106,6 @@ impl From<CompleteMultipartUploadError> for S3Error {
 }
 
 impl S3Output for CompleteMultipartUploadOutput {
-    #[allow(clippy::shadow_unrelated)]
     fn try_into_response(self) -> S3Result<Response> {
         wrap_internal_error(|res| {
             res.set_optional_header(X_AMZ_EXPIRATION, self.expiration)?;
@@ -85,18 +117,14 @@ impl S3Output for CompleteMultipartUploadOutput {
             )?;
             res.set_optional_header(X_AMZ_REQUEST_CHARGED, self.request_charged)?;
 
-            let location = self.location;
-            let bucket = self.bucket;
-            let key = self.key;
-            let e_tag = self.e_tag;
+            res.set_optional_header(ETAG, self.e_tag.clone())?;
 
             res.set_xml_body(256, |w| {
-                w.stack("CompleteMultipartUploadResult", |w| {
-                    w.opt_element("Location", location)?;
-                    w.opt_element("Bucket", bucket)?;
-                    w.opt_element("Key", key)?;
-                    w.opt_element("ETag", e_tag)?;
-                    Ok(())
+                xml_element!(w, root "CompleteMultipartUploadResult", self {
+                    "Location" => location,
+                    "Bucket" => bucket,
+                    "Key" => key,
+                    "ETag" => e_tag,
                 })
             })?;
 
@@ -113,17 +141,17 @@ mod xml {
     /// `CompletedMultipartUpload`
     pub struct CompletedMultipartUpload {
         /// Part
-        #[serde(rename = "Part")]
-        parts: Option<Vec<CompletedPart>>,
+        #[serde(rename = "Part", default)]
+        parts: Vec<CompletedPart>,
     }
 
     /// `CompletedPart`
     #[derive(Debug, Deserialize)]
     pub struct CompletedPart {
-        /// ETag
+        /// `ETag`
         #[serde(rename = "ETag")]
         e_tag: Option<String>,
-        /// PartNumber
+        /// `PartNumber`
         #[serde(rename = "PartNumber")]
         part_number: Option<i64>,
     }
@@ -131,7 +159,7 @@ mod xml {
     impl From<CompletedMultipartUpload> for super::CompletedMultipartUpload {
         fn from(m: CompletedMultipartUpload) -> Self {
             Self {
-                parts: m.parts.map(|v| v.into_iter().map(From::from).collect()),
+                parts: Some(m.parts.into_iter().map(From::from).collect()),
             }
         }
     }
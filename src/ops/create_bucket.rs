@@ -1,15 +1,14 @@
 //! [`CreateBucket`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CreateBucket.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{
+    wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource, Route, S3Handler,
+};
 
 use crate::dto::{
     CreateBucketConfiguration, CreateBucketError, CreateBucketOutput, CreateBucketRequest,
 };
 use crate::errors::{S3Error, S3ErrorCode, S3Result};
-use crate::headers::{
-    LOCATION, X_AMZ_ACL, X_AMZ_BUCKET_OBJECT_LOCK_ENABLED, X_AMZ_GRANT_FULL_CONTROL,
-    X_AMZ_GRANT_READ, X_AMZ_GRANT_READ_ACP, X_AMZ_GRANT_WRITE, X_AMZ_GRANT_WRITE_ACP,
-};
+use crate::headers::{LOCATION, X_AMZ_BUCKET_OBJECT_LOCK_ENABLED};
 use crate::output::S3Output;
 use crate::storage::S3Storage;
 use crate::utils::body::deserialize_xml_body;
@@ -21,9 +20,27 @@ pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::CreateBucket
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::PUT, Resource::Bucket)],
+            reference: Some(
+                "https://docs.aws.amazon.com/AmazonS3/latest/API/API_CreateBucket.html",
+            ),
+        }
+    }
+
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
         bool_try!(ctx.req.method() == Method::PUT);
-        ctx.path.is_bucket()
+        bool_try!(ctx.path.is_bucket());
+        // subresources of existing buckets
+        ctx.query_strings.as_ref().map_or(true, |qs| {
+            qs.get("accelerate").is_none() && qs.get("requestPayment").is_none()
+        })
     }
 
     async fn handle(
@@ -41,10 +58,9 @@ impl S3Handler for Handler {
 async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<CreateBucketRequest> {
     let bucket = ctx.unwrap_bucket_path();
 
-    let config: Option<self::xml::CreateBucketConfiguration> =
-        deserialize_xml_body(ctx.take_body())
-            .await
-            .map_err(|err| invalid_request!("Invalid xml format", err))?;
+    let config: Option<xml::CreateBucketConfiguration> = deserialize_xml_body(ctx.take_body())
+        .await
+        .map_err(|err| invalid_request!("Invalid xml format", err))?;
 
     let mut input: CreateBucketRequest = CreateBucketRequest {
         bucket: bucket.into(),
@@ -53,12 +69,10 @@ async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<CreateBucketRequest> {
     };
 
     let h = &ctx.headers;
-    h.assign_str(X_AMZ_ACL, &mut input.acl);
-    h.assign_str(X_AMZ_GRANT_FULL_CONTROL, &mut input.grant_full_control);
-    h.assign_str(X_AMZ_GRANT_READ, &mut input.grant_read);
-    h.assign_str(X_AMZ_GRANT_READ_ACP, &mut input.grant_read_acp);
-    h.assign_str(X_AMZ_GRANT_WRITE, &mut input.grant_write);
-    h.assign_str(X_AMZ_GRANT_WRITE_ACP, &mut input.grant_write_acp);
+    assign_headers!(ctx.headers => input {
+        @grants,
+        X_AMZ_GRANT_WRITE => grant_write,
+    });
     h.assign(
         X_AMZ_BUCKET_OBJECT_LOCK_ENABLED,
         &mut input.object_lock_enabled_for_bucket,
@@ -97,7 +111,7 @@ mod xml {
     #[derive(Debug, Deserialize)]
     /// `CreateBucketConfiguration`
     pub struct CreateBucketConfiguration {
-        /// LocationConstraint
+        /// `LocationConstraint`
         #[serde(rename = "LocationConstraint")]
         pub location_constraint: Option<String>,
     }
@@ -1,10 +1,9 @@
 //! [`DeleteBucket`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteBucket.html)
 
-use super::{ReqContext, S3Handler};
+use super::{OperationDoc, OperationKind, ReqContext, Resource, Route, S3Handler};
 
 use crate::dto::{DeleteBucketError, DeleteBucketOutput, DeleteBucketRequest};
 use crate::errors::{S3Error, S3Result};
-use crate::headers::X_AMZ_EXPECTED_BUCKET_OWNER;
 use crate::output::S3Output;
 use crate::storage::S3Storage;
 use crate::utils::{Apply, ResponseExt};
@@ -15,6 +14,20 @@ pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::DeleteBucket
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::DELETE, Resource::Bucket)],
+            reference: Some(
+                "https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteBucket.html",
+            ),
+        }
+    }
+
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
         bool_try!(ctx.req.method() == Method::DELETE);
         ctx.path.is_bucket()
@@ -25,6 +38,12 @@ impl S3Handler for Handler {
         ctx: &mut ReqContext<'_>,
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
+        if ctx.worm {
+            return Err(code_error!(
+                AccessDenied,
+                "A write-once bucket can not be deleted."
+            ));
+        }
         let input = extract(ctx)?;
         let output = storage.delete_bucket(input).await;
         output.try_into_response()
@@ -32,18 +51,16 @@ impl S3Handler for Handler {
 }
 
 /// extract operation request
-fn extract(ctx: &mut ReqContext<'_>) -> S3Result<DeleteBucketRequest> {
+fn extract(ctx: &ReqContext<'_>) -> S3Result<DeleteBucketRequest> {
     let bucket = ctx.unwrap_bucket_path();
     let mut input = DeleteBucketRequest {
         bucket: bucket.into(),
         expected_bucket_owner: None,
     };
 
-    let h = &ctx.headers;
-    h.assign_str(
-        X_AMZ_EXPECTED_BUCKET_OWNER,
-        &mut input.expected_bucket_owner,
-    );
+    assign_headers!(ctx.headers => input {
+        X_AMZ_EXPECTED_BUCKET_OWNER => expected_bucket_owner,
+    });
 
     Ok(input)
 }
@@ -1,12 +1,14 @@
 //! [`DeleteObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteObject.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{
+    object_exists, wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource, Route,
+    S3Handler,
+};
 
 use crate::dto::{DeleteObjectError, DeleteObjectOutput, DeleteObjectRequest};
 use crate::errors::{S3Error, S3Result};
 use crate::headers::{
-    X_AMZ_BYPASS_GOVERNANCE_RETENTION, X_AMZ_DELETE_MARKER, X_AMZ_MFA, X_AMZ_REQUEST_CHARGED,
-    X_AMZ_REQUEST_PAYER, X_AMZ_VERSION_ID,
+    X_AMZ_BYPASS_GOVERNANCE_RETENTION, X_AMZ_DELETE_MARKER, X_AMZ_REQUEST_CHARGED, X_AMZ_VERSION_ID,
 };
 use crate::output::S3Output;
 use crate::storage::S3Storage;
@@ -18,6 +20,20 @@ pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::DeleteObject
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::DELETE, Resource::Object)],
+            reference: Some(
+                "https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteObject.html",
+            ),
+        }
+    }
+
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
         bool_try!(ctx.req.method() == Method::DELETE);
         ctx.path.is_object()
@@ -29,13 +45,24 @@ impl S3Handler for Handler {
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
         let input = extract(ctx)?;
+        // the storage is never called for a write-once bucket,
+        // which would delete an object created after the check
+        if ctx.worm {
+            if object_exists(storage, &input.bucket, &input.key).await? {
+                return Err(code_error!(
+                    AccessDenied,
+                    "Objects of a write-once bucket can not be deleted."
+                ));
+            }
+            return DeleteObjectOutput::default().try_into_response();
+        }
         let output = storage.delete_object(input).await;
         output.try_into_response()
     }
 }
 
 /// extract operation request
-fn extract(ctx: &mut ReqContext<'_>) -> S3Result<DeleteObjectRequest> {
+fn extract(ctx: &ReqContext<'_>) -> S3Result<DeleteObjectRequest> {
     let (bucket, key) = ctx.unwrap_object_path();
 
     let mut input: DeleteObjectRequest = DeleteObjectRequest {
@@ -52,8 +79,10 @@ fn extract(ctx: &mut ReqContext<'_>) -> S3Result<DeleteObjectRequest> {
     )
     .map_err(|err| invalid_request!("Invalid header: x-amz-bypass-governance-retention", err))?;
 
-    h.assign_str(X_AMZ_MFA, &mut input.mfa);
-    h.assign_str(X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+    assign_headers!(ctx.headers => input {
+        X_AMZ_MFA => mfa,
+        X_AMZ_REQUEST_PAYER => request_payer,
+    });
 
     if let Some(ref qs) = ctx.query_strings {
         input.version_id = qs.get("versionId").map(ToOwned::to_owned);
@@ -1,18 +1,20 @@
 //! [`DeleteObjects`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteObjects.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{
+    wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource, Route, S3Handler,
+};
 
 use crate::dto::{
-    Delete, DeleteObjectsError, DeleteObjectsOutput, DeleteObjectsRequest, ObjectIdentifier,
-};
-use crate::errors::{S3Error, S3Result};
-use crate::headers::{
-    X_AMZ_BYPASS_GOVERNANCE_RETENTION, X_AMZ_MFA, X_AMZ_REQUEST_CHARGED, X_AMZ_REQUEST_PAYER,
+    self, Delete, DeleteObjectsError, DeleteObjectsOutput, DeleteObjectsRequest, DeletedObject,
+    HeadObjectsRequest, ObjectIdentifier,
 };
+use crate::errors::{S3Error, S3Result, S3StorageError};
+use crate::headers::{X_AMZ_BYPASS_GOVERNANCE_RETENTION, X_AMZ_REQUEST_CHARGED};
 use crate::output::S3Output;
+use crate::path::S3Path;
 use crate::storage::S3Storage;
 use crate::utils::body::deserialize_xml_body;
-use crate::utils::{ResponseExt, XmlWriterExt};
+use crate::utils::ResponseExt;
 use crate::{async_trait, Method, Response};
 
 /// `DeleteObject` handler
@@ -20,6 +22,20 @@ pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::DeleteObjects
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::POST, Resource::Bucket).query(&["delete"])],
+            reference: Some(
+                "https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteObjects.html",
+            ),
+        }
+    }
+
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
         bool_try!(ctx.req.method() == Method::POST);
         bool_try!(ctx.path.is_bucket());
@@ -32,8 +48,42 @@ impl S3Handler for Handler {
         ctx: &mut ReqContext<'_>,
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
-        let input = extract(ctx).await?;
-        let output = storage.delete_objects(input).await;
+        let mut input = extract(ctx).await?;
+        if !ctx.worm {
+            let output = storage.delete_objects(input).await;
+            return output.try_into_response();
+        }
+
+        // existing objects of a write-once bucket are reported as errors,
+        // and missing ones are not passed to the storage, which would delete them if they are
+        // created concurrently
+        let head = HeadObjectsRequest {
+            bucket: input.bucket.clone(),
+            keys: input.delete.objects.iter().map(|o| o.key.clone()).collect(),
+        };
+        let existing = match storage.head_objects(head).await {
+            Ok(output) => output.objects,
+            Err(S3StorageError::Operation(e)) => return Err(e.into()),
+            Err(S3StorageError::Other(e)) => return Err(e),
+        };
+        let mut denied = Vec::new();
+        for (idx, object) in input.delete.objects.drain(..).enumerate() {
+            // an object missing from the output is kept
+            if existing.get(idx).map_or(true, Option::is_some) {
+                denied.push(dto::S3Error {
+                    code: Some("AccessDenied".into()),
+                    message: Some("Objects of a write-once bucket can not be deleted.".into()),
+                    key: Some(object.key),
+                    version_id: object.version_id,
+                });
+            }
+        }
+
+        let output = DeleteObjectsOutput {
+            deleted: Some(Vec::new()),
+            errors: (!denied.is_empty()).then_some(denied),
+            ..DeleteObjectsOutput::default()
+        };
         output.try_into_response()
     }
 }
@@ -41,10 +91,17 @@ impl S3Handler for Handler {
 /// extract operation request
 pub async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<DeleteObjectsRequest> {
     let bucket = ctx.unwrap_bucket_path();
-    let delete: self::xml::Delete = deserialize_xml_body(ctx.take_body())
+    let delete: xml::Delete = deserialize_xml_body(ctx.take_body())
         .await
         .map_err(|err| invalid_request!("Invalid xml format", err))?;
 
+    if let Some(object) = delete.objects.iter().find(|o| !S3Path::is_safe_key(&o.key)) {
+        return Err(code_error!(
+            InvalidArgument,
+            format!("The specified key is not allowed: {:?}", object.key)
+        ));
+    }
+
     let mut input: DeleteObjectsRequest = DeleteObjectsRequest {
         delete: delete.into(),
         bucket: bucket.into(),
@@ -52,8 +109,10 @@ pub async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<DeleteObjectsRequest>
     };
 
     let h = &ctx.headers;
-    h.assign_str(X_AMZ_MFA, &mut input.mfa);
-    h.assign_str(X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+    assign_headers!(ctx.headers => input {
+        X_AMZ_MFA => mfa,
+        X_AMZ_REQUEST_PAYER => request_payer,
+    });
     h.assign(
         X_AMZ_BYPASS_GOVERNANCE_RETENTION,
         &mut input.bypass_governance_retention,
@@ -64,45 +123,14 @@ pub async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<DeleteObjectsRequest>
 }
 
 impl S3Output for DeleteObjectsOutput {
-    #[allow(clippy::shadow_unrelated)]
     fn try_into_response(self) -> S3Result<Response> {
         wrap_internal_error(|res| {
             res.set_optional_header(X_AMZ_REQUEST_CHARGED, self.request_charged)?;
 
-            let deleted = self.deleted;
-            let errors = self.errors;
-
             res.set_xml_body(4096, |w| {
-                w.stack("DeleteResult", |w| {
-                    if let Some(deleted) = deleted {
-                        w.iter_element(deleted.into_iter(), |w, deleted_object| {
-                            w.stack("Deleted", |w| {
-                                w.opt_element(
-                                    "DeleteMarker",
-                                    deleted_object.delete_marker.map(|b| b.to_string()),
-                                )?;
-                                w.opt_element(
-                                    "DeleteMarkerVersionId",
-                                    deleted_object.delete_marker_version_id,
-                                )?;
-                                w.opt_element("Key", deleted_object.key)?;
-                                w.opt_element("VersionId", deleted_object.version_id)?;
-                                Ok(())
-                            })
-                        })?;
-                    }
-                    if let Some(errors) = errors {
-                        w.iter_element(errors.into_iter(), |w, error| {
-                            w.stack("Error", |w| {
-                                w.opt_element("Code", error.code)?;
-                                w.opt_element("Key", error.key)?;
-                                w.opt_element("Message", error.message)?;
-                                w.opt_element("VersionId", error.version_id)?;
-                                Ok(())
-                            })
-                        })?;
-                    }
-                    Ok(())
+                xml_element!(w, root "DeleteResult", self {
+                    "Deleted" => deleted,
+                    "Error" => errors,
                 })
             })?;
 
@@ -111,6 +139,21 @@ impl S3Output for DeleteObjectsOutput {
     }
 }
 
+impl_xml_element! {
+    DeletedObject {
+        "DeleteMarker" => delete_marker,
+        "DeleteMarkerVersionId" => delete_marker_version_id,
+        "Key" => key,
+        "VersionId" => version_id,
+    }
+    dto::S3Error {
+        "Code" => code,
+        "Key" => key,
+        "Message" => message,
+        "VersionId" => version_id,
+    }
+}
+
 impl From<DeleteObjectsError> for S3Error {
     fn from(e: DeleteObjectsError) -> Self {
         match e {}
@@ -128,7 +171,7 @@ mod xml {
         /// Key name of the object to delete.
         #[serde(rename = "Key")]
         pub key: String,
-        /// VersionId for the specific version of the object to delete.
+        /// `VersionId` for the specific version of the object to delete.
         #[serde(rename = "VersionId")]
         pub version_id: Option<String>,
     }
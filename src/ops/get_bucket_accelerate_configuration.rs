@@ -0,0 +1,84 @@
+//! [`GetBucketAccelerateConfiguration`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketAccelerateConfiguration.html)
+
+use super::{
+    wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource, Route, S3Handler,
+};
+
+use crate::dto::{
+    GetBucketAccelerateConfigurationError, GetBucketAccelerateConfigurationOutput,
+    GetBucketAccelerateConfigurationRequest,
+};
+use crate::errors::{S3Error, S3Result};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{async_trait, Method, Response};
+
+/// `GetBucketAccelerateConfiguration` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::GetBucketAccelerateConfiguration
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::GET, Resource::Bucket).query(&["accelerate"])],
+            reference: Some("https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketAccelerateConfiguration.html"),
+        }
+    }
+
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.req.method() == Method::GET);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.get("accelerate").is_some()
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let output = storage.get_bucket_accelerate_configuration(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<GetBucketAccelerateConfigurationRequest> {
+    let bucket = ctx.unwrap_bucket_path();
+
+    let mut input = GetBucketAccelerateConfigurationRequest {
+        bucket: bucket.into(),
+        expected_bucket_owner: None,
+    };
+
+    assign_headers!(ctx.headers => input {
+        X_AMZ_EXPECTED_BUCKET_OWNER => expected_bucket_owner,
+    });
+
+    Ok(input)
+}
+
+impl S3Output for GetBucketAccelerateConfigurationOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|res| {
+            res.set_xml_body(256, |w| {
+                xml_element!(w, root "AccelerateConfiguration", self {
+                    "Status" => status,
+                })
+            })
+        })
+    }
+}
+
+impl From<GetBucketAccelerateConfigurationError> for S3Error {
+    fn from(e: GetBucketAccelerateConfigurationError) -> Self {
+        match e {}
+    }
+}
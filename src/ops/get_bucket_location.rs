@@ -1,10 +1,11 @@
 //! [`GetBucketLocation`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketLocation.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{
+    wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource, Route, S3Handler,
+};
 
 use crate::dto::{GetBucketLocationError, GetBucketLocationOutput, GetBucketLocationRequest};
 use crate::errors::{S3Error, S3Result};
-use crate::headers::X_AMZ_EXPECTED_BUCKET_OWNER;
 use crate::output::S3Output;
 use crate::storage::S3Storage;
 use crate::utils::{ResponseExt, XmlWriterExt};
@@ -15,6 +16,20 @@ pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::GetBucketLocation
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::GET, Resource::Bucket).query(&["location"])],
+            reference: Some(
+                "https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketLocation.html",
+            ),
+        }
+    }
+
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
         bool_try!(ctx.req.method() == Method::GET);
         bool_try!(ctx.path.is_bucket());
@@ -34,7 +49,7 @@ impl S3Handler for Handler {
 }
 
 /// extract operation request
-fn extract(ctx: &mut ReqContext<'_>) -> S3Result<GetBucketLocationRequest> {
+fn extract(ctx: &ReqContext<'_>) -> S3Result<GetBucketLocationRequest> {
     let bucket = ctx.unwrap_bucket_path();
 
     let mut input = GetBucketLocationRequest {
@@ -42,11 +57,9 @@ fn extract(ctx: &mut ReqContext<'_>) -> S3Result<GetBucketLocationRequest> {
         expected_bucket_owner: None,
     };
 
-    let h = &ctx.headers;
-    h.assign_str(
-        X_AMZ_EXPECTED_BUCKET_OWNER,
-        &mut input.expected_bucket_owner,
-    );
+    assign_headers!(ctx.headers => input {
+        X_AMZ_EXPECTED_BUCKET_OWNER => expected_bucket_owner,
+    });
 
     Ok(input)
 }
@@ -55,10 +68,9 @@ impl S3Output for GetBucketLocationOutput {
     fn try_into_response(self) -> S3Result<Response> {
         wrap_internal_error(|res| {
             res.set_xml_body(4096, |w| {
-                w.element(
-                    "LocationConstraint",
-                    self.location_constraint.as_deref().unwrap_or(""),
-                )
+                w.root("LocationConstraint", |w| {
+                    w.text(self.location_constraint.as_deref().unwrap_or(""))
+                })
             })
         })
     }
@@ -1,30 +1,50 @@
 //! [`GetObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObject.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::validation::Validate;
+use super::{
+    extract_part_number, wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource,
+    Route, S3Handler,
+};
 
 use crate::dto::{GetObjectError, GetObjectOutput, GetObjectRequest};
-use crate::errors::{S3Error, S3ErrorCode, S3Result};
+use crate::errors::{S3Error, S3ErrorCode, S3Result, S3StorageError};
+use crate::headers::Range;
 use crate::headers::{
     ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LANGUAGE,
-    CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, EXPIRES, IF_MATCH, IF_MODIFIED_SINCE,
-    IF_NONE_MATCH, IF_UNMODIFIED_SINCE, LAST_MODIFIED, RANGE, X_AMZ_DELETE_MARKER,
-    X_AMZ_EXPIRATION, X_AMZ_MISSING_META, X_AMZ_MP_PARTS_COUNT, X_AMZ_OBJECT_LOCK_LEGAL_HOLD,
-    X_AMZ_OBJECT_LOCK_MODE, X_AMZ_OBJECT_LOCK_RETAIN_UNTIL_DATE, X_AMZ_REPLICATION_STATUS,
-    X_AMZ_REQUEST_CHARGED, X_AMZ_REQUEST_PAYER, X_AMZ_RESTORE, X_AMZ_SERVER_SIDE_ENCRYPTION,
+    CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, EXPIRES, IF_RANGE, LAST_MODIFIED,
+    X_AMZ_DELETE_MARKER, X_AMZ_EXPIRATION, X_AMZ_MISSING_META, X_AMZ_MP_PARTS_COUNT,
+    X_AMZ_OBJECT_LOCK_LEGAL_HOLD, X_AMZ_OBJECT_LOCK_MODE, X_AMZ_OBJECT_LOCK_RETAIN_UNTIL_DATE,
+    X_AMZ_REPLICATION_STATUS, X_AMZ_REQUEST_CHARGED, X_AMZ_RESTORE, X_AMZ_SERVER_SIDE_ENCRYPTION,
     X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID, X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
-    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY, X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
-    X_AMZ_STORAGE_CLASS, X_AMZ_TAGGING_COUNT, X_AMZ_VERSION_ID, X_AMZ_WEBSITE_REDIRECT_LOCATION,
+    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5, X_AMZ_STORAGE_CLASS, X_AMZ_TAGGING_COUNT,
+    X_AMZ_VERSION_ID, X_AMZ_WEBSITE_REDIRECT_LOCATION,
 };
-use crate::output::S3Output;
+use crate::output::{MultipartByteranges, S3Output};
 use crate::storage::S3Storage;
 use crate::utils::{time, ResponseExt};
-use crate::{async_trait, Body, Method, Response};
+use crate::{async_trait, Body, Method, Response, StatusCode};
+
+use chrono::DateTime;
+use futures::future;
+use uuid::Uuid;
 
 /// `GetObject` handler
 pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::GetObject
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::GET, Resource::Object)],
+            reference: Some("https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObject.html"),
+        }
+    }
+
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
         bool_try!(ctx.req.method() == Method::GET);
         ctx.path.is_object()
@@ -35,41 +55,138 @@ impl S3Handler for Handler {
         ctx: &mut ReqContext<'_>,
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
-        let input = extract(ctx)?;
-        let output = storage.get_object(input).await;
+        let mut input = extract(ctx)?;
+        input.validate()?;
+
+        // the whole object is sent if the `If-Range` validator does not match
+        let mut whole = None;
+        if input.range.is_some() {
+            if let Some(if_range) = ctx.headers.get(IF_RANGE) {
+                whole = Some(GetObjectRequest {
+                    range: None,
+                    ..input.clone()
+                });
+                if !apply_if_range(&mut input, if_range) {
+                    input = whole.take().unwrap_or(input);
+                }
+            }
+        }
+
+        let ranges = match input.range.as_deref() {
+            Some(s) if s.contains(',') => Range::from_header_str_multi(s)
+                .map_err(|err| invalid_request!("Invalid header: range", err))?,
+            _ => Vec::new(),
+        };
+        if ranges.len() > 1 && ranges.len() <= MAX_RANGES {
+            let ret = get_ranges(storage, input.clone(), &ranges).await;
+            let failed =
+                matches!(ret, Err(ref e) if matches!(e.code(), S3ErrorCode::PreconditionFailed));
+            match whole.take() {
+                Some(whole) if failed => input = whole,
+                _ => return ret?.try_into_response(),
+            }
+        }
+        if ranges.len() > MAX_RANGES {
+            // a server may ignore the ranges and send the whole object
+            input.range = None;
+        }
+
+        let content_type = ctx.default_content_type(&input.bucket, &input.key);
+        let mut output = storage.get_object(input).await;
+        if let Some(whole) = whole {
+            if is_precondition_failed(&output) {
+                output = storage.get_object(whole).await;
+            }
+        }
+        if let Ok(ref mut output) = output {
+            if output.content_type.is_none() {
+                output.content_type = Some(content_type);
+            }
+        }
         output.try_into_response()
     }
 }
 
+/// max number of ranges in a `multipart/byteranges` response
+const MAX_RANGES: usize = 16;
+
+/// reads several ranges of an object in parallel
+async fn get_ranges(
+    storage: &(dyn S3Storage + Send + Sync),
+    input: GetObjectRequest,
+    ranges: &[Range],
+) -> S3Result<MultipartByteranges> {
+    let requests = ranges.iter().map(|range| {
+        let part = GetObjectRequest {
+            range: Some(range.to_string()),
+            ..input.clone()
+        };
+        storage.get_object(part)
+    });
+    let parts = future::try_join_all(requests)
+        .await
+        .map_err(|err| match err {
+            S3StorageError::Operation(e) => S3Error::from(e),
+            S3StorageError::Other(e) => e,
+        })?;
+
+    // all ranges must come from the same version of the object
+    let e_tag = parts.first().and_then(|p| p.e_tag.as_deref());
+    if parts.iter().any(|p| p.e_tag.as_deref() != e_tag) {
+        return Err(code_error!(
+            PreconditionFailed,
+            "The object was modified while its ranges were read."
+        ));
+    }
+
+    Ok(MultipartByteranges {
+        parts,
+        boundary: Uuid::new_v4().simple().to_string(),
+    })
+}
+
+/// turns the `If-Range` validator into a precondition of the ranged read,
+/// which the storage checks against the file it reads,
+/// returns `false` if the validator can never match
+///
+/// An entity tag becomes `If-Match`, and weak tags never match.
+/// A date becomes `If-Unmodified-Since`.
+/// A validator never matches if the request has its own precondition of the same kind.
+fn apply_if_range(input: &mut GetObjectRequest, if_range: &str) -> bool {
+    let if_range = if_range.trim();
+    if if_range.starts_with('"') {
+        return input.if_match.replace(if_range.to_owned()).is_none();
+    }
+    if if_range.starts_with("W/") || DateTime::parse_from_rfc2822(if_range).is_err() {
+        return false;
+    }
+    input
+        .if_unmodified_since
+        .replace(if_range.to_owned())
+        .is_none()
+}
+
+/// checks whether a read failed since its preconditions did not hold
+fn is_precondition_failed<T>(ret: &Result<T, S3StorageError<GetObjectError>>) -> bool {
+    matches!(ret, Err(S3StorageError::Other(e)) if matches!(e.code(), S3ErrorCode::PreconditionFailed))
+}
+
 /// extract operation request
-fn extract(ctx: &mut ReqContext<'_>) -> S3Result<GetObjectRequest> {
+fn extract(ctx: &ReqContext<'_>) -> S3Result<GetObjectRequest> {
     let (bucket, key) = ctx.unwrap_object_path();
 
     let mut input = GetObjectRequest {
         bucket: bucket.into(),
         key: key.into(),
+        part_number: extract_part_number(ctx)?,
         ..GetObjectRequest::default()
     };
 
-    let h = &ctx.headers;
-    h.assign_str(IF_MATCH, &mut input.if_match);
-    h.assign_str(IF_MODIFIED_SINCE, &mut input.if_modified_since);
-    h.assign_str(IF_NONE_MATCH, &mut input.if_none_match);
-    h.assign_str(IF_UNMODIFIED_SINCE, &mut input.if_unmodified_since);
-    h.assign_str(RANGE, &mut input.range);
-    h.assign_str(
-        X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
-        &mut input.sse_customer_algorithm,
-    );
-    h.assign_str(
-        X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
-        &mut input.sse_customer_key,
-    );
-    h.assign_str(
-        X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
-        &mut input.sse_customer_key_md5,
-    );
-    h.assign_str(X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+    assign_headers!(ctx.headers => input {
+        @sse_customer,
+        @read_conditions,
+        X_AMZ_REQUEST_PAYER => request_payer,
+    });
 
     Ok(input)
 }
@@ -104,6 +221,9 @@ impl S3Output for GetObjectOutput {
             res.set_optional_header(CONTENT_DISPOSITION, self.content_disposition)?;
             res.set_optional_header(CONTENT_ENCODING, self.content_encoding)?;
             res.set_optional_header(CONTENT_LANGUAGE, self.content_language)?;
+            if self.content_range.is_some() {
+                *res.status_mut() = StatusCode::PARTIAL_CONTENT;
+            }
             res.set_optional_header(CONTENT_RANGE, self.content_range)?;
             res.set_optional_header(CONTENT_TYPE, self.content_type)?;
 
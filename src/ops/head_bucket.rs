@@ -1,10 +1,9 @@
 //! [`HeadBucket`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_HeadBucket.html)
 
-use super::{ReqContext, S3Handler};
+use super::{OperationDoc, OperationKind, ReqContext, Resource, Route, S3Handler};
 
 use crate::dto::{HeadBucketError, HeadBucketOutput, HeadBucketRequest};
 use crate::errors::{S3Error, S3ErrorCode, S3Result};
-use crate::headers::X_AMZ_EXPECTED_BUCKET_OWNER;
 use crate::output::S3Output;
 use crate::storage::S3Storage;
 use crate::utils::Apply;
@@ -15,6 +14,18 @@ pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::HeadBucket
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::HEAD, Resource::Bucket)],
+            reference: Some("https://docs.aws.amazon.com/AmazonS3/latest/API/API_HeadBucket.html"),
+        }
+    }
+
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
         bool_try!(ctx.req.method() == Method::HEAD);
         ctx.path.is_bucket()
@@ -32,7 +43,7 @@ impl S3Handler for Handler {
 }
 
 /// extract operation request
-fn extract(ctx: &mut ReqContext<'_>) -> S3Result<HeadBucketRequest> {
+fn extract(ctx: &ReqContext<'_>) -> S3Result<HeadBucketRequest> {
     let bucket = ctx.unwrap_bucket_path();
 
     let mut input = HeadBucketRequest {
@@ -40,11 +51,9 @@ fn extract(ctx: &mut ReqContext<'_>) -> S3Result<HeadBucketRequest> {
         expected_bucket_owner: None,
     };
 
-    let h = &ctx.headers;
-    h.assign_str(
-        X_AMZ_EXPECTED_BUCKET_OWNER,
-        &mut input.expected_bucket_owner,
-    );
+    assign_headers!(ctx.headers => input {
+        X_AMZ_EXPECTED_BUCKET_OWNER => expected_bucket_owner,
+    });
 
     Ok(input)
 }
@@ -1,19 +1,22 @@
 //! [`HeadObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_HeadObject.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::validation::Validate;
+use super::{
+    extract_part_number, wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource,
+    Route, S3Handler,
+};
 
 use crate::dto::{HeadObjectError, HeadObjectOutput, HeadObjectRequest};
 use crate::errors::{S3Error, S3ErrorCode, S3Result};
 use crate::headers::{
     ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LANGUAGE,
-    CONTENT_LENGTH, CONTENT_TYPE, ETAG, EXPIRES, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH,
-    IF_UNMODIFIED_SINCE, LAST_MODIFIED, RANGE, X_AMZ_DELETE_MARKER, X_AMZ_EXPIRATION,
-    X_AMZ_MISSING_META, X_AMZ_MP_PARTS_COUNT, X_AMZ_OBJECT_LOCK_LEGAL_HOLD, X_AMZ_OBJECT_LOCK_MODE,
-    X_AMZ_OBJECT_LOCK_RETAIN_UNTIL_DATE, X_AMZ_REPLICATION_STATUS, X_AMZ_REQUEST_CHARGED,
-    X_AMZ_REQUEST_PAYER, X_AMZ_RESTORE, X_AMZ_SERVER_SIDE_ENCRYPTION,
+    CONTENT_LENGTH, CONTENT_TYPE, ETAG, EXPIRES, LAST_MODIFIED, X_AMZ_DELETE_MARKER,
+    X_AMZ_EXPIRATION, X_AMZ_MISSING_META, X_AMZ_MP_PARTS_COUNT, X_AMZ_OBJECT_LOCK_LEGAL_HOLD,
+    X_AMZ_OBJECT_LOCK_MODE, X_AMZ_OBJECT_LOCK_RETAIN_UNTIL_DATE, X_AMZ_REPLICATION_STATUS,
+    X_AMZ_REQUEST_CHARGED, X_AMZ_RESTORE, X_AMZ_SERVER_SIDE_ENCRYPTION,
     X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID, X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
-    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY, X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
-    X_AMZ_STORAGE_CLASS, X_AMZ_VERSION_ID, X_AMZ_WEBSITE_REDIRECT_LOCATION,
+    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5, X_AMZ_STORAGE_CLASS, X_AMZ_VERSION_ID,
+    X_AMZ_WEBSITE_REDIRECT_LOCATION,
 };
 use crate::output::S3Output;
 use crate::storage::S3Storage;
@@ -25,6 +28,18 @@ pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::HeadObject
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::HEAD, Resource::Object)],
+            reference: Some("https://docs.aws.amazon.com/AmazonS3/latest/API/API_HeadObject.html"),
+        }
+    }
+
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
         bool_try!(ctx.req.method() == Method::HEAD);
         ctx.path.is_object()
@@ -36,40 +51,38 @@ impl S3Handler for Handler {
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
         let input = extract(ctx)?;
-        let output = storage.head_object(input).await;
+        input.validate()?;
+        let content_type = ctx.default_content_type(&input.bucket, &input.key);
+        let mut output = storage.stat_object(input.clone()).await;
+        if matches!(output, Ok(ref o) if o.e_tag.is_none()) {
+            // the `ETag` is unknown without reading the content
+            output = storage.head_object(input).await;
+        }
+        if let Ok(ref mut output) = output {
+            if output.content_type.is_none() {
+                output.content_type = Some(content_type);
+            }
+        }
         output.try_into_response()
     }
 }
 
 /// extract operation request
-fn extract(ctx: &mut ReqContext<'_>) -> S3Result<HeadObjectRequest> {
+fn extract(ctx: &ReqContext<'_>) -> S3Result<HeadObjectRequest> {
     let (bucket, key) = ctx.unwrap_object_path();
 
     let mut input = HeadObjectRequest {
         bucket: bucket.into(),
         key: key.into(),
+        part_number: extract_part_number(ctx)?,
         ..HeadObjectRequest::default()
     };
 
-    let h = &ctx.headers;
-    h.assign_str(IF_MATCH, &mut input.if_match);
-    h.assign_str(IF_MODIFIED_SINCE, &mut input.if_modified_since);
-    h.assign_str(IF_NONE_MATCH, &mut input.if_none_match);
-    h.assign_str(IF_UNMODIFIED_SINCE, &mut input.if_unmodified_since);
-    h.assign_str(RANGE, &mut input.range);
-    h.assign_str(
-        X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
-        &mut input.sse_customer_algorithm,
-    );
-    h.assign_str(
-        X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
-        &mut input.sse_customer_key,
-    );
-    h.assign_str(
-        X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
-        &mut input.sse_customer_key_md5,
-    );
-    h.assign_str(X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+    assign_headers!(ctx.headers => input {
+        @sse_customer,
+        @read_conditions,
+        X_AMZ_REQUEST_PAYER => request_payer,
+    });
 
     Ok(input)
 }
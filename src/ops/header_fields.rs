@@ -0,0 +1,159 @@
+//! declarative mapping from request headers to the fields of operation requests
+//!
+//! Headers shared by several operations are listed once in a group,
+//! so a new header of a group only needs to be added here.
+//!
+//! + `@entity`: the representation headers stored with an object
+//! + `@grants`: the canned ACL and the grants of an object
+//! + `@sse_customer`: the customer-provided key of an object
+//! + `@sse`: all server-side encryption headers of a write, including `@sse_customer`
+//! + `@object_lock`: the object lock settings of a write
+//! + `@read_conditions`: the conditions and the range of a read
+//! + `@copy_source`: the conditions and the customer-provided key of the source of a copy
+//! + `@metadata`: the user-defined metadata, collected from `x-amz-meta-*` headers
+
+/// assigns request headers to the fields of an operation request
+///
+/// A field is either listed as `HEADER_NAME => field` with a name in [`crate::headers`],
+/// or taken from a group.
+///
+/// ```text
+/// assign_headers!(ctx.headers => input {
+///     @entity,
+///     @grants,
+///     X_AMZ_TAGGING => tagging,
+/// });
+/// ```
+macro_rules! assign_headers {
+    ($h:expr => $input:ident { $($fields:tt)* }) => {{
+        let h = &$h;
+        assign_headers!(@fields h, $input, $($fields)*);
+    }};
+
+    (@fields $h:ident, $input:ident, $(,)?) => {};
+    (@fields $h:ident, $input:ident, @entity $(, $($rest:tt)*)?) => {
+        assign_headers!(@fields $h, $input,
+            CACHE_CONTROL => cache_control,
+            CONTENT_DISPOSITION => content_disposition,
+            CONTENT_ENCODING => content_encoding,
+            CONTENT_LANGUAGE => content_language,
+            CONTENT_TYPE => content_type,
+            EXPIRES => expires,
+            $($($rest)*)?
+        );
+    };
+    (@fields $h:ident, $input:ident, @grants $(, $($rest:tt)*)?) => {
+        assign_headers!(@fields $h, $input,
+            X_AMZ_ACL => acl,
+            X_AMZ_GRANT_FULL_CONTROL => grant_full_control,
+            X_AMZ_GRANT_READ => grant_read,
+            X_AMZ_GRANT_READ_ACP => grant_read_acp,
+            X_AMZ_GRANT_WRITE_ACP => grant_write_acp,
+            $($($rest)*)?
+        );
+    };
+    (@fields $h:ident, $input:ident, @sse_customer $(, $($rest:tt)*)?) => {
+        assign_headers!(@fields $h, $input,
+            X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM => sse_customer_algorithm,
+            X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY => sse_customer_key,
+            X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5 => sse_customer_key_md5,
+            $($($rest)*)?
+        );
+    };
+    (@fields $h:ident, $input:ident, @sse $(, $($rest:tt)*)?) => {
+        assign_headers!(@fields $h, $input,
+            X_AMZ_SERVER_SIDE_ENCRYPTION => server_side_encryption,
+            @sse_customer,
+            X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID => ssekms_key_id,
+            X_AMZ_SERVER_SIDE_ENCRYPTION_CONTEXT => ssekms_encryption_context,
+            $($($rest)*)?
+        );
+    };
+    (@fields $h:ident, $input:ident, @object_lock $(, $($rest:tt)*)?) => {
+        assign_headers!(@fields $h, $input,
+            X_AMZ_OBJECT_LOCK_MODE => object_lock_mode,
+            X_AMZ_OBJECT_LOCK_RETAIN_UNTIL_DATE => object_lock_retain_until_date,
+            X_AMZ_OBJECT_LOCK_LEGAL_HOLD => object_lock_legal_hold_status,
+            $($($rest)*)?
+        );
+    };
+    (@fields $h:ident, $input:ident, @read_conditions $(, $($rest:tt)*)?) => {
+        assign_headers!(@fields $h, $input,
+            IF_MATCH => if_match,
+            IF_MODIFIED_SINCE => if_modified_since,
+            IF_NONE_MATCH => if_none_match,
+            IF_UNMODIFIED_SINCE => if_unmodified_since,
+            RANGE => range,
+            $($($rest)*)?
+        );
+    };
+    (@fields $h:ident, $input:ident, @copy_source $(, $($rest:tt)*)?) => {
+        assign_headers!(@fields $h, $input,
+            X_AMZ_COPY_SOURCE_IF_MATCH => copy_source_if_match,
+            X_AMZ_COPY_SOURCE_IF_MODIFIED_SINCE => copy_source_if_modified_since,
+            X_AMZ_COPY_SOURCE_IF_NONE_MATCH => copy_source_if_none_match,
+            X_AMZ_COPY_SOURCE_IF_UNMODIFIED_SINCE => copy_source_if_unmodified_since,
+            X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM
+                => copy_source_sse_customer_algorithm,
+            X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY => copy_source_sse_customer_key,
+            X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5
+                => copy_source_sse_customer_key_md5,
+            $($($rest)*)?
+        );
+    };
+    (@fields $h:ident, $input:ident, @metadata $(, $($rest:tt)*)?) => {
+        $input.metadata = $crate::ops::collect_metadata($h.as_ref().iter().copied());
+        assign_headers!(@fields $h, $input, $($($rest)*)?);
+    };
+    (@fields $h:ident, $input:ident, $name:ident => $field:ident $(, $($rest:tt)*)?) => {
+        $h.assign_str($crate::headers::$name, &mut $input.$field);
+        assign_headers!(@fields $h, $input, $($($rest)*)?);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_structures::OrderedHeaders;
+    use crate::dto::{GetObjectRequest, PutObjectRequest};
+
+    #[test]
+    #[allow(clippy::shadow_unrelated)]
+    fn groups() {
+        let headers = OrderedHeaders::from_slice_unchecked(&[
+            ("cache-control", "no-cache"),
+            ("content-type", "text/plain"),
+            ("x-amz-acl", "private"),
+            ("x-amz-server-side-encryption", "AES256"),
+            ("x-amz-server-side-encryption-customer-key-md5", "md5"),
+            ("x-amz-object-lock-mode", "GOVERNANCE"),
+            ("x-amz-meta-a", "b"),
+            ("x-amz-tagging", "k=v"),
+            ("if-match", "\"etag\""),
+        ]);
+
+        let mut input = PutObjectRequest::default();
+        assign_headers!(headers => input {
+            @entity,
+            @grants,
+            @sse,
+            @object_lock,
+            @metadata,
+            X_AMZ_TAGGING => tagging,
+        });
+        assert_eq!(input.cache_control.as_deref(), Some("no-cache"));
+        assert_eq!(input.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(input.content_encoding, None);
+        assert_eq!(input.acl.as_deref(), Some("private"));
+        assert_eq!(input.server_side_encryption.as_deref(), Some("AES256"));
+        assert_eq!(input.sse_customer_key_md5.as_deref(), Some("md5"));
+        assert_eq!(input.object_lock_mode.as_deref(), Some("GOVERNANCE"));
+        assert_eq!(input.metadata.unwrap()["a"], "b");
+        assert_eq!(input.tagging.as_deref(), Some("k=v"));
+
+        let mut input = GetObjectRequest::default();
+        assign_headers!(headers => input { @read_conditions, @sse_customer });
+        assert_eq!(input.if_match.as_deref(), Some("\"etag\""));
+        assert_eq!(input.range, None);
+        assert_eq!(input.sse_customer_key_md5.as_deref(), Some("md5"));
+    }
+}
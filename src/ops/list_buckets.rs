@@ -1,12 +1,14 @@
 //! [`ListBuckets`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListBuckets.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{
+    wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource, Route, S3Handler,
+};
 
-use crate::dto::{ListBucketsError, ListBucketsOutput, ListBucketsRequest};
+use crate::dto::{Bucket, ListBucketsError, ListBucketsOutput, ListBucketsRequest};
 use crate::errors::{S3Error, S3Result};
 use crate::output::S3Output;
 use crate::storage::S3Storage;
-use crate::utils::{ResponseExt, XmlWriterExt};
+use crate::utils::ResponseExt;
 use crate::{async_trait, Method, Response};
 
 /// `ListBuckets` handler
@@ -14,6 +16,18 @@ pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::ListBuckets
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::GET, Resource::Service)],
+            reference: Some("https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListBuckets.html"),
+        }
+    }
+
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
         bool_try!(ctx.req.method() == Method::GET);
         ctx.path.is_root()
@@ -36,32 +50,25 @@ fn extract(_: &mut ReqContext<'_>) -> S3Result<ListBucketsRequest> {
 }
 
 impl S3Output for ListBucketsOutput {
-    #[allow(clippy::shadow_unrelated)]
     fn try_into_response(self) -> S3Result<Response> {
         wrap_internal_error(|res| {
             res.set_xml_body(4096, |w| {
-                w.stack("ListAllMyBucketsResult", |w| {
-                    w.opt_stack("Buckets", self.buckets, |w, buckets| {
-                        for bucket in buckets {
-                            w.stack("Bucket", |w| {
-                                w.opt_element("CreationDate", bucket.creation_date)?;
-                                w.opt_element("Name", bucket.name)
-                            })?;
-                        }
-                        Ok(())
-                    })?;
-
-                    w.opt_stack("Owner", self.owner, |w, owner| {
-                        w.opt_element("DisplayName", owner.display_name)?;
-                        w.opt_element("ID", owner.id)
-                    })?;
-                    Ok(())
+                xml_element!(w, root "ListAllMyBucketsResult", self {
+                    "Buckets" / "Bucket" => buckets,
+                    "Owner" => owner,
                 })
             })
         })
     }
 }
 
+impl_xml_element! {
+    Bucket {
+        "CreationDate" => creation_date,
+        "Name" => name,
+    }
+}
+
 impl From<ListBucketsError> for S3Error {
     fn from(e: ListBucketsError) -> Self {
         match e {}
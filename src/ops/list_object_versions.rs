@@ -0,0 +1,177 @@
+//! [`ListObjectVersions`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectVersions.html)
+//!
+//! Buckets are not versioned, so each object is listed as its only version,
+//! whose version id is `null`. Clients such as `mc` list versions before removing objects.
+
+use super::{
+    listed_key_encoder, wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource,
+    Route, S3Handler,
+};
+
+use crate::dto::{ListObjectVersionsOutput, ListObjectsOutput, ListObjectsRequest, ObjectVersion};
+use crate::errors::S3Result;
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{async_trait, Method, Response};
+
+/// `ListObjectVersions` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::ListObjectVersions
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::GET, Resource::Bucket).query(&["versions"])],
+            reference: Some(
+                "https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectVersions.html",
+            ),
+        }
+    }
+
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.req.method() == Method::GET);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.get("versions").is_some()
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx)?;
+        let output = storage.list_objects(input).await.map(into_versions);
+        output.try_into_response()
+    }
+}
+
+/// extract operation request, as a `ListObjects` request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<ListObjectsRequest> {
+    let bucket = ctx.unwrap_bucket_path();
+
+    let mut input = ListObjectsRequest {
+        bucket: bucket.into(),
+        ..ListObjectsRequest::default()
+    };
+
+    if let Some(ref q) = ctx.query_strings {
+        q.assign_str("delimiter", &mut input.delimiter);
+        q.assign_str("encoding-type", &mut input.encoding_type);
+        q.assign_str("key-marker", &mut input.marker);
+        q.assign("max-keys", &mut input.max_keys)
+            .map_err(|err| invalid_request!("Invalid query: max-keys", err))?;
+        q.assign_str("prefix", &mut input.prefix);
+    }
+
+    if input.max_keys.map_or(false, |n| n < 0) {
+        return Err(code_error!(
+            InvalidArgument,
+            "max-keys must be a non-negative integer."
+        ));
+    }
+    ctx.clamp_max_keys(&mut input.max_keys);
+
+    assign_headers!(ctx.headers => input {
+        X_AMZ_REQUEST_PAYER => request_payer,
+    });
+
+    Ok(input)
+}
+
+/// lists each object as its only version
+fn into_versions(output: ListObjectsOutput) -> ListObjectVersionsOutput {
+    let is_truncated = output.is_truncated;
+    let versions = output.contents.map(|contents| {
+        contents
+            .into_iter()
+            .map(|object| ObjectVersion {
+                e_tag: object.e_tag,
+                is_latest: Some(true),
+                key: object.key,
+                last_modified: object.last_modified,
+                owner: object.owner,
+                size: object.size,
+                storage_class: object.storage_class,
+                version_id: Some("null".to_owned()),
+            })
+            .collect::<Vec<_>>()
+    });
+    let next_key_marker = match (is_truncated, output.next_marker) {
+        (Some(true), None) => versions
+            .as_ref()
+            .and_then(|v| v.last())
+            .and_then(|v| v.key.clone()),
+        (_, next_marker) => next_marker,
+    };
+
+    ListObjectVersionsOutput {
+        common_prefixes: output.common_prefixes,
+        delimiter: output.delimiter,
+        encoding_type: output.encoding_type,
+        is_truncated,
+        key_marker: Some(output.marker.unwrap_or_default()),
+        max_keys: output.max_keys,
+        name: output.name,
+        next_version_id_marker: next_key_marker.as_ref().map(|_| "null".to_owned()),
+        next_key_marker,
+        prefix: output.prefix,
+        version_id_marker: Some(String::new()),
+        versions,
+        ..ListObjectVersionsOutput::default()
+    }
+}
+
+impl S3Output for ListObjectVersionsOutput {
+    fn try_into_response(mut self) -> S3Result<Response> {
+        let encode = listed_key_encoder(self.encoding_type.as_deref());
+        for version in self.versions.iter_mut().flatten() {
+            version.key = encode(version.key.take())?;
+        }
+        for common_prefix in self.common_prefixes.iter_mut().flatten() {
+            common_prefix.prefix = encode(common_prefix.prefix.take())?;
+        }
+        self.prefix = encode(self.prefix.take())?;
+        self.delimiter = encode(self.delimiter.take())?;
+        self.key_marker = encode(self.key_marker.take())?;
+        self.next_key_marker = encode(self.next_key_marker.take())?;
+
+        wrap_internal_error(|res| {
+            res.set_xml_body(4096, |w| {
+                xml_element!(w, root "ListVersionsResult", self {
+                    "IsTruncated" => is_truncated,
+                    "KeyMarker" => key_marker,
+                    "VersionIdMarker" => version_id_marker,
+                    "NextKeyMarker" => next_key_marker,
+                    "NextVersionIdMarker" => next_version_id_marker,
+                    "Version" => versions,
+                    "Name" => name,
+                    "Prefix" => prefix,
+                    "Delimiter" => delimiter,
+                    "MaxKeys" => max_keys,
+                    "CommonPrefixes" => common_prefixes,
+                    "EncodingType" => encoding_type,
+                })
+            })
+        })
+    }
+}
+
+impl_xml_element! {
+    ObjectVersion {
+        "Key" => key,
+        "VersionId" => version_id,
+        "IsLatest" => is_latest,
+        "LastModified" => last_modified,
+        "ETag" => e_tag,
+        "Size" => size,
+        "StorageClass" => storage_class,
+        "Owner" => owner,
+    }
+}
@@ -1,13 +1,15 @@
 //! [`ListObjects`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjects.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{
+    listed_key_encoder, wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource,
+    Route, S3Handler,
+};
 
 use crate::dto::{ListObjectsError, ListObjectsOutput, ListObjectsRequest};
 use crate::errors::{S3Error, S3ErrorCode, S3Result};
-use crate::headers::X_AMZ_REQUEST_PAYER;
 use crate::output::S3Output;
 use crate::storage::S3Storage;
-use crate::utils::{ResponseExt, XmlWriterExt};
+use crate::utils::ResponseExt;
 use crate::{async_trait, Method, Response};
 
 /// `ListObjects` handler
@@ -15,6 +17,18 @@ pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::ListObjects
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::GET, Resource::Bucket)],
+            reference: Some("https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjects.html"),
+        }
+    }
+
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
         bool_try!(ctx.req.method() == Method::GET);
         bool_try!(ctx.path.is_bucket());
@@ -36,7 +50,7 @@ impl S3Handler for Handler {
 }
 
 /// extract operation request
-fn extract(ctx: &mut ReqContext<'_>) -> S3Result<ListObjectsRequest> {
+fn extract(ctx: &ReqContext<'_>) -> S3Result<ListObjectsRequest> {
     let bucket = ctx.unwrap_bucket_path();
 
     let mut input = ListObjectsRequest {
@@ -53,48 +67,48 @@ fn extract(ctx: &mut ReqContext<'_>) -> S3Result<ListObjectsRequest> {
         q.assign_str("prefix", &mut input.prefix);
     }
 
-    ctx.headers
-        .assign_str(X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+    if input.max_keys.map_or(false, |n| n < 0) {
+        return Err(code_error!(
+            InvalidArgument,
+            "max-keys must be a non-negative integer."
+        ));
+    }
+    ctx.clamp_max_keys(&mut input.max_keys);
+
+    assign_headers!(ctx.headers => input {
+        X_AMZ_REQUEST_PAYER => request_payer,
+    });
 
     Ok(input)
 }
 
 impl S3Output for ListObjectsOutput {
-    #[allow(clippy::shadow_unrelated)]
-    fn try_into_response(self) -> S3Result<Response> {
+    fn try_into_response(mut self) -> S3Result<Response> {
+        let encode = listed_key_encoder(self.encoding_type.as_deref());
+        for content in self.contents.iter_mut().flatten() {
+            content.key = encode(content.key.take())?;
+        }
+        for common_prefix in self.common_prefixes.iter_mut().flatten() {
+            common_prefix.prefix = encode(common_prefix.prefix.take())?;
+        }
+        self.prefix = encode(self.prefix.take())?;
+        self.delimiter = encode(self.delimiter.take())?;
+        self.marker = encode(self.marker.take())?;
+        self.next_marker = encode(self.next_marker.take())?;
+
         wrap_internal_error(|res| {
             res.set_xml_body(4096, |w| {
-                w.stack("ListBucketResult", |w| {
-                    w.opt_element("IsTruncated", self.is_truncated.map(|b| b.to_string()))?;
-                    w.opt_element("Marker", self.marker)?;
-                    w.opt_element("NextMarker", self.next_marker)?;
-                    if let Some(contents) = self.contents {
-                        for content in contents {
-                            w.stack("Contents", |w| {
-                                w.opt_element("Key", content.key)?;
-                                w.opt_element("LastModified", content.last_modified)?;
-                                w.opt_element("ETag", content.e_tag)?;
-                                w.opt_element("Size", content.size.map(|s| s.to_string()))?;
-                                w.opt_element("StorageClass", content.storage_class)?;
-                                w.opt_stack("Owner", content.owner, |w, owner| {
-                                    w.opt_element("ID", owner.id)?;
-                                    w.opt_element("DisplayName", owner.display_name)?;
-                                    Ok(())
-                                })
-                            })?;
-                        }
-                    }
-                    w.opt_element("Name", self.name)?;
-                    w.opt_element("Prefix", self.prefix)?;
-                    w.opt_element("Delimiter", self.delimiter)?;
-                    w.opt_element("MaxKeys", self.max_keys.map(|k| k.to_string()))?;
-                    w.opt_stack("CommonPrefixes", self.common_prefixes, |w, prefixes| {
-                        w.iter_element(prefixes.into_iter(), |w, common_prefix| {
-                            w.opt_element("Prefix", common_prefix.prefix)
-                        })
-                    })?;
-                    w.opt_element("EncodingType", self.encoding_type)?;
-                    Ok(())
+                xml_element!(w, root "ListBucketResult", self {
+                    "IsTruncated" => is_truncated,
+                    "Marker" => marker,
+                    "NextMarker" => next_marker,
+                    "Contents" => contents,
+                    "Name" => name,
+                    "Prefix" => prefix,
+                    "Delimiter" => delimiter,
+                    "MaxKeys" => max_keys,
+                    "CommonPrefixes" => common_prefixes,
+                    "EncodingType" => encoding_type,
                 })
             })
         })
@@ -1,20 +1,41 @@
 //! [`ListObjectsV2`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectsV2.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::{
+    listed_key_encoder, wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource,
+    Route, S3Handler,
+};
 
 use crate::dto::{ListObjectsV2Error, ListObjectsV2Output, ListObjectsV2Request};
 use crate::errors::{S3Error, S3ErrorCode, S3Result};
-use crate::headers::X_AMZ_REQUEST_PAYER;
 use crate::output::S3Output;
 use crate::storage::S3Storage;
-use crate::utils::{ResponseExt, XmlWriterExt};
+use crate::utils::ResponseExt;
 use crate::{async_trait, Method, Response};
 
+#[cfg(feature = "extensions")]
+use crate::dto::{FilteredListObjectsV2Request, ListObjectsFilter};
+#[cfg(feature = "extensions")]
+use crate::utils::time;
+
 /// `ListObjectsV2` handler
 pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::ListObjectsV2
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::GET, Resource::Bucket).query(&["list-type=2"])],
+            reference: Some(
+                "https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectsV2.html",
+            ),
+        }
+    }
+
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
         bool_try!(ctx.req.method() == Method::GET);
         bool_try!(ctx.path.is_bucket());
@@ -29,14 +50,61 @@ impl S3Handler for Handler {
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
         let input = extract(ctx)?;
+        #[cfg(feature = "extensions")]
+        {
+            let filter = extract_filter(ctx)?;
+            if filter != ListObjectsFilter::default() {
+                let input = FilteredListObjectsV2Request { input, filter };
+                let output = storage.list_objects_v2_filtered(input).await;
+                return output.map(|o| with_owner(o, ctx)).try_into_response();
+            }
+        }
         let output = storage.list_objects_v2(input).await;
-        output.try_into_response()
+        output.map(|o| with_owner(o, ctx)).try_into_response()
+    }
+}
+
+/// lists the owner resolved for `fetch-owner=true` with each object
+fn with_owner(mut output: ListObjectsV2Output, ctx: &ReqContext<'_>) -> ListObjectsV2Output {
+    if let Some(ref owner) = ctx.owner {
+        for content in output.contents.iter_mut().flatten() {
+            content.owner = Some(owner.clone());
+        }
     }
+    output
+}
+
+/// extract the vendor-specific filters
+#[cfg(feature = "extensions")]
+fn extract_filter(ctx: &ReqContext<'_>) -> S3Result<ListObjectsFilter> {
+    let mut filter = ListObjectsFilter::default();
+    let q = match ctx.query_strings {
+        Some(ref q) => q,
+        None => return Ok(filter),
+    };
+
+    let parse_time = |name: &str| {
+        q.get(name)
+            .map(|s| {
+                time::parse_rfc3339(s)
+                    .map_err(|err| invalid_request!(format!("Invalid query: {name}"), err))
+            })
+            .transpose()
+    };
+    filter.modified_after = parse_time("x-mtime-after")?;
+    filter.modified_before = parse_time("x-mtime-before")?;
+
+    q.assign("x-min-size", &mut filter.min_size)
+        .map_err(|err| invalid_request!("Invalid query: x-min-size", err))?;
+    q.assign("x-max-size", &mut filter.max_size)
+        .map_err(|err| invalid_request!("Invalid query: x-max-size", err))?;
+
+    Ok(filter)
 }
 
 /// extract operation request
 fn extract(
-    ctx: &mut ReqContext<'_>,
+    ctx: &ReqContext<'_>,
     // req: &Request,
     // query: GetQuery,
     // bucket: &str,
@@ -61,50 +129,49 @@ fn extract(
         q.assign_str("start-after", &mut input.start_after);
     }
 
-    ctx.headers
-        .assign_str(X_AMZ_REQUEST_PAYER, &mut input.request_payer);
+    if input.max_keys.map_or(false, |n| n < 0) {
+        return Err(code_error!(
+            InvalidArgument,
+            "max-keys must be a non-negative integer."
+        ));
+    }
+    ctx.clamp_max_keys(&mut input.max_keys);
+
+    assign_headers!(ctx.headers => input {
+        X_AMZ_REQUEST_PAYER => request_payer,
+    });
 
     Ok(input)
 }
 
 impl S3Output for ListObjectsV2Output {
-    #[allow(clippy::shadow_unrelated)]
-    fn try_into_response(self) -> S3Result<Response> {
+    fn try_into_response(mut self) -> S3Result<Response> {
+        let encode = listed_key_encoder(self.encoding_type.as_deref());
+        for content in self.contents.iter_mut().flatten() {
+            content.key = encode(content.key.take())?;
+        }
+        for common_prefix in self.common_prefixes.iter_mut().flatten() {
+            common_prefix.prefix = encode(common_prefix.prefix.take())?;
+        }
+        self.prefix = encode(self.prefix.take())?;
+        self.delimiter = encode(self.delimiter.take())?;
+        self.start_after = encode(self.start_after.take())?;
+
         wrap_internal_error(|res| {
             res.set_xml_body(4096, |w| {
-                w.stack("ListBucketResult", |w| {
-                    w.opt_element("IsTruncated", self.is_truncated.map(|b| b.to_string()))?;
-                    if let Some(contents) = self.contents {
-                        for content in contents {
-                            w.stack("Contents", |w| {
-                                w.opt_element("Key", content.key)?;
-                                w.opt_element("LastModified", content.last_modified)?;
-                                w.opt_element("ETag", content.e_tag)?;
-                                w.opt_element("Size", content.size.map(|s| s.to_string()))?;
-                                w.opt_element("StorageClass", content.storage_class)?;
-                                w.opt_stack("Owner", content.owner, |w, owner| {
-                                    w.opt_element("ID", owner.id)?;
-                                    w.opt_element("DisplayName", owner.display_name)?;
-                                    Ok(())
-                                })
-                            })?;
-                        }
-                    }
-                    w.opt_element("Name", self.name)?;
-                    w.opt_element("Prefix", self.prefix)?;
-                    w.opt_element("Delimiter", self.delimiter)?;
-                    w.opt_element("MaxKeys", self.max_keys.map(|k| k.to_string()))?;
-                    w.opt_stack("CommonPrefixes", self.common_prefixes, |w, prefixes| {
-                        w.iter_element(prefixes.into_iter(), |w, common_prefix| {
-                            w.opt_element("Prefix", common_prefix.prefix)
-                        })
-                    })?;
-                    w.opt_element("EncodingType", self.encoding_type)?;
-                    w.opt_element("KeyCount", self.key_count.map(|k| k.to_string()))?;
-                    w.opt_element("ContinuationToken", self.continuation_token)?;
-                    w.opt_element("NextContinuationToken", self.next_continuation_token)?;
-                    w.opt_element("StartAfter", self.start_after)?;
-                    Ok(())
+                xml_element!(w, root "ListBucketResult", self {
+                    "IsTruncated" => is_truncated,
+                    "Contents" => contents,
+                    "Name" => name,
+                    "Prefix" => prefix,
+                    "Delimiter" => delimiter,
+                    "MaxKeys" => max_keys,
+                    "CommonPrefixes" => common_prefixes,
+                    "EncodingType" => encoding_type,
+                    "KeyCount" => key_count,
+                    "ContinuationToken" => continuation_token,
+                    "NextContinuationToken" => next_continuation_token,
+                    "StartAfter" => start_after,
                 })
             })
         })
@@ -0,0 +1,101 @@
+//! kinds of S3 operations
+
+use std::fmt;
+
+/// A supported S3 operation, determined by routing a request to its handler
+///
+/// The names are the operation names of the S3 API and never change between versions,
+/// so they are suitable as log fields and metric labels.
+/// See [`RequestUsage::operation`](crate::RequestUsage::operation)
+/// and [`AuditRecord::operation`](crate::AuditRecord::operation).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OperationKind {
+    /// `CompleteMultipartUpload`
+    CompleteMultipartUpload,
+    /// `CopyObject`
+    CopyObject,
+    /// `CreateBucket`
+    CreateBucket,
+    /// `CreateMultipartUpload`
+    CreateMultipartUpload,
+    /// `DeleteBucket`
+    DeleteBucket,
+    /// `DeleteObject`
+    DeleteObject,
+    /// `DeleteObjects`
+    DeleteObjects,
+    /// `GetBucketAccelerateConfiguration`
+    GetBucketAccelerateConfiguration,
+    /// `GetBucketLocation`
+    GetBucketLocation,
+    /// `GetBucketRequestPayment`
+    GetBucketRequestPayment,
+    /// `GetObject`
+    GetObject,
+    /// `HeadBucket`
+    HeadBucket,
+    /// `HeadObject`
+    HeadObject,
+    /// `ListBuckets`
+    ListBuckets,
+    /// `ListObjectVersions`
+    ListObjectVersions,
+    /// `ListObjects`
+    ListObjects,
+    /// `ListObjectsV2`
+    ListObjectsV2,
+    /// `PutBucketAccelerateConfiguration`
+    PutBucketAccelerateConfiguration,
+    /// `PutBucketRequestPayment`
+    PutBucketRequestPayment,
+    /// `PutObject`, including POST Object of browser-based uploads
+    PutObject,
+    /// `RenameBucket` (custom)
+    RenameBucket,
+    /// `RenameObject` (custom)
+    RenameObject,
+    /// `UndeleteObject` (custom)
+    UndeleteObject,
+    /// `UploadPart`
+    UploadPart,
+}
+
+impl OperationKind {
+    /// Returns the operation name, such as `"GetObject"`
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::CompleteMultipartUpload => "CompleteMultipartUpload",
+            Self::CopyObject => "CopyObject",
+            Self::CreateBucket => "CreateBucket",
+            Self::CreateMultipartUpload => "CreateMultipartUpload",
+            Self::DeleteBucket => "DeleteBucket",
+            Self::DeleteObject => "DeleteObject",
+            Self::DeleteObjects => "DeleteObjects",
+            Self::GetBucketAccelerateConfiguration => "GetBucketAccelerateConfiguration",
+            Self::GetBucketLocation => "GetBucketLocation",
+            Self::GetBucketRequestPayment => "GetBucketRequestPayment",
+            Self::GetObject => "GetObject",
+            Self::HeadBucket => "HeadBucket",
+            Self::HeadObject => "HeadObject",
+            Self::ListBuckets => "ListBuckets",
+            Self::ListObjectVersions => "ListObjectVersions",
+            Self::ListObjects => "ListObjects",
+            Self::ListObjectsV2 => "ListObjectsV2",
+            Self::PutBucketAccelerateConfiguration => "PutBucketAccelerateConfiguration",
+            Self::PutBucketRequestPayment => "PutBucketRequestPayment",
+            Self::PutObject => "PutObject",
+            Self::RenameBucket => "RenameBucket",
+            Self::RenameObject => "RenameObject",
+            Self::UndeleteObject => "UndeleteObject",
+            Self::UploadPart => "UploadPart",
+        }
+    }
+}
+
+impl fmt::Display for OperationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,112 @@
+//! [`PutBucketAccelerateConfiguration`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketAccelerateConfiguration.html)
+
+use super::{OperationDoc, OperationKind, ReqContext, Resource, Route, S3Handler};
+
+use crate::dto::{
+    AccelerateConfiguration, PutBucketAccelerateConfigurationError,
+    PutBucketAccelerateConfigurationOutput, PutBucketAccelerateConfigurationRequest,
+};
+use crate::errors::{S3Error, S3Result};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::body::deserialize_xml_body;
+use crate::{async_trait, Body, Method, Response};
+
+/// `PutBucketAccelerateConfiguration` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::PutBucketAccelerateConfiguration
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::PUT, Resource::Bucket).query(&["accelerate"])],
+            reference: Some("https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketAccelerateConfiguration.html"),
+        }
+    }
+
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.req.method() == Method::PUT);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.get("accelerate").is_some()
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx).await?;
+        let output = storage.put_bucket_accelerate_configuration(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<PutBucketAccelerateConfigurationRequest> {
+    let config: xml::AccelerateConfiguration = deserialize_xml_body(ctx.take_body())
+        .await
+        .map_err(|err| code_error!(MalformedXML, "Invalid xml format", err))?;
+
+    if let Some(ref status) = config.status {
+        if status != "Enabled" && status != "Suspended" {
+            return Err(code_error!(
+                MalformedXML,
+                "The accelerate status must be Enabled or Suspended."
+            ));
+        }
+    }
+
+    let bucket = ctx.unwrap_bucket_path();
+
+    let mut input = PutBucketAccelerateConfigurationRequest {
+        accelerate_configuration: config.into(),
+        bucket: bucket.into(),
+        expected_bucket_owner: None,
+    };
+
+    assign_headers!(ctx.headers => input {
+        X_AMZ_EXPECTED_BUCKET_OWNER => expected_bucket_owner,
+    });
+
+    Ok(input)
+}
+
+impl S3Output for PutBucketAccelerateConfigurationOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        Ok(Response::new(Body::empty()))
+    }
+}
+
+impl From<PutBucketAccelerateConfigurationError> for S3Error {
+    fn from(e: PutBucketAccelerateConfigurationError) -> Self {
+        match e {}
+    }
+}
+
+mod xml {
+    //! xml repr
+
+    use serde::Deserialize;
+
+    /// `AccelerateConfiguration`
+    #[derive(Debug, Deserialize)]
+    pub struct AccelerateConfiguration {
+        /// `Status`
+        #[serde(rename = "Status")]
+        pub status: Option<String>,
+    }
+
+    impl From<AccelerateConfiguration> for super::AccelerateConfiguration {
+        fn from(config: AccelerateConfiguration) -> Self {
+            Self {
+                status: config.status,
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! [`PutBucketRequestPayment`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketRequestPayment.html)
+
+use super::{OperationDoc, OperationKind, ReqContext, Resource, Route, S3Handler};
+
+use crate::dto::{
+    PutBucketRequestPaymentError, PutBucketRequestPaymentOutput, PutBucketRequestPaymentRequest,
+    RequestPaymentConfiguration,
+};
+use crate::errors::{S3Error, S3Result};
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::utils::body::deserialize_xml_body;
+use crate::{async_trait, Body, Method, Response};
+
+/// `PutBucketRequestPayment` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::PutBucketRequestPayment
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::PUT, Resource::Bucket).query(&["requestPayment"])],
+            reference: Some(
+                "https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketRequestPayment.html",
+            ),
+        }
+    }
+
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.req.method() == Method::PUT);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.get("requestPayment").is_some()
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx).await?;
+        let output = storage.put_bucket_request_payment(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+async fn extract(ctx: &mut ReqContext<'_>) -> S3Result<PutBucketRequestPaymentRequest> {
+    let config: xml::RequestPaymentConfiguration = deserialize_xml_body(ctx.take_body())
+        .await
+        .map_err(|err| code_error!(MalformedXML, "Invalid xml format", err))?;
+
+    if config.payer != "Requester" && config.payer != "BucketOwner" {
+        return Err(code_error!(
+            MalformedXML,
+            "The payer must be Requester or BucketOwner."
+        ));
+    }
+
+    let bucket = ctx.unwrap_bucket_path();
+
+    let mut input = PutBucketRequestPaymentRequest {
+        bucket: bucket.into(),
+        request_payment_configuration: config.into(),
+        ..PutBucketRequestPaymentRequest::default()
+    };
+
+    assign_headers!(ctx.headers => input {
+        CONTENT_MD5 => content_md5,
+        X_AMZ_EXPECTED_BUCKET_OWNER => expected_bucket_owner,
+    });
+
+    Ok(input)
+}
+
+impl S3Output for PutBucketRequestPaymentOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        Ok(Response::new(Body::empty()))
+    }
+}
+
+impl From<PutBucketRequestPaymentError> for S3Error {
+    fn from(e: PutBucketRequestPaymentError) -> Self {
+        match e {}
+    }
+}
+
+mod xml {
+    //! xml repr
+
+    use serde::Deserialize;
+
+    /// `RequestPaymentConfiguration`
+    #[derive(Debug, Deserialize)]
+    pub struct RequestPaymentConfiguration {
+        /// `Payer`
+        #[serde(rename = "Payer")]
+        pub payer: String,
+    }
+
+    impl From<RequestPaymentConfiguration> for super::RequestPaymentConfiguration {
+        fn from(config: RequestPaymentConfiguration) -> Self {
+            Self {
+                payer: config.payer,
+            }
+        }
+    }
+}
@@ -1,36 +1,50 @@
 //! [`PutObject`](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObject.html)
 
-use super::{wrap_internal_error, ReqContext, S3Handler};
+use super::validation::Validate;
+use super::{
+    check_upload_size, collect_metadata, is_create_only, wrap_internal_error, OperationDoc,
+    OperationKind, ReqContext, Resource, Route, S3Handler,
+};
 
-use crate::dto::{PutObjectError, PutObjectOutput, PutObjectRequest};
+use crate::dto::{
+    AppendObjectRequest, ByteStream, PutObjectError, PutObjectOutput, PutObjectRequest,
+};
 use crate::errors::{S3Error, S3ErrorCode, S3Result};
 use crate::headers::{
-    CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LANGUAGE, CONTENT_LENGTH,
-    CONTENT_MD5, CONTENT_TYPE, ETAG, EXPIRES, X_AMZ_ACL, X_AMZ_EXPIRATION,
-    X_AMZ_GRANT_FULL_CONTROL, X_AMZ_GRANT_READ, X_AMZ_GRANT_READ_ACP, X_AMZ_GRANT_WRITE_ACP,
-    X_AMZ_OBJECT_LOCK_LEGAL_HOLD, X_AMZ_OBJECT_LOCK_MODE, X_AMZ_OBJECT_LOCK_RETAIN_UNTIL_DATE,
-    X_AMZ_REQUEST_CHARGED, X_AMZ_REQUEST_PAYER, X_AMZ_SERVER_SIDE_ENCRYPTION,
+    CONTENT_LENGTH, ETAG, X_AMZ_EXPIRATION, X_AMZ_REQUEST_CHARGED, X_AMZ_SERVER_SIDE_ENCRYPTION,
     X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID, X_AMZ_SERVER_SIDE_ENCRYPTION_CONTEXT,
-    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM, X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
-    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5, X_AMZ_STORAGE_CLASS, X_AMZ_TAGGING,
-    X_AMZ_VERSION_ID, X_AMZ_WEBSITE_REDIRECT_LOCATION,
+    X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM, X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
+    X_AMZ_VERSION_ID, X_AMZ_WRITE_OFFSET_BYTES,
 };
 use crate::output::S3Output;
 use crate::path::S3Path;
 use crate::storage::S3Storage;
+use crate::streams::content_length_range_stream::ContentLengthRangeStream;
 use crate::streams::multipart::Multipart;
 use crate::utils::body::{transform_body_stream, transform_file_stream};
 use crate::utils::{Apply, ResponseExt};
 use crate::{async_trait, Method, Response};
 
-use std::collections::HashMap;
-use std::mem;
-
 /// `PutObject` handler
 pub struct Handler;
 
 #[async_trait]
 impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::PutObject
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![
+                Route::new(Method::PUT, Resource::Object),
+                Route::new(Method::POST, Resource::Bucket).headers(&["content-type"]),
+            ],
+            reference: Some("https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObject.html"),
+        }
+    }
+
     fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
         if ctx.req.method() == Method::POST {
             bool_try!(ctx.path.is_bucket());
@@ -51,43 +65,115 @@ impl S3Handler for Handler {
         ctx: &mut ReqContext<'_>,
         storage: &(dyn S3Storage + Send + Sync),
     ) -> S3Result<Response> {
-        let input = extract(ctx)?;
-        let output = storage.put_object(input).await;
+        if ctx.req.method() == Method::PUT {
+            check_upload_size(ctx)?;
+        }
+        let write_offset_bytes = extract_write_offset(ctx)?;
+        let mut input = extract(ctx)?;
+        input.validate()?;
+        if input.content_type.is_none() {
+            input.content_type = ctx.guess_content_type(&input.bucket, &input.key);
+        }
+        if let Some(write_offset_bytes) = write_offset_bytes {
+            // an append always modifies an existing object
+            if ctx.worm {
+                return Err(code_error!(
+                    PreconditionFailed,
+                    "The object already exists and the bucket is write-once."
+                ));
+            }
+            let input = AppendObjectRequest {
+                input,
+                write_offset_bytes,
+            };
+            let output = storage.append_object(input).await;
+            return output.try_into_response();
+        }
+        let output = if is_create_only(ctx)? {
+            storage.put_object_if_absent(input).await
+        } else {
+            storage.put_object(input).await
+        };
         output.try_into_response()
     }
 }
 
 /// extract from multipart
-fn extract_from_multipart(input: &mut PutObjectRequest, mut multipart: Multipart) -> S3Result<()> {
+fn extract_from_multipart(
+    input: &mut PutObjectRequest,
+    mut multipart: Multipart,
+    (min_size, max_size): (u64, u64),
+) -> S3Result<()> {
     multipart.assign_str("acl", &mut input.acl);
     multipart.assign_str("content-type", &mut input.content_type);
     multipart.assign_str("expires", &mut input.expires);
     multipart.assign_str("tagging", &mut input.tagging);
     multipart.assign_str("x-amz-storage-class", &mut input.storage_class);
 
-    let mut metadata: HashMap<String, String> = HashMap::new();
-    for &mut (ref mut name, ref mut value) in &mut multipart.fields {
+    for &mut (ref mut name, _) in &mut multipart.fields {
         name.make_ascii_lowercase();
-        let meta_prefix = "x-amz-meta-";
-        if name.starts_with(meta_prefix) {
-            let (_, meta_key) = name.split_at(meta_prefix.len());
-            if !meta_key.is_empty() {
-                let _prev = metadata.insert(meta_key.to_owned(), mem::take(value));
-            }
-        }
-    }
-    if !metadata.is_empty() {
-        input.metadata = Some(metadata);
     }
+    input.metadata = collect_metadata(
+        multipart
+            .fields
+            .iter()
+            .map(|&(ref name, ref value)| (name.as_str(), value.as_str())),
+    );
     // TODO: how to handle the other fields?
 
     let file_stream = multipart.file.stream;
 
-    input.body = file_stream.apply(transform_file_stream).apply(Some);
+    let body = file_stream.apply(transform_file_stream);
+    input.body =
+        ByteStream::new(ContentLengthRangeStream::new(body, min_size, max_size)).apply(Some);
 
     Ok(())
 }
 
+/// extract the object key of POST Object
+fn extract_form_key(multipart: &Multipart) -> S3Result<&str> {
+    let key = multipart
+        .find_field_value("key")
+        .ok_or_else(|| S3Error::new(S3ErrorCode::UserKeyMustBeSpecified, "Missing key"))?;
+
+    if !S3Path::check_key(key) {
+        return Err(S3Error::new(
+            S3ErrorCode::KeyTooLongError,
+            "Your key is too long.",
+        ));
+    }
+
+    if !S3Path::is_safe_key(key) {
+        return Err(S3Error::new(
+            S3ErrorCode::InvalidArgument,
+            "The specified key is not allowed.",
+        ));
+    }
+
+    Ok(key)
+}
+
+/// extract `x-amz-write-offset-bytes` of an append request
+fn extract_write_offset(ctx: &ReqContext<'_>) -> S3Result<Option<u64>> {
+    let value = match ctx.headers.get(X_AMZ_WRITE_OFFSET_BYTES) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    if ctx.req.method() != Method::PUT {
+        return Err(invalid_request!(
+            "Appending is not supported by POST Object."
+        ));
+    }
+    let offset = value.parse::<u64>().map_err(|err| {
+        code_error!(
+            InvalidArgument,
+            "Invalid header: x-amz-write-offset-bytes",
+            err
+        )
+    })?;
+    Ok(Some(offset))
+}
+
 /// extract operation request
 fn extract(ctx: &mut ReqContext<'_>) -> S3Result<PutObjectRequest> {
     let (bucket, key) = if ctx.req.method() == Method::POST {
@@ -96,18 +182,7 @@ fn extract(ctx: &mut ReqContext<'_>) -> S3Result<PutObjectRequest> {
         #[allow(clippy::unwrap_used)]
         let multipart = ctx.multipart.as_ref().unwrap();
 
-        let key = multipart
-            .find_field_value("key")
-            .ok_or_else(|| S3Error::new(S3ErrorCode::UserKeyMustBeSpecified, "Missing key"))?;
-
-        if !S3Path::check_key(key) {
-            return Err(S3Error::new(
-                S3ErrorCode::KeyTooLongError,
-                "Your key is too long.",
-            ));
-        }
-
-        (bucket, key)
+        (bucket, extract_form_key(multipart)?)
     } else if ctx.req.method() == Method::PUT {
         ctx.unwrap_object_path()
     } else {
@@ -125,78 +200,28 @@ fn extract(ctx: &mut ReqContext<'_>) -> S3Result<PutObjectRequest> {
     h.assign(CONTENT_LENGTH, &mut input.content_length)
         .map_err(|err| invalid_request!("Invalid header: content-length", err))?;
 
-    h.assign_str(X_AMZ_ACL, &mut input.acl);
-    h.assign_str(CACHE_CONTROL, &mut input.cache_control);
-    h.assign_str(CONTENT_DISPOSITION, &mut input.content_disposition);
-    h.assign_str(CONTENT_ENCODING, &mut input.content_encoding);
-    h.assign_str(CONTENT_LANGUAGE, &mut input.content_language);
-    h.assign_str(CONTENT_MD5, &mut input.content_md5);
-    h.assign_str(CONTENT_TYPE, &mut input.content_type);
-    h.assign_str(EXPIRES, &mut input.expires);
-    h.assign_str(X_AMZ_GRANT_FULL_CONTROL, &mut input.grant_full_control);
-    h.assign_str(X_AMZ_GRANT_READ, &mut input.grant_read);
-    h.assign_str(X_AMZ_GRANT_READ_ACP, &mut input.grant_read_acp);
-    h.assign_str(X_AMZ_GRANT_WRITE_ACP, &mut input.grant_write_acp);
-
-    h.assign_str(
-        X_AMZ_SERVER_SIDE_ENCRYPTION,
-        &mut input.server_side_encryption,
-    );
-    h.assign_str(X_AMZ_STORAGE_CLASS, &mut input.storage_class);
-    h.assign_str(
-        X_AMZ_WEBSITE_REDIRECT_LOCATION,
-        &mut input.website_redirect_location,
-    );
-    h.assign_str(
-        X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
-        &mut input.sse_customer_algorithm,
-    );
-    h.assign_str(
-        X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
-        &mut input.sse_customer_key,
-    );
-    h.assign_str(
-        X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
-        &mut input.sse_customer_key_md5,
-    );
-    h.assign_str(
-        X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID,
-        &mut input.ssekms_key_id,
-    );
-    h.assign_str(
-        X_AMZ_SERVER_SIDE_ENCRYPTION_CONTEXT,
-        &mut input.ssekms_encryption_context,
-    );
-    h.assign_str(X_AMZ_REQUEST_PAYER, &mut input.request_payer);
-    h.assign_str(X_AMZ_TAGGING, &mut input.tagging);
-    h.assign_str(X_AMZ_OBJECT_LOCK_MODE, &mut input.object_lock_mode);
-    h.assign_str(
-        X_AMZ_OBJECT_LOCK_RETAIN_UNTIL_DATE,
-        &mut input.object_lock_retain_until_date,
-    );
-    h.assign_str(
-        X_AMZ_OBJECT_LOCK_LEGAL_HOLD,
-        &mut input.object_lock_legal_hold_status,
-    );
-
-    let mut metadata: HashMap<String, String> = HashMap::new();
-    for &(name, value) in ctx.headers.as_ref() {
-        let meta_prefix = "x-amz-meta-";
-        if name.starts_with(meta_prefix) {
-            let (_, meta_key) = name.split_at(meta_prefix.len());
-            if !meta_key.is_empty() {
-                let _prev = metadata.insert(meta_key.to_owned(), value.to_owned());
-            }
-        }
-    }
-    if !metadata.is_empty() {
-        input.metadata = Some(metadata);
-    }
+    assign_headers!(ctx.headers => input {
+        @entity,
+        @grants,
+        @sse,
+        @object_lock,
+        @metadata,
+        CONTENT_MD5 => content_md5,
+        X_AMZ_STORAGE_CLASS => storage_class,
+        X_AMZ_WEBSITE_REDIRECT_LOCATION => website_redirect_location,
+        X_AMZ_REQUEST_PAYER => request_payer,
+        X_AMZ_TAGGING => tagging,
+    });
 
     match ctx.multipart.take() {
         None => input.body = ctx.take_body().apply(transform_body_stream).apply(Some),
-        Some(multipart) => extract_from_multipart(&mut input, multipart)?,
-    };
+        Some(multipart) => {
+            // the file size is unknown until the form is parsed
+            let limit = ctx.limits.max_object_size;
+            let (min_size, max_size) = ctx.content_length_range.unwrap_or((0, limit));
+            extract_from_multipart(&mut input, multipart, (min_size, max_size.min(limit)))?;
+        }
+    }
 
     Ok(input)
 }
@@ -0,0 +1,75 @@
+//! `RenameBucket`, an extension which renames a bucket on the server side
+
+use super::{
+    wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource, Route, S3Handler,
+};
+
+use crate::dto::{RenameBucketOutput, RenameBucketRequest};
+use crate::errors::S3Result;
+use crate::output::S3Output;
+use crate::path::S3Path;
+use crate::storage::S3Storage;
+use crate::{async_trait, Method, Response};
+
+/// `RenameBucket` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::RenameBucket
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::POST, Resource::Bucket).query(&["rename"])],
+            reference: None,
+        }
+    }
+
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.req.method() == Method::POST);
+        bool_try!(ctx.path.is_bucket());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.get("rename").is_some()
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        if ctx.worm {
+            return Err(code_error!(
+                AccessDenied,
+                "A write-once bucket can not be renamed."
+            ));
+        }
+        let input = extract(ctx)?;
+        let output = storage.rename_bucket(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<RenameBucketRequest> {
+    let bucket = ctx.unwrap_bucket_path();
+    let new_bucket = ctx.unwrap_qs("rename");
+    if !S3Path::check_bucket_name(new_bucket) {
+        return Err(code_error!(
+            InvalidBucketName,
+            "The specified bucket is not valid."
+        ));
+    }
+    Ok(RenameBucketRequest {
+        bucket: bucket.into(),
+        new_bucket: new_bucket.into(),
+    })
+}
+
+impl S3Output for RenameBucketOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|_| Ok(()))
+    }
+}
@@ -0,0 +1,101 @@
+//! `RenameObject`, an extension which renames an object or a prefix on the server side
+
+use super::{
+    wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource, Route, S3Handler,
+};
+
+use crate::dto::{RenameObjectOutput, RenameObjectRequest};
+use crate::errors::S3Result;
+use crate::output::S3Output;
+use crate::path::S3Path;
+use crate::storage::S3Storage;
+use crate::utils::ResponseExt;
+use crate::{async_trait, Method, Response};
+
+/// `RenameObject` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::RenameObject
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::POST, Resource::Object).query(&["rename"])],
+            reference: None,
+        }
+    }
+
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.req.method() == Method::POST);
+        bool_try!(ctx.path.is_object());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.get("rename").is_some()
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        if ctx.worm {
+            return Err(code_error!(
+                AccessDenied,
+                "Objects of a write-once bucket can not be renamed."
+            ));
+        }
+        let input = extract(ctx)?;
+        let output = storage.rename_object(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> S3Result<RenameObjectRequest> {
+    let (bucket, key) = ctx.unwrap_object_path();
+    let new_key = ctx.unwrap_qs("rename");
+
+    if !S3Path::check_key(new_key) {
+        return Err(code_error!(KeyTooLongError, "Your key is too long."));
+    }
+    if new_key.is_empty() || !S3Path::is_safe_key(new_key) {
+        return Err(code_error!(
+            InvalidArgument,
+            "The specified key is not allowed."
+        ));
+    }
+    // a prefix is renamed to a prefix, and it can not be moved into itself
+    if key.ends_with('/') != new_key.ends_with('/') {
+        return Err(code_error!(
+            InvalidArgument,
+            "A prefix can only be renamed to a prefix."
+        ));
+    }
+    if key.ends_with('/') && new_key.starts_with(key) {
+        return Err(code_error!(
+            InvalidArgument,
+            "A prefix can not be renamed into itself."
+        ));
+    }
+
+    Ok(RenameObjectRequest {
+        bucket: bucket.into(),
+        key: key.into(),
+        new_key: new_key.into(),
+    })
+}
+
+impl S3Output for RenameObjectOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|res| {
+            res.set_xml_body(256, |w| {
+                xml_element!(w, root "RenameObjectResult", self {
+                    "RenamedCount" => renamed_count,
+                })
+            })
+        })
+    }
+}
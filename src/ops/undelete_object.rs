@@ -0,0 +1,61 @@
+//! `UndeleteObject`, an extension which restores a deleted object from the trash
+
+use super::{
+    wrap_internal_error, OperationDoc, OperationKind, ReqContext, Resource, Route, S3Handler,
+};
+
+use crate::dto::{UndeleteObjectOutput, UndeleteObjectRequest};
+use crate::errors::S3Result;
+use crate::output::S3Output;
+use crate::storage::S3Storage;
+use crate::{async_trait, Method, Response};
+
+/// `UndeleteObject` handler
+pub struct Handler;
+
+#[async_trait]
+impl S3Handler for Handler {
+    fn kind(&self) -> OperationKind {
+        OperationKind::UndeleteObject
+    }
+
+    fn doc(&self) -> OperationDoc {
+        OperationDoc {
+            kind: self.kind(),
+            routes: vec![Route::new(Method::POST, Resource::Object).query(&["undelete"])],
+            reference: None,
+        }
+    }
+
+    fn is_match(&self, ctx: &'_ ReqContext<'_>) -> bool {
+        bool_try!(ctx.req.method() == Method::POST);
+        bool_try!(ctx.path.is_object());
+        let qs = bool_try_some!(ctx.query_strings.as_ref());
+        qs.get("undelete").is_some()
+    }
+
+    async fn handle(
+        &self,
+        ctx: &mut ReqContext<'_>,
+        storage: &(dyn S3Storage + Send + Sync),
+    ) -> S3Result<Response> {
+        let input = extract(ctx);
+        let output = storage.undelete_object(input).await;
+        output.try_into_response()
+    }
+}
+
+/// extract operation request
+fn extract(ctx: &ReqContext<'_>) -> UndeleteObjectRequest {
+    let (bucket, key) = ctx.unwrap_object_path();
+    UndeleteObjectRequest {
+        bucket: bucket.into(),
+        key: key.into(),
+    }
+}
+
+impl S3Output for UndeleteObjectOutput {
+    fn try_into_response(self) -> S3Result<Response> {
+        wrap_internal_error(|_| Ok(()))
+    }
+}
@@ -0,0 +1,207 @@
+//! validation of extracted operation requests
+//!
+//! The checks run after extraction and before calling the storage,
+//! so that every storage receives inputs within the limits of S3.
+
+use crate::dto::{
+    CopyObjectRequest, CreateMultipartUploadRequest, GetObjectRequest, HeadObjectRequest,
+    PutObjectRequest, UploadPartRequest,
+};
+use crate::errors::S3Result;
+use crate::path::S3Path;
+
+use std::collections::HashMap;
+
+/// max total size of user-defined metadata, in bytes
+///
+/// See <https://docs.aws.amazon.com/AmazonS3/latest/userguide/UsingMetadata.html#UserMetadata>
+pub const MAX_METADATA_SIZE: usize = 2 * 1024;
+
+/// max part number of a multipart upload
+pub const MAX_PART_NUMBER: i64 = 10000;
+
+/// storage classes defined by S3
+///
+/// A storage may support only some of them and reject the others.
+const STORAGE_CLASSES: &[&str] = &[
+    "STANDARD",
+    "REDUCED_REDUNDANCY",
+    "STANDARD_IA",
+    "ONEZONE_IA",
+    "INTELLIGENT_TIERING",
+    "GLACIER",
+    "DEEP_ARCHIVE",
+    "OUTPOSTS",
+    "GLACIER_IR",
+];
+
+/// symbols which are valid in header names besides alphanumerics
+const HEADER_NAME_SYMBOLS: &str = "!#$%&'*+-.^_`|~";
+
+/// An extracted operation request which can be validated
+pub trait Validate {
+    /// Checks the request against the limits of S3
+    fn validate(&self) -> S3Result<()>;
+}
+
+/// checks the length of an object key
+fn check_key(key: &str) -> S3Result<()> {
+    if !S3Path::check_key(key) {
+        return Err(code_error!(KeyTooLongError, "Your key is too long."));
+    }
+    Ok(())
+}
+
+/// checks the total size of user-defined metadata
+///
+/// The size is the sum of the UTF-8 lengths of keys and values.
+fn check_metadata(metadata: Option<&HashMap<String, String>>) -> S3Result<()> {
+    let size = metadata.map_or(0, |m| {
+        m.iter()
+            .map(|(k, v)| k.len().saturating_add(v.len()))
+            .fold(0, usize::saturating_add)
+    });
+    if size > MAX_METADATA_SIZE {
+        return Err(code_error!(
+            MetadataTooLarge,
+            "Your metadata headers exceed the maximum allowed metadata size."
+        ));
+    }
+    Ok(())
+}
+
+/// lowercases a metadata key and replaces the characters which are invalid in header names with `_`
+///
+/// Keys of POST Object forms are arbitrary, while they are sent back as `x-amz-meta-*` headers.
+pub fn sanitize_metadata_key(key: &str) -> String {
+    key.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || HEADER_NAME_SYMBOLS.contains(c) {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// checks that a part number is in `1..=10000`
+pub fn check_part_number(part_number: i64) -> S3Result<()> {
+    if !(1..=MAX_PART_NUMBER).contains(&part_number) {
+        return Err(code_error!(
+            InvalidArgument,
+            "Part number must be an integer between 1 and 10000, inclusive"
+        ));
+    }
+    Ok(())
+}
+
+/// checks that a storage class is defined by S3
+fn check_storage_class(storage_class: Option<&str>) -> S3Result<()> {
+    match storage_class {
+        Some(s) if !STORAGE_CLASSES.contains(&s) => Err(code_error!(
+            InvalidStorageClass,
+            "The storage class you specified is not valid."
+        )),
+        _ => Ok(()),
+    }
+}
+
+impl Validate for PutObjectRequest {
+    fn validate(&self) -> S3Result<()> {
+        check_key(&self.key)?;
+        check_metadata(self.metadata.as_ref())?;
+        check_storage_class(self.storage_class.as_deref())
+    }
+}
+
+impl Validate for CopyObjectRequest {
+    fn validate(&self) -> S3Result<()> {
+        check_key(&self.key)?;
+        check_metadata(self.metadata.as_ref())?;
+        check_storage_class(self.storage_class.as_deref())
+    }
+}
+
+impl Validate for CreateMultipartUploadRequest {
+    fn validate(&self) -> S3Result<()> {
+        check_key(&self.key)?;
+        check_metadata(self.metadata.as_ref())?;
+        check_storage_class(self.storage_class.as_deref())
+    }
+}
+
+impl Validate for UploadPartRequest {
+    fn validate(&self) -> S3Result<()> {
+        check_key(&self.key)?;
+        check_part_number(self.part_number)
+    }
+}
+
+impl Validate for GetObjectRequest {
+    fn validate(&self) -> S3Result<()> {
+        check_key(&self.key)?;
+        self.part_number.map_or(Ok(()), check_part_number)
+    }
+}
+
+impl Validate for HeadObjectRequest {
+    fn validate(&self) -> S3Result<()> {
+        check_key(&self.key)?;
+        self.part_number.map_or(Ok(()), check_part_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::errors::S3ErrorCode;
+
+    #[test]
+    #[allow(clippy::shadow_unrelated)]
+    fn put_object() {
+        let code = |input: &PutObjectRequest| input.validate().map_err(|e| e.code());
+
+        let mut input = PutObjectRequest {
+            key: "a".into(),
+            storage_class: Some("STANDARD_IA".into()),
+            ..PutObjectRequest::default()
+        };
+        assert!(code(&input).is_ok());
+
+        let mut metadata = HashMap::new();
+        let _prev = metadata.insert("k".to_owned(), "v".repeat(MAX_METADATA_SIZE - 1));
+        input.metadata = Some(metadata.clone());
+        assert!(code(&input).is_ok());
+
+        let _prev = metadata.insert("k2".to_owned(), String::new());
+        input.metadata = Some(metadata);
+        assert!(matches!(code(&input), Err(S3ErrorCode::MetadataTooLarge)));
+
+        input.metadata = None;
+        input.storage_class = Some("COLD".into());
+        assert!(matches!(
+            code(&input),
+            Err(S3ErrorCode::InvalidStorageClass)
+        ));
+
+        input.key = "a".repeat(1025);
+        assert!(matches!(code(&input), Err(S3ErrorCode::KeyTooLongError)));
+    }
+
+    #[test]
+    fn metadata_key() {
+        assert_eq!(sanitize_metadata_key("Color"), "color");
+        assert_eq!(sanitize_metadata_key("a b:c\n\u{e9}"), "a_b_c__");
+        assert_eq!(sanitize_metadata_key("x.y-z~1"), "x.y-z~1");
+    }
+
+    #[test]
+    fn part_number() {
+        assert!(check_part_number(1).is_ok());
+        assert!(check_part_number(10000).is_ok());
+        assert!(check_part_number(0).is_err());
+        assert!(check_part_number(10001).is_err());
+    }
+}
@@ -1,8 +1,18 @@
 //! Types which can be converted into a response
 
-use crate::errors::{S3Error, S3Result, S3StorageError, S3StorageResult, XmlErrorResponse};
-use crate::utils::{ResponseExt, XmlWriterExt};
-use crate::{Body, Response, StatusCode};
+use crate::dto::GetObjectOutput;
+use crate::errors::{
+    S3Error, S3ErrorCode, S3Result, S3StorageError, S3StorageResult, XmlErrorResponse,
+};
+use crate::headers::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_TYPE, ETAG, LAST_MODIFIED};
+use crate::utils::{time, ResponseExt, XmlElement, XmlWriterExt};
+use crate::{Body, BoxStdError, Response, StatusCode};
+
+use std::{io, mem};
+
+use futures::stream::{self, BoxStream, StreamExt};
+use hyper::body::Bytes;
+use xml::writer::EventWriter;
 
 /// Types which can be converted into a response
 pub trait S3Output {
@@ -29,8 +39,102 @@ where
     }
 }
 
+/// A `multipart/byteranges` response, which carries several ranges of an object
+///
+/// See <https://httpwg.org/specs/rfc9110.html#multipart.byteranges>
+#[derive(Debug)]
+pub struct MultipartByteranges {
+    /// outputs of the ranges, in the requested order
+    pub parts: Vec<GetObjectOutput>,
+    /// the boundary between parts
+    pub boundary: String,
+}
+
+/// a stream of a single chunk
+fn once_bytes(bytes: impl Into<Bytes>) -> BoxStream<'static, io::Result<Bytes>> {
+    let bytes = bytes.into();
+    stream::once(async { Ok(bytes) }).boxed()
+}
+
+/// converts a length to `u64`
+fn len_u64(len: usize) -> u64 {
+    u64::try_from(len).unwrap_or(u64::MAX)
+}
+
+impl MultipartByteranges {
+    /// the delimiter and headers of a part
+    fn part_head(&self, part: &GetObjectOutput) -> String {
+        let content_type = part
+            .content_type
+            .as_deref()
+            .unwrap_or("application/octet-stream");
+        let content_range = part.content_range.as_deref().unwrap_or_default();
+        format!(
+            "--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+            self.boundary, content_type, content_range,
+        )
+    }
+
+    /// sets the headers which are common to all parts
+    fn set_headers(
+        &self,
+        res: &mut Response,
+        first: GetObjectOutput,
+        content_length: Option<u64>,
+    ) -> Result<(), BoxStdError> {
+        let content_type = format!("multipart/byteranges; boundary={}", self.boundary);
+        res.set_optional_header(CONTENT_TYPE, Some(content_type))?;
+        res.set_optional_header(CONTENT_LENGTH, content_length.map(|l| l.to_string()))?;
+        res.set_optional_header(ACCEPT_RANGES, first.accept_ranges)?;
+        res.set_optional_header(ETAG, first.e_tag)?;
+        res.set_optional_header(
+            LAST_MODIFIED,
+            time::map_opt_rfc3339_to_last_modified(first.last_modified.as_deref())?,
+        )?;
+        Ok(())
+    }
+}
+
+impl S3Output for MultipartByteranges {
+    fn try_into_response(mut self) -> S3Result<Response> {
+        let closing = format!("--{}--\r\n", self.boundary);
+
+        // the length is unknown if any part has an unknown length
+        let mut content_length = Some(len_u64(closing.len()));
+        let mut segments = Vec::with_capacity(self.parts.len().saturating_mul(3).saturating_add(1));
+        let mut first = None;
+        for mut part in mem::take(&mut self.parts) {
+            let head = self.part_head(&part);
+            let part_len = part.content_length.and_then(|l| u64::try_from(l).ok());
+            content_length = content_length.zip(part_len).map(|(sum, len)| {
+                sum.saturating_add(len_u64(head.len()))
+                    .saturating_add(len)
+                    .saturating_add(2)
+            });
+
+            segments.push(once_bytes(head));
+            if let Some(body) = part.body.take() {
+                segments.push(body.boxed());
+            }
+            segments.push(once_bytes(&b"\r\n"[..]));
+            if first.is_none() {
+                first = Some(part);
+            }
+        }
+        segments.push(once_bytes(closing));
+
+        let mut res = Response::new_with_status(
+            Body::wrap_stream(stream::iter(segments).flatten()),
+            StatusCode::PARTIAL_CONTENT,
+        );
+        let first = first.unwrap_or_default();
+        self.set_headers(&mut res, first, content_length)
+            .map_err(|e| internal_error!(e))?;
+        Ok(res)
+    }
+}
+
 impl S3Output for XmlErrorResponse {
-    #[allow(clippy::shadow_unrelated)]
     fn try_into_response(self) -> S3Result<Response> {
         let status = self
             .code
@@ -39,13 +143,13 @@ impl S3Output for XmlErrorResponse {
 
         let mut res = Response::new_with_status(Body::empty(), status);
 
+        // error bodies are not namespaced, see `S3_NAMESPACE`
         res.set_xml_body(64, |w| {
-            w.stack("Error", |w| {
-                w.element("Code", self.code.as_static_str())?;
-                w.opt_element("Message", self.message)?;
-                // w.opt_element("Resource", self.resource)?;
-                // w.opt_element("RequestId", self.request_id)?;
-                Ok(())
+            xml_element!(w, "Error", self {
+                "Code" => code,
+                "Message" => message,
+                "Resource" => resource,
+                "RequestId" => request_id,
             })
         })
         .map_err(|e| internal_error!(e))?;
@@ -53,3 +157,13 @@ impl S3Output for XmlErrorResponse {
         Ok(res)
     }
 }
+
+impl XmlElement for S3ErrorCode {
+    fn write_element<W: io::Write>(
+        self,
+        w: &mut EventWriter<W>,
+        name: &str,
+    ) -> xml::writer::Result<()> {
+        w.element(name, self.as_static_str())
+    }
+}
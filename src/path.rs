@@ -56,6 +56,25 @@ pub enum S3PathErrorKind {
     InvalidBucketName,
     /// The object key is too long
     KeyTooLong,
+    /// The object key may escape from the bucket
+    UnsafeKey,
+    /// The path is outside of the path prefix
+    OutsidePrefix,
+}
+
+/// Strips a path prefix, such as `/s3`, from a path
+///
+/// A trailing slash of the prefix is ignored.
+/// Returns `None` if the path is outside of the prefix.
+pub(crate) fn strip_path_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix(prefix.trim_end_matches('/'))?;
+    if rest.is_empty() {
+        Some("/")
+    } else if rest.starts_with('/') {
+        Some(rest)
+    } else {
+        None
+    }
 }
 
 impl<'a> S3Path<'a> {
@@ -74,20 +93,12 @@ impl<'a> S3Path<'a> {
             return false;
         }
 
-        if name
-            .as_bytes()
-            .first()
-            .map(|&b| b.is_ascii_lowercase() || b.is_ascii_digit())
-            != Some(true)
+        if !matches!(name.as_bytes().first(), Some(&b) if b.is_ascii_lowercase() || b.is_ascii_digit())
         {
             return false;
         }
 
-        if name
-            .as_bytes()
-            .last()
-            .map(|&b| b.is_ascii_lowercase() || b.is_ascii_digit())
-            != Some(true)
+        if !matches!(name.as_bytes().last(), Some(&b) if b.is_ascii_lowercase() || b.is_ascii_digit())
         {
             return false;
         }
@@ -110,6 +121,19 @@ impl<'a> S3Path<'a> {
         key.len() <= 1024
     }
 
+    /// Checks whether a key can be mapped to a file path under the bucket directory
+    ///
+    /// A safe key does not contain NUL, does not begin with a separator
+    /// and has no `..` segment, so it never normalizes to a path outside the bucket.
+    /// Both `/` and `\\` are treated as separators.
+    #[must_use]
+    pub fn is_safe_key(key: &str) -> bool {
+        if key.contains('\0') || key.starts_with(['/', '\\']) {
+            return false;
+        }
+        key.split(['/', '\\']).all(|segment| segment != "..")
+    }
+
     /// Parse a path-style request
     /// # Errors
     /// Returns an `Err` if the s3 path is invalid
@@ -149,9 +173,29 @@ impl<'a> S3Path<'a> {
             });
         }
 
+        if !Self::is_safe_key(key) {
+            return Err(ParseS3PathError {
+                kind: S3PathErrorKind::UnsafeKey,
+            });
+        }
+
         Ok(Self::Object { bucket, key })
     }
 
+    /// Parse a path-style request under a path prefix, such as `/s3`
+    ///
+    /// A trailing slash of the prefix is ignored.
+    /// # Errors
+    /// Returns an `Err` if the path is outside of the prefix or the s3 path is invalid
+    pub fn try_from_prefixed_path(path: &'a str, prefix: &str) -> Result<Self, ParseS3PathError> {
+        match strip_path_prefix(path, prefix) {
+            Some(path) => Self::try_from_path(path),
+            None => Err(ParseS3PathError {
+                kind: S3PathErrorKind::OutsidePrefix,
+            }),
+        }
+    }
+
     /// is root
     #[must_use]
     pub const fn is_root(&self) -> bool {
@@ -169,6 +213,15 @@ impl<'a> S3Path<'a> {
     pub const fn is_object(&self) -> bool {
         matches!(*self, Self::Object { .. })
     }
+
+    /// Returns the bucket name, or `None` if it is the root path
+    #[must_use]
+    pub const fn bucket(&self) -> Option<&'a str> {
+        match *self {
+            Self::Root => None,
+            Self::Bucket { bucket } | Self::Object { bucket, .. } => Some(bucket),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -219,4 +272,54 @@ mod tests {
             &S3PathErrorKind::KeyTooLong
         );
     }
+
+    #[test]
+    fn prefixed_path() {
+        for prefix in ["/s3", "/s3/"] {
+            assert!(matches!(
+                S3Path::try_from_prefixed_path("/s3", prefix),
+                Ok(S3Path::Root)
+            ));
+            assert!(matches!(
+                S3Path::try_from_prefixed_path("/s3/bucket/dir/object", prefix),
+                Ok(S3Path::Object {
+                    bucket: "bucket",
+                    key: "dir/object"
+                })
+            ));
+            for path in ["/", "/s3x/bucket", "/bucket/s3"] {
+                assert_eq!(
+                    S3Path::try_from_prefixed_path(path, prefix)
+                        .unwrap_err()
+                        .kind(),
+                    &S3PathErrorKind::OutsidePrefix
+                );
+            }
+        }
+
+        assert!(matches!(
+            S3Path::try_from_prefixed_path("/bucket", ""),
+            Ok(S3Path::Bucket { bucket: "bucket" })
+        ));
+    }
+
+    #[test]
+    fn safe_key() {
+        let safe_keys = ["a", "a/b", "a..b", "...", "a/.../b", ".a", "a/", "%2e%2e"];
+        for key in safe_keys {
+            assert!(S3Path::is_safe_key(key), "key = {key:?}");
+        }
+
+        let unsafe_keys = [
+            "..", "../a", "a/..", "a/../b", "a\\..\\b", "/a", "\\a", "a\0b",
+        ];
+        for key in unsafe_keys {
+            assert!(!S3Path::is_safe_key(key), "key = {key:?}");
+        }
+
+        assert_eq!(
+            S3Path::try_from_path("/asd/../qwe").unwrap_err().kind(),
+            &S3PathErrorKind::UnsafeKey
+        );
+    }
 }
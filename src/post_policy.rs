@@ -0,0 +1,324 @@
+//! POST policy
+//!
+//! See <https://docs.aws.amazon.com/AmazonS3/latest/API/sigv4-HTTPPOSTConstructPolicy.html>
+
+use std::time::SystemTime;
+
+use chrono::DateTime;
+use serde_json::Value;
+
+/// form fields which are not covered by conditions
+const EXEMPT_FIELDS: &[&str] = &["policy", "x-amz-signature", "file"];
+
+/// A decoded POST policy
+#[derive(Debug)]
+pub struct PostPolicy {
+    /// the time after which the policy is rejected
+    expiration: SystemTime,
+    /// conditions of form fields
+    conditions: Vec<Condition>,
+}
+
+/// a condition of a POST policy
+#[derive(Debug)]
+enum Condition {
+    /// the field must equal the value
+    Eq {
+        /// lowercase field name without `$`
+        field: String,
+        /// value
+        value: String,
+    },
+    /// the field must start with the prefix
+    StartsWith {
+        /// lowercase field name without `$`
+        field: String,
+        /// prefix
+        prefix: String,
+    },
+    /// the size of the uploaded file must be in the range
+    ContentLengthRange {
+        /// min size in bytes
+        min: u64,
+        /// max size in bytes
+        max: u64,
+    },
+}
+
+/// `InvalidPolicyDocumentError`
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid Policy: {0}")]
+pub struct InvalidPolicyDocumentError(&'static str);
+
+/// `PolicyViolationError`
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid according to Policy: {0}")]
+pub struct PolicyViolationError(String);
+
+impl PostPolicy {
+    /// Decodes a base64 encoded policy
+    pub fn from_base64(encoded: &str) -> Result<Self, InvalidPolicyDocumentError> {
+        let json = base64_simd::STANDARD
+            .decode_to_vec(encoded)
+            .map_err(|_err| InvalidPolicyDocumentError("Invalid Base64 Encoding"))?;
+        let json: Value = serde_json::from_slice(&json)
+            .map_err(|_err| InvalidPolicyDocumentError("Invalid JSON."))?;
+
+        let expiration =
+            json.get("expiration")
+                .and_then(Value::as_str)
+                .ok_or(InvalidPolicyDocumentError(
+                    "Policy missing expiration field.",
+                ))?;
+        let expiration = DateTime::parse_from_rfc3339(expiration)
+            .map_err(|_err| InvalidPolicyDocumentError("Invalid Policy expiration."))?;
+
+        let conditions = json
+            .get("conditions")
+            .and_then(Value::as_array)
+            .ok_or(InvalidPolicyDocumentError(
+                "Policy missing conditions field.",
+            ))?
+            .iter()
+            .map(parse_condition)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            expiration: expiration.into(),
+            conditions,
+        })
+    }
+
+    /// Checks the expiration and the conditions against the bucket and the form fields
+    pub fn check(
+        &self,
+        now: SystemTime,
+        bucket: &str,
+        fields: &[(String, String)],
+    ) -> Result<(), PolicyViolationError> {
+        if now > self.expiration {
+            return Err(PolicyViolationError("Policy expired.".into()));
+        }
+
+        let find_field = |name: &str| {
+            if name == "bucket" {
+                return Some(bucket);
+            }
+            fields
+                .iter()
+                .rev()
+                .find_map(|&(ref n, ref v)| n.eq_ignore_ascii_case(name).then_some(v.as_str()))
+        };
+
+        for condition in &self.conditions {
+            let ok = match *condition {
+                Condition::Eq {
+                    ref field,
+                    ref value,
+                } => find_field(field) == Some(value.as_str()),
+                Condition::StartsWith {
+                    ref field,
+                    ref prefix,
+                } => find_field(field).map_or(false, |v| v.starts_with(prefix.as_str())),
+                Condition::ContentLengthRange { .. } => continue,
+            };
+            if !ok {
+                return Err(PolicyViolationError(format!(
+                    "Policy Condition failed: {condition}"
+                )));
+            }
+        }
+
+        for &(ref name, _) in fields {
+            let name = name.to_ascii_lowercase();
+            if EXEMPT_FIELDS.contains(&name.as_str()) || name.starts_with("x-ignore-") {
+                continue;
+            }
+            if !self.conditions.iter().any(|c| c.field() == Some(&name)) {
+                return Err(PolicyViolationError(format!("Extra input fields: {name}")));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the allowed range of the file size
+    pub fn content_length_range(&self) -> Option<(u64, u64)> {
+        self.conditions.iter().find_map(|c| match *c {
+            Condition::ContentLengthRange { min, max } => Some((min, max)),
+            Condition::Eq { .. } | Condition::StartsWith { .. } => None,
+        })
+    }
+}
+
+impl Condition {
+    /// the field name of the condition
+    fn field(&self) -> Option<&str> {
+        match *self {
+            Self::Eq { ref field, .. } | Self::StartsWith { ref field, .. } => Some(field),
+            Self::ContentLengthRange { .. } => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Eq {
+                ref field,
+                ref value,
+            } => write!(f, "[\"eq\", \"${field}\", \"{value}\"]"),
+            Self::StartsWith {
+                ref field,
+                ref prefix,
+            } => write!(f, "[\"starts-with\", \"${field}\", \"{prefix}\"]"),
+            Self::ContentLengthRange { min, max } => {
+                write!(f, "[\"content-length-range\", {min}, {max}]")
+            }
+        }
+    }
+}
+
+/// parses a condition in the form of `{"field": "value"}` or `[op, ...]`
+fn parse_condition(condition: &Value) -> Result<Condition, InvalidPolicyDocumentError> {
+    /// the error of an invalid condition
+    const INVALID: InvalidPolicyDocumentError =
+        InvalidPolicyDocumentError("Invalid Policy condition.");
+
+    /// parses a field name like `$key`
+    fn parse_field(value: &Value) -> Result<String, InvalidPolicyDocumentError> {
+        value
+            .as_str()
+            .and_then(|s| s.strip_prefix('$'))
+            .map(str::to_ascii_lowercase)
+            .ok_or(INVALID)
+    }
+
+    /// parses a size, which may be a number or a string
+    fn parse_size(value: &Value) -> Result<u64, InvalidPolicyDocumentError> {
+        match *value {
+            Value::Number(ref n) => n.as_u64(),
+            Value::String(ref s) => s.parse().ok(),
+            Value::Null | Value::Bool(_) | Value::Array(_) | Value::Object(_) => None,
+        }
+        .ok_or(INVALID)
+    }
+
+    if let Some(map) = condition.as_object() {
+        let mut entries = map.iter();
+        return match (entries.next(), entries.next()) {
+            (Some((field, &Value::String(ref value))), None) => Ok(Condition::Eq {
+                field: field.to_ascii_lowercase(),
+                value: value.clone(),
+            }),
+            _ => Err(INVALID),
+        };
+    }
+
+    match condition.as_array().map(Vec::as_slice) {
+        Some(&[ref op, ref field, ref value]) => {
+            let op = op.as_str().ok_or(INVALID)?.to_ascii_lowercase();
+            match op.as_str() {
+                "eq" => Ok(Condition::Eq {
+                    field: parse_field(field)?,
+                    value: value.as_str().ok_or(INVALID)?.to_owned(),
+                }),
+                "starts-with" => Ok(Condition::StartsWith {
+                    field: parse_field(field)?,
+                    prefix: value.as_str().ok_or(INVALID)?.to_owned(),
+                }),
+                "content-length-range" => {
+                    let (min, max) = (parse_size(field)?, parse_size(value)?);
+                    if min > max {
+                        return Err(INVALID);
+                    }
+                    Ok(Condition::ContentLengthRange { min, max })
+                }
+                _ => Err(INVALID),
+            }
+        }
+        _ => Err(INVALID),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    fn encode(json: &str) -> String {
+        base64_simd::STANDARD.encode_to_string(json)
+    }
+
+    fn fields(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|&(n, v)| (n.to_owned(), v.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    #[allow(clippy::shadow_unrelated)]
+    fn conditions() {
+        let policy = encode(concat!(
+            r#"{"expiration":"2015-12-30T12:00:00.000Z","conditions":["#,
+            r#"{"bucket":"sigv4examplebucket"},"#,
+            r#"["starts-with","$key","user/user1/"],"#,
+            r#"["eq","$Content-Type","image/jpeg"],"#,
+            r#"["content-length-range",1,"1024"],"#,
+            r#"{"x-amz-date":"20151229T000000Z"}"#,
+            r#"]}"#
+        ));
+        let policy = PostPolicy::from_base64(&policy).unwrap();
+        assert_eq!(policy.content_length_range(), Some((1, 1024)));
+
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_451_347_200); // 2015-12-29
+        let bucket = "sigv4examplebucket";
+        let mut form = fields(&[
+            ("key", "user/user1/a.jpg"),
+            ("Content-Type", "image/jpeg"),
+            ("X-Amz-Date", "20151229T000000Z"),
+            ("Policy", "..."),
+            ("x-ignore-a", "b"),
+        ]);
+        policy.check(now, bucket, &form).unwrap();
+
+        let expired = now + Duration::from_secs(2 * 86400);
+        assert!(policy.check(expired, bucket, &form).is_err());
+        assert!(policy.check(now, "other", &form).is_err());
+
+        form.push(("x-amz-meta-a".into(), "b".into()));
+        let err = policy.check(now, bucket, &form).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid according to Policy: Extra input fields: x-amz-meta-a"
+        );
+
+        form.truncate(4);
+        form[0].1 = "user/user2/a.jpg".into();
+        let err = policy.check(now, bucket, &form).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"Invalid according to Policy: Policy Condition failed: ["starts-with", "$key", "user/user1/"]"#
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let cases = [
+            "{",
+            r#"{"conditions":[]}"#,
+            r#"{"expiration":"2015-12-30","conditions":[]}"#,
+            r#"{"expiration":"2015-12-30T12:00:00.000Z"}"#,
+            r#"{"expiration":"2015-12-30T12:00:00.000Z","conditions":[["eq","key","a"]]}"#,
+            r#"{"expiration":"2015-12-30T12:00:00.000Z","conditions":[["lt","$key","a"]]}"#,
+            r#"{"expiration":"2015-12-30T12:00:00.000Z","conditions":[["content-length-range",2,1]]}"#,
+            r#"{"expiration":"2015-12-30T12:00:00.000Z","conditions":[{"a":"b","c":"d"}]}"#,
+        ];
+        for json in cases {
+            assert!(PostPolicy::from_base64(&encode(json)).is_err(), "{json}");
+        }
+        assert!(PostPolicy::from_base64("!!!").is_err());
+    }
+}
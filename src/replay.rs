@@ -0,0 +1,134 @@
+//! replay protection of signed requests
+
+use crate::Method;
+
+use std::collections::{BTreeSet, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, PoisonError};
+use std::time::SystemTime;
+
+/// A bounded cache of recently used signatures, which rejects exact replays of signed requests
+///
+/// By default, presigned `PUT` urls are one-time, such as the upload urls given to browsers.
+/// A signature is recorded once it is verified, so an upload which fails later consumes its url too.
+///
+/// A signature is remembered until its request is no longer valid anyway,
+/// that is the expiration of a presigned url or 15 minutes after the date of a signed request.
+/// When the cache is full, the signature which expires first is forgotten,
+/// so the capacity should exceed the number of one-time requests in such a period.
+///
+/// ```
+/// use s3_server::ReplayCache;
+/// use std::num::NonZeroUsize;
+///
+/// let capacity = NonZeroUsize::new(100_000).unwrap();
+/// let replay_cache = ReplayCache::new(capacity);
+/// ```
+#[derive(Debug)]
+pub struct ReplayCache {
+    /// max number of remembered signatures
+    capacity: usize,
+    /// whether header-signed writes are one-time
+    signed_requests: bool,
+    /// remembered signatures
+    seen: Mutex<SeenSignatures>,
+}
+
+/// signatures and their expiration times
+#[derive(Debug, Default)]
+struct SeenSignatures {
+    /// signatures
+    signatures: HashSet<Box<str>>,
+    /// signatures ordered by expiration times
+    expirations: BTreeSet<(SystemTime, Box<str>)>,
+}
+
+impl SeenSignatures {
+    /// forgets the signature which expires first
+    fn pop_first(&mut self) -> Option<SystemTime> {
+        let first = self.expirations.iter().next().cloned()?;
+        let _expiration = self.expirations.remove(&first);
+        let _signature = self.signatures.remove(&first.1);
+        Some(first.0)
+    }
+
+    /// forgets the expired signatures
+    fn purge(&mut self, now: SystemTime) {
+        while let Some(&(expires_at, _)) = self.expirations.iter().next() {
+            if expires_at > now {
+                break;
+            }
+            let _first = self.pop_first();
+        }
+    }
+}
+
+impl ReplayCache {
+    /// Constructs a `ReplayCache` which remembers at most `capacity` signatures
+    #[must_use]
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            capacity: capacity.get(),
+            signed_requests: false,
+            seen: Mutex::new(SeenSignatures::default()),
+        }
+    }
+
+    /// Also rejects replays of `PUT`, `POST` and `DELETE` requests signed by headers
+    ///
+    /// The dates of signatures are accurate to the second, so a client must not send
+    /// the same write twice in a second, and SDKs must sign each retry again.
+    pub fn set_signed_requests(&mut self, enabled: bool) {
+        self.signed_requests = enabled;
+    }
+
+    /// whether a request with the method can be sent only once
+    pub(crate) fn is_one_time(&self, method: &Method, presigned: bool) -> bool {
+        if presigned {
+            return *method == Method::PUT;
+        }
+        self.signed_requests
+            && (*method == Method::PUT || *method == Method::POST || *method == Method::DELETE)
+    }
+
+    /// records a signature valid until `expires_at`, returns `false` if it has been used
+    pub(crate) fn record(&self, signature: &str, expires_at: SystemTime, now: SystemTime) -> bool {
+        let mut seen = self.seen.lock().unwrap_or_else(PoisonError::into_inner);
+        seen.purge(now);
+        if seen.signatures.contains(signature) {
+            return false;
+        }
+        if seen.signatures.len() >= self.capacity {
+            let _first = seen.pop_first();
+        }
+        let _new_signature = seen.signatures.insert(signature.into());
+        let _new_expiration = seen.expirations.insert((expires_at, signature.into()));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn bounded() {
+        let cache = ReplayCache::new(NonZeroUsize::new(2).unwrap());
+        let now = SystemTime::UNIX_EPOCH;
+        let secs = |n| now + Duration::from_secs(n);
+
+        assert!(cache.record("a", secs(10), now));
+        assert!(!cache.record("a", secs(10), now));
+        assert!(cache.record("b", secs(20), now));
+
+        // "a" expires first, so it is forgotten
+        assert!(cache.record("c", secs(30), now));
+        assert!(cache.record("a", secs(10), now));
+        assert!(!cache.record("c", secs(30), now));
+
+        // expired signatures are forgotten
+        assert!(cache.record("b", secs(20), secs(20)));
+    }
+}
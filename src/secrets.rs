@@ -0,0 +1,157 @@
+//! Authentication providers backed by secret stores
+//!
+//! [`SecretAuth`] is an [`S3Auth`] which looks up secret access keys from a [`SecretSource`]
+//! and caches them for a TTL, so that credentials can be rotated without restarting the server.
+//!
+//! + [`EnvSecretSource`] reads environment variables.
+//! + [`FileSecretSource`] reads an htpasswd-like file.
+//! + [`VaultSecretSource`] reads a KV v2 secret engine of `HashiCorp Vault`, with the feature `vault`.
+//! + [`SecretsManagerSource`] reads AWS Secrets Manager, with the feature `secrets-manager`.
+//!
+//! ```no_run
+//! use s3_server::secrets::{FileSecretSource, SecretAuth};
+//! use s3_server::S3Service;
+//! # use s3_server::storages::fs::FileSystem;
+//! use std::time::Duration;
+//!
+//! # fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! # let mut service = S3Service::new(FileSystem::new(".")?);
+//! let mut auth = SecretAuth::new(FileSecretSource::new("/etc/s3-server/credentials"));
+//! auth.set_ttl(Duration::from_secs(60));
+//! service.set_auth(auth);
+//! # Ok(())
+//! # }
+//! ```
+
+mod env;
+mod file;
+#[cfg(feature = "secrets-manager")]
+mod secrets_manager;
+#[cfg(feature = "vault")]
+mod vault;
+
+pub use self::env::EnvSecretSource;
+pub use self::file::FileSecretSource;
+#[cfg(feature = "secrets-manager")]
+pub use self::secrets_manager::SecretsManagerSource;
+#[cfg(feature = "vault")]
+pub use self::vault::VaultSecretSource;
+
+use crate::auth::S3Auth;
+use crate::errors::S3AuthError;
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tracing::{debug, warn};
+
+/// default TTL of cached secrets
+const DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+/// A store of secret access keys
+#[async_trait]
+pub trait SecretSource {
+    /// Fetches the secret access key of an access key id, `Ok(None)` if the key is unknown
+    async fn fetch_secret(&self, access_key_id: &str) -> io::Result<Option<String>>;
+}
+
+/// a cached lookup result
+#[derive(Debug)]
+struct CachedSecret {
+    /// the secret access key, `None` if the access key is unknown
+    secret: Option<String>,
+    /// time of the fetch
+    fetched_at: Instant,
+}
+
+/// An authentication provider which caches the secret access keys of a [`SecretSource`]
+///
+/// A lookup result, including an unknown access key, is cached for a TTL of 5 minutes by default.
+/// If a refresh fails, the expired secret is used until the source recovers.
+pub struct SecretAuth<S> {
+    /// secret store
+    source: S,
+    /// cached lookup results
+    cache: Mutex<HashMap<String, CachedSecret>>,
+    /// TTL of cached results
+    ttl: Duration,
+}
+
+impl<S> std::fmt::Debug for SecretAuth<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretAuth")
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> SecretAuth<S> {
+    /// Constructs a `SecretAuth` of the source
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            cache: Mutex::new(HashMap::new()),
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    /// Sets the TTL of cached secrets, zero disables the cache
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    /// Drops all cached secrets, so that they are fetched again
+    pub fn clear_cache(&self) {
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+#[async_trait]
+impl<S> S3Auth for SecretAuth<S>
+where
+    S: SecretSource + Send + Sync,
+{
+    async fn get_secret_access_key(&self, access_key_id: &str) -> Result<String, S3AuthError> {
+        let to_result = |secret: Option<String>| secret.ok_or(S3AuthError::NotSignedUp);
+
+        let stale = {
+            let cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+            match cache.get(access_key_id) {
+                Some(cached) if cached.fetched_at.elapsed() < self.ttl => {
+                    return to_result(cached.secret.clone())
+                }
+                Some(cached) => cached.secret.clone(),
+                None => None,
+            }
+        };
+
+        let secret = match self.source.fetch_secret(access_key_id).await {
+            Ok(secret) => secret,
+            Err(err) => {
+                if let Some(secret) = stale {
+                    warn!(%err, access_key_id, "failed to refresh secret, using the expired one");
+                    return Ok(secret);
+                }
+                return Err(internal_error!(err).into());
+            }
+        };
+        debug!(access_key_id, found = secret.is_some(), "fetched secret");
+
+        let cached = CachedSecret {
+            secret: secret.clone(),
+            fetched_at: Instant::now(),
+        };
+        let _prev = self
+            .cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(access_key_id.to_owned(), cached);
+        to_result(secret)
+    }
+}
@@ -0,0 +1,54 @@
+//! secrets of environment variables
+
+use super::SecretSource;
+
+use std::env;
+use std::io;
+
+use async_trait::async_trait;
+
+/// Reads the secret access key of `AKID` from the environment variable `{prefix}AKID`
+///
+/// The default prefix is `S3_SECRET_ACCESS_KEY_`.
+/// Access key ids which are not valid in variable names are unknown.
+#[derive(Debug, Clone)]
+pub struct EnvSecretSource {
+    /// prefix of variable names
+    prefix: String,
+}
+
+impl Default for EnvSecretSource {
+    fn default() -> Self {
+        Self::new("S3_SECRET_ACCESS_KEY_")
+    }
+}
+
+impl EnvSecretSource {
+    /// Constructs an `EnvSecretSource` with a prefix of variable names
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl SecretSource for EnvSecretSource {
+    async fn fetch_secret(&self, access_key_id: &str) -> io::Result<Option<String>> {
+        let is_valid = !access_key_id.is_empty()
+            && access_key_id
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_');
+        if !is_valid {
+            return Ok(None);
+        }
+        match env::var(format!("{}{access_key_id}", self.prefix)) {
+            Ok(secret) => Ok(Some(secret)),
+            Err(env::VarError::NotPresent) => Ok(None),
+            Err(env::VarError::NotUnicode(_)) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "secret access key is not unicode",
+            )),
+        }
+    }
+}
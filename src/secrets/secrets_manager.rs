@@ -0,0 +1,122 @@
+//! secrets of AWS Secrets Manager
+
+use super::SecretSource;
+
+use std::fmt;
+use std::io;
+
+use async_trait::async_trait;
+use rusoto_core::credential::DefaultCredentialsProvider;
+use rusoto_core::signature::SignedRequest;
+use rusoto_core::{Client, HttpClient, Region};
+use serde_json::{json, Value};
+
+/// Reads secret access keys from AWS Secrets Manager by `GetSecretValue`
+///
+/// The secret of `AKID` is the secret named `s3/AKID`. If its string is a JSON object,
+/// the secret access key is its field `secret_access_key`, otherwise the whole string.
+pub struct SecretsManagerSource {
+    /// signing client
+    client: Client,
+    /// region of the secrets
+    region: Region,
+    /// name prefix of secrets
+    prefix: String,
+    /// field of the secret access key in JSON secrets
+    field: String,
+}
+
+impl fmt::Debug for SecretsManagerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretsManagerSource")
+            .field("region", &self.region)
+            .field("prefix", &self.prefix)
+            .field("field", &self.field)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SecretsManagerSource {
+    /// Constructs a `SecretsManagerSource` with a client, which holds the credentials of the server
+    #[must_use]
+    pub fn new(client: Client, region: Region) -> Self {
+        Self {
+            client,
+            region,
+            prefix: "s3/".into(),
+            field: "secret_access_key".into(),
+        }
+    }
+
+    /// Constructs a `SecretsManagerSource` with the default credentials chain of AWS
+    ///
+    /// # Errors
+    /// Returns an `Err` if the credentials provider or the http client can not be created
+    pub fn from_env(region: Region) -> io::Result<Self> {
+        let other = |e: &dyn fmt::Display| io::Error::new(io::ErrorKind::Other, e.to_string());
+        let credentials = DefaultCredentialsProvider::new().map_err(|e| other(&e))?;
+        let dispatcher = HttpClient::new().map_err(|e| other(&e))?;
+        Ok(Self::new(Client::new_with(credentials, dispatcher), region))
+    }
+
+    /// Sets the name prefix of secrets
+    pub fn set_prefix(&mut self, prefix: impl Into<String>) {
+        self.prefix = prefix.into();
+    }
+
+    /// Sets the field of the secret access key in JSON secrets
+    pub fn set_field(&mut self, field: impl Into<String>) {
+        self.field = field.into();
+    }
+}
+
+#[async_trait]
+impl SecretSource for SecretsManagerSource {
+    async fn fetch_secret(&self, access_key_id: &str) -> io::Result<Option<String>> {
+        let other = |msg: String| io::Error::new(io::ErrorKind::Other, msg);
+        let invalid_data = |e: serde_json::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+
+        let payload = json!({ "SecretId": format!("{}{access_key_id}", self.prefix) });
+        let mut req = SignedRequest::new("POST", "secretsmanager", &self.region, "/");
+        req.set_content_type("application/x-amz-json-1.1".into());
+        req.add_header("x-amz-target", "secretsmanager.GetSecretValue");
+        req.set_payload(Some(payload.to_string()));
+
+        let mut res = self
+            .client
+            .sign_and_dispatch(req)
+            .await
+            .map_err(|e| other(format!("failed to call secrets manager: {e:?}")))?;
+        let res = res
+            .buffer()
+            .await
+            .map_err(|e| other(format!("failed to call secrets manager: {e}")))?;
+        let body: Value = serde_json::from_slice(&res.body).map_err(invalid_data)?;
+
+        if !res.status.is_success() {
+            let error_type = body.get("__type").and_then(Value::as_str).unwrap_or("");
+            if error_type.ends_with("ResourceNotFoundException") {
+                return Ok(None);
+            }
+            return Err(other(format!(
+                "secrets manager responded {}: {error_type}",
+                res.status
+            )));
+        }
+
+        let secret_string = body
+            .get("SecretString")
+            .and_then(Value::as_str)
+            .ok_or_else(|| other("secret has no string".into()))?;
+        match serde_json::from_str::<Value>(secret_string) {
+            Ok(Value::Object(fields)) => {
+                let secret = fields
+                    .get(&self.field)
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| other("secret has no such string field".into()))?;
+                Ok(Some(secret.to_owned()))
+            }
+            _ => Ok(Some(secret_string.to_owned())),
+        }
+    }
+}
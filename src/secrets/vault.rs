@@ -0,0 +1,120 @@
+//! secrets of `HashiCorp Vault`
+
+use super::SecretSource;
+
+use crate::utils::http_client;
+
+use std::fmt;
+use std::io;
+
+use async_trait::async_trait;
+use hyper::header::HeaderName;
+use hyper::{StatusCode, Uri};
+use serde_json::Value;
+
+/// Reads secret access keys from a KV version 2 secret engine of `HashiCorp Vault`
+///
+/// The secret of `AKID` is the field `secret_access_key` of the secret `s3/AKID`
+/// in the engine mounted at `secret`, that is `GET {addr}/v1/secret/data/s3/AKID`.
+///
+/// Only `http` is supported. An `https` server can be reached through a TLS-terminating proxy.
+pub struct VaultSecretSource {
+    /// address of the server
+    addr: String,
+    /// client token
+    token: String,
+    /// mount path of the secret engine
+    mount: String,
+    /// path prefix of secrets
+    prefix: String,
+    /// field of the secret access key
+    field: String,
+}
+
+impl fmt::Debug for VaultSecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultSecretSource")
+            .field("addr", &self.addr)
+            .field("mount", &self.mount)
+            .field("prefix", &self.prefix)
+            .field("field", &self.field)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VaultSecretSource {
+    /// Constructs a `VaultSecretSource` of the server address, such as `http://127.0.0.1:8200`
+    pub fn new(addr: impl Into<String>, token: impl Into<String>) -> Self {
+        let mut addr = addr.into();
+        while addr.ends_with('/') {
+            let _slash = addr.pop();
+        }
+        Self {
+            addr,
+            token: token.into(),
+            mount: "secret".into(),
+            prefix: "s3".into(),
+            field: "secret_access_key".into(),
+        }
+    }
+
+    /// Sets the mount path of the secret engine
+    pub fn set_mount(&mut self, mount: impl Into<String>) {
+        self.mount = mount.into();
+    }
+
+    /// Sets the path prefix of secrets, empty if secrets are named by access key ids directly
+    pub fn set_prefix(&mut self, prefix: impl Into<String>) {
+        self.prefix = prefix.into();
+    }
+
+    /// Sets the field of the secret access key
+    pub fn set_field(&mut self, field: impl Into<String>) {
+        self.field = field.into();
+    }
+}
+
+#[async_trait]
+impl SecretSource for VaultSecretSource {
+    async fn fetch_secret(&self, access_key_id: &str) -> io::Result<Option<String>> {
+        let is_valid = !access_key_id.is_empty()
+            && access_key_id
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"-_.".contains(&b));
+        if !is_valid {
+            return Ok(None);
+        }
+
+        let mut uri = format!("{}/v1/{}/data/", self.addr, self.mount);
+        if !self.prefix.is_empty() {
+            uri.push_str(&self.prefix);
+            uri.push('/');
+        }
+        uri.push_str(access_key_id);
+        let uri: Uri = uri
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let token = HeaderName::from_static("x-vault-token");
+        let (status, body) = http_client::get(&uri, &[(token, &self.token)]).await?;
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let msg = format!("vault responded {status}");
+            return Err(io::Error::new(io::ErrorKind::Other, msg));
+        }
+
+        let invalid_data = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let value: Value = serde_json::from_slice(&body)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let secret = value
+            .pointer("/data/data")
+            .and_then(|data| data.get(&self.field))
+            .ok_or_else(|| invalid_data("vault secret has no such field"))?;
+        let secret = secret
+            .as_str()
+            .ok_or_else(|| invalid_data("vault secret field is not a string"))?;
+        Ok(Some(secret.to_owned()))
+    }
+}
@@ -1,33 +1,95 @@
 //! S3 service
 
-use crate::auth::S3Auth;
+use crate::acl::NetworkAcl;
+use crate::admin::AdminService;
+use crate::audit::{AuditLog, AuditRecord};
+use crate::auth::{
+    self, AnonymousPolicy, ClientCertificate, PublicRead, RequestUsage, S3Auth, SignatureDebug,
+};
+use crate::clock::{Clock, SystemClock};
+use crate::content_types::ContentTypes;
+use crate::cors::{self, CorsConfig, CorsHeaders};
 use crate::data_structures::{OrderedHeaders, OrderedQs};
-use crate::errors::{S3AuthError, S3ErrorCode, S3Result};
-use crate::headers::{AmzContentSha256, AmzDate, AuthorizationV4, CredentialV4};
-use crate::headers::{AUTHORIZATION, CONTENT_TYPE, X_AMZ_CONTENT_SHA256, X_AMZ_DATE};
-use crate::ops::{ReqContext, S3Handler};
+use crate::dto::{HeadObjectRequest, Owner};
+use crate::errors::{S3AuthError, S3Error, S3ErrorCode, S3Result};
+use crate::headers::{
+    AmzContentSha256, AmzCopySource, AmzDate, AuthorizationV4, CredentialV4, ForwardedFor,
+    TraceParent,
+};
+use crate::headers::{
+    ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+    ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ALLOW, AUTHORIZATION,
+    CONTENT_TYPE, FORWARDED, ORIGIN, TRACEPARENT, TRACESTATE, VARY, X_AMZ_CONTENT_SHA256,
+    X_AMZ_COPY_SOURCE, X_AMZ_DATE, X_AMZ_REQUEST_ID, X_FORWARDED_FOR,
+    X_S3_SERVER_CANONICAL_REQUEST, X_S3_SERVER_STRING_TO_SIGN,
+};
+use crate::limits::ObjectLimits;
+use crate::ops::{OperationDoc, OperationKind, ReqContext, S3Handler};
 use crate::output::S3Output;
-use crate::path::{S3Path, S3PathErrorKind};
-use crate::signature_v4;
+use crate::path::{strip_path_prefix, S3Path, S3PathErrorKind};
+use crate::post_policy::PostPolicy;
+use crate::replay::ReplayCache;
+use crate::signature_v4::{self, SigningKeyCache};
 use crate::storage::S3Storage;
 use crate::streams::aws_chunked_stream::AwsChunkedStream;
-use crate::streams::multipart::{self, Multipart};
-use crate::utils::{crypto, Apply};
-use crate::{Body, BoxStdError, Method, Mime, Request, Response};
+use crate::streams::content_sha256_stream::ContentSha256Stream;
+use crate::streams::counting_stream::{ByteCounter, CountingStream};
+use crate::streams::multipart::{self, Multipart, MultipartError, MultipartLimits};
+use crate::utils::{self, crypto, Apply, XmlBody};
+use crate::{Body, BoxStdError, Method, Mime, Request, Response, StatusCode};
 
+use std::any::Any;
 use std::borrow::Cow;
+use std::collections::HashSet;
+use std::convert::Infallible;
 use std::fmt::{self, Debug};
 use std::io;
 use std::mem;
+use std::net::{IpAddr, SocketAddr};
+use std::num::{NonZeroI64, NonZeroU32, NonZeroUsize};
 use std::ops::Deref;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
 
-use futures::future::BoxFuture;
+use futures::future::{self, BoxFuture, FutureExt, Ready};
 use futures::stream::{Stream, StreamExt};
 use hyper::body::Bytes;
+use hyper::header::HeaderValue;
 
-use tracing::{debug, error};
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+/// max number of cached signing keys
+const SIGNING_KEY_CACHE_CAPACITY: usize = 64;
+
+/// max difference between the request time and the server time
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(15 * 60);
+
+/// max lifetime of presigned urls, which is the limit of S3
+const MAX_PRESIGNED_EXPIRES: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// max size of an encoded POST policy
+const MAX_POST_POLICY_SIZE: usize = 20 * 1024;
+
+/// default max number of keys in a page of listings, which is the limit of S3
+const DEFAULT_MAX_LIST_KEYS: i64 = 1000;
+
+/// methods allowed on the root path
+const ROOT_METHODS: &[Method] = &[Method::GET, Method::OPTIONS];
+
+/// methods allowed on buckets and objects
+const RESOURCE_METHODS: &[Method] = &[
+    Method::GET,
+    Method::HEAD,
+    Method::PUT,
+    Method::POST,
+    Method::DELETE,
+    Method::OPTIONS,
+];
 
 /// S3 service
 pub struct S3Service {
@@ -38,14 +100,116 @@ pub struct S3Service {
     storage: Box<dyn S3Storage + Send + Sync + 'static>,
 
     /// auth
-    auth: Option<Box<dyn S3Auth + Send + Sync + 'static>>,
+    auth: Option<Arc<dyn S3Auth + Send + Sync + 'static>>,
+
+    /// signing key cache
+    signing_keys: SigningKeyCache,
+
+    /// anonymous request policy
+    anonymous_policy: AnonymousPolicy,
+    /// buckets and key prefixes which anonymous requests may read
+    public_read: PublicRead,
+
+    /// limits of POST Object forms
+    multipart_limits: MultipartLimits,
+    /// size limits of objects and multipart uploads
+    object_limits: ObjectLimits,
+    /// content types guessed by the extensions of keys
+    content_types: Option<Arc<ContentTypes>>,
+    /// max number of keys in a page of listings
+    max_list_keys: i64,
+    /// max size of xml response bodies
+    max_xml_response_size: Option<usize>,
+    /// whether xml response bodies are pretty-printed
+    pretty_xml: bool,
+
+    /// path normalizer
+    path_normalizer: Option<PathNormalizer>,
+
+    /// path prefix
+    path_prefix: Option<String>,
+
+    /// number of trusted proxies in front of the service
+    trusted_proxy_hops: usize,
+
+    /// network access control
+    network_acl: Option<NetworkAcl>,
+
+    /// CORS rules of buckets
+    cors: Option<CorsConfig>,
+
+    /// audit log of mutating requests
+    audit_log: Option<AuditLog>,
+
+    /// write-once-read-many buckets
+    worm_buckets: HashSet<String>,
+
+    /// max number of in-flight requests of a connection
+    max_requests_per_connection: Option<usize>,
+
+    /// time source
+    clock: Box<dyn Clock + Send + Sync + 'static>,
+
+    /// whether signatures are accepted without verification, see [`S3Service::insecure_dev`]
+    insecure_dev: bool,
+
+    /// how to report signature mismatches
+    signature_debug: SignatureDebug,
+
+    /// recently used one-time signatures
+    replay_cache: Option<ReplayCache>,
+
+    /// max lifetime of presigned urls
+    max_presigned_expires: Duration,
 }
 
+/// `Box<dyn Fn(&str) -> String + Send + Sync + 'static>`
+type PathNormalizer = Box<dyn Fn(&str) -> String + Send + Sync + 'static>;
+
 /// Shared S3 service
 #[derive(Debug)]
 pub struct SharedS3Service {
     /// inner service
     inner: Arc<S3Service>,
+    /// remote address of the connection
+    remote_addr: Option<SocketAddr>,
+    /// in-flight requests of the connection
+    in_flight: InFlightRequests,
+    /// client certificate of the connection
+    client_cert: Option<ClientCertificate>,
+}
+
+/// The address of the peer of a connection, as a request extension
+///
+/// A server inserts it into each request, for example by [`SharedS3Service::with_remote_addr`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct RemoteAddr(pub SocketAddr);
+
+/// The in-flight requests of a connection, passed to the service by request extensions
+#[derive(Debug, Clone, Default)]
+struct InFlightRequests(Arc<AtomicUsize>);
+
+/// A reserved in-flight request, which is released when dropped
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl InFlightRequests {
+    /// reserves a request, returns `None` if there are already `max` in-flight requests
+    fn try_reserve(&self, max: usize) -> Option<InFlightGuard> {
+        let reserved = self
+            .0
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < max).then(|| n.wrapping_add(1))
+            })
+            .is_ok();
+        reserved.then(|| InFlightGuard(Arc::clone(&self.0)))
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let _prev = self.0.fetch_sub(1, Ordering::AcqRel);
+    }
 }
 
 impl Debug for S3Service {
@@ -65,11 +229,34 @@ impl Clone for SharedS3Service {
     fn clone(&self) -> Self {
         Self {
             inner: Arc::clone(&self.inner),
+            remote_addr: self.remote_addr,
+            in_flight: self.in_flight.clone(),
+            client_cert: self.client_cert.clone(),
         }
     }
 }
 
-impl hyper::service::Service<Request> for SharedS3Service {
+impl<B> hyper::service::Service<http::Request<B>> for SharedS3Service
+where
+    B: http_body::Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxStdError>,
+{
+    type Response = Response;
+
+    type Error = BoxStdError;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn call(&self, req: http::Request<B>) -> Self::Future {
+        self.serve(req.map(Body::new))
+    }
+}
+
+impl<B> tower_service::Service<http::Request<B>> for SharedS3Service
+where
+    B: http_body::Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxStdError>,
+{
     type Response = Response;
 
     type Error = BoxStdError;
@@ -80,10 +267,192 @@ impl hyper::service::Service<Request> for SharedS3Service {
         Poll::Ready(Ok(())) // FIXME: back pressue
     }
 
-    fn call(&mut self, req: Request) -> Self::Future {
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        self.serve(req.map(Body::new))
+    }
+}
+
+/// Creates a [`SharedS3Service`] for each connection
+///
+/// It is a tower `MakeService`, whose target, such as the accepted connection, is ignored.
+#[derive(Debug, Clone)]
+pub struct MakeSharedS3Service {
+    /// inner service
+    inner: SharedS3Service,
+}
+
+impl<T> tower_service::Service<T> for MakeSharedS3Service {
+    type Response = SharedS3Service;
+
+    type Error = Infallible;
+
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: T) -> Self::Future {
+        future::ready(Ok(self.inner.clone().new_connection()))
+    }
+}
+
+/// A [`SharedS3Service`] mounted at a path prefix, such as `/s3`
+///
+/// The prefix is stripped before the bucket and the key are extracted,
+/// while signatures are still checked against the whole path.
+/// Requests outside of the prefix are answered with `404 Not Found`.
+///
+/// Its error type is [`Infallible`], so it can be used as a tower service
+/// by web frameworks which require that, such as the fallback service of an axum router.
+#[derive(Debug, Clone)]
+pub struct MountedS3Service {
+    /// inner service
+    inner: SharedS3Service,
+    /// path prefix
+    prefix: MountPrefix,
+}
+
+/// The path prefix of a mounted service, passed to the service by request extensions
+#[derive(Debug, Clone)]
+struct MountPrefix(Arc<str>);
+
+impl MountedS3Service {
+    /// serves a request
+    fn serve(&self, mut req: Request) -> BoxFuture<'static, Result<Response, Infallible>> {
+        self.inner.insert_extensions(&mut req);
+        let service = self.inner.clone();
+        let prefix = self.prefix.clone();
+        Box::pin(async move {
+            if strip_path_prefix(req.uri().path(), &prefix.0).is_none() {
+                let mut res = Response::default();
+                *res.status_mut() = StatusCode::NOT_FOUND;
+                return Ok(res);
+            }
+            let _prev = req.extensions_mut().insert(prefix);
+            match service.hyper_call(req).await {
+                Ok(res) => Ok(res),
+                Err(err) => {
+                    error!(%err, "failed to respond");
+                    let mut res = Response::default();
+                    *res.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                    Ok(res)
+                }
+            }
+        })
+    }
+}
+
+impl<B> hyper::service::Service<http::Request<B>> for MountedS3Service
+where
+    B: http_body::Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxStdError>,
+{
+    type Response = Response;
+
+    type Error = Infallible;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn call(&self, req: http::Request<B>) -> Self::Future {
+        self.serve(req.map(Body::new))
+    }
+}
+
+impl<B> tower_service::Service<http::Request<B>> for MountedS3Service
+where
+    B: http_body::Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxStdError>,
+{
+    type Response = Response;
+
+    type Error = Infallible;
+
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        self.serve(req.map(Body::new))
+    }
+}
+
+impl SharedS3Service {
+    /// Sets the remote address of the connection served by this clone
+    ///
+    /// It is inserted into each request as [`RemoteAddr`].
+    /// The clone counts its own in-flight requests, see [`S3Service::set_max_requests_per_connection`].
+    #[must_use]
+    pub fn with_remote_addr(mut self, addr: SocketAddr) -> Self {
+        self.remote_addr = Some(addr);
+        self.new_connection()
+    }
+
+    /// Sets the verified client certificate of the mutual TLS connection served by this clone
+    ///
+    /// It is inserted into each request as [`ClientCertificate`].
+    #[must_use]
+    pub fn with_client_cert(mut self, cert: ClientCertificate) -> Self {
+        self.client_cert = Some(cert);
+        self
+    }
+
+    /// resets the in-flight requests for a new connection
+    fn new_connection(mut self) -> Self {
+        self.in_flight = InFlightRequests::default();
+        self
+    }
+
+    /// serves a request of the connection
+    fn serve(&self, mut req: Request) -> BoxFuture<'static, Result<Response, BoxStdError>> {
+        self.insert_extensions(&mut req);
         let service = self.clone();
         Box::pin(async move { service.hyper_call(req).await })
     }
+
+    /// inserts the connection states into a request
+    fn insert_extensions(&self, req: &mut Request) {
+        let extensions = req.extensions_mut();
+        if let Some(addr) = self.remote_addr {
+            if extensions.get::<RemoteAddr>().is_none() {
+                let _prev = extensions.insert(RemoteAddr(addr));
+            }
+        }
+        if extensions.get::<InFlightRequests>().is_none() {
+            let _prev = extensions.insert(self.in_flight.clone());
+        }
+        if let Some(ref cert) = self.client_cert {
+            if extensions.get::<ClientCertificate>().is_none() {
+                let _prev = extensions.insert(cert.clone());
+            }
+        }
+    }
+
+    /// Returns the admin endpoints of the service
+    #[must_use]
+    pub fn admin_service(&self) -> AdminService {
+        AdminService::new(self.clone())
+    }
+
+    /// Converts the service to a [`MakeSharedS3Service`]
+    #[must_use]
+    pub const fn into_make_service(self) -> MakeSharedS3Service {
+        MakeSharedS3Service { inner: self }
+    }
+
+    /// Mounts the service at a path prefix, such as `/s3`
+    ///
+    /// A trailing slash of the prefix is ignored.
+    /// See also [`S3Service::set_path_prefix`].
+    #[must_use]
+    pub fn mount(self, prefix: &str) -> MountedS3Service {
+        MountedS3Service {
+            inner: self,
+            prefix: MountPrefix(prefix.into()),
+        }
+    }
 }
 
 impl S3Service {
@@ -93,15 +462,261 @@ impl S3Service {
             handlers: crate::ops::setup_handlers(),
             storage: Box::new(storage),
             auth: None,
+            signing_keys: SigningKeyCache::new(SIGNING_KEY_CACHE_CAPACITY),
+            anonymous_policy: AnonymousPolicy::default(),
+            public_read: PublicRead::default(),
+            multipart_limits: MultipartLimits::default(),
+            object_limits: ObjectLimits::default(),
+            content_types: None,
+            max_list_keys: DEFAULT_MAX_LIST_KEYS,
+            max_xml_response_size: None,
+            pretty_xml: false,
+            path_normalizer: None,
+            path_prefix: None,
+            trusted_proxy_hops: 0,
+            network_acl: None,
+            cors: None,
+            audit_log: None,
+            worm_buckets: HashSet::new(),
+            max_requests_per_connection: None,
+            clock: Box::new(SystemClock),
+            insecure_dev: false,
+            signature_debug: SignatureDebug::Disabled,
+            replay_cache: None,
+            max_presigned_expires: MAX_PRESIGNED_EXPIRES,
         }
     }
 
+    /// Constructs a `S3Service` for local development, which must never be exposed
+    ///
+    /// Any access key id and secret access key are accepted: signatures are parsed
+    /// but not verified, and unsigned requests are allowed.
+    /// Each request is logged at the `INFO` level.
+    pub fn insecure_dev(storage: impl S3Storage + Send + Sync + 'static) -> Self {
+        let mut service = Self::new(storage);
+        service.anonymous_policy = AnonymousPolicy::AllowAll;
+        service.insecure_dev = true;
+        service
+    }
+
     /// Set the authentication provider
     pub fn set_auth<A>(&mut self, auth: A)
     where
         A: S3Auth + Send + Sync + 'static,
     {
-        self.auth = Some(Box::new(auth));
+        self.auth = Some(Arc::new(auth));
+    }
+
+    /// Set the policy of anonymous requests
+    ///
+    /// All anonymous requests are denied by default.
+    pub fn set_anonymous_policy(&mut self, policy: AnonymousPolicy) {
+        self.anonymous_policy = policy;
+    }
+
+    /// Set the buckets and key prefixes which anonymous requests may read
+    ///
+    /// It applies to anonymous requests which are denied by the anonymous policy.
+    pub fn set_public_read(&mut self, public_read: PublicRead) {
+        self.public_read = public_read;
+    }
+
+    /// Set the limits of multipart/form-data bodies of POST Object requests
+    pub fn set_multipart_limits(&mut self, limits: MultipartLimits) {
+        self.multipart_limits = limits;
+    }
+
+    /// Set the size limits of objects and multipart uploads
+    pub fn set_object_limits(&mut self, limits: ObjectLimits) {
+        self.object_limits = limits;
+    }
+
+    /// Set the content types which are guessed by the extensions of keys
+    ///
+    /// They are stored with objects written without `Content-Type`,
+    /// and sent with objects whose storage records no content type.
+    pub fn set_content_types(&mut self, content_types: ContentTypes) {
+        self.content_types = Some(Arc::new(content_types));
+    }
+
+    /// Set the max number of keys in a page of listings, 1000 by default
+    ///
+    /// Larger `max-keys` of listing requests are lowered to it before the storage is called,
+    /// so that clients have to paginate through large buckets.
+    pub fn set_max_list_keys(&mut self, max_keys: NonZeroU32) {
+        self.max_list_keys = NonZeroI64::from(max_keys).get();
+    }
+
+    /// Set the max size of xml response bodies, which are unlimited by default
+    ///
+    /// A response exceeding it is replaced by an `InternalError`, instead of being sent.
+    pub fn set_max_xml_response_size(&mut self, size: usize) {
+        self.max_xml_response_size = Some(size);
+    }
+
+    /// Pretty-prints xml response bodies for human inspection, which is a debug option
+    pub fn set_pretty_xml(&mut self, enabled: bool) {
+        self.pretty_xml = enabled;
+    }
+
+    /// Reports the canonical request and the string to sign of mismatched signatures,
+    /// which is a debug option
+    pub fn set_signature_debug(&mut self, signature_debug: SignatureDebug) {
+        self.signature_debug = signature_debug;
+    }
+
+    /// Rejects replays of one-time signed requests, such as presigned `PUT` urls
+    pub fn set_replay_cache(&mut self, replay_cache: ReplayCache) {
+        self.replay_cache = Some(replay_cache);
+    }
+
+    /// Set the max lifetime of presigned urls, which is 7 days by default and can not be longer
+    ///
+    /// A presigned url whose `X-Amz-Expires` exceeds it is rejected.
+    pub fn set_max_presigned_expires(&mut self, max: Duration) {
+        self.max_presigned_expires = max.min(MAX_PRESIGNED_EXPIRES);
+    }
+
+    /// Set the time source which is used to check request dates and presigned urls
+    pub fn set_clock<C>(&mut self, clock: C)
+    where
+        C: Clock + Send + Sync + 'static,
+    {
+        self.clock = Box::new(clock);
+    }
+
+    /// Set a function which normalizes decoded uri paths before resolving buckets and keys
+    ///
+    /// For example, keys can be normalized into Unicode NFC by the `unicode-normalization` crate.
+    /// Signatures are always checked against the path which the client sent.
+    pub fn set_path_normalizer<F>(&mut self, f: F)
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.path_normalizer = Some(Box::new(f));
+    }
+
+    /// Set a path prefix, such as `/s3`, which is stripped before resolving buckets and keys
+    ///
+    /// It serves clients of a reverse proxy which forwards requests without rewriting paths.
+    /// Signatures are checked against the whole path, including the prefix.
+    /// The prefix of [`SharedS3Service::mount`] takes precedence over this one.
+    pub fn set_path_prefix(&mut self, prefix: impl Into<String>) {
+        self.path_prefix = Some(prefix.into());
+    }
+
+    /// Set the number of trusted proxies in front of the service
+    ///
+    /// The client address is taken from `Forwarded` or `X-Forwarded-For` headers,
+    /// skipping the addresses appended by `n` trusted proxies.
+    /// Headers are ignored by default, since any client can send them.
+    pub fn set_trusted_proxy_hops(&mut self, n: usize) {
+        self.trusted_proxy_hops = n;
+    }
+
+    /// Set the network access control, which is checked before authentication
+    ///
+    /// Requests from disallowed addresses are rejected with `AccessDenied`.
+    /// The client address is resolved by [`S3Service::client_ip`].
+    pub fn set_network_acl(&mut self, acl: NetworkAcl) {
+        self.network_acl = Some(acl);
+    }
+
+    /// Set the CORS rules of buckets
+    ///
+    /// Preflight requests are answered by the rules and cached by browsers for `MaxAgeSeconds`.
+    /// Responses carry `Vary: Origin` once CORS is configured,
+    /// so that shared caches do not mix responses to different origins.
+    pub fn set_cors(&mut self, cors: CorsConfig) {
+        self.cors = Some(cors);
+    }
+
+    /// Set the audit log, which records every mutating request
+    ///
+    /// A record contains the access key, the request, the response status
+    /// and the `ETag` of the object before and after the request.
+    pub fn set_audit_log(&mut self, audit_log: AuditLog) {
+        self.audit_log = Some(audit_log);
+    }
+
+    /// Set the write-once-read-many buckets
+    ///
+    /// Existing objects of the buckets can not be overwritten or deleted.
+    /// Overwrites are rejected with `PreconditionFailed` and deletes with `AccessDenied`.
+    /// The buckets themselves can not be renamed or deleted.
+    /// It is independent of object lock, so retention periods are not supported.
+    pub fn set_worm_buckets(&mut self, buckets: impl IntoIterator<Item = impl Into<String>>) {
+        self.worm_buckets = buckets.into_iter().map(Into::into).collect();
+    }
+
+    /// Set the max number of in-flight requests of a connection, which is unlimited by default
+    ///
+    /// Requests beyond the limit are rejected with `SlowDown`,
+    /// so that a client multiplexing many requests over a connection can not starve others.
+    /// A connection is a clone made by [`SharedS3Service::with_remote_addr`]
+    /// or by [`MakeSharedS3Service`].
+    pub fn set_max_requests_per_connection(&mut self, n: NonZeroUsize) {
+        self.max_requests_per_connection = Some(n.get());
+    }
+
+    /// Returns the address of the client who sent the request
+    ///
+    /// It is the [`RemoteAddr`] of the request if no proxy is trusted.
+    /// See [`S3Service::set_trusted_proxy_hops`].
+    #[must_use]
+    pub fn client_ip<B>(&self, req: &http::Request<B>) -> Option<IpAddr> {
+        let remote_ip = req.extensions().get::<RemoteAddr>().map(|addr| addr.0.ip());
+        if self.trusted_proxy_hops == 0 {
+            return remote_ip;
+        }
+
+        let headers = req.headers();
+        let mut forwarded = ForwardedFor::default();
+        if headers.contains_key(FORWARDED) {
+            for value in headers.get_all(FORWARDED) {
+                let value = value.to_str().unwrap_or_default();
+                forwarded.extend(ForwardedFor::from_forwarded(value));
+            }
+        } else {
+            for value in headers.get_all(X_FORWARDED_FOR) {
+                let value = value.to_str().unwrap_or_default();
+                forwarded.extend(ForwardedFor::from_x_forwarded_for(value));
+            }
+        }
+
+        // the chain is `addrs..., remote_ip`, and its last `n` addresses are trusted proxies
+        let addrs = forwarded.addrs();
+        let idx = addrs.len().saturating_sub(self.trusted_proxy_hops);
+        match addrs.get(idx) {
+            Some(addr) => *addr,
+            None => remote_ip,
+        }
+    }
+
+    /// Returns the documentation of the supported operations, in the order of routing
+    #[must_use]
+    pub fn operations(&self) -> Vec<OperationDoc> {
+        self.handlers.iter().map(|h| h.doc()).collect()
+    }
+
+    /// Returns an `OpenAPI 3.0` description of the supported operations in JSON
+    ///
+    /// Query parameters selecting subresources are part of the path keys, such as
+    /// `/{Bucket}?location`, and operations sharing a path and a method are told apart
+    /// by the `x-id` query parameter, as in the `OpenAPI` models of the AWS SDKs.
+    #[must_use]
+    pub fn openapi_json(&self) -> String {
+        let doc = crate::ops::openapi(
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            &self.operations(),
+        );
+        doc.to_string()
+    }
+
+    /// Returns the storage
+    pub(crate) fn storage(&self) -> &(dyn S3Storage + Send + Sync) {
+        &*self.storage
     }
 
     /// Converts `S3Service` to `SharedS3Service`
@@ -109,6 +724,9 @@ impl S3Service {
     pub fn into_shared(self) -> SharedS3Service {
         SharedS3Service {
             inner: Arc::new(self),
+            remote_addr: None,
+            in_flight: InFlightRequests::default(),
+            client_cert: None,
         }
     }
 
@@ -121,46 +739,395 @@ impl S3Service {
         fields(
             method = ?req.method(),
             uri = ?req.uri(),
+            client_ip = ?self.client_ip(&req),
             start_time = ?chrono::Utc::now(),
+            operation = tracing::field::Empty,
+            trace_id = tracing::field::Empty,
+            parent_span_id = tracing::field::Empty,
+            tracestate = tracing::field::Empty,
         )
     )]
-    pub async fn hyper_call(&self, req: Request) -> Result<Response, BoxStdError> {
+    pub async fn hyper_call<B>(&self, req: http::Request<B>) -> Result<Response, BoxStdError>
+    where
+        B: http_body::Body<Data = Bytes> + Send + 'static,
+        B::Error: Into<BoxStdError>,
+    {
+        let mut req = req.map(Body::new);
         debug!("req = \n{:#?}", req);
-        let ret = match self.handle(req).await {
+        let method = req.method().clone();
+        let resource = req.uri().path().to_owned();
+        let request_id = trace_request(&req);
+        let request_bytes = self.auth.as_ref().map(|_| {
+            let counter = Arc::new(AtomicU64::new(0));
+            let body = mem::take(req.body_mut());
+            *req.body_mut() = Body::wrap_stream(CountingStream::new(body, Arc::clone(&counter)));
+            counter
+        });
+
+        let mut identity = RequestIdentity::default();
+        let mut handled = match self.reserve_in_flight(&req) {
+            Ok(_guard) => self.handle_with_identity(req, &mut identity).await,
+            Err(err) => Err(err),
+        };
+        if let (true, Ok(ref mut resp)) = (self.pretty_xml, handled.as_mut()) {
+            pretty_print_xml(resp).await?;
+        }
+        let handled = handled.and_then(|resp| self.check_xml_response_size(resp));
+        let mut ret = match handled {
             Ok(resp) => Ok(resp),
-            Err(err) => err.into_xml_response().try_into_response(),
+            Err(err) => {
+                let mismatch = match self.signature_debug {
+                    SignatureDebug::Respond => SignatureMismatch::find(&err).cloned(),
+                    SignatureDebug::Disabled | SignatureDebug::Log => None,
+                };
+                let mut xml = err.into_xml_response();
+                xml.resource = Some(resource);
+                xml.request_id = Some(request_id.clone());
+                let mut resp = xml.try_into_response();
+                if let (Some(mismatch), Ok(ref mut resp)) = (mismatch, resp.as_mut()) {
+                    mismatch.attach(resp);
+                }
+                if let (true, Ok(ref mut resp)) = (self.pretty_xml, resp.as_mut()) {
+                    pretty_print_xml(resp).await?;
+                }
+                resp
+            }
         };
+        if let Ok(ref mut resp) = ret {
+            let _prev = resp
+                .headers_mut()
+                .insert(X_AMZ_REQUEST_ID, HeaderValue::try_from(request_id)?);
+        }
+
+        if let (Some(audit_log), Ok(resp)) = (self.audit_log.as_ref(), ret.as_ref()) {
+            if is_mutating(&method) {
+                self.audit(audit_log, &identity, &method, resp.status())
+                    .await;
+            }
+        }
+
+        if self.insecure_dev {
+            if let Ok(ref resp) = ret {
+                info!(
+                    %method,
+                    operation = ?identity.operation.map(OperationKind::as_str),
+                    bucket = ?identity.bucket,
+                    key = ?identity.key,
+                    access_key_id = ?identity.access_key_id,
+                    status = resp.status().as_u16(),
+                    "dev request",
+                );
+            }
+        }
+
+        if let (Some(auth), Some(request_bytes), Ok(ref mut resp)) =
+            (self.auth.as_ref(), request_bytes, ret.as_mut())
+        {
+            let report = UsageReport {
+                auth: Arc::clone(auth),
+                usage: RequestUsage {
+                    access_key_id: identity.access_key_id,
+                    bucket: identity.bucket,
+                    method,
+                    operation: identity.operation,
+                    status: resp.status(),
+                    request_bytes: 0,
+                    response_bytes: 0,
+                },
+                request_bytes,
+            };
+            report.attach(resp);
+        }
 
         match ret {
             Ok(ref resp) => debug!("resp = \n{:#?}", resp),
             Err(ref err) => error!(%err),
-        };
+        }
 
         Ok(ret?)
     }
 
+    /// appends a record of a mutating request to the audit log
+    async fn audit(
+        &self,
+        audit_log: &AuditLog,
+        identity: &RequestIdentity,
+        method: &Method,
+        status: StatusCode,
+    ) {
+        let etag_after = match (identity.bucket.as_deref(), identity.key.as_deref()) {
+            (Some(bucket), Some(key)) if status.is_success() => {
+                self.current_etag(bucket, key).await
+            }
+            _ => None,
+        };
+        let record = AuditRecord {
+            time: self.clock.now(),
+            access_key_id: identity.access_key_id.clone(),
+            method: method.clone(),
+            operation: identity.operation,
+            bucket: identity.bucket.clone(),
+            key: identity.key.clone(),
+            status,
+            etag_before: identity.etag_before.clone(),
+            etag_after,
+        };
+        if let Err(err) = audit_log.append(&record).await {
+            error!(%err, "failed to append an audit record");
+        }
+    }
+
+    /// returns the `ETag` of an object, or `None` if it does not exist or is unknown without reading it
+    async fn current_etag(&self, bucket: &str, key: &str) -> Option<String> {
+        let input = HeadObjectRequest {
+            bucket: bucket.into(),
+            key: key.into(),
+            ..HeadObjectRequest::default()
+        };
+        self.storage.stat_object(input).await.ok()?.e_tag
+    }
+
+    /// replaces a xml response exceeding `max_xml_response_size` by an error
+    ///
+    /// The response is measured as it is sent, after pretty-printing.
+    fn check_xml_response_size(&self, resp: Response) -> S3Result<Response> {
+        let max = match self.max_xml_response_size {
+            Some(max) if resp.extensions().get::<XmlBody>().is_some() => max,
+            _ => return Ok(resp),
+        };
+        let size = http_body::Body::size_hint(resp.body()).lower();
+        if size > u64::try_from(max).unwrap_or(u64::MAX) {
+            error!(size, max, "the xml response is too large");
+            return Err(code_error!(
+                InternalError,
+                "The response exceeds the size limit of the service."
+            ));
+        }
+        Ok(resp)
+    }
+
+    /// resolves the owner of the objects in the bucket if `fetch-owner=true` is requested
+    async fn fetch_owner(&self, ctx: &ReqContext<'_>) -> S3Result<Option<Owner>> {
+        let fetch_owner = ctx
+            .query_strings
+            .as_ref()
+            .and_then(|qs| qs.get("fetch-owner"))
+            == Some("true");
+        let (bucket, auth) = match (&ctx.path, self.auth.as_deref()) {
+            (&S3Path::Bucket { bucket }, Some(auth)) if fetch_owner => (bucket, auth),
+            _ => return Ok(None),
+        };
+        match auth.get_owner(bucket).await {
+            Ok(owner) => Ok(owner),
+            Err(S3AuthError::NotSignedUp) => Ok(None),
+            Err(S3AuthError::Other(e)) => Err(e),
+        }
+    }
+
+    /// authorizes a routed operation by the network acl of each accessed bucket,
+    /// and by the auth provider if the access key is identified
+    async fn authorize(
+        &self,
+        ctx: &ReqContext<'_>,
+        kind: OperationKind,
+        access_key_id: Option<&str>,
+    ) -> S3Result<()> {
+        let auth = match (self.auth.as_deref(), access_key_id) {
+            (Some(auth), Some(access_key_id)) => Some((auth, access_key_id)),
+            _ => None,
+        };
+        if auth.is_none() && self.network_acl.is_none() {
+            return Ok(());
+        }
+
+        // the header value is url-encoded and may begin with a slash
+        let copy_source = match ctx.headers.get(X_AMZ_COPY_SOURCE) {
+            Some(header) if kind == OperationKind::CopyObject => {
+                let decoded = urlencoding::decode(header)
+                    .map_err(|err| invalid_request!("Invalid header: x-amz-copy-source", err))?;
+                Some(decoded.into_owned())
+            }
+            _ => None,
+        };
+        let copy_source = match copy_source {
+            Some(ref copy_source) => {
+                let copy_source = copy_source.strip_prefix('/').unwrap_or(copy_source);
+                match AmzCopySource::from_header_str(copy_source) {
+                    Ok(AmzCopySource::Bucket { bucket, key }) => Some((bucket, key)),
+                    Ok(AmzCopySource::AccessPoint { .. }) => {
+                        return Err(not_supported!("Access point is not supported yet."))
+                    }
+                    Err(err) => {
+                        return Err(invalid_request!("Invalid header: x-amz-copy-source", err))
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let resources = ctx.resource_accesses(kind, copy_source);
+
+        // the copy source and the renamed bucket are checked as well as the bucket of the path
+        if let Some(ref acl) = self.network_acl {
+            let client_ip = self.client_ip(ctx.req);
+            for bucket in resources.iter().filter_map(|r| r.bucket) {
+                if !acl.is_allowed(Some(bucket), client_ip) {
+                    debug!(?client_ip, %bucket, "denied by network acl");
+                    return Err(code_error!(AccessDenied, "Access Denied"));
+                }
+            }
+        }
+
+        let (auth, key_id) = match auth {
+            Some(auth) => auth,
+            None => return Ok(()),
+        };
+        match auth.authorize(key_id, kind, &resources).await {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                debug!(access_key_id = %key_id, ?resources, "denied by auth provider");
+                Err(code_error!(AccessDenied, "Access Denied"))
+            }
+            Err(S3AuthError::Other(e)) => Err(e),
+            Err(S3AuthError::NotSignedUp) => {
+                Err(code_error!(NotSignedUp, "Your account is not signed up"))
+            }
+        }
+    }
+
+    /// reserves an in-flight request of the connection
+    fn reserve_in_flight(&self, req: &Request) -> S3Result<Option<InFlightGuard>> {
+        let max = match self.max_requests_per_connection {
+            Some(max) => max,
+            None => return Ok(None),
+        };
+        let in_flight = match req.extensions().get::<InFlightRequests>() {
+            Some(in_flight) => in_flight,
+            None => return Ok(None),
+        };
+        let guard = in_flight.try_reserve(max).ok_or_else(|| {
+            debug!(max, "too many in-flight requests of the connection");
+            code_error!(SlowDown, "Please reduce your request rate.")
+        })?;
+        Ok(Some(guard))
+    }
+
     /// handle a request
     /// # Errors
     /// Returns an `Err` if any component failed
-    pub async fn handle(&self, mut req: Request) -> S3Result<Response> {
+    pub async fn handle<B>(&self, req: http::Request<B>) -> S3Result<Response>
+    where
+        B: http_body::Body<Data = Bytes> + Send + 'static,
+        B::Error: Into<BoxStdError>,
+    {
+        self.handle_with_identity(req.map(Body::new), &mut RequestIdentity::default())
+            .await
+    }
+
+    /// handle a request and record who sent it to which bucket
+    async fn handle_with_identity(
+        &self,
+        mut req: Request,
+        identity: &mut RequestIdentity,
+    ) -> S3Result<Response> {
         let body = mem::take(req.body_mut());
         let uri_path = decode_uri_path(&req)?;
-        let path = extract_s3_path(&uri_path)?;
-        let headers = extract_headers(&req)?;
-        let query_strings = extract_qs(&req)?;
+        let normalized_path = match self.path_normalizer {
+            Some(ref normalize) => Cow::Owned(normalize(&uri_path)),
+            None => Cow::Borrowed(&*uri_path),
+        };
+        let path_prefix = match req.extensions().get::<MountPrefix>() {
+            Some(prefix) => Some(&*prefix.0),
+            None => self.path_prefix.as_deref(),
+        };
+        let path = extract_s3_path(&normalized_path, path_prefix)?;
+        identity.bucket = path.bucket().map(ToOwned::to_owned);
+        if let S3Path::Object { key, .. } = path {
+            identity.key = Some(key.to_owned());
+        }
+
+        if let Some(ref acl) = self.network_acl {
+            let client_ip = self.client_ip(&req);
+            if !acl.is_allowed(path.bucket(), client_ip) {
+                debug!(?client_ip, "denied by network acl");
+                return Err(code_error!(AccessDenied, "Access Denied"));
+            }
+        }
+
+        let allowed_methods = allowed_methods(&path);
+        if req.method() == Method::OPTIONS {
+            if let Some(ref cors) = self.cors {
+                if req.headers().contains_key(ORIGIN) {
+                    return preflight_response(cors, &req, &path);
+                }
+            }
+            return options_response(allowed_methods);
+        }
+
+        let cors_headers = self
+            .cors
+            .as_ref()
+            .map(|cors| match_actual_request(cors, &req, &path));
+        // a panic of a handler, the auth provider or the storage only fails its own request,
+        // instead of unwinding through the connection task with the other requests on it
+        let dispatch = self.dispatch(&req, &uri_path, path, body, identity);
+        let mut res = match AssertUnwindSafe(dispatch).catch_unwind().await {
+            Ok(ret) => ret?,
+            Err(payload) => return Err(internal_error!(HandlerPanic::new(&*payload))),
+        };
+        if let Some(ref cors_headers) = cors_headers {
+            set_cors_headers(&mut res, cors_headers.as_ref())?;
+        }
+        Ok(res)
+    }
+
+    /// dispatch a request to its handler
+    async fn dispatch(
+        &self,
+        req: &Request,
+        uri_path: &str,
+        path: S3Path<'_>,
+        body: Body,
+        identity: &mut RequestIdentity,
+    ) -> S3Result<Response> {
+        let allowed_methods = allowed_methods(&path);
+        if !allowed_methods.contains(req.method()) {
+            return method_not_allowed_response(allowed_methods);
+        }
+
+        let headers = extract_headers(req)?;
+        let query_strings = extract_qs(req)?;
         let mime = extract_mime(&headers)?;
+        let worm = path
+            .bucket()
+            .map_or(false, |b| self.worm_buckets.contains(b));
 
         let mut ctx: ReqContext<'_> = ReqContext {
-            req: &req,
+            req,
+            uri_path,
             headers,
             query_strings,
             path,
             body,
             mime,
             multipart: None,
+            content_length_range: None,
+            access_key_id: None,
+            worm,
+            limits: self.object_limits,
+            content_types: self.content_types.clone(),
+            max_keys: self.max_list_keys,
+            owner: None,
         };
 
-        check_signature(&mut ctx, self.auth.as_deref()).await?;
+        check_signature(&mut ctx, self).await?;
+        identity.access_key_id = ctx.access_key_id.take();
+
+        if self.audit_log.is_some() && is_mutating(ctx.req.method()) {
+            if let S3Path::Object { bucket, key } = ctx.path {
+                identity.etag_before = self.current_etag(bucket, key).await;
+            }
+        }
 
         if ctx.req.method() == Method::POST && ctx.path.is_object() && ctx.multipart.is_some() {
             return Err(code_error!(
@@ -169,8 +1136,15 @@ impl S3Service {
             ));
         }
 
+        ctx.owner = self.fetch_owner(&ctx).await?;
+
         for handler in &self.handlers {
             if handler.is_match(&ctx) {
+                let kind = handler.kind();
+                identity.operation = Some(kind);
+                let _span = tracing::Span::current().record("operation", kind.as_str());
+                self.authorize(&ctx, kind, identity.access_key_id.as_deref())
+                    .await?;
                 return handler.handle(&mut ctx, &*self.storage).await;
             }
         }
@@ -179,6 +1153,247 @@ impl S3Service {
     }
 }
 
+/// A panic caught while a request was dispatched
+///
+/// The location of the panic is reported by the panic hook,
+/// and the internal error carries the backtrace and the span trace of the request.
+#[derive(Debug, thiserror::Error)]
+#[error("panicked while handling the request: {message}")]
+struct HandlerPanic {
+    /// panic message
+    message: String,
+}
+
+impl HandlerPanic {
+    /// takes the message of a panic payload
+    fn new(payload: &(dyn Any + Send)) -> Self {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(s) => (*s).to_owned(),
+            None => payload
+                .downcast_ref::<String>()
+                .cloned()
+                .unwrap_or_else(|| "unknown panic payload".to_owned()),
+        };
+        Self { message }
+    }
+}
+
+/// the sender and the bucket of a request
+#[derive(Debug, Default)]
+struct RequestIdentity {
+    /// access key id of the verified signature
+    access_key_id: Option<String>,
+    /// bucket of the request path
+    bucket: Option<String>,
+    /// key of the request path
+    key: Option<String>,
+    /// operation of the matched handler
+    operation: Option<OperationKind>,
+    /// `ETag` of the object before the request, which is looked up for the audit log
+    etag_before: Option<String>,
+}
+
+/// usage of a request, reported to the auth provider when dropped
+struct UsageReport {
+    /// auth provider
+    auth: Arc<dyn S3Auth + Send + Sync + 'static>,
+    /// usage with the response bytes counted so far
+    usage: RequestUsage,
+    /// request bytes counted by the request body
+    request_bytes: Arc<AtomicU64>,
+}
+
+impl UsageReport {
+    /// reports when the response body is dropped, or now if its size is known
+    fn attach(mut self, res: &mut Response) {
+        if let Some(size) = http_body::Body::size_hint(res.body()).exact() {
+            self.usage.response_bytes = size;
+            return;
+        }
+        let body = mem::take(res.body_mut());
+        *res.body_mut() = Body::wrap_stream(CountingStream::new(body, self));
+    }
+}
+
+impl ByteCounter for UsageReport {
+    fn add(&mut self, n: u64) {
+        self.usage.response_bytes = self.usage.response_bytes.saturating_add(n);
+    }
+}
+
+impl Drop for UsageReport {
+    fn drop(&mut self) {
+        let mut usage = self.usage.clone();
+        usage.request_bytes = self.request_bytes.load(Ordering::Relaxed);
+        self.auth.record_usage(usage);
+    }
+}
+
+/// attaches the remote context of a W3C `traceparent` header to the current span,
+/// and generates the request id
+///
+/// The request id of a traced request starts with its trace id,
+/// so that the requests of a trace can be found by the prefix.
+/// Invalid `traceparent` headers are ignored.
+fn trace_request(req: &Request) -> String {
+    let traceparent = req
+        .headers()
+        .get(TRACEPARENT)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| TraceParent::from_header_str(s).ok());
+
+    let trace = match traceparent {
+        Some(trace) => trace,
+        None => return Uuid::new_v4().simple().to_string().to_ascii_uppercase(),
+    };
+
+    let span = tracing::Span::current();
+    let _: &tracing::Span = span.record("trace_id", trace.trace_id);
+    let _: &tracing::Span = span.record("parent_span_id", trace.parent_id);
+    if let Some(tracestate) = req.headers().get(TRACESTATE).and_then(|v| v.to_str().ok()) {
+        let _: &tracing::Span = span.record("tracestate", tracestate);
+    }
+
+    let (suffix, _) = Uuid::new_v4().as_u64_pair();
+    format!("{}-{:016X}", trace.trace_id.to_ascii_uppercase(), suffix)
+}
+
+/// pretty-prints the body of a xml response
+async fn pretty_print_xml(resp: &mut Response) -> Result<(), BoxStdError> {
+    if resp.extensions().get::<XmlBody>().is_none() {
+        return Ok(());
+    }
+    let body = mem::take(resp.body_mut()).into_bytes().await?;
+    let pretty = utils::pretty_print(&body)?;
+    let _prev = resp.headers_mut().remove(hyper::header::CONTENT_LENGTH);
+    *resp.body_mut() = Body::from(pretty);
+    Ok(())
+}
+
+/// whether the request may modify buckets or objects
+fn is_mutating(method: &Method) -> bool {
+    [Method::PUT, Method::POST, Method::DELETE].contains(method)
+}
+
+/// methods allowed on the resource
+const fn allowed_methods(path: &S3Path<'_>) -> &'static [Method] {
+    match *path {
+        S3Path::Root => ROOT_METHODS,
+        S3Path::Bucket { .. } | S3Path::Object { .. } => RESOURCE_METHODS,
+    }
+}
+
+/// set `Allow` header
+fn set_allow_header(res: &mut Response, methods: &[Method]) -> S3Result<()> {
+    let value = methods
+        .iter()
+        .map(Method::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let value = HeaderValue::from_str(&value).map_err(|e| internal_error!(e))?;
+    drop(res.headers_mut().insert(ALLOW, value));
+    Ok(())
+}
+
+/// answer OPTIONS with the allowed methods
+fn options_response(methods: &[Method]) -> S3Result<Response> {
+    let mut res = Response::new(Body::empty());
+    set_allow_header(&mut res, methods)?;
+    Ok(res)
+}
+
+/// answer a CORS preflight request with the matched rule
+fn preflight_response(cors: &CorsConfig, req: &Request, path: &S3Path<'_>) -> S3Result<Response> {
+    let header_str = |name| {
+        req.headers()
+            .get(name)
+            .map(|v: &HeaderValue| {
+                v.to_str()
+                    .map_err(|e| invalid_request!("Invalid header", e))
+            })
+            .transpose()
+    };
+    let origin = header_str(ORIGIN)?.unwrap_or_default();
+    let method = header_str(ACCESS_CONTROL_REQUEST_METHOD)?
+        .ok_or_else(|| invalid_request!("Missing Access-Control-Request-Method header"))?;
+    let method = Method::from_bytes(method.as_bytes())
+        .map_err(|e| invalid_request!("Invalid Access-Control-Request-Method header", e))?;
+    let request_headers: Vec<&str> = header_str(ACCESS_CONTROL_REQUEST_HEADERS)?
+        .map(|s| {
+            s.split(',')
+                .map(str::trim)
+                .filter(|h| !h.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let matched = path
+        .bucket()
+        .and_then(|bucket| cors.match_request(bucket, origin, &method, &request_headers));
+    let cors_headers = matched.ok_or_else(|| {
+        debug!(origin, %method, "cors preflight is not allowed");
+        code_error!(
+            AccessDenied,
+            "CORSResponse: This CORS request is not allowed."
+        )
+    })?;
+
+    let mut res = Response::new(Body::empty());
+    set_cors_headers(&mut res, Some(&cors_headers))?;
+    Ok(res)
+}
+
+/// match the origin of an actual cross-origin request
+fn match_actual_request(
+    cors: &CorsConfig,
+    req: &Request,
+    path: &S3Path<'_>,
+) -> Option<CorsHeaders> {
+    let origin = req.headers().get(ORIGIN)?.to_str().ok()?;
+    cors.match_request(path.bucket()?, origin, req.method(), &[])
+}
+
+/// set `Access-Control-*` headers and `Vary`
+fn set_cors_headers(res: &mut Response, cors_headers: Option<&CorsHeaders>) -> S3Result<()> {
+    let headers = res.headers_mut();
+    drop(headers.insert(VARY, HeaderValue::from_static(cors::VARY_HEADERS)));
+
+    let h = match cors_headers {
+        Some(h) => h,
+        None => return Ok(()),
+    };
+    let value = |s: &str| HeaderValue::from_str(s).map_err(|e| internal_error!(e));
+    drop(headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, value(&h.allow_origin)?));
+    drop(headers.insert(ACCESS_CONTROL_ALLOW_METHODS, value(&h.allow_methods)?));
+    if h.allow_credentials {
+        drop(headers.insert(
+            ACCESS_CONTROL_ALLOW_CREDENTIALS,
+            HeaderValue::from_static("true"),
+        ));
+    }
+    if let Some(ref allow_headers) = h.allow_headers {
+        drop(headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, value(allow_headers)?));
+    }
+    if let Some(ref expose_headers) = h.expose_headers {
+        drop(headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, value(expose_headers)?));
+    }
+    if let Some(max_age) = h.max_age {
+        drop(headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from(max_age)));
+    }
+    Ok(())
+}
+
+/// reject a method which is not allowed on the resource
+fn method_not_allowed_response(methods: &[Method]) -> S3Result<Response> {
+    let err = code_error!(
+        MethodNotAllowed,
+        "The specified method is not allowed against this resource."
+    );
+    let mut res = err.into_xml_response().try_into_response()?;
+    set_allow_header(&mut res, methods)?;
+    Ok(res)
+}
+
 /// Extract urlencoded URI from Request
 fn decode_uri_path(req: &Request) -> S3Result<Cow<'_, str>> {
     urlencoding::decode(req.uri().path())
@@ -186,11 +1401,14 @@ fn decode_uri_path(req: &Request) -> S3Result<Cow<'_, str>> {
 }
 
 /// util function
-fn extract_s3_path(uri_path: &str) -> S3Result<S3Path<'_>> {
-    let result = S3Path::try_from_path(uri_path);
+fn extract_s3_path<'a>(uri_path: &'a str, prefix: Option<&str>) -> S3Result<S3Path<'a>> {
+    let result = match prefix {
+        Some(prefix) => S3Path::try_from_prefixed_path(uri_path, prefix),
+        None => S3Path::try_from_path(uri_path),
+    };
     let err = try_err!(result);
     let (code, msg) = match *err.kind() {
-        S3PathErrorKind::InvalidPath => {
+        S3PathErrorKind::InvalidPath | S3PathErrorKind::UnsafeKey => {
             (S3ErrorCode::InvalidURI, "Couldn't parse the specified URI.")
         }
         S3PathErrorKind::InvalidBucketName => (
@@ -198,6 +1416,10 @@ fn extract_s3_path(uri_path: &str) -> S3Result<S3Path<'_>> {
             "The specified bucket is not valid.",
         ),
         S3PathErrorKind::KeyTooLong => (S3ErrorCode::KeyTooLongError, "Your key is too long."),
+        S3PathErrorKind::OutsidePrefix => (
+            S3ErrorCode::InvalidURI,
+            "The path is outside of the path prefix.",
+        ),
     };
     Err(code_error!(code = code, msg, err))
 }
@@ -244,6 +1466,13 @@ fn extract_authorization_v4<'a>(
     Err(invalid_request!("Invalid header: Authorization", err))
 }
 
+/// extract the token of `Authorization: Bearer <token>`
+fn extract_bearer_token<'a>(headers: &'_ OrderedHeaders<'a>) -> Option<&'a str> {
+    let value = headers.get(AUTHORIZATION)?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("Bearer").then(|| token.trim())
+}
+
 /// extract `AmzDate` from headers
 fn extract_amz_date(headers: &'_ OrderedHeaders<'_>) -> S3Result<Option<AmzDate>> {
     let value = try_some!(headers.get(X_AMZ_DATE));
@@ -261,15 +1490,12 @@ fn take_io_body(body: &mut Body) -> impl Stream<Item = io::Result<Bytes>> + Send
 }
 
 /// check signature (v4)
-async fn check_signature(
-    ctx: &mut ReqContext<'_>,
-    auth: Option<&(dyn S3Auth + Send + Sync)>,
-) -> S3Result<()> {
+async fn check_signature(ctx: &mut ReqContext<'_>, service: &S3Service) -> S3Result<()> {
     // --- POST auth ---
     if ctx.req.method() == Method::POST {
         if let Some(mime) = ctx.mime.as_ref() {
             if mime.type_() == mime::MULTIPART && mime.subtype() == mime::FORM_DATA {
-                return check_post_signature(ctx, auth).await;
+                return check_post_signature(ctx, service).await;
             }
         }
     }
@@ -277,12 +1503,12 @@ async fn check_signature(
     // --- query auth ---
     if let Some(qs) = ctx.query_strings.as_ref() {
         if qs.get("X-Amz-Signature").is_some() {
-            return check_presigned_url(ctx, auth).await;
+            return check_presigned_url(ctx, service).await;
         }
     }
 
     // --- header auth ---
-    check_header_auth(ctx, auth).await
+    check_header_auth(ctx, service).await
 }
 
 /// fetch secret key from auth
@@ -293,11 +1519,53 @@ async fn fetch_secret_key(auth: &(dyn S3Auth + Send + Sync), access_key: &str) -
     }
 }
 
+/// identify a request by its bearer token
+async fn identify_bearer_token(token: &str, service: &S3Service) -> S3Result<String> {
+    let auth_provider = service
+        .auth
+        .as_deref()
+        .ok_or_else(|| not_supported!("The service has no authentication provider."))?;
+    let access_key_id = match auth_provider.identify_bearer_token(token).await {
+        Ok(Some(access_key_id)) => access_key_id,
+        Ok(None) => return Err(code_error!(AccessDenied, "Access Denied")),
+        Err(S3AuthError::Other(e)) => return Err(e),
+        Err(S3AuthError::NotSignedUp) => {
+            return Err(code_error!(NotSignedUp, "Your account is not signed up"))
+        }
+    };
+    debug!(%access_key_id, "identified by bearer token");
+    Ok(access_key_id)
+}
+
+/// identify an unsigned request by the client certificate of its connection
+///
+/// An unmapped certificate falls back to the anonymous policy.
+async fn identify_client_cert(
+    ctx: &ReqContext<'_>,
+    service: &S3Service,
+) -> S3Result<Option<String>> {
+    let (cert, auth) = match (
+        ctx.req.extensions().get::<ClientCertificate>(),
+        service.auth.as_deref(),
+    ) {
+        (Some(cert), Some(auth)) => (cert, auth),
+        _ => return Ok(None),
+    };
+    let ans = match auth.identify_client_cert(cert).await {
+        Ok(ans) => ans,
+        Err(S3AuthError::Other(e)) => return Err(e),
+        Err(S3AuthError::NotSignedUp) => {
+            return Err(code_error!(NotSignedUp, "Your account is not signed up"))
+        }
+    };
+    if let Some(ref access_key_id) = ans {
+        debug!(%access_key_id, "identified by client certificate");
+    }
+    Ok(ans)
+}
+
 /// check post signature (v4)
-async fn check_post_signature(
-    ctx: &mut ReqContext<'_>,
-    auth: Option<&(dyn S3Auth + Send + Sync)>,
-) -> S3Result<()> {
+async fn check_post_signature(ctx: &mut ReqContext<'_>, service: &S3Service) -> S3Result<()> {
     /// util method
     fn find_info(multipart: &Multipart) -> Option<(&str, &str, &str, &str, &str)> {
         let policy = multipart.find_field_value("policy")?;
@@ -314,15 +1582,6 @@ async fn check_post_signature(
         ))
     }
 
-    let auth_provider = match auth {
-        Some(a) => a,
-        None => {
-            return Err(not_supported!(
-                "The service has no authentication provider."
-            ))
-        }
-    };
-
     let mime = ctx.mime.as_ref().unwrap_or_else(|| panic!("missing mime"));
 
     let boundary = mime
@@ -331,9 +1590,26 @@ async fn check_post_signature(
 
     let body = take_io_body(&mut ctx.body);
 
-    let multipart = multipart::transform_multipart(body, boundary.as_str().as_bytes())
-        .await
-        .map_err(|err| invalid_request!("Invalid multipart/form-data body", err))?;
+    let multipart = multipart::transform_multipart(
+        body,
+        boundary.as_str().as_bytes(),
+        service.multipart_limits,
+    )
+    .await
+    .map_err(|err| {
+        if let MultipartError::LimitExceeded(_) = err {
+            return code_error!(
+                MaxPostPreDataLengthExceededError,
+                "Your POST request fields preceding the upload file were too large.",
+                err
+            );
+        }
+        code_error!(
+            MalformedPOSTRequest,
+            "The body of your POST request is not well-formed multipart/form-data.",
+            err
+        )
+    })?;
     {
         let (policy, x_amz_algorithm, x_amz_credential, x_amz_date, x_amz_signature) = {
             match find_info(&multipart) {
@@ -343,6 +1619,12 @@ async fn check_post_signature(
         };
 
         // check policy
+        if policy.len() > MAX_POST_POLICY_SIZE {
+            return Err(code_error!(
+                MaxPostPreDataLengthExceededError,
+                "Your POST request policy is too large."
+            ));
+        }
         if !crypto::is_base64_encoded(policy.as_bytes()) {
             return Err(invalid_request!("Invalid field: policy"));
         }
@@ -362,22 +1644,42 @@ async fn check_post_signature(
         let amz_date = AmzDate::from_header_str(x_amz_date)
             .map_err(|err| invalid_request!("Invalid field: x-amz-date", err))?;
 
-        // fetch secret_key
-        let secret_key = fetch_secret_key(auth_provider, credential.access_key_id).await?;
-
-        // calculate signature
-        let string_to_sign = policy;
-        let signature = signature_v4::calculate_signature(
-            string_to_sign,
-            &secret_key,
-            &amz_date,
-            credential.aws_region,
-        );
-
-        // check x_amz_signature
-        if signature != x_amz_signature {
-            return Err(signature_mismatch!());
+        if !service.insecure_dev {
+            let auth_provider = service
+                .auth
+                .as_deref()
+                .ok_or_else(|| not_supported!("The service has no authentication provider."))?;
+
+            // fetch secret_key
+            let secret_key = fetch_secret_key(auth_provider, credential.access_key_id).await?;
+
+            // calculate signature
+            let string_to_sign = policy;
+            let signing_key =
+                service
+                    .signing_keys
+                    .get_or_derive(&secret_key, &amz_date, credential.aws_region);
+            let signature =
+                signature_v4::calculate_signature_with_key(string_to_sign, &signing_key);
+
+            // check x_amz_signature
+            if !crypto::constant_time_eq(signature.as_bytes(), x_amz_signature.as_bytes()) {
+                return Err(signature_mismatch!());
+            }
         }
+        ctx.access_key_id = Some(credential.access_key_id.into());
+
+        // check policy conditions
+        let policy = PostPolicy::from_base64(policy)
+            .map_err(|err| code_error!(InvalidPolicyDocument, err.to_string()))?;
+        let bucket = match ctx.path {
+            S3Path::Bucket { bucket } | S3Path::Object { bucket, .. } => bucket,
+            S3Path::Root => "",
+        };
+        policy
+            .check(service.clock.now(), bucket, &multipart.fields)
+            .map_err(|err| code_error!(AccessDenied, err.to_string()))?;
+        ctx.content_length_range = policy.content_length_range();
     }
 
     // store ctx value
@@ -386,23 +1688,195 @@ async fn check_post_signature(
     Ok(())
 }
 
-/// check presigned url (v4)
-async fn check_presigned_url(
-    ctx: &mut ReqContext<'_>,
-    auth: Option<&(dyn S3Auth + Send + Sync)>,
+/// check that the request time is close to the server time,
+/// returns the time until which the request is valid
+fn check_request_time(service: &S3Service, amz_date: &AmzDate) -> S3Result<SystemTime> {
+    let request_time = amz_date
+        .to_system_time()
+        .ok_or_else(|| invalid_request!("Invalid header: x-amz-date"))?;
+
+    let now = service.clock.now();
+    let skew = now
+        .duration_since(request_time)
+        .unwrap_or_else(|err| err.duration());
+
+    if skew > MAX_CLOCK_SKEW {
+        return Err(code_error!(
+            RequestTimeTooSkewed,
+            "The difference between the request time and the current time is too large."
+        ));
+    }
+    request_time
+        .checked_add(MAX_CLOCK_SKEW)
+        .ok_or_else(|| invalid_request!("Invalid header: x-amz-date"))
+}
+
+/// check that `X-Amz-Expires` is a number of seconds within the max lifetime of presigned urls
+fn check_presigned_expires(service: &S3Service, qs: &OrderedQs) -> S3Result<()> {
+    // a missing field is reported with the others
+    let expires = match qs.get("X-Amz-Expires") {
+        Some(s) => s,
+        None => return Ok(()),
+    };
+    let secs: u64 = expires.parse().map_err(|_err| {
+        code_error!(
+            AuthorizationQueryParametersError,
+            "X-Amz-Expires should be a non-negative number"
+        )
+    })?;
+    let max = service.max_presigned_expires.as_secs();
+    if secs > max {
+        return Err(code_error!(
+            AuthorizationQueryParametersError,
+            format!("X-Amz-Expires must be at most {max} seconds")
+        ));
+    }
+    Ok(())
+}
+
+/// check that a presigned url is valid at the current time, returns its expiration time
+fn check_presigned_expiration(
+    service: &S3Service,
+    presigned_url: &signature_v4::PresignedUrl<'_>,
+) -> S3Result<SystemTime> {
+    let signed_time = presigned_url
+        .amz_date
+        .to_system_time()
+        .ok_or_else(|| invalid_request!("Invalid query: X-Amz-Date"))?;
+
+    let now = service.clock.now();
+    if signed_time
+        .duration_since(now)
+        .map_or(false, |d| d > MAX_CLOCK_SKEW)
+    {
+        return Err(code_error!(AccessDenied, "Request is not valid yet"));
+    }
+
+    let expires = Duration::from_secs(presigned_url.expires.into());
+    if now
+        .duration_since(signed_time)
+        .map_or(false, |d| d > expires)
+    {
+        return Err(code_error!(AccessDenied, "Request has expired"));
+    }
+    signed_time
+        .checked_add(expires)
+        .ok_or_else(|| invalid_request!("Invalid query: X-Amz-Expires"))
+}
+
+/// check that a one-time signature has not been used, and record it
+fn check_replay(
+    service: &S3Service,
+    method: &Method,
+    presigned: bool,
+    signature: &str,
+    expires_at: SystemTime,
 ) -> S3Result<()> {
+    let replay_cache = match service.replay_cache {
+        Some(ref c) if c.is_one_time(method, presigned) => c,
+        _ => return Ok(()),
+    };
+    if replay_cache.record(signature, expires_at, service.clock.now()) {
+        return Ok(());
+    }
+    Err(code_error!(
+        AccessDenied,
+        "The request has already been used"
+    ))
+}
+
+/// check that the headers which must be signed are signed
+fn check_signed_headers(signed_headers: &[&str], headers: &OrderedHeaders<'_>) -> S3Result<()> {
+    let unsigned = signature_v4::unsigned_headers(signed_headers, headers);
+    if unsigned.is_empty() {
+        return Ok(());
+    }
+    Err(code_error!(
+        AccessDenied,
+        format!(
+            "There were headers present in the request which were not signed: {}",
+            unsigned.join(", ")
+        )
+    ))
+}
+
+/// the server-computed signing inputs of a mismatched signature
+#[derive(Debug, Clone)]
+struct SignatureMismatch {
+    /// canonical request
+    canonical_request: String,
+    /// string to sign
+    string_to_sign: String,
+}
+
+impl fmt::Display for SignatureMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "canonical request:\n{}\nstring to sign:\n{}",
+            self.canonical_request, self.string_to_sign
+        )
+    }
+}
+
+impl std::error::Error for SignatureMismatch {}
+
+impl SignatureMismatch {
+    /// creates a `SignatureDoesNotMatch` error, with the signing inputs if they are reported
+    fn report(service: &S3Service, canonical_request: String, string_to_sign: String) -> S3Error {
+        if service.signature_debug == SignatureDebug::Disabled {
+            return signature_mismatch!();
+        }
+        info!(%canonical_request, %string_to_sign, "signature mismatch");
+        signature_mismatch!(Self {
+            canonical_request,
+            string_to_sign,
+        })
+    }
+
+    /// finds the signing inputs in an error
+    fn find(err: &S3Error) -> Option<&Self> {
+        std::error::Error::source(err)?.downcast_ref::<Self>()
+    }
+
+    /// sets the signing inputs to the headers of an error response
+    fn attach(&self, resp: &mut Response) {
+        let fields = [
+            (X_S3_SERVER_CANONICAL_REQUEST, &self.canonical_request),
+            (X_S3_SERVER_STRING_TO_SIGN, &self.string_to_sign),
+        ];
+        for (name, field) in fields {
+            let encoded = base64_simd::STANDARD.encode_to_string(field);
+            if let Ok(value) = HeaderValue::try_from(encoded) {
+                let _prev = resp.headers_mut().insert(name, value);
+            }
+        }
+    }
+}
+
+/// check presigned url (v4)
+async fn check_presigned_url(ctx: &mut ReqContext<'_>, service: &S3Service) -> S3Result<()> {
     let qs = ctx
         .query_strings
         .as_ref()
         .unwrap_or_else(|| panic!("missing query string"));
 
+    check_presigned_expires(service, qs)?;
     let presigned_url = signature_v4::PresignedUrl::from_query(qs)
         .map_err(|err| invalid_request!("Missing presigned fields", err))?;
 
     // TODO: how to use it?
     let _content_sha256: Option<AmzContentSha256<'_>> = extract_amz_content_sha256(&ctx.headers)?;
 
-    let auth_provider = match auth {
+    let valid_until = check_presigned_expiration(service, &presigned_url)?;
+    check_signed_headers(&presigned_url.signed_headers, &ctx.headers)?;
+
+    if service.insecure_dev {
+        ctx.access_key_id = Some(presigned_url.credential.access_key_id.into());
+        return Ok(());
+    }
+
+    let auth_provider = match service.auth.as_deref() {
         Some(a) => a,
         None => {
             return Err(not_supported!(
@@ -414,67 +1888,136 @@ async fn check_presigned_url(
     let secret_key =
         fetch_secret_key(auth_provider, presigned_url.credential.access_key_id).await?;
 
-    let signature = {
+    let region = presigned_url.credential.aws_region;
+    let amz_date = &presigned_url.amz_date;
+
+    let (canonical_request, string_to_sign) = {
         let headers = ctx
             .headers
             .map_signed_headers(&presigned_url.signed_headers);
 
         let canonical_request = signature_v4::create_presigned_canonical_request(
             ctx.req.method(),
-            ctx.req.uri().path(),
+            ctx.uri_path,
             qs.as_ref(),
             &headers,
         );
 
-        let region = presigned_url.credential.aws_region;
-        let amz_date = &presigned_url.amz_date;
         let string_to_sign =
             signature_v4::create_string_to_sign(&canonical_request, amz_date, region);
-
-        signature_v4::calculate_signature(&string_to_sign, &secret_key, amz_date, region)
+        (canonical_request, string_to_sign)
     };
 
-    if signature != presigned_url.signature {
-        return Err(signature_mismatch!());
+    let signing_key = service
+        .signing_keys
+        .get_or_derive(&secret_key, amz_date, region);
+    let signature = signature_v4::calculate_signature_with_key(&string_to_sign, &signing_key);
+
+    if !crypto::constant_time_eq(signature.as_bytes(), presigned_url.signature.as_bytes()) {
+        return Err(SignatureMismatch::report(
+            service,
+            canonical_request,
+            string_to_sign,
+        ));
     }
+    check_replay(
+        service,
+        ctx.req.method(),
+        true,
+        presigned_url.signature,
+        valid_until,
+    )?;
+    ctx.access_key_id = Some(presigned_url.credential.access_key_id.into());
 
     Ok(())
 }
 
+/// check a request without any signature
+async fn check_unsigned_request(ctx: &mut ReqContext<'_>, service: &S3Service) -> S3Result<()> {
+    if let Some(access_key_id) = identify_client_cert(ctx, service).await? {
+        ctx.access_key_id = Some(access_key_id);
+        return Ok(());
+    }
+    let allowed = auth::is_anonymous_allowed(
+        service.anonymous_policy,
+        &service.public_read,
+        ctx.req.method(),
+        &ctx.path,
+        ctx.query_strings.as_ref(),
+    );
+    if allowed {
+        return Ok(());
+    }
+    Err(code_error!(AccessDenied, "Access Denied"))
+}
+
+/// the payload declared by `x-amz-content-sha256`
+const fn signed_payload<'a>(
+    amz_content_sha256: &AmzContentSha256<'a>,
+) -> signature_v4::Payload<'a> {
+    match *amz_content_sha256 {
+        AmzContentSha256::MultipleChunks => signature_v4::Payload::MultipleChunks,
+        AmzContentSha256::SingleChunk { payload_checksum } => {
+            signature_v4::Payload::SingleChunk(payload_checksum)
+        }
+        AmzContentSha256::UnsignedPayload => signature_v4::Payload::Unsigned,
+    }
+}
+
 /// check header auth (v4)
-async fn check_header_auth(
-    ctx: &mut ReqContext<'_>,
-    auth: Option<&(dyn S3Auth + Send + Sync)>,
-) -> S3Result<()> {
+async fn check_header_auth(ctx: &mut ReqContext<'_>, service: &S3Service) -> S3Result<()> {
+    let auth = service.auth.as_deref();
+    if let Some(token) = extract_bearer_token(&ctx.headers) {
+        ctx.access_key_id = Some(identify_bearer_token(token, service).await?);
+        return Ok(());
+    }
+
     let authorization: AuthorizationV4<'_> = {
         if let Some(mut a) = extract_authorization_v4(&ctx.headers)? {
             a.signed_headers.sort_unstable();
             a
         } else {
-            if auth.is_some() {
-                return Err(code_error!(AccessDenied, "Access Denied"));
-            }
-            return Ok(());
+            return check_unsigned_request(ctx, service).await;
         }
     };
 
+    check_signed_headers(&authorization.signed_headers, &ctx.headers)?;
+
+    let amz_content_sha256 = extract_amz_content_sha256(&ctx.headers)?.ok_or_else(|| {
+        code_error!(
+            MissingSecurityHeader,
+            "Your request is missing a required header: x-amz-content-sha256"
+        )
+    })?;
+
+    if service.insecure_dev {
+        ctx.access_key_id = Some(authorization.credential.access_key_id.into());
+        if matches!(amz_content_sha256, AmzContentSha256::MultipleChunks) {
+            let body = take_io_body(&mut ctx.body);
+            ctx.body = Body::wrap_stream(AwsChunkedStream::new_unverified(body));
+        }
+        return Ok(());
+    }
+
     let auth_provider =
         auth.ok_or_else(|| not_supported!("The service has no authentication provider."))?;
 
-    let amz_content_sha256 = extract_amz_content_sha256(&ctx.headers)?
-        .ok_or_else(|| invalid_request!("Missing header: x-amz-content-sha256"))?;
-
     let secret_key =
         fetch_secret_key(auth_provider, authorization.credential.access_key_id).await?;
 
     let amz_date = extract_amz_date(&ctx.headers)?
         .ok_or_else(|| invalid_request!("Missing header: x-amz-date"))?;
+    let valid_until = check_request_time(service, &amz_date)?;
 
-    let is_stream = matches!(amz_content_sha256, AmzContentSha256::MultipleChunks);
+    let signing_key = service.signing_keys.get_or_derive(
+        &secret_key,
+        &amz_date,
+        authorization.credential.aws_region,
+    );
 
-    let signature = {
+    let (canonical_request, string_to_sign) = {
         let method = ctx.req.method();
-        let uri_path = ctx.req.uri().path();
+        let uri_path = ctx.uri_path;
         let query_strings: &[(String, String)] =
             ctx.query_strings.as_ref().map_or(&[], AsRef::as_ref);
 
@@ -483,64 +2026,61 @@ async fn check_header_auth(
             .headers
             .map_signed_headers(&authorization.signed_headers);
 
-        let canonical_request = if is_stream {
-            signature_v4::create_canonical_request(
-                method,
-                uri_path,
-                query_strings,
-                &headers,
-                signature_v4::Payload::MultipleChunks,
-            )
-        } else {
-            let bytes = std::mem::take(&mut ctx.body)
-                .apply(hyper::body::to_bytes)
-                .await
-                .map_err(|err| invalid_request!("Can not obtain the whole request body.", err))?;
-
-            let payload = if matches!(amz_content_sha256, AmzContentSha256::UnsignedPayload) {
-                signature_v4::Payload::Unsigned
-            } else if bytes.is_empty() {
-                signature_v4::Payload::Empty
-            } else {
-                signature_v4::Payload::SingleChunk(&bytes)
-            };
-
-            let ans = signature_v4::create_canonical_request(
-                method,
-                uri_path,
-                query_strings,
-                &headers,
-                payload,
-            );
-
-            ctx.body = Body::from(bytes);
+        // the payload is verified against the declared checksum while streaming
+        let payload = signed_payload(&amz_content_sha256);
 
-            ans
-        };
+        let canonical_request = signature_v4::create_canonical_request(
+            method,
+            uri_path,
+            query_strings,
+            &headers,
+            payload,
+        );
 
         let region = authorization.credential.aws_region;
         let string_to_sign =
             signature_v4::create_string_to_sign(&canonical_request, &amz_date, region);
-
-        signature_v4::calculate_signature(&string_to_sign, &secret_key, &amz_date, region)
+        (canonical_request, string_to_sign)
     };
 
-    if signature != authorization.signature {
-        return Err(signature_mismatch!());
-    }
-
-    if is_stream {
-        let body = take_io_body(&mut ctx.body);
+    let signature = signature_v4::calculate_signature_with_key(&string_to_sign, &signing_key);
 
-        let chunked_stream = AwsChunkedStream::new(
-            body,
-            signature.into(),
-            amz_date,
-            authorization.credential.aws_region.into(),
-            secret_key.into(),
-        );
+    if !crypto::constant_time_eq(signature.as_bytes(), authorization.signature.as_bytes()) {
+        return Err(SignatureMismatch::report(
+            service,
+            canonical_request,
+            string_to_sign,
+        ));
+    }
+    check_replay(
+        service,
+        ctx.req.method(),
+        false,
+        authorization.signature,
+        valid_until,
+    )?;
+    ctx.access_key_id = Some(authorization.credential.access_key_id.into());
+
+    match amz_content_sha256 {
+        AmzContentSha256::MultipleChunks => {
+            let body = take_io_body(&mut ctx.body);
+
+            let chunked_stream = AwsChunkedStream::new(
+                body,
+                signature.into(),
+                amz_date,
+                authorization.credential.aws_region.into(),
+                signing_key,
+            );
 
-        ctx.body = Body::wrap_stream(chunked_stream);
+            ctx.body = Body::wrap_stream(chunked_stream);
+        }
+        AmzContentSha256::SingleChunk { payload_checksum } => {
+            let body = take_io_body(&mut ctx.body);
+            let checked_stream = ContentSha256Stream::new(body, payload_checksum.into());
+            ctx.body = Body::wrap_stream(checked_stream);
+        }
+        AmzContentSha256::UnsignedPayload => {}
     }
 
     Ok(())
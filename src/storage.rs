@@ -1,22 +1,33 @@
 //! Trait representing the capabilities of the Amazon S3 API at server side
 
-use crate::errors::S3StorageResult;
+use crate::errors::{S3ErrorCode, S3Result, S3StorageError, S3StorageResult};
 
 use crate::dto::{
-    CompleteMultipartUploadError, CompleteMultipartUploadOutput, CompleteMultipartUploadRequest,
-    CopyObjectError, CopyObjectOutput, CopyObjectRequest, CreateBucketError, CreateBucketOutput,
+    AppendObjectRequest, BucketStatsOutput, BucketStatsRequest, CompleteMultipartUploadError,
+    CompleteMultipartUploadOutput, CompleteMultipartUploadRequest, CopyObjectError,
+    CopyObjectOutput, CopyObjectRequest, CreateBucketError, CreateBucketOutput,
     CreateBucketRequest, CreateMultipartUploadError, CreateMultipartUploadOutput,
     CreateMultipartUploadRequest, DeleteBucketError, DeleteBucketOutput, DeleteBucketRequest,
     DeleteObjectError, DeleteObjectOutput, DeleteObjectRequest, DeleteObjectsError,
-    DeleteObjectsOutput, DeleteObjectsRequest, GetBucketLocationError, GetBucketLocationOutput,
-    GetBucketLocationRequest, GetObjectError, GetObjectOutput, GetObjectRequest, HeadBucketError,
-    HeadBucketOutput, HeadBucketRequest, HeadObjectError, HeadObjectOutput, HeadObjectRequest,
-    ListBucketsError, ListBucketsOutput, ListBucketsRequest, ListObjectsError, ListObjectsOutput,
-    ListObjectsRequest, ListObjectsV2Error, ListObjectsV2Output, ListObjectsV2Request,
-    PutObjectError, PutObjectOutput, PutObjectRequest, UploadPartError, UploadPartOutput,
-    UploadPartRequest,
+    DeleteObjectsOutput, DeleteObjectsRequest, GetBucketAccelerateConfigurationError,
+    GetBucketAccelerateConfigurationOutput, GetBucketAccelerateConfigurationRequest,
+    GetBucketLocationError, GetBucketLocationOutput, GetBucketLocationRequest,
+    GetBucketRequestPaymentError, GetBucketRequestPaymentOutput, GetBucketRequestPaymentRequest,
+    GetObjectError, GetObjectOutput, GetObjectRequest, HeadBucketError, HeadBucketOutput,
+    HeadBucketRequest, HeadObjectError, HeadObjectOutput, HeadObjectRequest, HeadObjectsOutput,
+    HeadObjectsRequest, ListBucketsError, ListBucketsOutput, ListBucketsRequest, ListObjectsError,
+    ListObjectsOutput, ListObjectsRequest, ListObjectsV2Error, ListObjectsV2Output,
+    ListObjectsV2Request, PutBucketAccelerateConfigurationError,
+    PutBucketAccelerateConfigurationOutput, PutBucketAccelerateConfigurationRequest,
+    PutBucketRequestPaymentError, PutBucketRequestPaymentOutput, PutBucketRequestPaymentRequest,
+    PutObjectError, PutObjectOutput, PutObjectRequest, RenameBucketOutput, RenameBucketRequest,
+    RenameObjectOutput, RenameObjectRequest, UndeleteObjectOutput, UndeleteObjectRequest,
+    UploadPartError, UploadPartOutput, UploadPartRequest,
 };
 
+#[cfg(feature = "extensions")]
+use crate::dto::FilteredListObjectsV2Request;
+
 use async_trait::async_trait;
 
 /// Trait representing the capabilities of the Amazon S3 API at server side.
@@ -24,18 +35,72 @@ use async_trait::async_trait;
 /// See <https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations_Amazon_Simple_Storage_Service.html>
 #[async_trait]
 pub trait S3Storage {
+    /// Appends data to an existing object at the declared offset.
+    ///
+    /// See [PutObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObject.html) with `x-amz-write-offset-bytes`.
+    ///
+    /// Appending is opt-in. The default implementation returns `NotImplemented`.
+    async fn append_object(
+        &self,
+        input: AppendObjectRequest,
+    ) -> S3StorageResult<PutObjectOutput, PutObjectError> {
+        drop(input);
+        Err(code_error!(NotImplemented, "Appending to objects is not supported.").into())
+    }
+
+    /// Returns the number of objects and the total size of a bucket.
+    ///
+    /// It is not an S3 operation. The service exposes it through [`AdminService`](crate::AdminService).
+    ///
+    /// The default implementation returns `NotImplemented`.
+    async fn bucket_stats(
+        &self,
+        input: BucketStatsRequest,
+    ) -> S3StorageResult<BucketStatsOutput, HeadBucketError> {
+        drop(input);
+        Err(code_error!(NotImplemented, "Bucket statistics are not supported.").into())
+    }
+
     /// See [CompleteMultipartUpload](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CompleteMultipartUpload.html)
     async fn complete_multipart_upload(
         &self,
         input: CompleteMultipartUploadRequest,
     ) -> S3StorageResult<CompleteMultipartUploadOutput, CompleteMultipartUploadError>;
 
+    /// Completes a multipart upload only if the object does not exist.
+    ///
+    /// See [CompleteMultipartUpload](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CompleteMultipartUpload.html) with `If-None-Match: *`.
+    ///
+    /// It fails with `PreconditionFailed` if the object exists when the upload is committed.
+    /// The default implementation checks the object by [`S3Storage::stat_object`] before completing,
+    /// so a concurrent write between the check and the commit is overwritten.
+    async fn complete_multipart_upload_if_absent(
+        &self,
+        input: CompleteMultipartUploadRequest,
+    ) -> S3StorageResult<CompleteMultipartUploadOutput, CompleteMultipartUploadError> {
+        check_absent(self, &input.bucket, &input.key).await?;
+        self.complete_multipart_upload(input).await
+    }
+
     /// See [CopyObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CopyObject.html)
     async fn copy_object(
         &self,
         input: CopyObjectRequest,
     ) -> S3StorageResult<CopyObjectOutput, CopyObjectError>;
 
+    /// Copies an object only if the destination does not exist.
+    ///
+    /// It fails with `PreconditionFailed` if the destination exists when the copy is committed.
+    /// The default implementation checks the destination by [`S3Storage::stat_object`] before copying,
+    /// so a concurrent write between the check and the commit is overwritten.
+    async fn copy_object_if_absent(
+        &self,
+        input: CopyObjectRequest,
+    ) -> S3StorageResult<CopyObjectOutput, CopyObjectError> {
+        check_absent(self, &input.bucket, &input.key).await?;
+        self.copy_object(input).await
+    }
+
     /// See [CreateMultipartUpload](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CreateMultipartUpload.html)
     async fn create_multipart_upload(
         &self,
@@ -66,12 +131,39 @@ pub trait S3Storage {
         input: DeleteObjectsRequest,
     ) -> S3StorageResult<DeleteObjectsOutput, DeleteObjectsError>;
 
+    /// See [GetBucketAccelerateConfiguration](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketAccelerateConfiguration.html)
+    ///
+    /// Acceleration has no effect on this server. The default implementation returns an empty configuration.
+    async fn get_bucket_accelerate_configuration(
+        &self,
+        input: GetBucketAccelerateConfigurationRequest,
+    ) -> S3StorageResult<
+        GetBucketAccelerateConfigurationOutput,
+        GetBucketAccelerateConfigurationError,
+    > {
+        drop(input);
+        Ok(GetBucketAccelerateConfigurationOutput { status: None })
+    }
+
     /// See [GetBucketLocation](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketLocation.html)
     async fn get_bucket_location(
         &self,
         input: GetBucketLocationRequest,
     ) -> S3StorageResult<GetBucketLocationOutput, GetBucketLocationError>;
 
+    /// See [GetBucketRequestPayment](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetBucketRequestPayment.html)
+    ///
+    /// The default implementation returns `BucketOwner`.
+    async fn get_bucket_request_payment(
+        &self,
+        input: GetBucketRequestPaymentRequest,
+    ) -> S3StorageResult<GetBucketRequestPaymentOutput, GetBucketRequestPaymentError> {
+        drop(input);
+        Ok(GetBucketRequestPaymentOutput {
+            payer: Some("BucketOwner".into()),
+        })
+    }
+
     /// See [GetObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObject.html)
     async fn get_object(
         &self,
@@ -90,6 +182,28 @@ pub trait S3Storage {
         input: HeadObjectRequest,
     ) -> S3StorageResult<HeadObjectOutput, HeadObjectError>;
 
+    /// Returns the attributes of many objects of a bucket, as [`S3Storage::stat_object`] does.
+    ///
+    /// It is not an S3 operation. The existence checks of `DeleteObjects` use it,
+    /// so that a storage may look up the objects in a batch or concurrently.
+    ///
+    /// The default implementation calls [`S3Storage::stat_object`] for each key in sequence.
+    async fn head_objects(
+        &self,
+        input: HeadObjectsRequest,
+    ) -> S3StorageResult<HeadObjectsOutput, HeadBucketError> {
+        let mut objects = Vec::with_capacity(input.keys.len());
+        for key in input.keys {
+            let head = HeadObjectRequest {
+                bucket: input.bucket.clone(),
+                key,
+                ..HeadObjectRequest::default()
+            };
+            objects.push(stat_or_missing(self.stat_object(head).await)?);
+        }
+        Ok(HeadObjectsOutput { objects })
+    }
+
     /// See [ListBuckets](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListBuckets.html)
     async fn list_buckets(
         &self,
@@ -108,15 +222,171 @@ pub trait S3Storage {
         input: ListObjectsV2Request,
     ) -> S3StorageResult<ListObjectsV2Output, ListObjectsV2Error>;
 
+    /// Lists the objects which pass vendor-specific filters.
+    ///
+    /// It is a vendor extension of `ListObjectsV2`, which is only available with the feature `extensions`.
+    ///
+    /// The default implementation filters the output of `list_objects_v2`.
+    /// A storage should evaluate the filters while traversing, to avoid building huge listings.
+    #[cfg(feature = "extensions")]
+    async fn list_objects_v2_filtered(
+        &self,
+        input: FilteredListObjectsV2Request,
+    ) -> S3StorageResult<ListObjectsV2Output, ListObjectsV2Error> {
+        let filter = input.filter;
+        let mut output = self.list_objects_v2(input.input).await?;
+        if let Some(ref mut contents) = output.contents {
+            contents.retain(|object| {
+                let last_modified = object
+                    .last_modified
+                    .as_deref()
+                    .and_then(|s| crate::utils::time::parse_rfc3339(s).ok());
+                let size = object.size.and_then(|s| u64::try_from(s).ok());
+                filter.is_match(
+                    last_modified.unwrap_or(std::time::UNIX_EPOCH),
+                    size.unwrap_or(0),
+                )
+            });
+            let prefix_count = output.common_prefixes.as_ref().map_or(0, Vec::len);
+            output.key_count = i64::try_from(contents.len().saturating_add(prefix_count)).ok();
+        }
+        Ok(output)
+    }
+
+    /// See [PutBucketAccelerateConfiguration](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketAccelerateConfiguration.html)
+    ///
+    /// The status is only recorded. The default implementation returns `NotImplemented`.
+    async fn put_bucket_accelerate_configuration(
+        &self,
+        input: PutBucketAccelerateConfigurationRequest,
+    ) -> S3StorageResult<
+        PutBucketAccelerateConfigurationOutput,
+        PutBucketAccelerateConfigurationError,
+    > {
+        drop(input);
+        Err(code_error!(NotImplemented, "Transfer acceleration is not supported.").into())
+    }
+
+    /// See [PutBucketRequestPayment](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutBucketRequestPayment.html)
+    ///
+    /// The payer is only recorded. The default implementation returns `NotImplemented`.
+    async fn put_bucket_request_payment(
+        &self,
+        input: PutBucketRequestPaymentRequest,
+    ) -> S3StorageResult<PutBucketRequestPaymentOutput, PutBucketRequestPaymentError> {
+        drop(input);
+        Err(code_error!(NotImplemented, "Requester pays is not supported.").into())
+    }
+
     /// See [PutObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObject.html)
     async fn put_object(
         &self,
         input: PutObjectRequest,
     ) -> S3StorageResult<PutObjectOutput, PutObjectError>;
 
+    /// Writes an object only if it does not exist.
+    ///
+    /// See [PutObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObject.html) with `If-None-Match: *`.
+    ///
+    /// It fails with `PreconditionFailed` if the object exists when the write is committed.
+    /// The default implementation checks the object by [`S3Storage::stat_object`] before writing,
+    /// so a concurrent write between the check and the commit is overwritten.
+    async fn put_object_if_absent(
+        &self,
+        input: PutObjectRequest,
+    ) -> S3StorageResult<PutObjectOutput, PutObjectError> {
+        check_absent(self, &input.bucket, &input.key).await?;
+        self.put_object(input).await
+    }
+
+    /// Renames a bucket on the server side.
+    ///
+    /// It is not an S3 operation. The service exposes it as `POST /{bucket}?rename={new_bucket}`.
+    ///
+    /// The default implementation returns `NotImplemented`.
+    async fn rename_bucket(
+        &self,
+        input: RenameBucketRequest,
+    ) -> S3StorageResult<RenameBucketOutput, CreateBucketError> {
+        drop(input);
+        Err(code_error!(NotImplemented, "Renaming buckets is not supported.").into())
+    }
+
+    /// Renames an object, or all objects under a prefix, on the server side.
+    ///
+    /// It is not an S3 operation. The service exposes it as `POST /{bucket}/{key}?rename={new_key}`.
+    ///
+    /// The default implementation returns `NotImplemented`.
+    async fn rename_object(
+        &self,
+        input: RenameObjectRequest,
+    ) -> S3StorageResult<RenameObjectOutput, CopyObjectError> {
+        drop(input);
+        Err(code_error!(NotImplemented, "Renaming objects is not supported.").into())
+    }
+
+    /// Returns the attributes of an object without opening its content.
+    ///
+    /// It is not an S3 operation. `HeadObject` and the existence checks of other operations use it,
+    /// so that they never read object files. The `ETag` may be omitted if it is unknown
+    /// without reading the content, then `HeadObject` falls back to [`S3Storage::head_object`].
+    ///
+    /// The default implementation calls [`S3Storage::head_object`].
+    async fn stat_object(
+        &self,
+        input: HeadObjectRequest,
+    ) -> S3StorageResult<HeadObjectOutput, HeadObjectError> {
+        self.head_object(input).await
+    }
+
+    /// Restores the latest deleted version of an object from the trash.
+    ///
+    /// It is not an S3 operation. The service exposes it as `POST /{bucket}/{key}?undelete`.
+    ///
+    /// The default implementation returns `NotImplemented`.
+    async fn undelete_object(
+        &self,
+        input: UndeleteObjectRequest,
+    ) -> S3StorageResult<UndeleteObjectOutput, GetObjectError> {
+        drop(input);
+        Err(code_error!(NotImplemented, "Undeleting objects is not supported.").into())
+    }
+
     /// See [UploadPart](https://docs.aws.amazon.com/AmazonS3/latest/API/API_UploadPart.html)
     async fn upload_part(
         &self,
         input: UploadPartRequest,
     ) -> S3StorageResult<UploadPartOutput, UploadPartError>;
 }
+
+/// maps the result of [`S3Storage::stat_object`] to `None` if the object does not exist
+pub fn stat_or_missing(
+    ret: S3StorageResult<HeadObjectOutput, HeadObjectError>,
+) -> S3Result<Option<HeadObjectOutput>> {
+    match ret {
+        Ok(output) => Ok(Some(output)),
+        Err(S3StorageError::Operation(HeadObjectError::NoSuchKey(_))) => Ok(None),
+        Err(S3StorageError::Other(err)) if matches!(err.code(), S3ErrorCode::NoSuchKey) => Ok(None),
+        Err(S3StorageError::Other(err)) => Err(err),
+    }
+}
+
+/// fails with `PreconditionFailed` if an object exists
+async fn check_absent<S, E>(storage: &S, bucket: &str, key: &str) -> S3StorageResult<(), E>
+where
+    S: S3Storage + Sync + ?Sized,
+{
+    let input = HeadObjectRequest {
+        bucket: bucket.into(),
+        key: key.into(),
+        ..HeadObjectRequest::default()
+    };
+    if stat_or_missing(storage.stat_object(input).await)?.is_some() {
+        let err = code_error!(
+            PreconditionFailed,
+            "At least one of the pre-conditions you specified did not hold"
+        );
+        return Err(err.into());
+    }
+    Ok(())
+}
@@ -1,857 +1,4639 @@
 //! fs implementation
 
+mod bucket_config;
+mod builder;
+mod file_body;
+mod inventory;
+mod key_locks;
+mod listing;
+mod partial_write;
+mod recover;
+mod rename;
+mod rt;
+mod scrub;
+mod trash;
+mod validators;
+mod walk;
+
+#[cfg(feature = "listing-index")]
+mod index;
+
+#[cfg(feature = "pread")]
+mod pread;
+
+#[cfg(all(feature = "rt-uring", target_os = "linux"))]
+mod uring;
+
+#[cfg(all(feature = "xattr", unix))]
+mod xattrs;
+
+pub use self::builder::FileSystemBuilder;
+pub use self::inventory::{Inventory, InventoryConfig};
+pub use self::recover::RecoveryReport;
+pub use self::scrub::{CorruptedObject, ScrubReport};
+
+use self::key_locks::KeyLocks;
+use self::listing::{PageParams, MAX_KEYS};
+use self::partial_write::PartialWrite;
+use self::validators::Validators;
+
 use crate::async_trait;
+use crate::clock::{Clock, SystemClock};
 use crate::data_structures::BytesStream;
 use crate::dto::{
-    Bucket, CompleteMultipartUploadError, CompleteMultipartUploadOutput,
-    CompleteMultipartUploadRequest, CopyObjectError, CopyObjectOutput, CopyObjectRequest,
-    CopyObjectResult, CreateBucketError, CreateBucketOutput, CreateBucketRequest,
-    CreateMultipartUploadError, CreateMultipartUploadOutput, CreateMultipartUploadRequest,
-    DeleteBucketError, DeleteBucketOutput, DeleteBucketRequest, DeleteObjectError,
-    DeleteObjectOutput, DeleteObjectRequest, DeleteObjectsError, DeleteObjectsOutput,
-    DeleteObjectsRequest, DeletedObject, GetBucketLocationError, GetBucketLocationOutput,
-    GetBucketLocationRequest, GetObjectError, GetObjectOutput, GetObjectRequest, HeadBucketError,
-    HeadBucketOutput, HeadBucketRequest, HeadObjectError, HeadObjectOutput, HeadObjectRequest,
-    ListBucketsError, ListBucketsOutput, ListBucketsRequest, ListObjectsError, ListObjectsOutput,
-    ListObjectsRequest, ListObjectsV2Error, ListObjectsV2Output, ListObjectsV2Request, Object,
-    PutObjectError, PutObjectOutput, PutObjectRequest, UploadPartError, UploadPartOutput,
-    UploadPartRequest,
+    self, AppendObjectRequest, Bucket, BucketStatsOutput, BucketStatsRequest, CommonPrefix,
+    CompleteMultipartUploadError, CompleteMultipartUploadOutput, CompleteMultipartUploadRequest,
+    CopyObjectError, CopyObjectOutput, CopyObjectRequest, CopyObjectResult, CreateBucketError,
+    CreateBucketOutput, CreateBucketRequest, CreateMultipartUploadError,
+    CreateMultipartUploadOutput, CreateMultipartUploadRequest, DeleteBucketError,
+    DeleteBucketOutput, DeleteBucketRequest, DeleteObjectError, DeleteObjectOutput,
+    DeleteObjectRequest, DeleteObjectsError, DeleteObjectsOutput, DeleteObjectsRequest,
+    DeletedObject, GetBucketAccelerateConfigurationError, GetBucketAccelerateConfigurationOutput,
+    GetBucketAccelerateConfigurationRequest, GetBucketLocationError, GetBucketLocationOutput,
+    GetBucketLocationRequest, GetBucketRequestPaymentError, GetBucketRequestPaymentOutput,
+    GetBucketRequestPaymentRequest, GetObjectError, GetObjectOutput, GetObjectRequest,
+    HeadBucketError, HeadBucketOutput, HeadBucketRequest, HeadObjectError, HeadObjectOutput,
+    HeadObjectRequest, HeadObjectsOutput, HeadObjectsRequest, ListBucketsError, ListBucketsOutput,
+    ListBucketsRequest, ListObjectsError, ListObjectsOutput, ListObjectsRequest,
+    ListObjectsV2Error, ListObjectsV2Output, ListObjectsV2Request, Object,
+    PutBucketAccelerateConfigurationError, PutBucketAccelerateConfigurationOutput,
+    PutBucketAccelerateConfigurationRequest, PutBucketRequestPaymentError,
+    PutBucketRequestPaymentOutput, PutBucketRequestPaymentRequest, PutObjectError, PutObjectOutput,
+    PutObjectRequest, RenameBucketOutput, RenameBucketRequest, RenameObjectOutput,
+    RenameObjectRequest, UndeleteObjectOutput, UndeleteObjectRequest, UploadPartError,
+    UploadPartOutput, UploadPartRequest,
 };
-use crate::errors::{S3StorageError, S3StorageResult};
+use crate::errors::{S3ErrorCode, S3StorageError, S3StorageResult};
+
+#[cfg(feature = "extensions")]
+use crate::dto::FilteredListObjectsV2Request;
+
 use crate::headers::{AmzCopySource, Range};
+use crate::limits::ObjectLimits;
 use crate::path::S3Path;
-use crate::storage::S3Storage;
+use crate::storage::{stat_or_missing, S3Storage};
+use crate::streams::content_length_range_stream::ContentLengthRangeError;
+#[cfg(all(feature = "rt-uring", target_os = "linux"))]
+use crate::streams::content_length_range_stream::ContentLengthRangeStream;
+use crate::streams::content_sha256_stream::ContentSha256MismatchError;
+use crate::streams::gzip_stream::{GzipDecodeError, GzipStream};
+use crate::streams::tee_hash_stream::{Checksums, MultiHasher, TeeHashStream};
+use crate::utils::copy::StreamCopier;
 use crate::utils::{crypto, time, Apply};
 
 use std::collections::{HashMap, VecDeque};
 use std::convert::TryInto;
 use std::env;
+use std::fmt;
+use std::fs::Metadata;
 use std::io::{self, SeekFrom};
+use std::iter;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
 
-use futures::io::{AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufWriter};
+use futures::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter};
 use futures::stream::{Stream, StreamExt, TryStreamExt};
 use hyper::body::Bytes;
-use md5::{Digest, Md5};
 use path_absolutize::Absolutize;
-use tracing::{debug, error};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, trace};
 use uuid::Uuid;
 
-use async_fs::File;
-
 /// A S3 storage implementation based on file system
 #[derive(Debug)]
 pub struct FileSystem {
     /// root path
     root: PathBuf,
+    /// directory of the files of multipart uploads
+    temp_dir: PathBuf,
+    /// max number of concurrent removals in `DeleteObjects`
+    delete_concurrency: usize,
+    /// cached bucket stats and the time when they were computed
+    stats_cache: Mutex<HashMap<String, (Instant, BucketStatsOutput)>>,
+    /// how long bucket stats are cached
+    stats_ttl: Duration,
+    /// locks which serialize appends and scrub repairs with other writes of an object
+    key_locks: KeyLocks,
+    /// `io_uring` worker of object reads and writes
+    #[cfg(all(feature = "rt-uring", target_os = "linux"))]
+    uring: Option<uring::Uring>,
+    /// max size of positional reads
+    #[cfg(feature = "pread")]
+    pread_threshold: Option<usize>,
+    /// whether whole objects are served by file-backed bodies
+    file_body: bool,
+    /// I/O tuning knobs
+    config: FileSystemConfig,
+    /// how long deleted objects are kept in the trash, or `None` to delete them at once
+    trash_retention: Option<Duration>,
+    /// sorted keys of each bucket, which save the walks of `ListObjectsV2`
+    #[cfg(feature = "listing-index")]
+    listing_index: Option<index::ListingIndex>,
+    /// custom validators of storage classes, ACLs and metadata keys
+    validators: Validators,
+    /// source of the current time
+    clock: StorageClock,
 }
 
-impl FileSystem {
-    /// Constructs a file system storage located at `root`
-    /// # Errors
-    /// Returns an `Err` if current working directory is invalid or `root` doesn't exist
-    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
-        let root = env::current_dir()?.join(root).canonicalize()?;
-        Ok(Self { root })
-    }
+/// the clock of a storage
+struct StorageClock(Box<dyn Clock + Send + Sync + 'static>);
 
-    /// resolve object path under the virtual root
-    fn get_object_path(&self, bucket: &str, key: &str) -> io::Result<PathBuf> {
-        let dir = Path::new(&bucket);
-        let file_path = Path::new(&key);
-        let ans = dir
-            .join(&file_path)
-            .absolutize_virtually(&self.root)?
-            .into();
-        Ok(ans)
+impl fmt::Debug for StorageClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Clock").finish_non_exhaustive()
     }
+}
 
-    /// resolve bucket path under the virtual root
-    fn get_bucket_path(&self, bucket: &str) -> io::Result<PathBuf> {
-        let dir = Path::new(&bucket);
-        let ans = dir.absolutize_virtually(&self.root)?.into();
-        Ok(ans)
-    }
+/// I/O tuning knobs of [`FileSystem`]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct FileSystemConfig {
+    /// size of the chunks read from object files, 4 KiB by default
+    pub read_buf_size: usize,
+    /// size of the buffer coalescing writes to object files, 8 KiB by default
+    pub write_buf_size: usize,
+    /// when written object files are synced to the disk
+    pub fsync: FsyncPolicy,
+    /// size limits of uploads: parts are limited by `UploadPart`,
+    /// and the parts of a multipart upload are checked by `CompleteMultipartUpload`
+    pub limits: ObjectLimits,
+    /// how `PutObject` stores bodies uploaded with `Content-Encoding: gzip`
+    pub content_encoding: ContentEncodingPolicy,
+    /// where the metadata of objects is stored
+    pub metadata: MetadataBackend,
+    /// how the `ETag` of written objects is computed
+    pub etag: ETagStrategy,
+    /// directory of the files of multipart uploads, the root by default
+    ///
+    /// Parts on another device than the root are streamed into the object
+    /// by `CompleteMultipartUpload` instead of being concatenated in the kernel.
+    pub temp_dir: Option<PathBuf>,
+}
 
-    /// resolve metadata path under the virtual root (custom format)
-    fn get_metadata_path(&self, bucket: &str, key: &str) -> io::Result<PathBuf> {
-        let encode = |s: &str| base64_simd::URL_SAFE_NO_PAD.encode_to_string(s);
+/// When written object files are synced to the disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FsyncPolicy {
+    /// leave it to the OS, which may lose recent writes on power failure
+    Never,
+    /// sync an object file before responding to the request which writes it
+    Always,
+}
 
-        let file_path_str = format!(
-            ".bucket-{}.object-{}.metadata.json",
-            encode(bucket),
-            encode(key),
-        );
-        let file_path = Path::new(&file_path_str);
-        let ans = file_path.absolutize_virtually(&self.root)?.into();
-        Ok(ans)
-    }
+/// Where the metadata of objects is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MetadataBackend {
+    /// json files beside the buckets under the root (custom format)
+    Json,
+    /// extended attributes `user.s3.*` of object files, which are moved and removed
+    /// together with the files
+    ///
+    /// Only available on Unix with the feature `xattr`. Data which can not be stored in
+    /// an attribute, such as on file systems without extended attributes, falls back to json files.
+    #[cfg(all(feature = "xattr", unix))]
+    Xattr,
+}
 
-    /// load metadata from fs
-    async fn load_metadata(
-        &self,
-        bucket: &str,
-        key: &str,
-    ) -> io::Result<Option<HashMap<String, String>>> {
-        let path = self.get_metadata_path(bucket, key)?;
-        if path.exists() {
-            let content = async_fs::read(&path).await?;
-            let map = serde_json::from_slice(&content)
-                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-            Ok(Some(map))
-        } else {
-            Ok(None)
+/// How the `ETag` of written objects is computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ETagStrategy {
+    /// the hex md5 sum of the content, which is expected by most clients
+    Md5,
+    /// the hex BLAKE3 hash of the content prefixed with `blake3-`, such as `"blake3-af13..."`,
+    /// which is much faster to compute for large objects
+    ///
+    /// Only available with the feature `blake3`.
+    /// SDKs which verify the `ETag` of a single-part upload against the MD5 of the content
+    /// skip `ETag`s containing `-`, which look like those of multipart uploads.
+    /// However, tools which compare `ETag`s with local MD5 sums, such as sync tools,
+    /// see every object as modified. Changing the strategy changes the `ETag`s of existing objects.
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+/// prefix of the checksums computed by [`ETagStrategy::Blake3`]
+#[cfg(feature = "blake3")]
+const BLAKE3_CHECKSUM_PREFIX: &str = "blake3-";
+
+impl ETagStrategy {
+    /// returns the strategy which computed a recorded checksum
+    #[cfg_attr(
+        not(feature = "blake3"),
+        allow(clippy::missing_const_for_fn, unused_variables)
+    )]
+    fn of_checksum(checksum: &str) -> Self {
+        #[cfg(feature = "blake3")]
+        if checksum.starts_with(BLAKE3_CHECKSUM_PREFIX) {
+            return Self::Blake3;
         }
+        Self::Md5
     }
 
-    /// save metadata
-    async fn save_metadata(
-        &self,
-        bucket: &str,
-        key: &str,
-        metadata: &HashMap<String, String>,
-    ) -> io::Result<()> {
-        let path = self.get_metadata_path(bucket, key)?;
-        let content = serde_json::to_vec(metadata)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        async_fs::write(&path, &content).await
+    /// returns a hasher of the checksum
+    fn hasher(self) -> MultiHasher {
+        match self {
+            Self::Md5 => MultiHasher::new().with_md5(),
+            #[cfg(feature = "blake3")]
+            Self::Blake3 => MultiHasher::new().with_blake3(),
+        }
     }
 
-    /// get md5 sum
-    async fn get_md5_sum(&self, bucket: &str, key: &str) -> io::Result<String> {
-        let object_path = self.get_object_path(bucket, key)?;
-        let mut file = File::open(&object_path).await?;
-        let mut buf = vec![0; 4_usize.wrapping_mul(1024).wrapping_mul(1024)];
-        let mut md5_hash = Md5::new();
-        loop {
-            let nread = file.read(&mut buf).await?;
-            if nread == 0 {
-                break;
+    /// returns the checksum of the hashed content
+    fn checksum(self, checksums: Checksums) -> String {
+        match self {
+            Self::Md5 => checksums.md5.unwrap_or_default(),
+            #[cfg(feature = "blake3")]
+            Self::Blake3 => {
+                let hash = checksums.blake3.unwrap_or_default();
+                format!("{BLAKE3_CHECKSUM_PREFIX}{hash}")
             }
-            md5_hash.update(buf.get(..nread).unwrap_or_else(|| {
-                panic!(
-                    "nread is larger than buffer size: nread = {}, size = {}",
-                    nread,
-                    buf.len()
-                )
-            }));
         }
-        md5_hash.finalize().apply(crypto::to_hex_string).apply(Ok)
     }
 }
 
-/// copy bytes from a stream to a writer
-async fn copy_bytes<S, W>(mut stream: S, writer: &mut W) -> io::Result<usize>
-where
-    S: Stream<Item = io::Result<Bytes>> + Send + Unpin,
-    W: AsyncWrite + Send + Unpin,
-{
-    let mut nwrite: usize = 0;
-    while let Some(bytes) = stream.next().await {
-        let bytes = bytes?;
+impl FromStr for ETagStrategy {
+    type Err = io::Error;
 
-        let amt_u64 = futures::io::copy_buf(bytes.as_ref(), writer).await?;
-        let amt: usize = amt_u64.try_into().unwrap_or_else(|err| {
-            panic!(
-                "number overflow: u64 to usize, n = {}, err = {}",
-                amt_u64, err
-            )
-        });
+    /// Parses `md5` or `blake3`, failing for `blake3` without the feature `blake3`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "md5" => Ok(Self::Md5),
+            #[cfg(feature = "blake3")]
+            "blake3" => Ok(Self::Blake3),
+            #[cfg(not(feature = "blake3"))]
+            "blake3" => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the ETag strategy blake3 requires the feature blake3",
+            )),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unknown ETag strategy, expected md5 or blake3",
+            )),
+        }
+    }
+}
 
-        assert_eq!(
-            bytes.len(),
-            amt,
-            "amt mismatch: bytes.len() = {}, amt = {}, nwrite = {}",
-            bytes.len(),
-            amt,
-            nwrite
-        );
+/// How `PutObject` stores bodies uploaded with `Content-Encoding: gzip`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ContentEncodingPolicy {
+    /// store the body as-is without recording the encoding
+    Ignore,
+    /// store the compressed body and record the encoding with the compressed and logical sizes,
+    /// so `GetObject` and `HeadObject` return the stored entity with its `Content-Encoding`
+    Record,
+    /// decompress the body and store the decoded content, whose size and checksum are reported
+    Decode,
+}
 
-        nwrite = nwrite
-            .checked_add(amt)
-            .unwrap_or_else(|| panic!("nwrite overflow: amt = {}, nwrite = {}", amt, nwrite));
-    }
-    writer.flush().await?;
-    Ok(nwrite)
+/// the json files attached to a new object
+struct ObjectJsons<'a> {
+    /// checksum of the content
+    checksum: &'a str,
+    /// recorded content encoding
+    encoding: Option<&'a EncodingRecord>,
+    /// user metadata
+    metadata: Option<&'a HashMap<String, String>>,
+    /// part sizes of a multipart object
+    parts: Option<&'a [u64]>,
 }
 
-/// wrap operation error
-const fn operation_error<E>(e: E) -> S3StorageError<E> {
-    S3StorageError::Operation(e)
+/// The encoding recorded by [`ContentEncodingPolicy::Record`] (custom format)
+#[derive(Debug, Serialize, Deserialize)]
+struct EncodingRecord {
+    /// value of `Content-Encoding`
+    content_encoding: String,
+    /// size of the stored file
+    stored_size: u64,
+    /// size of the decoded content
+    logical_size: u64,
 }
 
-#[async_trait]
-impl S3Storage for FileSystem {
-    #[tracing::instrument]
-    async fn create_bucket(
-        &self,
-        input: CreateBucketRequest,
-    ) -> S3StorageResult<CreateBucketOutput, CreateBucketError> {
-        let path = trace_try!(self.get_bucket_path(&input.bucket));
+/// The manifest of the json files attached to an object (custom format)
+///
+/// The json files are named by a hash of the object, which the manifest maps back to the object.
+#[derive(Debug, Serialize, Deserialize)]
+struct ObjectManifest {
+    /// bucket of the object
+    bucket: String,
+    /// key of the object
+    key: String,
+}
 
-        if path.exists() {
-            let err = CreateBucketError::BucketAlreadyExists(String::from(
-                "The requested bucket name is not available. \
-                    The bucket namespace is shared by all users of the system. \
-                    Please select a different name and try again.",
-            ));
-            return Err(operation_error(err));
+/// The record of a multipart upload (custom format)
+///
+/// The record is written by `CreateMultipartUpload`, so that parts are accepted
+/// only for the uploads created by the server, with their buckets and keys.
+#[derive(Debug, Serialize, Deserialize)]
+struct UploadRecord {
+    /// key of the object
+    key: String,
+    /// metadata applied to the object by `CompleteMultipartUpload`
+    metadata: Option<HashMap<String, String>>,
+}
+
+/// The record of an uploaded part (custom format)
+///
+/// The records of the parts are the manifest of a multipart upload,
+/// which `CompleteMultipartUpload` checks before assembling the parts.
+#[derive(Debug, Serialize, Deserialize)]
+struct PartRecord {
+    /// size of the part
+    size: u64,
+    /// checksum of the part, which is its `ETag` without quotes
+    checksum: String,
+}
+
+impl Default for FileSystemConfig {
+    fn default() -> Self {
+        Self {
+            read_buf_size: 4096,
+            write_buf_size: 8 * 1024,
+            fsync: FsyncPolicy::Never,
+            limits: ObjectLimits::default(),
+            content_encoding: ContentEncodingPolicy::Ignore,
+            metadata: MetadataBackend::Json,
+            etag: ETagStrategy::Md5,
+            temp_dir: None,
         }
+    }
+}
 
-        trace_try!(async_fs::create_dir(&path).await);
+/// default max number of concurrent removals in `DeleteObjects`
+const DEFAULT_DELETE_CONCURRENCY: usize = 16;
 
-        let output = CreateBucketOutput::default(); // TODO: handle other fields
-        Ok(output)
+/// prefix of the directories of multipart uploads, one for each bucket in the temp dir
+const UPLOADS_PREFIX: &str = ".uploads-";
+
+/// prefix of the json files attached to objects, which are named by hashes of the objects
+const OBJECT_JSON_PREFIX: &str = ".object-";
+
+/// prefix of the temp files of writes, which are renamed into place on commit
+const WRITE_PREFIX: &str = ".write-";
+
+/// kinds of the json files attached to an object
+const OBJECT_JSON_KINDS: &[&str] = &["metadata", "parts", "checksum", "encoding"];
+
+/// max length of a file name, which limits each segment of a key
+const MAX_FILE_NAME_LEN: usize = 255;
+
+/// max number of concurrent stats in `head_objects`
+const HEAD_OBJECTS_CONCURRENCY: usize = 16;
+
+/// default time to live of cached bucket stats
+const DEFAULT_STATS_TTL: Duration = Duration::from_secs(60);
+
+impl FileSystem {
+    /// Constructs a file system storage located at `root`
+    /// # Errors
+    /// Returns an `Err` if current working directory is invalid or `root` doesn't exist
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new_with_config(root, FileSystemConfig::default())
     }
 
-    #[tracing::instrument]
-    async fn copy_object(
-        &self,
-        input: CopyObjectRequest,
-    ) -> S3StorageResult<CopyObjectOutput, CopyObjectError> {
-        let copy_source = AmzCopySource::from_header_str(&input.copy_source)
-            .map_err(|err| invalid_request!("Invalid header: x-amz-copy-source", err))?;
+    /// Returns a builder of a file system storage located at `root`
+    pub fn builder(root: impl Into<PathBuf>) -> FileSystemBuilder {
+        FileSystemBuilder::new(root)
+    }
 
-        let (bucket, key) = match copy_source {
-            AmzCopySource::AccessPoint { .. } => {
-                return Err(not_supported!("Access point is not supported yet.").into())
-            }
-            AmzCopySource::Bucket { bucket, key } => (bucket, key),
+    /// Constructs a file system storage located at `root` with I/O tuning knobs
+    ///
+    /// Larger buffers trade memory per request for throughput on fast networks and disks.
+    /// # Errors
+    /// Returns an `Err` if current working directory is invalid, `root` or the temp dir
+    /// doesn't exist, or a buffer size is zero
+    pub fn new_with_config(root: impl AsRef<Path>, config: FileSystemConfig) -> io::Result<Self> {
+        if config.read_buf_size == 0 || config.write_buf_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "buffer size must not be zero",
+            ));
+        }
+        let cwd = env::current_dir()?;
+        let root = cwd.join(root).canonicalize()?;
+        let temp_dir = match config.temp_dir {
+            Some(ref dir) => cwd.join(dir).canonicalize()?,
+            None => root.clone(),
         };
+        Ok(Self {
+            root,
+            temp_dir,
+            delete_concurrency: DEFAULT_DELETE_CONCURRENCY,
+            stats_cache: Mutex::new(HashMap::new()),
+            key_locks: KeyLocks::default(),
+            stats_ttl: DEFAULT_STATS_TTL,
+            #[cfg(all(feature = "rt-uring", target_os = "linux"))]
+            uring: None,
+            #[cfg(feature = "pread")]
+            pread_threshold: None,
+            file_body: false,
+            config,
+            trash_retention: None,
+            #[cfg(feature = "listing-index")]
+            listing_index: None,
+            validators: Validators::default(),
+            clock: StorageClock(Box::new(SystemClock)),
+        })
+    }
 
-        let src_path = trace_try!(self.get_object_path(bucket, key));
-        let dst_path = trace_try!(self.get_object_path(&input.bucket, &input.key));
+    /// Reads and writes object content with `io_uring`, which saves the thread pool round trips
+    /// of `GetObject` and `PutObject`
+    ///
+    /// Only available on Linux with the feature `rt-uring`.
+    /// # Errors
+    /// Returns an `Err` if `io_uring` is not available, in which case the storage keeps
+    /// using the default file I/O
+    #[cfg(all(feature = "rt-uring", target_os = "linux"))]
+    pub fn enable_io_uring(&mut self) -> io::Result<()> {
+        self.uring = Some(uring::Uring::start()?);
+        Ok(())
+    }
 
-        let file_metadata = trace_try!(async_fs::metadata(&src_path).await);
-        let last_modified = time::to_rfc3339(trace_try!(file_metadata.modified()));
+    /// Reads object content of at most `max_size` bytes by one positional read, or disables it if `None`
+    ///
+    /// Only available with the feature `pread`.
+    /// A single `pread` on the blocking thread pool saves the syscalls of the read loop
+    /// for many small and medium objects. It falls back to the read loop if the read fails,
+    /// such as when the object has been overwritten since it was opened.
+    ///
+    /// It takes the place of a memory map, whose accesses fault with `SIGBUS`
+    /// if the file is truncated concurrently, which can not be handled without unsafe code.
+    #[cfg(feature = "pread")]
+    pub fn set_pread_threshold(&mut self, max_size: Option<usize>) {
+        self.pread_threshold = max_size;
+    }
 
-        let _ = trace_try!(async_fs::copy(&src_path, &dst_path).await);
+    /// Serves whole objects by file-backed bodies, which is disabled by default
+    ///
+    /// The object file is read on the blocking thread pool in chunks of up to 1 MiB,
+    /// each read straight into the buffer handed to hyper.
+    /// Ranges, parts and objects stored with a `Content-Encoding` take the normal read path,
+    /// as does an object whose file can not be opened again.
+    ///
+    /// `sendfile` and `splice` are not used: hyper owns the socket and only takes bodies from user space.
+    pub fn set_file_body(&mut self, enabled: bool) {
+        self.file_body = enabled;
+    }
 
-        debug!(
-            from = %src_path.display(),
-            to = %dst_path.display(),
-            "CopyObject: copy file",
-        );
+    /// Keeps the sorted keys of each bucket in memory, so `ListObjectsV2` selects a page
+    /// without walking the whole bucket
+    ///
+    /// Only available with the feature `listing-index`. The first listing of a bucket walks it
+    /// and fills the index, which is then updated by the writes of this storage and by
+    /// file system notifications. Changes made by other processes are visible after
+    /// their notifications are delivered.
+    /// # Errors
+    /// Returns an `Err` if the root can not be watched
+    #[cfg(feature = "listing-index")]
+    pub fn enable_listing_index(&mut self) -> io::Result<()> {
+        self.listing_index = Some(index::ListingIndex::start(&self.root)?);
+        Ok(())
+    }
 
-        let src_metadata_path = trace_try!(self.get_metadata_path(bucket, key));
-        if src_metadata_path.exists() {
-            let dst_metadata_path = trace_try!(self.get_metadata_path(&input.bucket, &input.key));
-            let _ = trace_try!(async_fs::copy(src_metadata_path, dst_metadata_path).await);
+    /// updates the listing index after an object file is written or removed
+    #[cfg_attr(
+        not(feature = "listing-index"),
+        allow(clippy::unused_self, clippy::missing_const_for_fn, unused_variables)
+    )]
+    fn index_object(&self, path: &Path) {
+        #[cfg(feature = "listing-index")]
+        if let Some(ref index) = self.listing_index {
+            index.update(path);
         }
+    }
 
-        let md5_sum = trace_try!(self.get_md5_sum(bucket, key).await);
+    /// invalidates the listing index of a bucket after many object files are moved
+    #[cfg_attr(
+        not(feature = "listing-index"),
+        allow(clippy::unused_self, clippy::missing_const_for_fn, unused_variables)
+    )]
+    fn index_bucket(&self, bucket: &str) {
+        #[cfg(feature = "listing-index")]
+        if let Some(ref index) = self.listing_index {
+            index.invalidate(bucket);
+        }
+    }
 
-        let output = CopyObjectOutput {
-            copy_object_result: CopyObjectResult {
-                e_tag: Some(format!("\"{}\"", md5_sum)),
-                last_modified: Some(last_modified),
-            }
-            .apply(Some),
-            ..CopyObjectOutput::default()
-        };
+    /// Moves deleted objects into the trash of their bucket, or deletes them at once if `None`
+    ///
+    /// Trashed objects can be restored by `UndeleteObject` until they are removed
+    /// by [`FileSystem::purge_trash`] after the retention.
+    /// Checksums and part boundaries of trashed objects are not kept.
+    ///
+    /// While the trash is enabled, `DeleteBucket` rejects non-empty buckets with `BucketNotEmpty`,
+    /// and the trash of a deleted bucket is kept until it is purged.
+    pub fn set_trash_retention(&mut self, retention: Option<Duration>) {
+        self.trash_retention = retention;
+    }
 
-        Ok(output)
+    /// Sets which values of `x-amz-storage-class` are accepted by `PutObject`, `CopyObject`
+    /// and `CreateMultipartUpload`, instead of `STANDARD` and `REDUCED_REDUNDANCY`
+    ///
+    /// Objects are stored in the same way whatever their class is. The service has already
+    /// rejected the classes which are not defined by S3, so the validator only sees those.
+    /// Rejected values fail with `InvalidStorageClass`.
+    pub fn set_storage_class_validator<F>(&mut self, f: F)
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.validators.storage_class = Some(Box::new(f));
     }
 
-    #[tracing::instrument]
-    async fn delete_bucket(
-        &self,
-        input: DeleteBucketRequest,
-    ) -> S3StorageResult<DeleteBucketOutput, DeleteBucketError> {
-        let path = trace_try!(self.get_bucket_path(&input.bucket));
-        trace_try!(async_fs::remove_dir_all(path).await);
-        Ok(DeleteBucketOutput)
+    /// Sets which canned ACLs of `x-amz-acl` are accepted by `CreateBucket`, `PutObject`,
+    /// `CopyObject` and `CreateMultipartUpload`, instead of all of them
+    ///
+    /// ACLs are not enforced by this storage. Rejected values fail with `InvalidArgument`.
+    pub fn set_acl_validator<F>(&mut self, f: F)
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.validators.acl = Some(Box::new(f));
     }
 
-    #[tracing::instrument]
-    async fn delete_object(
-        &self,
-        input: DeleteObjectRequest,
-    ) -> S3StorageResult<DeleteObjectOutput, DeleteObjectError> {
-        let path = trace_try!(self.get_object_path(&input.bucket, &input.key));
-        if input.key.ends_with('/') {
-            let mut dir = trace_try!(async_fs::read_dir(&path).await);
-            let is_empty = dir.next().await.is_none();
-            if is_empty {
-                trace_try!(async_fs::remove_dir(&path).await);
-            }
-        } else {
-            trace_try!(async_fs::remove_file(path).await);
-        }
-        let output = DeleteObjectOutput::default(); // TODO: handle other fields
-        Ok(output)
+    /// Sets which keys of user-defined metadata are accepted by `PutObject`, `CopyObject`
+    /// and `CreateMultipartUpload`, instead of all of them
+    ///
+    /// The validator receives the keys without the `x-amz-meta-` prefix.
+    /// Rejected keys fail with `InvalidArgument`.
+    pub fn set_metadata_key_validator<F>(&mut self, f: F)
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.validators.metadata_key = Some(Box::new(f));
     }
 
-    #[tracing::instrument]
-    async fn delete_objects(
-        &self,
-        input: DeleteObjectsRequest,
-    ) -> S3StorageResult<DeleteObjectsOutput, DeleteObjectsError> {
-        let mut objects: Vec<(PathBuf, String)> = Vec::new();
-        for object in input.delete.objects {
-            let path = trace_try!(self.get_object_path(&input.bucket, &object.key));
-            if path.exists() {
-                objects.push((path, object.key));
-            }
+    /// Sets the time source of the storage, which is the system clock by default
+    ///
+    /// It stamps the deletions in the trash and the modification times of written objects,
+    /// which are their `LastModified`, and it is the current time of
+    /// [`FileSystem::purge_trash`] and [`FileSystem::recover`].
+    /// Modification times are only set on unix, elsewhere they are set by the OS.
+    pub fn set_clock<C>(&mut self, clock: C)
+    where
+        C: Clock + Send + Sync + 'static,
+    {
+        self.clock = StorageClock(Box::new(clock));
+    }
+
+    /// Permanently removes the objects which have been in the trash longer than the retention,
+    /// returns the number of removed deletions
+    /// # Errors
+    /// Returns an `Err` if the trash can not be read or removed
+    pub async fn purge_trash(&self) -> io::Result<u64> {
+        match self.trash_retention {
+            Some(retention) => trash::purge(self, self.clock.0.now(), retention).await,
+            None => Ok(0),
         }
+    }
 
-        let mut deleted: Vec<DeletedObject> = Vec::new();
-        for (path, key) in objects {
-            trace_try!(async_fs::remove_file(path).await);
-            deleted.push(DeletedObject {
-                key: Some(key),
-                ..DeletedObject::default()
-            });
+    /// writes a request body into a new file, returns the number of written bytes
+    ///
+    /// The body is limited to `max_object_size`, which also bounds decompressed bodies.
+    async fn write_file<S>(&self, path: &Path, stream: S) -> io::Result<u64>
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + Unpin,
+    {
+        let max_size = self.config.limits.max_object_size;
+        #[cfg(all(feature = "rt-uring", target_os = "linux"))]
+        if let Some(ref uring) = self.uring {
+            let stream = ContentLengthRangeStream::new(stream, 0, max_size);
+            let size = uring.write(path.to_owned(), stream).await?;
+            return Ok(size.try_into().unwrap_or(u64::MAX));
         }
-        let output = DeleteObjectsOutput {
-            deleted: Some(deleted),
-            ..DeleteObjectsOutput::default()
+        let file = rt::create(path).await?;
+        let mut writer = BufWriter::with_capacity(self.config.write_buf_size, file);
+        StreamCopier::new()
+            .with_max_size(max_size)
+            .with_progress(|size| trace!(path = %path.display(), size, "write file: progress"))
+            .copy(stream, &mut writer)
+            .await
+    }
+
+    /// returns a new temp file path, which can be renamed to `dst`
+    ///
+    /// Temp files are in the temp dir, or in the root if the temp dir is on another device.
+    async fn staging_path(&self, dst: &Path) -> io::Result<PathBuf> {
+        let dst_dir = dst.parent().unwrap_or(&self.root);
+        let dir = if is_same_device(&self.temp_dir, dst_dir).await? {
+            &self.temp_dir
+        } else {
+            &self.root
         };
-        Ok(output)
+        Ok(dir.join(format!("{WRITE_PREFIX}{}", Uuid::new_v4().simple())))
     }
 
-    #[tracing::instrument]
-    async fn get_bucket_location(
+    /// stages the json attached to an object, which is removed on commit if `value` is `None`
+    ///
+    /// With the xattr backend, the attribute is set on the staged object file.
+    #[cfg_attr(not(all(feature = "xattr", unix)), allow(unused_variables))]
+    async fn stage_object_json<T: Serialize + Sync + ?Sized>(
         &self,
-        input: GetBucketLocationRequest,
-    ) -> S3StorageResult<GetBucketLocationOutput, GetBucketLocationError> {
-        let path = trace_try!(self.get_bucket_path(&input.bucket));
-
-        if !path.exists() {
-            let err = code_error!(NoSuchBucket, "NotFound");
-            return Err(err.into());
+        write: &mut PartialWrite,
+        staged_object: &Path,
+        (bucket, key, kind): (&str, &str, &str),
+        value: Option<&T>,
+    ) -> io::Result<()> {
+        let json_path = self.get_object_json_path(bucket, key, kind)?;
+        if let Some(legacy_path) = self.get_legacy_object_json_path(bucket, key, kind)? {
+            write.remove_on_commit(legacy_path);
         }
-
-        let output = GetBucketLocationOutput {
-            location_constraint: None, // TODO: handle region
+        let value = if let Some(value) = value {
+            value
+        } else {
+            write.remove_on_commit(json_path);
+            return Ok(());
         };
+        let content =
+            serde_json::to_vec(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-        Ok(output)
+        #[cfg(all(feature = "xattr", unix))]
+        if self.config.metadata == MetadataBackend::Xattr
+            && xattrs::set(staged_object, kind, &content).await?
+        {
+            // a json file left by a fallback would shadow the attribute
+            write.remove_on_commit(json_path);
+            return Ok(());
+        }
+
+        self.save_object_manifest(bucket, key).await?;
+        let temp_path = self.staging_path(&json_path).await?;
+        rt::write(&temp_path, &content).await?;
+        write.add(temp_path, json_path);
+        Ok(())
     }
 
-    #[tracing::instrument]
-    async fn get_object(
+    /// stages all json files attached to a new object, replacing those of the previous one
+    async fn stage_object_jsons(
         &self,
-        input: GetObjectRequest,
-    ) -> S3StorageResult<GetObjectOutput, GetObjectError> {
-        let object_path = trace_try!(self.get_object_path(&input.bucket, &input.key));
+        write: &mut PartialWrite,
+        staged_object: &Path,
+        (bucket, key): (&str, &str),
+        jsons: ObjectJsons<'_>,
+    ) -> io::Result<()> {
+        let stage = |kind| (bucket, key, kind);
+        let ObjectJsons {
+            checksum,
+            encoding,
+            metadata,
+            parts,
+        } = jsons;
+        self.stage_object_json(write, staged_object, stage("checksum"), Some(checksum))
+            .await?;
+        self.stage_object_json(write, staged_object, stage("encoding"), encoding)
+            .await?;
+        self.stage_object_json(write, staged_object, stage("metadata"), metadata)
+            .await?;
+        self.stage_object_json(write, staged_object, stage("parts"), parts)
+            .await
+    }
 
-        let parse_range = |s: &str| {
-            Range::from_header_str(s).map_err(|err| invalid_request!("Invalid header: range", err))
-        };
-        let range: Option<Range> = input.range.as_deref().map(parse_range).transpose()?;
+    /// sets the modification time of a written object file to the time of the clock
+    async fn stamp_file(&self, path: &Path) -> io::Result<()> {
+        let (path, now) = (path.to_owned(), self.clock.0.now());
+        rt::unblock(move || set_modified(&path, now)).await
+    }
 
-        let mut file = match File::open(&object_path).await {
-            Ok(file) => file,
-            Err(e) => {
-                error!(error = %e, "GetObject: open file");
-                let err = code_error!(NoSuchKey, "The specified key does not exist.");
-                return Err(err.into());
+    /// syncs a written object file if required by the fsync policy
+    async fn sync_file(&self, path: &Path) -> io::Result<()> {
+        if self.config.fsync == FsyncPolicy::Always {
+            rt::sync_all(&rt::open(path).await?).await?;
+        }
+        Ok(())
+    }
+
+    /// returns a stream of `len` bytes of an object file from `offset`,
+    /// to which the opened `file` has been positioned
+    #[cfg_attr(
+        not(any(feature = "pread", all(feature = "rt-uring", target_os = "linux"))),
+        allow(unused_variables)
+    )]
+    #[cfg_attr(not(feature = "pread"), allow(clippy::unused_async))]
+    async fn object_stream(
+        &self,
+        file: rt::File,
+        path: &Path,
+        offset: u64,
+        len: usize,
+    ) -> dto::ByteStream {
+        #[cfg(feature = "pread")]
+        if self.pread_threshold.map_or(false, |max| len <= max) {
+            let read = match rt::file_metadata(&file).await {
+                Ok(opened) => pread::read(path.to_owned(), opened, offset, len).await,
+                Err(e) => Err(e),
+            };
+            match read {
+                Ok(bytes) => {
+                    return dto::ByteStream::new(futures::stream::once(async { Ok(bytes) }))
+                }
+                Err(e) => debug!(path = %path.display(), error = %e, "failed to pread file"),
             }
-        };
+        }
+        #[cfg(all(feature = "rt-uring", target_os = "linux"))]
+        if let Some(ref uring) = self.uring {
+            drop(file);
+            return dto::ByteStream::new(uring.read(path.to_owned(), offset, len));
+        }
+        dto::ByteStream::new(BytesStream::new(file, self.config.read_buf_size, Some(len)))
+    }
 
-        let file_metadata = trace_try!(file.metadata().await);
-        let last_modified = time::to_rfc3339(trace_try!(file_metadata.modified()));
+    /// Sets the max number of files removed concurrently by `DeleteObjects`
+    pub fn set_delete_concurrency(&mut self, n: NonZeroUsize) {
+        self.delete_concurrency = n.get();
+    }
 
-        let content_length = {
-            let file_len = file_metadata.len();
-            let content_len = match range {
-                None => file_len,
-                Some(Range::Normal { first, last }) => {
-                    if first >= file_len {
-                        let err =
-                            code_error!(InvalidRange, "The requested range cannot be satisfied.");
-                        return Err(err.into());
-                    }
-                    let _ = trace_try!(file.seek(SeekFrom::Start(first)).await);
+    /// Sets how long the results of `bucket_stats` are cached, 60 seconds by default
+    ///
+    /// Computing the stats walks the whole bucket.
+    pub fn set_stats_ttl(&mut self, ttl: Duration) {
+        self.stats_ttl = ttl;
+    }
 
-                    // HTTP byte range is inclusive
-                    //      len = last + 1 - first
-                    // or   len = file_len - first
+    /// returns the cached stats of a bucket unless they have expired
+    fn cached_stats(&self, bucket: &str) -> Option<BucketStatsOutput> {
+        let entry = self
+            .stats_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(bucket)
+            .copied();
+        let (time, stats) = entry?;
+        (time.elapsed() < self.stats_ttl).then_some(stats)
+    }
 
-                    last.and_then(|x| x.checked_add(1))
-                        .unwrap_or(file_len)
-                        .wrapping_sub(first)
-                }
-                Some(Range::Suffix { last }) => {
-                    let offset = Some(last)
-                        .filter(|&x| x <= file_len)
-                        .and_then(|x| i64::try_from(x).ok())
-                        .and_then(i64::checked_neg);
-
-                    if let Some(x) = offset {
-                        let _ = trace_try!(file.seek(SeekFrom::End(x)).await);
-                    } else {
-                        let err =
-                            code_error!(InvalidRange, "The requested range cannot be satisfied.");
-                        return Err(err.into());
-                    }
-                    last
-                }
-            };
-            trace_try!(usize::try_from(content_len))
-        };
+    /// caches the stats of a bucket, or removes them if `stats` is `None`
+    fn cache_stats(&self, bucket: &str, stats: Option<BucketStatsOutput>) {
+        let mut cache = self
+            .stats_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        match stats {
+            Some(stats) => drop(cache.insert(bucket.to_owned(), (Instant::now(), stats))),
+            None => drop(cache.remove(bucket)),
+        }
+    }
 
-        let stream = BytesStream::new(file, 4096, Some(content_length));
+    /// Recomputes the md5 sums of all objects and compares them with the recorded ones
+    ///
+    /// A corrupted object is restored from `replica`, a storage with the same layout,
+    /// if the replica holds an intact copy.
+    /// Each corrupted object emits a `WARN` event, and each failed repair emits an `ERROR` event.
+    /// Objects which were not written through the storage have no recorded checksum.
+    ///
+    /// It reads every object, so it should run in the background at a low frequency.
+    /// # Errors
+    /// Returns an `Err` if the buckets can not be walked
+    pub async fn scrub(&self, replica: Option<&Self>) -> io::Result<ScrubReport> {
+        scrub::run(self, replica).await
+    }
 
-        let object_metadata = trace_try!(self.load_metadata(&input.bucket, &input.key).await);
+    /// Removes the files left by crashes which are older than `max_age`
+    ///
+    /// The parts of a multipart upload are removed together when none of them has been
+    /// written within `max_age`, so recent uploads can still be completed.
+    /// Temporary files of writes and of [`FileSystem::scrub`] are removed as well,
+    /// and so are the parts left in the temp dir by older versions, which can not be completed.
+    /// The json files of objects which no longer exist are found by their manifests and removed,
+    /// unless the trash is enabled.
+    /// Objects are never touched: a partial object left by a crash in the middle of a write
+    /// is indistinguishable from a complete one.
+    ///
+    /// It should run at startup, with `max_age` longer than the slowest upload.
+    /// # Errors
+    /// Returns an `Err` if the root can not be read or a file can not be removed
+    pub async fn recover(&self, max_age: Duration) -> io::Result<RecoveryReport> {
+        recover::run(self, self.clock.0.now(), max_age).await
+    }
 
-        let (md5_sum, duration) = {
-            let (ret, duration) =
-                time::count_duration(self.get_md5_sum(&input.bucket, &input.key)).await;
-            let md5_sum = trace_try!(ret);
-            (md5_sum, duration)
+    /// Writes an inventory report of a bucket into the destination bucket, like S3 Inventory
+    ///
+    /// The report consists of a CSV data file at `{prefix}{source}/{id}/data/{uuid}.csv`
+    /// and a manifest at `{prefix}{source}/{id}/{YYYY-MM-DDTHH-MMZ}/manifest.json`.
+    /// Each record contains the bucket, key, size, last modified time, `ETag` and storage class.
+    /// # Errors
+    /// Returns an `Err` if the source bucket can not be walked
+    /// or the destination bucket does not exist
+    pub async fn write_inventory(
+        &self,
+        source_bucket: &str,
+        config: &InventoryConfig,
+    ) -> io::Result<Inventory> {
+        inventory::write(self, source_bucket, config).await
+    }
+
+    /// lists the objects of a bucket which satisfy `keep(last_modified, size)`
+    async fn list_objects_v2_where(
+        &self,
+        input: ListObjectsV2Request,
+        keep: impl Fn(SystemTime, u64) -> bool + Send + Sync,
+    ) -> S3StorageResult<ListObjectsV2Output, ListObjectsV2Error> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        // the continuation token takes precedence over `start-after`
+        let marker = match input.continuation_token {
+            Some(ref token) => {
+                let marker = listing::decode_continuation_token(token).ok_or_else(|| {
+                    code_error!(
+                        InvalidArgument,
+                        "The continuation token provided is incorrect."
+                    )
+                })?;
+                Some(marker)
+            }
+            None => input.start_after.clone(),
         };
 
-        debug!(
-            sum = ?md5_sum,
-            path = %object_path.display(),
-            size = ?content_length,
-            ?duration,
-            "GetObject: calculate md5 sum",
+        let max_keys = match input.max_keys {
+            None => MAX_KEYS,
+            Some(n) => trace_try!(usize::try_from(n)).min(MAX_KEYS),
+        };
+        let params = PageParams {
+            prefix: input.prefix.as_deref().unwrap_or(""),
+            delimiter: input.delimiter.as_deref(),
+            marker: marker.as_deref(),
+            max_keys,
+        };
+        let files = trace_try!(
+            self.listed_files(&input.bucket, &path, &params, &keep)
+                .await
         );
 
-        let output: GetObjectOutput = GetObjectOutput {
-            body: Some(crate::dto::ByteStream::new(stream)),
-            content_length: Some(trace_try!(content_length.try_into())),
-            last_modified: Some(last_modified),
-            metadata: object_metadata,
-            e_tag: Some(format!("\"{}\"", md5_sum)),
-            ..GetObjectOutput::default() // TODO: handle other fields
+        let mut objects = Vec::with_capacity(files.len());
+        for file in files {
+            let modified = trace_try!(file.metadata.modified());
+            let size = file.metadata.len();
+            if !keep(modified, size) {
+                continue;
+            }
+            objects.push(Object {
+                e_tag: None,
+                key: Some(file.key),
+                last_modified: Some(time::to_rfc3339(modified)),
+                owner: None,
+                size: Some(trace_try!(size.try_into())),
+                storage_class: None,
+            });
+        }
+
+        let mut page = listing::select_page(objects, |o| o.key.as_deref().unwrap_or(""), &params);
+        trace_try!(
+            self.fill_listed_e_tags(&input.bucket, &mut page.contents)
+                .await
+        );
+
+        let key_count = page.contents.len().wrapping_add(page.common_prefixes.len());
+        let common_prefixes = page
+            .common_prefixes
+            .into_iter()
+            .map(|common_prefix| CommonPrefix {
+                prefix: Some(common_prefix),
+            })
+            .collect::<Vec<_>>();
+        let next_continuation_token = page
+            .next_marker
+            .as_deref()
+            .map(listing::encode_continuation_token);
+
+        // TODO: handle other fields
+        let output = ListObjectsV2Output {
+            key_count: Some(trace_try!(key_count.try_into())),
+            contents: Some(page.contents),
+            delimiter: input.delimiter,
+            encoding_type: input.encoding_type,
+            name: Some(input.bucket),
+            common_prefixes: Some(common_prefixes).filter(|v| !v.is_empty()),
+            is_truncated: Some(page.is_truncated),
+            max_keys: Some(trace_try!(max_keys.try_into())),
+            prefix: input.prefix,
+            continuation_token: input.continuation_token,
+            next_continuation_token,
+            start_after: input.start_after,
         };
 
         Ok(output)
     }
 
-    #[tracing::instrument]
-    async fn head_bucket(
+    /// returns the object files of a listing, sorted by key
+    ///
+    /// With the listing index, only the files of the page and the one after it are returned,
+    /// otherwise all files under the prefix are walked.
+    #[cfg_attr(not(feature = "listing-index"), allow(unused_variables))]
+    async fn listed_files(
         &self,
-        input: HeadBucketRequest,
-    ) -> S3StorageResult<HeadBucketOutput, HeadBucketError> {
-        let path = trace_try!(self.get_bucket_path(&input.bucket));
+        bucket: &str,
+        bucket_path: &Path,
+        params: &PageParams<'_>,
+        keep: &(impl Fn(SystemTime, u64) -> bool + Sync),
+    ) -> io::Result<Vec<walk::ObjectFile>> {
+        #[cfg(feature = "listing-index")]
+        if let Some(ref index) = self.listing_index {
+            let keep_file = |file: &walk::ObjectFile| {
+                let modified = file.metadata.modified();
+                modified.map_or(false, |m| keep(m, file.metadata.len()))
+            };
+            let indexed = index::object_files(index, bucket_path, bucket, params, keep_file);
+            if let Some(files) = indexed.await? {
+                return Ok(files);
+            }
 
-        if !path.exists() {
-            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
-            return Err(err.into());
+            // a cold bucket is walked as a whole to fill the index
+            let version = index.version(bucket);
+            let files = walk::object_files(bucket_path).await?;
+            index.warm(bucket, version, files.iter().map(|file| file.key.clone()));
+            return Ok(files
+                .into_iter()
+                .filter(|file| file.key.starts_with(params.prefix))
+                .collect());
         }
-
-        Ok(HeadBucketOutput)
+        walk::object_files_with_prefix(bucket_path, params.prefix).await
     }
 
-    #[tracing::instrument]
-    async fn head_object(
+    /// concatenates part files into an object file by streaming their content,
+    /// which works across devices
+    async fn concat_parts_via_stream(
         &self,
-        input: HeadObjectRequest,
-    ) -> S3StorageResult<HeadObjectOutput, HeadObjectError> {
-        let path = trace_try!(self.get_object_path(&input.bucket, &input.key));
+        object_path: &Path,
+        part_paths: Vec<PathBuf>,
+    ) -> io::Result<()> {
+        let file = rt::create(object_path).await?;
+        let mut writer = BufWriter::with_capacity(self.config.write_buf_size, file);
 
-        if !path.exists() {
-            let err = code_error!(NoSuchKey, "The specified key does not exist.");
-            return Err(err.into());
+        for part_path in part_paths {
+            let mut reader = rt::open(&part_path).await?;
+            let (ret, duration) =
+                time::count_duration(futures::io::copy(&mut reader, &mut writer)).await;
+            let size = ret?;
+
+            debug!(
+                from = %part_path.display(),
+                to = %object_path.display(),
+                ?size,
+                ?duration,
+                "CompleteMultipartUpload: write file",
+            );
+            rt::remove_file(&part_path).await?;
         }
+        writer.flush().await
+    }
 
-        let file_metadata = trace_try!(async_fs::metadata(path).await);
-        let last_modified = time::to_rfc3339(trace_try!(file_metadata.modified()));
-        let size = file_metadata.len();
+    /// removes an object file, returns `false` if the object does not exist
+    async fn remove_object_file(&self, bucket: &str, key: &str) -> io::Result<bool> {
+        let path = self.get_object_path(bucket, key)?;
+        // a scrub never restores an object in the middle of its removal
+        let _lock = self.key_locks.lock(&path).await;
+        self.remove_part_sizes(bucket, key).await?;
+        self.remove_checksum(bucket, key).await?;
+        self.save_encoding(bucket, key, None).await?;
+        // trashed objects keep their metadata, so that they can be restored with it
+        if self.trash_retention.is_none() {
+            self.remove_metadata(bucket, key).await?;
+        }
+        let ret = if self.trash_retention.is_some() {
+            trash::move_to_trash(self, bucket, key, self.clock.0.now()).await
+        } else {
+            match rt::remove_file(&path).await {
+                Ok(()) => Ok(true),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+                Err(e) => Err(e),
+            }
+        };
+        self.index_object(&path);
+        ret
+    }
 
-        let object_metadata = trace_try!(self.load_metadata(&input.bucket, &input.key).await);
+    /// resolve object path under the virtual root
+    fn get_object_path(&self, bucket: &str, key: &str) -> io::Result<PathBuf> {
+        if !S3Path::is_safe_key(key) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "unsafe key"));
+        }
+        let dir = Path::new(&bucket);
+        let file_path = Path::new(&key);
+        let ans = dir.join(file_path).absolutize_virtually(&self.root)?.into();
+        Ok(ans)
+    }
 
-        let output: HeadObjectOutput = HeadObjectOutput {
-            content_length: Some(trace_try!(size.try_into())),
-            content_type: Some(mime::APPLICATION_OCTET_STREAM.as_ref().to_owned()), // TODO: handle content type
-            last_modified: Some(last_modified),
-            metadata: object_metadata,
-            ..HeadObjectOutput::default()
+    /// resolve bucket path under the virtual root
+    fn get_bucket_path(&self, bucket: &str) -> io::Result<PathBuf> {
+        let dir = Path::new(&bucket);
+        let ans = dir.absolutize_virtually(&self.root)?.into();
+        Ok(ans)
+    }
+
+    /// resolve the path of a json file attached to an object (custom format)
+    ///
+    /// The file is named by the sha256 of `{bucket}/{key}`, so that its name is short for any key.
+    /// The manifest of the hash maps it back to the object.
+    fn get_object_json_path(&self, bucket: &str, key: &str, kind: &str) -> io::Result<PathBuf> {
+        let hash = crypto::hex_sha256(format!("{bucket}/{key}").as_bytes());
+        let file_path_str = format!("{OBJECT_JSON_PREFIX}{hash}.{kind}.json");
+        let ans = Path::new(&file_path_str)
+            .absolutize_virtually(&self.root)?
+            .into();
+        Ok(ans)
+    }
+
+    /// resolve the path of the manifest of the json files attached to an object
+    fn get_object_manifest_path(&self, bucket: &str, key: &str) -> io::Result<PathBuf> {
+        let hash = crypto::hex_sha256(format!("{bucket}/{key}").as_bytes());
+        let file_path_str = format!("{OBJECT_JSON_PREFIX}{hash}.json");
+        let ans = Path::new(&file_path_str)
+            .absolutize_virtually(&self.root)?
+            .into();
+        Ok(ans)
+    }
+
+    /// save the manifest of the json files attached to an object if it does not exist
+    async fn save_object_manifest(&self, bucket: &str, key: &str) -> io::Result<()> {
+        let path = self.get_object_manifest_path(bucket, key)?;
+        if rt::metadata(&path).await.is_ok() {
+            return Ok(());
+        }
+        let manifest = ObjectManifest {
+            bucket: bucket.to_owned(),
+            key: key.to_owned(),
         };
-        Ok(output)
+        let content = serde_json::to_vec(&manifest)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        rt::write(&path, &content).await
     }
 
-    #[tracing::instrument]
-    async fn list_buckets(
-        &self,
-        _: ListBucketsRequest,
-    ) -> S3StorageResult<ListBucketsOutput, ListBucketsError> {
-        let mut buckets = Vec::new();
+    /// remove the manifest of an object once none of its json files is left
+    async fn remove_unused_object_manifest(&self, bucket: &str, key: &str) -> io::Result<()> {
+        for kind in OBJECT_JSON_KINDS {
+            let json_path = self.get_object_json_path(bucket, key, kind)?;
+            if rt::metadata(&json_path).await.is_ok() {
+                return Ok(());
+            }
+        }
+        remove_file_if_exists(&self.get_object_manifest_path(bucket, key)?).await
+    }
 
-        let mut iter = trace_try!(async_fs::read_dir(&self.root).await);
-        while let Some(entry) = iter.next().await {
-            let entry = trace_try!(entry);
-            let file_type = trace_try!(entry.file_type().await);
-            if file_type.is_dir() {
-                let file_name = entry.file_name();
-                let name = file_name.to_string_lossy();
-                if S3Path::check_bucket_name(&*name) {
-                    let file_meta = trace_try!(entry.metadata().await);
-                    let creation_date = trace_try!(file_meta.created());
-                    buckets.push(Bucket {
-                        creation_date: Some(time::to_rfc3339(creation_date)),
-                        name: Some(name.into()),
-                    });
+    /// lists the manifests under the root with their paths and modification times
+    ///
+    /// A manifest which can not be parsed is skipped.
+    async fn object_manifests(&self) -> io::Result<Vec<(PathBuf, SystemTime, ObjectManifest)>> {
+        let is_manifest_name = |name: &str| {
+            name.strip_prefix(OBJECT_JSON_PREFIX)
+                .and_then(|rest| rest.strip_suffix(".json"))
+                .map_or(false, |hash| {
+                    hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit())
+                })
+        };
+
+        let mut manifests = Vec::new();
+        let mut entries = rt::read_dir(&self.root).await?;
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            if !is_manifest_name(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            let path = entry.path();
+            let content = match rt::read(&path).await {
+                Ok(content) => content,
+                // removed by a concurrent request
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            let manifest = match serde_json::from_slice::<ObjectManifest>(&content) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    error!(path = %path.display(), error = %e, "failed to parse object manifest");
+                    continue;
                 }
+            };
+            let modified = entry.metadata().await?.modified()?;
+            manifests.push((path, modified, manifest));
+        }
+        Ok(manifests)
+    }
+
+    /// remove the json files attached to an object and its manifest
+    async fn remove_object_jsons(&self, bucket: &str, key: &str) -> io::Result<()> {
+        for kind in OBJECT_JSON_KINDS {
+            remove_file_if_exists(&self.get_object_json_path(bucket, key, kind)?).await?;
+            self.remove_legacy_object_json(bucket, key, kind).await?;
+        }
+        remove_file_if_exists(&self.get_object_manifest_path(bucket, key)?).await
+    }
+
+    /// remove the json files attached to the objects of a deleted bucket,
+    /// which are found by their manifests
+    async fn remove_bucket_object_jsons(&self, bucket: &str) -> io::Result<()> {
+        for (_, _, manifest) in self.object_manifests().await? {
+            if manifest.bucket == bucket {
+                self.remove_object_jsons(bucket, &manifest.key).await?;
             }
         }
+        Ok(())
+    }
 
-        let output = ListBucketsOutput {
-            buckets: Some(buckets),
-            owner: None, // TODO: handle owner
-        };
-        Ok(output)
+    /// resolve the path of a json file named by the former base64 encoding,
+    /// returns `None` if the name would be too long to exist
+    fn get_legacy_object_json_path(
+        &self,
+        bucket: &str,
+        key: &str,
+        kind: &str,
+    ) -> io::Result<Option<PathBuf>> {
+        let encode = |s: &str| base64_simd::URL_SAFE_NO_PAD.encode_to_string(s);
+
+        let file_path_str = format!(
+            ".bucket-{}.object-{}.{}.json",
+            encode(bucket),
+            encode(key),
+            kind,
+        );
+        if file_path_str.len() > MAX_FILE_NAME_LEN {
+            return Ok(None);
+        }
+        let file_path = Path::new(&file_path_str);
+        let ans = file_path.absolutize_virtually(&self.root)?.into();
+        Ok(Some(ans))
     }
 
-    #[tracing::instrument]
-    async fn list_objects(
+    /// resolve metadata path under the virtual root (custom format)
+    #[cfg(test)]
+    fn get_metadata_path(&self, bucket: &str, key: &str) -> io::Result<PathBuf> {
+        self.get_object_json_path(bucket, key, "metadata")
+    }
+
+    /// load the json attached to an object, returns `None` if it does not exist
+    async fn load_object_json<T: DeserializeOwned>(
         &self,
-        input: ListObjectsRequest,
-    ) -> S3StorageResult<ListObjectsOutput, ListObjectsError> {
-        let path = trace_try!(self.get_bucket_path(&input.bucket));
+        bucket: &str,
+        key: &str,
+        kind: &str,
+    ) -> io::Result<Option<T>> {
+        let parse = |content: &[u8]| {
+            serde_json::from_slice(content)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        };
 
-        let mut objects = Vec::new();
-        let mut dir_queue = VecDeque::new();
-        dir_queue.push_back(path.clone());
+        #[cfg(all(feature = "xattr", unix))]
+        if self.config.metadata == MetadataBackend::Xattr {
+            let path = self.get_object_path(bucket, key)?;
+            if let Some(content) = xattrs::get(&path, kind).await? {
+                return parse(&content);
+            }
+        }
 
-        while let Some(dir) = dir_queue.pop_front() {
-            let mut entries = trace_try!(async_fs::read_dir(dir).await);
-            while let Some(entry) = entries.next().await {
-                let entry = trace_try!(entry);
-                let file_type = trace_try!(entry.file_type().await);
-                if file_type.is_dir() {
-                    dir_queue.push_back(entry.path());
-                } else {
-                    let file_path = entry.path();
-                    let key = trace_try!(file_path.strip_prefix(&path));
-                    if let Some(ref prefix) = input.prefix {
-                        if !key.to_string_lossy().as_ref().starts_with(prefix) {
-                            continue;
-                        }
-                    }
+        let json_path = self.get_object_json_path(bucket, key, kind)?;
+        let legacy_path = self.get_legacy_object_json_path(bucket, key, kind)?;
+        for path in iter::once(json_path).chain(legacy_path) {
+            match rt::read(&path).await {
+                Ok(content) => return parse(&content),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
 
-                    let metadata = trace_try!(entry.metadata().await);
-                    let last_modified = time::to_rfc3339(trace_try!(metadata.modified()));
-                    let size = metadata.len();
+    /// remove the json file of an object named by the former encoding
+    async fn remove_legacy_object_json(
+        &self,
+        bucket: &str,
+        key: &str,
+        kind: &str,
+    ) -> io::Result<()> {
+        match self.get_legacy_object_json_path(bucket, key, kind)? {
+            Some(path) => remove_file_if_exists(&path).await,
+            None => Ok(()),
+        }
+    }
 
-                    objects.push(Object {
-                        e_tag: None,
-                        key: Some(key.to_string_lossy().into()),
-                        last_modified: Some(last_modified),
-                        owner: None,
-                        size: Some(trace_try!(size.try_into())),
-                        storage_class: None,
-                    });
-                }
+    /// save the json attached to an object
+    async fn save_object_json<T: Serialize + Sync + ?Sized>(
+        &self,
+        bucket: &str,
+        key: &str,
+        kind: &str,
+        value: &T,
+    ) -> io::Result<()> {
+        let content =
+            serde_json::to_vec(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let json_path = self.get_object_json_path(bucket, key, kind)?;
+
+        #[cfg(all(feature = "xattr", unix))]
+        if self.config.metadata == MetadataBackend::Xattr {
+            let path = self.get_object_path(bucket, key)?;
+            if xattrs::set(&path, kind, &content).await? {
+                // a json file left by a fallback would shadow later removals of the attribute
+                self.remove_legacy_object_json(bucket, key, kind).await?;
+                return remove_file_if_exists(&json_path).await;
             }
         }
 
-        objects.sort_by(|lhs, rhs| {
-            let lhs_key = lhs.key.as_deref().unwrap_or("");
-            let rhs_key = rhs.key.as_deref().unwrap_or("");
-            lhs_key.cmp(rhs_key)
-        });
+        self.save_object_manifest(bucket, key).await?;
+        rt::write(&json_path, &content).await?;
+        self.remove_legacy_object_json(bucket, key, kind).await
+    }
 
-        // TODO: handle other fields
-        let output = ListObjectsOutput {
-            contents: Some(objects),
-            delimiter: input.delimiter,
-            encoding_type: input.encoding_type,
-            name: Some(input.bucket),
-            common_prefixes: None,
-            is_truncated: None,
-            marker: None,
-            max_keys: None,
-            next_marker: None,
-            prefix: None,
+    /// remove the json attached to an object if it exists
+    async fn remove_object_json(&self, bucket: &str, key: &str, kind: &str) -> io::Result<()> {
+        #[cfg(all(feature = "xattr", unix))]
+        if self.config.metadata == MetadataBackend::Xattr {
+            let path = self.get_object_path(bucket, key)?;
+            xattrs::remove(&path, kind).await?;
+        }
+
+        let path = self.get_object_json_path(bucket, key, kind)?;
+        if remove_file_existed(&path).await? {
+            self.remove_unused_object_manifest(bucket, key).await?;
+        }
+        self.remove_legacy_object_json(bucket, key, kind).await
+    }
+
+    /// load metadata from fs
+    async fn load_metadata(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> io::Result<Option<HashMap<String, String>>> {
+        self.load_object_json(bucket, key, "metadata").await
+    }
+
+    /// save metadata
+    async fn save_metadata(
+        &self,
+        bucket: &str,
+        key: &str,
+        metadata: &HashMap<String, String>,
+    ) -> io::Result<()> {
+        self.save_object_json(bucket, key, "metadata", metadata)
+            .await
+    }
+
+    /// remove the metadata when an object is overwritten without metadata
+    async fn remove_metadata(&self, bucket: &str, key: &str) -> io::Result<()> {
+        self.remove_object_json(bucket, key, "metadata").await
+    }
+
+    /// load the part sizes of a multipart object, returns `None` for other objects
+    async fn load_part_sizes(&self, bucket: &str, key: &str) -> io::Result<Option<Vec<u64>>> {
+        self.load_object_json(bucket, key, "parts").await
+    }
+
+    /// remove the part sizes when an object is overwritten or deleted
+    async fn remove_part_sizes(&self, bucket: &str, key: &str) -> io::Result<()> {
+        self.remove_object_json(bucket, key, "parts").await
+    }
+
+    /// load the encoding of an object stored compressed, returns `None` for other objects
+    async fn load_encoding(&self, bucket: &str, key: &str) -> io::Result<Option<EncodingRecord>> {
+        self.load_object_json(bucket, key, "encoding").await
+    }
+
+    /// save or remove the encoding of an object
+    async fn save_encoding(
+        &self,
+        bucket: &str,
+        key: &str,
+        record: Option<&EncodingRecord>,
+    ) -> io::Result<()> {
+        match record {
+            Some(record) => self.save_object_json(bucket, key, "encoding", record).await,
+            None => self.remove_object_json(bucket, key, "encoding").await,
+        }
+    }
+
+    /// load the checksum recorded when the object was written
+    async fn load_checksum(&self, bucket: &str, key: &str) -> io::Result<Option<String>> {
+        self.load_object_json(bucket, key, "checksum").await
+    }
+
+    /// load the recorded checksum if it is computed by the `ETag` strategy of this storage
+    async fn load_e_tag_checksum(&self, bucket: &str, key: &str) -> io::Result<Option<String>> {
+        let checksum = self.load_checksum(bucket, key).await?;
+        Ok(checksum.filter(|c| ETagStrategy::of_checksum(c) == self.config.etag))
+    }
+
+    /// fill the `ETag` of listed objects from their recorded checksums
+    ///
+    /// Objects without a recorded checksum are listed without an `ETag`,
+    /// because hashing them would read every listed object.
+    async fn fill_listed_e_tags(&self, bucket: &str, objects: &mut [Object]) -> io::Result<()> {
+        for object in objects {
+            if let Some(ref key) = object.key {
+                let checksum = self.load_e_tag_checksum(bucket, key).await?;
+                object.e_tag = checksum.map(|checksum| format!("\"{checksum}\""));
+            }
+        }
+        Ok(())
+    }
+
+    /// record the checksum of a written object, which is verified by [`FileSystem::scrub`]
+    async fn save_checksum(&self, bucket: &str, key: &str, checksum: &str) -> io::Result<()> {
+        self.save_object_json(bucket, key, "checksum", checksum)
+            .await
+    }
+
+    /// remove the checksum when an object is deleted
+    async fn remove_checksum(&self, bucket: &str, key: &str) -> io::Result<()> {
+        self.remove_object_json(bucket, key, "checksum").await
+    }
+
+    /// resolve the directory of the multipart uploads of a bucket under the temp dir
+    fn get_upload_dir(&self, bucket: &str) -> io::Result<PathBuf> {
+        let encoded = base64_simd::URL_SAFE_NO_PAD.encode_to_string(bucket);
+        let dir_name = format!("{UPLOADS_PREFIX}{encoded}");
+        let ans = Path::new(&dir_name)
+            .absolutize_virtually(&self.temp_dir)?
+            .into();
+        Ok(ans)
+    }
+
+    /// resolve a file of a multipart upload under the upload directory of its bucket
+    fn get_upload_file_path(&self, bucket: &str, file_name: &str) -> io::Result<PathBuf> {
+        let dir = self.get_upload_dir(bucket)?;
+        let ans = Path::new(file_name).absolutize_virtually(&dir)?.into();
+        Ok(ans)
+    }
+
+    /// resolve upload part path
+    fn get_upload_part_path(
+        &self,
+        bucket: &str,
+        upload_id: &str,
+        part_number: i64,
+    ) -> io::Result<PathBuf> {
+        let file_name = format!(".upload_id-{upload_id}.part-{part_number}");
+        self.get_upload_file_path(bucket, &file_name)
+    }
+
+    /// resolve the path of the record of an uploaded part
+    fn get_upload_part_record_path(
+        &self,
+        bucket: &str,
+        upload_id: &str,
+        part_number: i64,
+    ) -> io::Result<PathBuf> {
+        let file_name = format!(".upload_id-{upload_id}.part-{part_number}.json");
+        self.get_upload_file_path(bucket, &file_name)
+    }
+
+    /// load the record of an uploaded part, returns `None` if it does not exist
+    async fn load_part_record(
+        &self,
+        bucket: &str,
+        upload_id: &str,
+        part_number: i64,
+    ) -> io::Result<Option<PartRecord>> {
+        let path = self.get_upload_part_record_path(bucket, upload_id, part_number)?;
+        match rt::read(&path).await {
+            Ok(content) => serde_json::from_slice(&content)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// locate the parts of a multipart upload and check them against their records,
+    /// returns the paths and the sizes of the parts
+    async fn locate_upload_parts<E>(
+        &self,
+        bucket: &str,
+        upload_id: &str,
+        parts: Vec<dto::CompletedPart>,
+    ) -> S3StorageResult<(Vec<PathBuf>, Vec<u64>), E> {
+        let invalid_part = || {
+            code_error!(
+                InvalidPart,
+                "One or more of the specified parts could not be found. \
+                The part may not have been uploaded, \
+                or the specified entity tag may not have matched the part's entity tag."
+            )
+        };
+
+        let mut part_paths: Vec<PathBuf> = Vec::new();
+        let mut part_sizes: Vec<u64> = Vec::new();
+        let mut cnt: i64 = 0;
+        for part in parts {
+            let part_number = if let Some(n) = part.part_number {
+                n
+            } else {
+                let err = code_error!(InvalidPart, "Missing part_number");
+                return Err(err.into());
+            };
+            cnt = cnt.wrapping_add(1);
+            if part_number != cnt {
+                let err = code_error!(
+                    InvalidPartOrder,
+                    "The list of parts was not in ascending order."
+                );
+                return Err(err.into());
+            }
+            let part_path = trace_try!(self.get_upload_part_path(bucket, upload_id, part_number));
+            let part_size = match rt::metadata(&part_path).await {
+                Ok(m) if m.is_file() => m.len(),
+                _ => return Err(invalid_part().into()),
+            };
+            let record = trace_try!(self.load_part_record(bucket, upload_id, part_number).await);
+            // parts written by versions before the records are not accepted, see README
+            let record = record.ok_or_else(invalid_part)?;
+            let e_tag_matches = part
+                .e_tag
+                .as_deref()
+                .map_or(true, |e_tag| e_tag.trim_matches('"') == record.checksum);
+            // a part with another size is a leftover of a failed upload
+            if record.size != part_size || !e_tag_matches {
+                return Err(invalid_part().into());
+            }
+            part_paths.push(part_path);
+            part_sizes.push(part_size);
+        }
+        check_part_sizes(&self.config.limits, &part_sizes)?;
+        Ok((part_paths, part_sizes))
+    }
+
+    /// resolve the path of the record of a multipart upload
+    fn get_upload_record_path(&self, bucket: &str, upload_id: &str) -> io::Result<PathBuf> {
+        let file_name = format!(".upload_id-{upload_id}.json");
+        self.get_upload_file_path(bucket, &file_name)
+    }
+
+    /// load the record of a multipart upload of an object,
+    /// returns `NoSuchUpload` if the upload was not created for the object
+    async fn load_upload_record<E>(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+    ) -> S3StorageResult<UploadRecord, E> {
+        let no_such_upload = || code_error!(NoSuchUpload, "The specified upload does not exist.");
+
+        // upload ids are generated as uuids and must not be paths
+        if Uuid::parse_str(upload_id).is_err() {
+            return Err(no_such_upload().into());
+        }
+        let path = trace_try!(self.get_upload_record_path(bucket, upload_id));
+        let content = match rt::read(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(no_such_upload().into()),
+            Err(e) => return Err(internal_error!(e).into()),
+        };
+        let record: UploadRecord = trace_try!(serde_json::from_slice(&content));
+        if record.key != key {
+            return Err(no_such_upload().into());
+        }
+        Ok(record)
+    }
+
+    /// compute the checksum of an object by reading it
+    async fn get_checksum(
+        &self,
+        bucket: &str,
+        key: &str,
+        strategy: ETagStrategy,
+    ) -> io::Result<String> {
+        let object_path = self.get_object_path(bucket, key)?;
+        let mut file = rt::open(&object_path).await?;
+        hash_file(&mut file, strategy, u64::MAX).await
+    }
+
+    /// returns the checksum of what is read from an opened object
+    ///
+    /// The recorded checksum is used if the path is still the opened version of the object.
+    /// It is checked under the key lock, since writers replace a file and its records under it.
+    /// Otherwise the first `opened.len()` bytes of `file` are hashed, then `file` is rewound.
+    async fn opened_checksum(
+        &self,
+        bucket: &str,
+        key: &str,
+        file: &mut rt::File,
+        opened: &Metadata,
+    ) -> io::Result<String> {
+        let object_path = self.get_object_path(bucket, key)?;
+        let recorded = {
+            let _lock = self.key_locks.lock(&object_path).await;
+            match rt::metadata(&object_path).await {
+                Ok(ref current) if is_same_version(current, opened) => {
+                    self.load_e_tag_checksum(bucket, key).await?
+                }
+                Ok(_) => None,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e),
+            }
+        };
+        if let Some(checksum) = recorded {
+            return Ok(checksum);
+        }
+        let checksum = hash_file(file, self.config.etag, opened.len()).await?;
+        let _: u64 = file.seek(SeekFrom::Start(0)).await?;
+        Ok(checksum)
+    }
+
+    /// reads the attributes of an object without opening its file
+    ///
+    /// The `ETag` is the recorded checksum, which is omitted if the object has no checksum
+    /// computed by the `ETag` strategy of this storage.
+    async fn stat(
+        &self,
+        input: &HeadObjectRequest,
+    ) -> S3StorageResult<HeadObjectOutput, HeadObjectError> {
+        let path = trace_try!(self.get_object_path(&input.bucket, &input.key));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchKey, "The specified key does not exist.");
+            return Err(err.into());
+        }
+
+        let file_metadata = trace_try!(rt::metadata(path).await);
+        let last_modified = time::to_rfc3339(trace_try!(file_metadata.modified()));
+        let mut size = file_metadata.len();
+
+        let mut parts_count = None;
+        if let Some(part_number) = input.part_number {
+            let part_sizes = trace_try!(self.load_part_sizes(&input.bucket, &input.key).await);
+            if let Some(ref sizes) = part_sizes {
+                parts_count = Some(trace_try!(i64::try_from(sizes.len())));
+            }
+            let (_, len) =
+                locate_part(part_sizes.as_deref(), part_number, size).ok_or_else(|| {
+                    code_error!(
+                        InvalidPartNumber,
+                        "The requested partnumber is not satisfiable"
+                    )
+                })?;
+            size = len;
+        }
+
+        let object_metadata = trace_try!(self.load_metadata(&input.bucket, &input.key).await);
+        let encoding_record = trace_try!(self.load_encoding(&input.bucket, &input.key).await);
+
+        let checksum = trace_try!(self.load_e_tag_checksum(&input.bucket, &input.key).await);
+
+        let output: HeadObjectOutput = HeadObjectOutput {
+            accept_ranges: Some("bytes".into()),
+            content_length: Some(trace_try!(size.try_into())),
+            last_modified: Some(last_modified),
+            parts_count,
+            metadata: object_metadata,
+            content_encoding: encoding_record.map(|r| r.content_encoding),
+            e_tag: checksum.map(|checksum| format!("\"{checksum}\"")),
+            ..HeadObjectOutput::default()
+        };
+        Ok(output)
+    }
+
+    /// completes a multipart upload, which fails if the object exists and `create_new` is set
+    #[allow(clippy::cognitive_complexity)] // the steps are in the order of the write
+    async fn complete_multipart_upload_with(
+        &self,
+        input: CompleteMultipartUploadRequest,
+        create_new: bool,
+    ) -> S3StorageResult<CompleteMultipartUploadOutput, CompleteMultipartUploadError> {
+        let CompleteMultipartUploadRequest {
+            multipart_upload,
+            bucket,
+            key,
+            upload_id,
+            ..
+        } = input;
+
+        let multipart_upload = if let Some(multipart_upload) = multipart_upload {
+            multipart_upload
+        } else {
+            let err = code_error!(InvalidPart, "Missing multipart_upload");
+            return Err(err.into());
+        };
+
+        let bucket_path = trace_try!(self.get_bucket_path(&bucket));
+        if !bucket_path.is_dir() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+        let record = self.load_upload_record(&bucket, &key, &upload_id).await?;
+
+        let parts = multipart_upload.parts.unwrap_or_default();
+        let part_count = trace_try!(i64::try_from(parts.len()));
+        let (part_paths, part_sizes) = self.locate_upload_parts(&bucket, &upload_id, parts).await?;
+
+        let object_path = trace_try!(self.get_object_path(&bucket, &key));
+        // fails early, the parts are consumed by the concatenation
+        if create_new && object_path.is_file() {
+            let err = code_error!(
+                PreconditionFailed,
+                "At least one of the pre-conditions you specified did not hold"
+            );
+            return Err(err.into());
+        }
+
+        // the parts are concatenated into a temp file, which is moved into place on commit
+        let mut guard = PartialWrite::stage();
+        let temp_path = trace_try!(self.staging_path(&object_path).await);
+        guard.add_with(temp_path.clone(), object_path.clone(), create_new);
+        let object_dir = object_path.parent().unwrap_or(&object_path);
+        let in_kernel = match part_paths.first() {
+            Some(first) => trace_try!(is_same_device(first, object_dir).await),
+            None => false,
+        };
+        if in_kernel {
+            let concat = concat_parts(temp_path.clone(), part_paths);
+            let (ret, duration) = time::count_duration(concat).await;
+            let size = trace_try!(ret);
+
+            debug!(
+                to = %object_path.display(),
+                ?size,
+                ?duration,
+                "CompleteMultipartUpload: concatenate parts",
+            );
+        } else {
+            trace_try!(self.concat_parts_via_stream(&temp_path, part_paths).await);
+        }
+        trace_try!(self.stamp_file(&temp_path).await);
+        trace_try!(self.sync_file(&temp_path).await);
+
+        let (checksum, duration) = {
+            let mut file = trace_try!(rt::open(&temp_path).await);
+            let hash = hash_file(&mut file, self.config.etag, u64::MAX);
+            let (ret, duration) = time::count_duration(hash).await;
+            (trace_try!(ret), duration)
+        };
+
+        debug!(
+            sum = ?checksum,
+            path = %object_path.display(),
+            ?duration,
+            "CompleteMultipartUpload: calculate checksum",
+        );
+
+        // the json files are staged with the object, so they are replaced along with it
+        let jsons = ObjectJsons {
+            checksum: &checksum,
+            encoding: None,
+            metadata: record.metadata.as_ref(),
+            parts: Some(&part_sizes),
+        };
+        trace_try!(
+            self.stage_object_jsons(&mut guard, &temp_path, (&bucket, &key), jsons)
+                .await
+        );
+        // an object is not replaced in the middle of an append
+        let _lock = self.key_locks.lock(&object_path).await;
+        guard.commit().await.map_err(commit_error)?;
+        self.index_object(&object_path);
+
+        for part_number in 1..=part_count {
+            let path =
+                trace_try!(self.get_upload_part_record_path(&bucket, &upload_id, part_number));
+            trace_try!(remove_file_if_exists(&path).await);
+        }
+        let record_path = trace_try!(self.get_upload_record_path(&bucket, &upload_id));
+        trace_try!(rt::remove_file(&record_path).await);
+
+        let e_tag = format!("\"{checksum}\"");
+        let output = CompleteMultipartUploadOutput {
+            bucket: Some(bucket),
+            key: Some(key),
+            e_tag: Some(e_tag),
+            ..CompleteMultipartUploadOutput::default()
+        };
+        Ok(output)
+    }
+
+    /// copies an object, which fails if the destination exists and `create_new` is set
+    #[allow(clippy::cognitive_complexity)] // the steps are in the order of the write
+    async fn copy_object_with(
+        &self,
+        input: CopyObjectRequest,
+        create_new: bool,
+    ) -> S3StorageResult<CopyObjectOutput, CopyObjectError> {
+        self.validators.check(
+            input.storage_class.as_deref(),
+            input.acl.as_deref(),
+            input.metadata.as_ref(),
+        )?;
+
+        // the header value is url-encoded and may begin with a slash
+        let copy_source = urlencoding::decode(&input.copy_source)
+            .map_err(|err| invalid_request!("Invalid header: x-amz-copy-source", err))?;
+        let copy_source = copy_source.strip_prefix('/').unwrap_or(&copy_source);
+
+        let copy_source = AmzCopySource::from_header_str(copy_source)
+            .map_err(|err| invalid_request!("Invalid header: x-amz-copy-source", err))?;
+
+        let (bucket, key) = match copy_source {
+            AmzCopySource::AccessPoint { .. } => {
+                return Err(not_supported!("Access point is not supported yet.").into())
+            }
+            AmzCopySource::Bucket { bucket, key } => (bucket, key),
+        };
+
+        check_key_segments(&input.key)?;
+        let src_path = trace_try!(self.get_object_path(bucket, key));
+        let dst_path = trace_try!(self.get_object_path(&input.bucket, &input.key));
+
+        let recorded_checksum = trace_try!(self.load_e_tag_checksum(bucket, key).await);
+
+        if create_new && src_path == dst_path && src_path.is_file() {
+            let err = code_error!(
+                PreconditionFailed,
+                "At least one of the pre-conditions you specified did not hold"
+            );
+            return Err(err.into());
+        }
+
+        let metadata = if input.metadata_directive.as_deref() == Some("REPLACE") {
+            input.metadata
+        } else {
+            trace_try!(self.load_metadata(bucket, key).await)
+        };
+
+        // an object is not replaced in the middle of an append
+        let _lock = self.key_locks.lock(&dst_path).await;
+
+        // the recorded checksum of the source is reused, objects without one are hashed
+        let strategy = self.config.etag;
+        // copying an object to itself only replaces its metadata
+        let checksum = if src_path == dst_path {
+            match metadata {
+                Some(ref metadata) => {
+                    trace_try!(
+                        self.save_metadata(&input.bucket, &input.key, metadata)
+                            .await
+                    );
+                }
+                None => trace_try!(self.remove_metadata(&input.bucket, &input.key).await),
+            }
+            let checksum = match recorded_checksum {
+                Some(checksum) => checksum,
+                None => trace_try!(self.get_checksum(&input.bucket, &input.key, strategy).await),
+            };
+            trace_try!(
+                self.save_checksum(&input.bucket, &input.key, &checksum)
+                    .await
+            );
+            checksum
+        } else {
+            let encoding_record = trace_try!(self.load_encoding(bucket, key).await);
+            let mut guard = PartialWrite::stage();
+            let temp_path = trace_try!(self.staging_path(&dst_path).await);
+            guard.add_with(temp_path.clone(), dst_path.clone(), create_new);
+            let _: u64 = trace_try!(copy_file(&src_path, &temp_path).await);
+            trace_try!(self.stamp_file(&temp_path).await);
+            trace_try!(self.sync_file(&temp_path).await);
+            let checksum = if let Some(checksum) = recorded_checksum {
+                checksum
+            } else {
+                let mut file = trace_try!(rt::open(&temp_path).await);
+                trace_try!(hash_file(&mut file, strategy, u64::MAX).await)
+            };
+
+            // the json files are staged with the object, so they are replaced along with it
+            let jsons = ObjectJsons {
+                checksum: &checksum,
+                encoding: encoding_record.as_ref(),
+                metadata: metadata.as_ref(),
+                parts: None,
+            };
+            trace_try!(
+                self.stage_object_jsons(&mut guard, &temp_path, (&input.bucket, &input.key), jsons)
+                    .await
+            );
+            guard.commit().await.map_err(commit_error)?;
+            self.index_object(&dst_path);
+            checksum
+        };
+
+        let file_metadata = trace_try!(rt::metadata(&dst_path).await);
+        let last_modified = time::to_rfc3339(trace_try!(file_metadata.modified()));
+
+        debug!(
+            from = %src_path.display(),
+            to = %dst_path.display(),
+            "CopyObject: copy file",
+        );
+
+        let output = CopyObjectOutput {
+            copy_object_result: CopyObjectResult {
+                e_tag: Some(format!("\"{checksum}\"")),
+                last_modified: Some(last_modified),
+            }
+            .apply(Some),
+            ..CopyObjectOutput::default()
+        };
+
+        Ok(output)
+    }
+
+    /// writes an object, which fails if the object exists and `create_new` is set
+    #[allow(clippy::cognitive_complexity, clippy::too_many_lines)] // the steps are in the order of the write
+    async fn put_object_with(
+        &self,
+        input: PutObjectRequest,
+        create_new: bool,
+    ) -> S3StorageResult<PutObjectOutput, PutObjectError> {
+        self.validators.check(
+            input.storage_class.as_deref(),
+            input.acl.as_deref(),
+            input.metadata.as_ref(),
+        )?;
+
+        let PutObjectRequest {
+            body,
+            bucket,
+            key,
+            metadata,
+            content_length,
+            content_encoding,
+            ..
+        } = input;
+
+        let body = body.ok_or_else(||{
+            code_error!(IncompleteBody,"You did not provide the number of bytes specified by the Content-Length HTTP header.")
+        })?;
+        check_key_segments(&key)?;
+
+        // reject before polling the body, which saves the upload of `Expect: 100-continue` requests
+        let bucket_path = trace_try!(self.get_bucket_path(&bucket));
+        if !bucket_path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        if key.ends_with('/') {
+            if content_length == Some(0) {
+                let object_path = trace_try!(self.get_object_path(&bucket, &key));
+                trace_try!(rt::create_dir_all(&object_path).await);
+                let output = PutObjectOutput::default();
+                return Ok(output);
+            }
+            let err = code_error!(
+                UnexpectedContent,
+                "Unexpected request body when creating a directory object."
+            );
+            return Err(err.into());
+        }
+
+        let object_path = trace_try!(self.get_object_path(&bucket, &key));
+        if let Some(dir_path) = object_path.parent() {
+            trace_try!(rt::create_dir_all(&dir_path).await);
+        }
+
+        let is_gzip = content_encoding.as_deref().map_or(false, |encoding| {
+            encoding.trim().eq_ignore_ascii_case("gzip")
+        });
+        let encoding_policy = if is_gzip {
+            self.config.content_encoding
+        } else {
+            ContentEncodingPolicy::Ignore
+        };
+        let mut logical_size: u64 = 0;
+        let body: Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send + '_>> = match encoding_policy
+        {
+            ContentEncodingPolicy::Ignore => Box::pin(body),
+            ContentEncodingPolicy::Record => Box::pin(GzipStream::inspect(body, &mut logical_size)),
+            ContentEncodingPolicy::Decode => Box::pin(GzipStream::decode(body, &mut logical_size)),
+        };
+
+        let mut hasher = self.config.etag.hasher();
+        let stream = TeeHashStream::new(body, &mut hasher);
+
+        // the object and its json files are written to temp files, which are renamed into place
+        // on commit, or removed if the request fails or is dropped
+        let mut guard = PartialWrite::stage();
+        let temp_path = trace_try!(self.staging_path(&object_path).await);
+        guard.add_with(temp_path.clone(), object_path.clone(), create_new);
+        let write = self.write_file(&temp_path, stream);
+        let (ret, duration) = time::count_duration(write).await;
+        let size = match ret {
+            Ok(size) => size,
+            Err(e) => return Err(write_error(e)),
+        };
+        trace_try!(self.stamp_file(&temp_path).await);
+        trace_try!(self.sync_file(&temp_path).await);
+        let checksum = self.config.etag.checksum(hasher.finalize());
+
+        debug!(
+            path = %object_path.display(),
+            ?size,
+            ?duration,
+            %checksum,
+            "PutObject: write file",
+        );
+
+        let encoding_record = (encoding_policy == ContentEncodingPolicy::Record).then(|| {
+            debug!(
+                path = %object_path.display(),
+                stored_size = ?size,
+                ?logical_size,
+                "PutObject: record content encoding",
+            );
+            EncodingRecord {
+                content_encoding: "gzip".to_owned(),
+                stored_size: size,
+                logical_size,
+            }
+        });
+        let jsons = ObjectJsons {
+            checksum: &checksum,
+            encoding: encoding_record.as_ref(),
+            metadata: metadata.as_ref(),
+            // an overwritten object is no longer addressed by the parts of the previous one
+            parts: None,
+        };
+        trace_try!(
+            self.stage_object_jsons(&mut guard, &temp_path, (&bucket, &key), jsons)
+                .await
+        );
+        // an object is not replaced in the middle of an append
+        let _lock = self.key_locks.lock(&object_path).await;
+        guard.commit().await.map_err(commit_error)?;
+        self.index_object(&object_path);
+
+        let output = PutObjectOutput {
+            e_tag: Some(format!("\"{checksum}\"")),
+            ..PutObjectOutput::default()
+        }; // TODO: handle other fields
+
+        Ok(output)
+    }
+}
+
+/// locates a part of an object, returns `(offset, size)`
+///
+/// An object without part sizes consists of a single part.
+fn locate_part(part_sizes: Option<&[u64]>, part_number: i64, file_len: u64) -> Option<(u64, u64)> {
+    let sizes = match part_sizes {
+        Some(sizes) => sizes,
+        None => return (part_number == 1).then_some((0, file_len)),
+    };
+    let idx = usize::try_from(part_number).ok()?.checked_sub(1)?;
+    let size = *sizes.get(idx)?;
+    let offset = sizes
+        .get(..idx)?
+        .iter()
+        .try_fold(0_u64, |acc, &x| acc.checked_add(x))?;
+    // the object may have been modified by others
+    (offset.checked_add(size)? <= file_len).then_some((offset, size))
+}
+
+/// removes a file, ignoring `NotFound`
+async fn remove_file_if_exists(path: &Path) -> io::Result<()> {
+    remove_file_existed(path).await.map(drop)
+}
+
+/// checks whether an `If-Match` list of entity tags matches an entity tag
+fn e_tag_matches(if_match: &str, e_tag: &str) -> bool {
+    if_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag == e_tag)
+}
+
+/// removes a file, returns `false` if it does not exist
+async fn remove_file_existed(path: &Path) -> io::Result<bool> {
+    match rt::remove_file(path).await {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// checks that each segment of a key fits in a file name
+fn check_key_segments<E>(key: &str) -> S3StorageResult<(), E> {
+    if key
+        .split('/')
+        .any(|segment| segment.len() > MAX_FILE_NAME_LEN)
+    {
+        let err = code_error!(KeyTooLongError, "Your key is too long.");
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+/// checks the sizes of the parts of a multipart upload
+fn check_part_sizes<E>(limits: &ObjectLimits, part_sizes: &[u64]) -> S3StorageResult<(), E> {
+    let (_last, init) = match part_sizes.split_last() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+    if init.iter().any(|&size| size < limits.min_part_size) {
+        return Err(code_error!(
+            EntityTooSmall,
+            "Your proposed upload is smaller than the minimum allowed object size."
+        )
+        .into());
+    }
+    let total = part_sizes.iter().copied().fold(0, u64::saturating_add);
+    if total > limits.max_multipart_object_size {
+        return Err(code_error!(
+            EntityTooLarge,
+            "Your proposed upload exceeds the maximum allowed size."
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// maps an error of committing a write, which fails with `AlreadyExists` if a new object exists
+fn commit_error<E>(err: io::Error) -> S3StorageError<E> {
+    if err.kind() == io::ErrorKind::AlreadyExists {
+        return code_error!(
+            PreconditionFailed,
+            "At least one of the pre-conditions you specified did not hold"
+        )
+        .into();
+    }
+    internal_error!(err).into()
+}
+
+/// converts the error of writing a request body
+fn write_error<E>(err: io::Error) -> S3StorageError<E> {
+    if GzipDecodeError::is_caused_by(&err) {
+        return code_error!(InvalidRequest, "The request body is not valid gzip data.").into();
+    }
+    if ContentSha256MismatchError::is_caused_by(&err) {
+        return code_error!(
+            XAmzContentSHA256Mismatch,
+            "The provided 'x-amz-content-sha256' header does not match what was computed."
+        )
+        .into();
+    }
+    match ContentLengthRangeError::find_cause(&err) {
+        Some(ContentLengthRangeError::TooSmall) => {
+            return code_error!(
+                EntityTooSmall,
+                "Your proposed upload is smaller than the minimum allowed size."
+            )
+            .into();
+        }
+        Some(ContentLengthRangeError::TooLarge) => {
+            return code_error!(
+                EntityTooLarge,
+                "Your proposed upload exceeds the maximum allowed size."
+            )
+            .into();
+        }
+        None => {}
+    }
+
+    internal_error!(err).into()
+}
+
+/// copies a file, streaming its content if the destination is on another device
+async fn copy_file(src: &Path, dst: &Path) -> io::Result<u64> {
+    let dst_dir = dst.parent().unwrap_or(dst);
+    if is_same_device(src, dst_dir).await? {
+        return rt::copy(src, dst).await;
+    }
+    debug!(
+        from = %src.display(),
+        to = %dst.display(),
+        "copy across devices via stream",
+    );
+    copy_via_stream(src, dst).await
+}
+
+/// concatenates part files on the same device as the object file and removes them,
+/// returns the size of the object
+///
+/// A single part is renamed to the object. Otherwise the parts are copied by `std::io::copy`
+/// on the blocking thread pool, which uses `copy_file_range` on Linux, so the content is copied
+/// in the kernel or shared by reflinks on file systems such as XFS and btrfs.
+async fn concat_parts(object_path: PathBuf, part_paths: Vec<PathBuf>) -> io::Result<u64> {
+    rt::unblock(move || {
+        if let [ref part_path] = *part_paths {
+            let size = std::fs::metadata(part_path)?.len();
+            std::fs::rename(part_path, &object_path)?;
+            return Ok(size);
+        }
+
+        let mut writer = std::fs::File::create(&object_path)?;
+        let mut size: u64 = 0;
+        for part_path in &part_paths {
+            let mut reader = std::fs::File::open(part_path)?;
+            size = size.wrapping_add(io::copy(&mut reader, &mut writer)?);
+        }
+        for part_path in &part_paths {
+            std::fs::remove_file(part_path)?;
+        }
+        Ok(size)
+    })
+    .await
+}
+
+/// hashes at most `limit` bytes of a file from its current position
+async fn hash_file(file: &mut rt::File, strategy: ETagStrategy, limit: u64) -> io::Result<String> {
+    let mut reader = (&mut *file).take(limit);
+    let mut buf = vec![0; 4_usize.wrapping_mul(1024).wrapping_mul(1024)];
+    let mut hasher = strategy.hasher();
+    loop {
+        let nread = reader.read(&mut buf).await?;
+        if nread == 0 {
+            break;
+        }
+        hasher.update(buf.get(..nread).unwrap_or_else(|| {
+            panic!(
+                "nread is larger than buffer size: nread = {}, size = {}",
+                nread,
+                buf.len()
+            )
+        }));
+    }
+    Ok(strategy.checksum(hasher.finalize()))
+}
+
+/// checks whether two metadata are of the same file
+#[cfg(unix)]
+fn is_same_file(lhs: &Metadata, rhs: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    lhs.dev() == rhs.dev() && lhs.ino() == rhs.ino()
+}
+
+/// checks whether two metadata are of the same file
+#[cfg(not(unix))]
+fn is_same_file(lhs: &Metadata, rhs: &Metadata) -> bool {
+    lhs.len() == rhs.len() && lhs.modified().ok() == rhs.modified().ok()
+}
+
+/// sets the modification time of a file
+#[cfg(unix)]
+#[allow(clippy::unnecessary_fallible_conversions)] // `Nsecs` is a 32-bit `c_long` on some targets
+fn set_modified(path: &Path, time: SystemTime) -> io::Result<()> {
+    use rustix::fs::{utimensat, AtFlags, Nsecs, Timespec, Timestamps, CWD, UTIME_OMIT};
+    fn invalid(e: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    }
+    let since_epoch = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(invalid)?;
+    let modified = Timespec {
+        tv_sec: since_epoch.as_secs().try_into().map_err(invalid)?,
+        tv_nsec: Nsecs::try_from(since_epoch.subsec_nanos()).map_err(invalid)?,
+    };
+    let times = Timestamps {
+        last_access: Timespec {
+            tv_sec: 0,
+            tv_nsec: UTIME_OMIT,
+        },
+        last_modification: modified,
+    };
+    utimensat(CWD, path, &times, AtFlags::empty()).map_err(Into::into)
+}
+
+/// keeps the modification time set by the OS
+#[cfg(not(unix))]
+fn set_modified(_: &Path, _: SystemTime) -> io::Result<()> {
+    Ok(())
+}
+
+/// checks whether two metadata are of the same file, which has not been modified in place
+fn is_same_version(lhs: &Metadata, rhs: &Metadata) -> bool {
+    is_same_file(lhs, rhs) && lhs.len() == rhs.len() && lhs.modified().ok() == rhs.modified().ok()
+}
+
+/// checks whether two paths are on the same device
+#[cfg(unix)]
+async fn is_same_device(lhs: &Path, rhs: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(rt::metadata(lhs).await?.dev() == rt::metadata(rhs).await?.dev())
+}
+
+/// checks whether two paths are on the same device
+#[cfg(not(unix))]
+async fn is_same_device(_: &Path, _: &Path) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// copies a file by reading it as a stream and writing the stream to the destination
+///
+/// Unlike `rt::copy`, this does not rely on the file system,
+/// so it works across devices and backends.
+/// The partial destination file is removed on failure.
+async fn copy_via_stream(src: &Path, dst: &Path) -> io::Result<u64> {
+    let file = rt::open(src).await?;
+    let len: usize = rt::file_metadata(&file)
+        .await?
+        .len()
+        .try_into()
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    let stream = BytesStream::new(file, 4096, Some(len));
+
+    let mut writer = BufWriter::new(rt::create(dst).await?);
+    match StreamCopier::new().copy(stream, &mut writer).await {
+        Ok(nwrite) => Ok(nwrite),
+        Err(err) => {
+            drop(writer);
+            if let Err(e) = rt::remove_file(dst).await {
+                error!(path = %dst.display(), error = %e, "failed to remove partial file");
+            }
+            Err(err)
+        }
+    }
+}
+
+/// wrap operation error
+const fn operation_error<E>(e: E) -> S3StorageError<E> {
+    S3StorageError::Operation(e)
+}
+
+#[async_trait]
+impl S3Storage for FileSystem {
+    #[tracing::instrument]
+    async fn create_bucket(
+        &self,
+        input: CreateBucketRequest,
+    ) -> S3StorageResult<CreateBucketOutput, CreateBucketError> {
+        self.validators.check_acl(input.acl.as_deref())?;
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if path.exists() {
+            let err = CreateBucketError::BucketAlreadyExists(String::from(
+                "The requested bucket name is not available. \
+                    The bucket namespace is shared by all users of the system. \
+                    Please select a different name and try again.",
+            ));
+            return Err(operation_error(err));
+        }
+
+        trace_try!(rt::create_dir(&path).await);
+        self.index_bucket(&input.bucket);
+
+        let output = CreateBucketOutput::default(); // TODO: handle other fields
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn copy_object(
+        &self,
+        input: CopyObjectRequest,
+    ) -> S3StorageResult<CopyObjectOutput, CopyObjectError> {
+        self.copy_object_with(input, false).await
+    }
+
+    #[tracing::instrument]
+    async fn copy_object_if_absent(
+        &self,
+        input: CopyObjectRequest,
+    ) -> S3StorageResult<CopyObjectOutput, CopyObjectError> {
+        self.copy_object_with(input, true).await
+    }
+
+    #[tracing::instrument]
+    async fn delete_bucket(
+        &self,
+        input: DeleteBucketRequest,
+    ) -> S3StorageResult<DeleteBucketOutput, DeleteBucketError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+        // the objects must be deleted into the trash first, where they are kept until purged
+        if self.trash_retention.is_some() && !trace_try!(walk::object_files(&path).await).is_empty()
+        {
+            let err = code_error!(
+                BucketNotEmpty,
+                "The bucket you tried to delete is not empty."
+            );
+            return Err(err.into());
+        }
+        trace_try!(rt::remove_dir_all(path).await);
+        trace_try!(self.remove_bucket_object_jsons(&input.bucket).await);
+        trace_try!(bucket_config::remove(self, &input.bucket).await);
+        if self.trash_retention.is_none() {
+            trace_try!(trash::remove(self, &input.bucket).await);
+        }
+        // the multipart uploads of the bucket are aborted
+        match rt::remove_dir_all(trace_try!(self.get_upload_dir(&input.bucket))).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(internal_error!(e).into()),
+            _ => {}
+        }
+        self.cache_stats(&input.bucket, None);
+        self.index_bucket(&input.bucket);
+        Ok(DeleteBucketOutput)
+    }
+
+    #[tracing::instrument]
+    async fn delete_object(
+        &self,
+        input: DeleteObjectRequest,
+    ) -> S3StorageResult<DeleteObjectOutput, DeleteObjectError> {
+        let path = trace_try!(self.get_object_path(&input.bucket, &input.key));
+        if input.key.ends_with('/') {
+            let mut dir = trace_try!(rt::read_dir(&path).await);
+            let is_empty = dir.next().await.is_none();
+            if is_empty {
+                trace_try!(rt::remove_dir(&path).await);
+                self.index_object(&path);
+            }
+        } else {
+            // deleting a missing object is not an error
+            let _existed = trace_try!(self.remove_object_file(&input.bucket, &input.key).await);
+        }
+        let output = DeleteObjectOutput::default(); // TODO: handle other fields
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn delete_objects(
+        &self,
+        input: DeleteObjectsRequest,
+    ) -> S3StorageResult<DeleteObjectsOutput, DeleteObjectsError> {
+        let bucket = input.bucket.as_str();
+        let mut results: Vec<_> =
+            futures::stream::iter(input.delete.objects.into_iter().enumerate())
+                .map(|(idx, object)| async move {
+                    let ret = self.remove_object_file(bucket, &object.key).await;
+                    (idx, object.key, ret)
+                })
+                .buffer_unordered(self.delete_concurrency)
+                .collect()
+                .await;
+
+        // keep the order of the request
+        results.sort_by_key(|&(idx, _, _)| idx);
+
+        let mut deleted: Vec<DeletedObject> = Vec::new();
+        let mut errors: Vec<dto::S3Error> = Vec::new();
+        for (_, key, ret) in results {
+            match ret {
+                Ok(true) => deleted.push(DeletedObject {
+                    key: Some(key),
+                    ..DeletedObject::default()
+                }),
+                Ok(false) => {}
+                Err(e) => {
+                    error!(%bucket, %key, error = %e, "DeleteObjects: failed to remove file");
+                    errors.push(dto::S3Error {
+                        code: Some(S3ErrorCode::InternalError.as_static_str().to_owned()),
+                        key: Some(key),
+                        message: Some(
+                            "We encountered an internal error. Please try again.".to_owned(),
+                        ),
+                        version_id: None,
+                    });
+                }
+            }
+        }
+        let output = DeleteObjectsOutput {
+            deleted: Some(deleted),
+            errors: (!errors.is_empty()).then_some(errors),
+            ..DeleteObjectsOutput::default()
+        };
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn get_bucket_location(
+        &self,
+        input: GetBucketLocationRequest,
+    ) -> S3StorageResult<GetBucketLocationOutput, GetBucketLocationError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "NotFound");
+            return Err(err.into());
+        }
+
+        let output = GetBucketLocationOutput {
+            location_constraint: None, // TODO: handle region
+        };
+
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn get_bucket_accelerate_configuration(
+        &self,
+        input: GetBucketAccelerateConfigurationRequest,
+    ) -> S3StorageResult<
+        GetBucketAccelerateConfigurationOutput,
+        GetBucketAccelerateConfigurationError,
+    > {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let config = trace_try!(bucket_config::load(self, &input.bucket).await);
+        let status = config
+            .accelerate
+            .map(|enabled| if enabled { "Enabled" } else { "Suspended" }.to_owned());
+        Ok(GetBucketAccelerateConfigurationOutput { status })
+    }
+
+    #[tracing::instrument]
+    async fn get_bucket_request_payment(
+        &self,
+        input: GetBucketRequestPaymentRequest,
+    ) -> S3StorageResult<GetBucketRequestPaymentOutput, GetBucketRequestPaymentError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let config = trace_try!(bucket_config::load(self, &input.bucket).await);
+        let payer = if config.requester_pays {
+            "Requester"
+        } else {
+            "BucketOwner"
+        };
+        Ok(GetBucketRequestPaymentOutput {
+            payer: Some(payer.into()),
+        })
+    }
+
+    #[tracing::instrument]
+    async fn get_object(
+        &self,
+        input: GetObjectRequest,
+    ) -> S3StorageResult<GetObjectOutput, GetObjectError> {
+        let object_path = trace_try!(self.get_object_path(&input.bucket, &input.key));
+
+        let parse_range = |s: &str| {
+            Range::from_header_str(s).map_err(|err| invalid_request!("Invalid header: range", err))
+        };
+        let range: Option<Range> = input.range.as_deref().map(parse_range).transpose()?;
+
+        let mut file = match rt::open(&object_path).await {
+            Ok(file) => file,
+            Err(e) => {
+                error!(error = %e, "GetObject: open file");
+                let err = code_error!(NoSuchKey, "The specified key does not exist.");
+                return Err(err.into());
+            }
+        };
+
+        let file_metadata = trace_try!(rt::file_metadata(&file).await);
+        let modified = trace_try!(file_metadata.modified());
+        let last_modified = time::to_rfc3339(modified);
+
+        // the conditions are checked against the opened file,
+        // so that a ranged read never splices two versions of the object
+        let (checksum, duration) = {
+            let (ret, duration) = time::count_duration(self.opened_checksum(
+                &input.bucket,
+                &input.key,
+                &mut file,
+                &file_metadata,
+            ))
+            .await;
+            (trace_try!(ret), duration)
+        };
+        debug!(
+            sum = ?checksum,
+            path = %object_path.display(),
+            ?duration,
+            "GetObject: get checksum",
+        );
+        let e_tag = format!("\"{checksum}\"");
+
+        let precondition_failed = || {
+            let err = code_error!(
+                PreconditionFailed,
+                "At least one of the pre-conditions you specified did not hold"
+            );
+            Err(err.into())
+        };
+        // an invalid date is ignored
+        if let Some(ref since) = input.if_unmodified_since {
+            if time::is_modified_since(modified, since).unwrap_or(false) {
+                return precondition_failed();
+            }
+        }
+        if let Some(ref if_match) = input.if_match {
+            if !e_tag_matches(if_match, &e_tag) {
+                return precondition_failed();
+            }
+        }
+
+        let file_len = file_metadata.len();
+        let mut parts_count = None;
+
+        // the selected bytes: (first, len)
+        let selected: Option<(u64, u64)> = if let Some(part_number) = input.part_number {
+            let part_sizes = trace_try!(self.load_part_sizes(&input.bucket, &input.key).await);
+            if let Some(ref sizes) = part_sizes {
+                parts_count = Some(trace_try!(i64::try_from(sizes.len())));
+            }
+            let part =
+                locate_part(part_sizes.as_deref(), part_number, file_len).ok_or_else(|| {
+                    code_error!(
+                        InvalidPartNumber,
+                        "The requested partnumber is not satisfiable"
+                    )
+                })?;
+            Some(part)
+        } else {
+            match range {
+                None => None,
+                Some(Range::Normal { first, last }) => {
+                    if first >= file_len {
+                        let err =
+                            code_error!(InvalidRange, "The requested range cannot be satisfied.");
+                        return Err(err.into());
+                    }
+
+                    // HTTP byte range is inclusive
+                    //      len = last + 1 - first
+                    // or   len = file_len - first
+
+                    let end = last
+                        .and_then(|x| x.checked_add(1))
+                        .map_or(file_len, |x| x.min(file_len));
+                    Some((first, end.wrapping_sub(first)))
+                }
+                Some(Range::Suffix { last }) => {
+                    if last > file_len {
+                        let err =
+                            code_error!(InvalidRange, "The requested range cannot be satisfied.");
+                        return Err(err.into());
+                    }
+                    Some((file_len.wrapping_sub(last), last))
+                }
+            }
+        };
+
+        let mut content_range = None;
+        let offset = selected.map_or(0, |(first, _)| first);
+        let content_length = match selected {
+            None => trace_try!(usize::try_from(file_len)),
+            Some((first, len)) => {
+                let _: u64 = trace_try!(file.seek(SeekFrom::Start(first)).await);
+                if len > 0 {
+                    let last = first.wrapping_add(len).wrapping_sub(1);
+                    content_range = Some(format!("bytes {first}-{last}/{file_len}"));
+                }
+                trace_try!(usize::try_from(len))
+            }
+        };
+
+        let object_metadata = trace_try!(self.load_metadata(&input.bucket, &input.key).await);
+        let encoding_record = trace_try!(self.load_encoding(&input.bucket, &input.key).await);
+
+        let whole_file = if self.file_body && selected.is_none() && encoding_record.is_none() {
+            match file_body::open(object_path.clone(), file_metadata).await {
+                Ok(whole_file) => Some(whole_file),
+                Err(e) => {
+                    debug!(path = %object_path.display(), error = %e, "failed to open file body");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let body = match whole_file {
+            Some(whole_file) => dto::ByteStream::new(file_body::stream(whole_file, file_len)),
+            None => {
+                self.object_stream(file, &object_path, offset, content_length)
+                    .await
+            }
+        };
+
+        let output: GetObjectOutput = GetObjectOutput {
+            body: Some(body),
+            accept_ranges: Some("bytes".into()),
+            content_length: Some(trace_try!(content_length.try_into())),
+            last_modified: Some(last_modified),
+            content_range,
+            parts_count,
+            metadata: object_metadata,
+            content_encoding: encoding_record.map(|r| r.content_encoding),
+            e_tag: Some(e_tag),
+            ..GetObjectOutput::default() // TODO: handle other fields
+        };
+
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn head_bucket(
+        &self,
+        input: HeadBucketRequest,
+    ) -> S3StorageResult<HeadBucketOutput, HeadBucketError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        Ok(HeadBucketOutput)
+    }
+
+    #[tracing::instrument]
+    async fn head_object(
+        &self,
+        input: HeadObjectRequest,
+    ) -> S3StorageResult<HeadObjectOutput, HeadObjectError> {
+        let mut output = self.stat(&input).await?;
+        if output.e_tag.is_none() {
+            let strategy = self.config.etag;
+            let checksum = trace_try!(self.get_checksum(&input.bucket, &input.key, strategy).await);
+            output.e_tag = Some(format!("\"{checksum}\""));
+        }
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn head_objects(
+        &self,
+        input: HeadObjectsRequest,
+    ) -> S3StorageResult<HeadObjectsOutput, HeadBucketError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let bucket = input.bucket.as_str();
+        let objects = futures::stream::iter(input.keys)
+            .map(|key| async move {
+                let head = HeadObjectRequest {
+                    bucket: bucket.to_owned(),
+                    key,
+                    ..HeadObjectRequest::default()
+                };
+                stat_or_missing(self.stat(&head).await)
+            })
+            .buffered(HEAD_OBJECTS_CONCURRENCY)
+            .try_collect()
+            .await?;
+        Ok(HeadObjectsOutput { objects })
+    }
+
+    #[tracing::instrument]
+    async fn stat_object(
+        &self,
+        input: HeadObjectRequest,
+    ) -> S3StorageResult<HeadObjectOutput, HeadObjectError> {
+        self.stat(&input).await
+    }
+
+    #[tracing::instrument]
+    async fn list_buckets(
+        &self,
+        _: ListBucketsRequest,
+    ) -> S3StorageResult<ListBucketsOutput, ListBucketsError> {
+        let mut buckets = Vec::new();
+
+        let mut iter = trace_try!(rt::read_dir(&self.root).await);
+        while let Some(entry) = iter.next().await {
+            let entry = trace_try!(entry);
+            let file_type = trace_try!(entry.file_type().await);
+            if file_type.is_dir() {
+                let file_name = entry.file_name();
+                let name = file_name.to_string_lossy();
+                if S3Path::check_bucket_name(&name) {
+                    let file_meta = trace_try!(entry.metadata().await);
+                    let creation_date = trace_try!(file_meta.created());
+                    buckets.push(Bucket {
+                        creation_date: Some(time::to_rfc3339(creation_date)),
+                        name: Some(name.into()),
+                    });
+                }
+            }
+        }
+
+        let output = ListBucketsOutput {
+            buckets: Some(buckets),
+            owner: None, // TODO: handle owner
+        };
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn list_objects(
+        &self,
+        input: ListObjectsRequest,
+    ) -> S3StorageResult<ListObjectsOutput, ListObjectsError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+
+        let mut objects = Vec::new();
+        let mut dir_queue = VecDeque::new();
+        dir_queue.push_back(path.clone());
+
+        while let Some(dir) = dir_queue.pop_front() {
+            let mut entries = trace_try!(rt::read_dir(dir).await);
+            while let Some(entry) = entries.next().await {
+                let entry = trace_try!(entry);
+                let file_type = trace_try!(entry.file_type().await);
+                if file_type.is_dir() {
+                    dir_queue.push_back(entry.path());
+                } else {
+                    let file_path = entry.path();
+                    let key = trace_try!(file_path.strip_prefix(&path));
+                    if let Some(ref prefix) = input.prefix {
+                        if !key.to_string_lossy().as_ref().starts_with(prefix) {
+                            continue;
+                        }
+                    }
+
+                    let metadata = trace_try!(entry.metadata().await);
+                    let last_modified = time::to_rfc3339(trace_try!(metadata.modified()));
+                    let size = metadata.len();
+
+                    objects.push(Object {
+                        e_tag: None,
+                        key: Some(key.to_string_lossy().into()),
+                        last_modified: Some(last_modified),
+                        owner: None,
+                        size: Some(trace_try!(size.try_into())),
+                        storage_class: None,
+                    });
+                }
+            }
+        }
+
+        objects.sort_by(|lhs, rhs| {
+            let lhs_key = lhs.key.as_deref().unwrap_or("");
+            let rhs_key = rhs.key.as_deref().unwrap_or("");
+            lhs_key.cmp(rhs_key)
+        });
+
+        let max_keys = match input.max_keys {
+            None => MAX_KEYS,
+            Some(n) => trace_try!(usize::try_from(n)).min(MAX_KEYS),
+        };
+        let params = PageParams {
+            prefix: input.prefix.as_deref().unwrap_or(""),
+            delimiter: input.delimiter.as_deref(),
+            marker: input.marker.as_deref(),
+            max_keys,
+        };
+        let mut page = listing::select_page(objects, |o| o.key.as_deref().unwrap_or(""), &params);
+        trace_try!(
+            self.fill_listed_e_tags(&input.bucket, &mut page.contents)
+                .await
+        );
+
+        let common_prefixes = page
+            .common_prefixes
+            .into_iter()
+            .map(|prefix| CommonPrefix {
+                prefix: Some(prefix),
+            })
+            .collect::<Vec<_>>();
+
+        // NextMarker is returned only if the delimiter is specified
+        let next_marker = page.next_marker.filter(|_| input.delimiter.is_some());
+
+        // TODO: handle other fields
+        let output = ListObjectsOutput {
+            contents: Some(page.contents),
+            delimiter: input.delimiter,
+            encoding_type: input.encoding_type,
+            name: Some(input.bucket),
+            common_prefixes: Some(common_prefixes).filter(|v| !v.is_empty()),
+            is_truncated: Some(page.is_truncated),
+            marker: input.marker,
+            max_keys: Some(trace_try!(max_keys.try_into())),
+            next_marker,
+            prefix: input.prefix,
+        };
+
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn list_objects_v2(
+        &self,
+        input: ListObjectsV2Request,
+    ) -> S3StorageResult<ListObjectsV2Output, ListObjectsV2Error> {
+        self.list_objects_v2_where(input, |_, _| true).await
+    }
+
+    #[cfg(feature = "extensions")]
+    #[tracing::instrument]
+    async fn list_objects_v2_filtered(
+        &self,
+        input: FilteredListObjectsV2Request,
+    ) -> S3StorageResult<ListObjectsV2Output, ListObjectsV2Error> {
+        let filter = input.filter;
+        self.list_objects_v2_where(input.input, |modified, size| {
+            filter.is_match(modified, size)
+        })
+        .await
+    }
+
+    #[tracing::instrument]
+    async fn put_bucket_accelerate_configuration(
+        &self,
+        input: PutBucketAccelerateConfigurationRequest,
+    ) -> S3StorageResult<
+        PutBucketAccelerateConfigurationOutput,
+        PutBucketAccelerateConfigurationError,
+    > {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let mut config = trace_try!(bucket_config::load(self, &input.bucket).await);
+        config.accelerate = input
+            .accelerate_configuration
+            .status
+            .map(|status| status == "Enabled");
+        trace_try!(bucket_config::save(self, &input.bucket, &config).await);
+        Ok(PutBucketAccelerateConfigurationOutput)
+    }
+
+    #[tracing::instrument]
+    async fn put_bucket_request_payment(
+        &self,
+        input: PutBucketRequestPaymentRequest,
+    ) -> S3StorageResult<PutBucketRequestPaymentOutput, PutBucketRequestPaymentError> {
+        let path = trace_try!(self.get_bucket_path(&input.bucket));
+        if !path.exists() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        let mut config = trace_try!(bucket_config::load(self, &input.bucket).await);
+        config.requester_pays = input.request_payment_configuration.payer == "Requester";
+        trace_try!(bucket_config::save(self, &input.bucket, &config).await);
+        Ok(PutBucketRequestPaymentOutput)
+    }
+
+    #[tracing::instrument]
+    async fn put_object(
+        &self,
+        input: PutObjectRequest,
+    ) -> S3StorageResult<PutObjectOutput, PutObjectError> {
+        self.put_object_with(input, false).await
+    }
+
+    #[tracing::instrument]
+    async fn put_object_if_absent(
+        &self,
+        input: PutObjectRequest,
+    ) -> S3StorageResult<PutObjectOutput, PutObjectError> {
+        self.put_object_with(input, true).await
+    }
+
+    #[tracing::instrument]
+    async fn create_multipart_upload(
+        &self,
+        input: CreateMultipartUploadRequest,
+    ) -> S3StorageResult<CreateMultipartUploadOutput, CreateMultipartUploadError> {
+        self.validators.check(
+            input.storage_class.as_deref(),
+            input.acl.as_deref(),
+            input.metadata.as_ref(),
+        )?;
+
+        let bucket_path = trace_try!(self.get_bucket_path(&input.bucket));
+        if !bucket_path.is_dir() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+        check_key_segments(&input.key)?;
+        trace_try!(rt::create_dir_all(trace_try!(self.get_upload_dir(&input.bucket))).await);
+
+        let upload_id = Uuid::new_v4().to_string();
+
+        let record = UploadRecord {
+            key: input.key.clone(),
+            metadata: input.metadata,
+        };
+        let path = trace_try!(self.get_upload_record_path(&input.bucket, &upload_id));
+        trace_try!(rt::write(&path, &trace_try!(serde_json::to_vec(&record))).await);
+
+        let output = CreateMultipartUploadOutput {
+            bucket: Some(input.bucket),
+            key: Some(input.key),
+            upload_id: Some(upload_id),
+            ..CreateMultipartUploadOutput::default()
+        };
+
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn rename_bucket(
+        &self,
+        input: RenameBucketRequest,
+    ) -> S3StorageResult<RenameBucketOutput, CreateBucketError> {
+        match rename::rename_bucket(self, &input.bucket, &input.new_bucket).await {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+                return Err(err.into());
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let err = CreateBucketError::BucketAlreadyExists(String::from(
+                    "The requested bucket name is not available.",
+                ));
+                return Err(operation_error(err));
+            }
+            ret => trace_try!(ret),
+        }
+        self.cache_stats(&input.bucket, None);
+        self.cache_stats(&input.new_bucket, None);
+        self.index_bucket(&input.bucket);
+        self.index_bucket(&input.new_bucket);
+        Ok(RenameBucketOutput)
+    }
+
+    #[tracing::instrument]
+    async fn rename_object(
+        &self,
+        input: RenameObjectRequest,
+    ) -> S3StorageResult<RenameObjectOutput, CopyObjectError> {
+        check_key_segments(&input.new_key)?;
+        let renamed =
+            match rename::rename_objects(self, &input.bucket, &input.key, &input.new_key).await {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    let err = code_error!(NoSuchKey, "The specified key does not exist.");
+                    return Err(err.into());
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let err = code_error!(
+                        PreconditionFailed,
+                        "An object with the new key already exists."
+                    );
+                    return Err(err.into());
+                }
+                ret => trace_try!(ret),
+            };
+        self.index_bucket(&input.bucket);
+        Ok(RenameObjectOutput {
+            renamed_count: renamed,
+        })
+    }
+
+    #[tracing::instrument]
+    async fn undelete_object(
+        &self,
+        input: UndeleteObjectRequest,
+    ) -> S3StorageResult<UndeleteObjectOutput, GetObjectError> {
+        if self.trash_retention.is_none() {
+            return Err(code_error!(NotImplemented, "The trash is not enabled.").into());
+        }
+        // the trash of a deleted bucket is kept, but restoring it does not recreate the bucket
+        if !trace_try!(self.get_bucket_path(&input.bucket)).is_dir() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+        let restored = match trash::restore(self, &input.bucket, &input.key).await {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let err = code_error!(
+                    PreconditionFailed,
+                    "An object with the specified key already exists."
+                );
+                return Err(err.into());
+            }
+            ret => trace_try!(ret),
+        };
+        if !restored {
+            let err = code_error!(NoSuchKey, "The specified key does not exist in the trash.");
+            return Err(err.into());
+        }
+        self.index_object(&trace_try!(self.get_object_path(&input.bucket, &input.key)));
+        Ok(UndeleteObjectOutput)
+    }
+
+    #[tracing::instrument]
+    async fn upload_part(
+        &self,
+        input: UploadPartRequest,
+    ) -> S3StorageResult<UploadPartOutput, UploadPartError> {
+        let UploadPartRequest {
+            body,
+            bucket,
+            key,
+            upload_id,
+            part_number,
+            ..
+        } = input;
+
+        let body = body.ok_or_else(||{
+            code_error!(IncompleteBody, "You did not provide the number of bytes specified by the Content-Length HTTP header.")
+        })?;
+
+        let bucket_path = trace_try!(self.get_bucket_path(&bucket));
+        if !bucket_path.is_dir() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+        let _record = self.load_upload_record(&bucket, &key, &upload_id).await?;
+
+        let file_path = trace_try!(self.get_upload_part_path(&bucket, &upload_id, part_number));
+
+        let mut hasher = self.config.etag.hasher();
+        let stream = TeeHashStream::new(body, &mut hasher);
+
+        // the part and its record are written to temp files, which replace a previous upload
+        // of the part on commit, or are removed if the request fails or is dropped
+        let mut guard = PartialWrite::stage();
+        let temp_path = trace_try!(self.staging_path(&file_path).await);
+        guard.add(temp_path.clone(), file_path.clone());
+        let write = self.write_file(&temp_path, stream);
+        let (ret, duration) = time::count_duration(write).await;
+        let size = match ret {
+            Ok(size) => size,
+            Err(e) => return Err(write_error(e)),
+        };
+        trace_try!(self.sync_file(&temp_path).await);
+        let checksum = self.config.etag.checksum(hasher.finalize());
+
+        debug!(
+            path = %file_path.display(),
+            ?size,
+            ?duration,
+            %checksum,
+            "UploadPart: write file",
+        );
+
+        let record = PartRecord {
+            size,
+            checksum: checksum.clone(),
+        };
+        let record_path =
+            trace_try!(self.get_upload_part_record_path(&bucket, &upload_id, part_number));
+        let temp_record_path = trace_try!(self.staging_path(&record_path).await);
+        guard.add(temp_record_path.clone(), record_path);
+        trace_try!(rt::write(&temp_record_path, &trace_try!(serde_json::to_vec(&record))).await);
+        trace_try!(guard.commit().await);
+
+        let e_tag = format!("\"{checksum}\"");
+
+        let output = UploadPartOutput {
+            e_tag: Some(e_tag),
+            ..UploadPartOutput::default()
+        };
+
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn append_object(
+        &self,
+        input: AppendObjectRequest,
+    ) -> S3StorageResult<PutObjectOutput, PutObjectError> {
+        let AppendObjectRequest {
+            input: PutObjectRequest {
+                body, bucket, key, ..
+            },
+            write_offset_bytes,
+        } = input;
+
+        let body = body.ok_or_else(||{
+            code_error!(IncompleteBody,"You did not provide the number of bytes specified by the Content-Length HTTP header.")
+        })?;
+
+        let object_path = trace_try!(self.get_object_path(&bucket, &key));
+        // the lock is held until the checksum is saved, and released after a rollback
+        // of the guard below, so a failed append never truncates the data of another one
+        let _lock = self.key_locks.lock(&object_path).await;
+        if !object_path.is_file() {
+            let err = code_error!(NoSuchKey, "The specified key does not exist.");
+            return Err(err.into());
+        }
+
+        let file = trace_try!(rt::append(&object_path).await);
+        let size = trace_try!(rt::file_metadata(&file).await).len();
+        if size != write_offset_bytes {
+            let err = code_error!(
+                InvalidWriteOffset,
+                "The write offset value that you provided does not match the current object size."
+            );
+            return Err(err.into());
+        }
+
+        // the partially appended data is truncated if the request fails or is dropped
+        let guard = PartialWrite::append(object_path.clone(), size);
+        let mut writer = BufWriter::with_capacity(self.config.write_buf_size, file);
+        let copy = StreamCopier::new()
+            .with_max_size(self.config.limits.max_object_size.saturating_sub(size))
+            .copy(body, &mut writer);
+        let (ret, duration) = time::count_duration(copy).await;
+        let nwrite = match ret {
+            Ok(nwrite) => nwrite,
+            Err(e) => return Err(write_error(e)),
+        };
+        trace_try!(self.stamp_file(&object_path).await);
+        trace_try!(self.sync_file(&object_path).await);
+        trace_try!(guard.commit().await);
+
+        debug!(
+            path = %object_path.display(),
+            offset = size,
+            ?nwrite,
+            ?duration,
+            "AppendObject: append file",
+        );
+
+        // an appended object is no longer addressed by its original parts,
+        // nor described by the recorded encoding
+        trace_try!(self.remove_part_sizes(&bucket, &key).await);
+        trace_try!(self.save_encoding(&bucket, &key, None).await);
+
+        let checksum = trace_try!(self.get_checksum(&bucket, &key, self.config.etag).await);
+        trace_try!(self.save_checksum(&bucket, &key, &checksum).await);
+
+        let output = PutObjectOutput {
+            e_tag: Some(format!("\"{checksum}\"")),
+            ..PutObjectOutput::default()
+        };
+        Ok(output)
+    }
+
+    #[tracing::instrument]
+    async fn bucket_stats(
+        &self,
+        input: BucketStatsRequest,
+    ) -> S3StorageResult<BucketStatsOutput, HeadBucketError> {
+        let bucket_path = trace_try!(self.get_bucket_path(&input.bucket));
+        if !bucket_path.is_dir() {
+            let err = code_error!(NoSuchBucket, "The specified bucket does not exist.");
+            return Err(err.into());
+        }
+
+        if let Some(stats) = self.cached_stats(&input.bucket) {
+            return Ok(stats);
+        }
+
+        let (ret, duration) = time::count_duration(walk::object_files(&bucket_path)).await;
+        let files = trace_try!(ret);
+        let stats = BucketStatsOutput {
+            object_count: trace_try!(u64::try_from(files.len())),
+            size: files
+                .iter()
+                .fold(0_u64, |acc, file| acc.saturating_add(file.metadata.len())),
+        };
+        debug!(bucket = %input.bucket, ?stats, ?duration, "BucketStats: walk bucket");
+
+        self.cache_stats(&input.bucket, Some(stats));
+        Ok(stats)
+    }
+
+    #[tracing::instrument]
+    async fn complete_multipart_upload(
+        &self,
+        input: CompleteMultipartUploadRequest,
+    ) -> S3StorageResult<CompleteMultipartUploadOutput, CompleteMultipartUploadError> {
+        self.complete_multipart_upload_with(input, false).await
+    }
+
+    #[tracing::instrument]
+    async fn complete_multipart_upload_if_absent(
+        &self,
+        input: CompleteMultipartUploadRequest,
+    ) -> S3StorageResult<CompleteMultipartUploadOutput, CompleteMultipartUploadError> {
+        self.complete_multipart_upload_with(input, true).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::stream::TryStreamExt;
+
+    #[tokio::test]
+    async fn copy_via_stream_and_copy_file() {
+        let root = Path::new("target/s3-test-copy");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root).unwrap();
+
+        let src = root.join("src");
+        let content: Vec<u8> = (0..10_000_u32).flat_map(u32::to_le_bytes).collect();
+        std::fs::write(&src, &content).unwrap();
+
+        let streamed = root.join("streamed");
+        assert_eq!(copy_via_stream(&src, &streamed).await.unwrap(), 40_000);
+        assert_eq!(std::fs::read(&streamed).unwrap(), content);
+
+        let copied = root.join("copied");
+        assert_eq!(copy_file(&src, &copied).await.unwrap(), 40_000);
+        assert_eq!(std::fs::read(&copied).unwrap(), content);
+
+        let missing = root.join("missing").join("dst");
+        assert!(copy_via_stream(&src, &missing).await.is_err());
+        assert!(!missing.exists());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn config() {
+        let root = Path::new("target/s3-test-config");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+
+        let config = FileSystemConfig {
+            read_buf_size: 0,
+            ..FileSystemConfig::default()
+        };
+        assert!(FileSystem::new_with_config(root, config).is_err());
+
+        let config = FileSystemConfig {
+            read_buf_size: 5,
+            write_buf_size: 3,
+            fsync: FsyncPolicy::Always,
+            ..FileSystemConfig::default()
+        };
+        let fs = FileSystem::new_with_config(root, config).unwrap();
+
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            body: Some(b"Hello World!".to_vec().into()),
+            ..PutObjectRequest::default()
+        };
+        let _ = fs.put_object(input).await.unwrap();
+
+        let input = GetObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            ..GetObjectRequest::default()
+        };
+        let output = fs.get_object(input).await.unwrap();
+        let chunks: Vec<Bytes> = output.body.unwrap().try_collect().await.unwrap();
+        assert_eq!(chunks, ["Hello", " Worl", "d!"]);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn builder() {
+        let root = Path::new("target/s3-test-builder");
+        let temp_dir = Path::new("target/s3-test-builder-temp");
+        for dir in [root, temp_dir] {
+            if dir.exists() {
+                std::fs::remove_dir_all(dir).unwrap();
+            }
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+
+        let builder = FileSystem::builder(root).with_temp_dir(temp_dir);
+        assert!(builder.clone().build().is_err());
+        assert!(builder.clone().with_read_chunk_size(0).build().is_err());
+
+        std::fs::create_dir_all(temp_dir).unwrap();
+        let fs = builder
+            .with_metadata_backend(MetadataBackend::Json)
+            .with_etag(ETagStrategy::Md5)
+            .with_read_chunk_size(5)
+            .with_fsync(FsyncPolicy::Always)
+            .build()
+            .unwrap();
+
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            body: Some(b"Hello World!".to_vec().into()),
+            ..PutObjectRequest::default()
+        };
+        let _ = fs.put_object(input).await.unwrap();
+
+        let input = GetObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            ..GetObjectRequest::default()
+        };
+        let output = fs.get_object(input).await.unwrap();
+        let chunks: Vec<Bytes> = output.body.unwrap().try_collect().await.unwrap();
+        assert_eq!(chunks, ["Hello", " Worl", "d!"]);
+
+        let input = CreateMultipartUploadRequest {
+            bucket: "asd".into(),
+            key: "b".into(),
+            ..CreateMultipartUploadRequest::default()
+        };
+        let upload_id = fs
+            .create_multipart_upload(input)
+            .await
+            .unwrap()
+            .upload_id
+            .unwrap();
+        let input = UploadPartRequest {
+            bucket: "asd".into(),
+            key: "b".into(),
+            upload_id: upload_id.clone(),
+            part_number: 1,
+            body: Some(b"Hello".to_vec().into()),
+            ..UploadPartRequest::default()
+        };
+        let _ = fs.upload_part(input).await.unwrap();
+        let part_path = fs.get_upload_part_path("asd", &upload_id, 1).unwrap();
+        assert!(part_path.starts_with(temp_dir.canonicalize().unwrap()));
+        assert!(part_path.exists());
+
+        let report = fs.recover(Duration::ZERO).await.unwrap();
+        assert_eq!(report.removed_uploads, 1);
+        assert!(!part_path.exists());
+    }
+
+    #[cfg(feature = "pread")]
+    #[tokio::test]
+    async fn pread_threshold() {
+        let root = Path::new("target/s3-test-pread-threshold");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+        std::fs::write(root.join("asd").join("a"), "Hello World!").unwrap();
+
+        let config = FileSystemConfig {
+            read_buf_size: 4,
+            ..FileSystemConfig::default()
+        };
+        let mut fs = FileSystem::new_with_config(root, config).unwrap();
+        fs.set_pread_threshold(Some(8));
+
+        let get = |range: &str| GetObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            range: Some(range.into()),
+            ..GetObjectRequest::default()
+        };
+        let cases: [(&str, &[&str]); 2] = [
+            ("bytes=6-10", &["World"]),
+            ("bytes=0-11", &["Hell", "o Wo", "rld!"]),
+        ];
+        for (range, expected) in cases {
+            let output = fs.get_object(get(range)).await.unwrap();
+            let chunks: Vec<Bytes> = output.body.unwrap().try_collect().await.unwrap();
+            assert_eq!(chunks, expected, "{range}");
+        }
+    }
+
+    #[tokio::test]
+    async fn file_body() {
+        let root = Path::new("target/s3-test-file-body");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+        let content = vec![b'a'; 1024 * 1024 + 1];
+        std::fs::write(root.join("asd").join("a"), &content).unwrap();
+        std::fs::write(root.join("asd").join("b"), "Hello World!").unwrap();
+
+        let config = FileSystemConfig {
+            read_buf_size: 4,
+            ..FileSystemConfig::default()
+        };
+        let mut fs = FileSystem::new_with_config(root, config).unwrap();
+        fs.set_file_body(true);
+        let record = EncodingRecord {
+            content_encoding: "gzip".into(),
+            stored_size: 12,
+            logical_size: 12,
+        };
+        fs.save_encoding("asd", "b", Some(&record)).await.unwrap();
+
+        let get = |key: &str, range: Option<&str>| GetObjectRequest {
+            bucket: "asd".into(),
+            key: key.into(),
+            range: range.map(Into::into),
+            ..GetObjectRequest::default()
+        };
+        let chunks = |req| async {
+            let output = fs.get_object(req).await.unwrap();
+            output
+                .body
+                .unwrap()
+                .try_collect::<Vec<Bytes>>()
+                .await
+                .unwrap()
+        };
+
+        let whole = chunks(get("a", None)).await;
+        assert_eq!(whole.len(), 2);
+        assert_eq!(whole.concat(), content);
+
+        // ranges and encoded objects take the normal read path
+        assert_eq!(chunks(get("a", Some("bytes=0-7"))).await, ["aaaa", "aaaa"]);
+        assert_eq!(chunks(get("b", None)).await, ["Hell", "o Wo", "rld!"]);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn rename_race() {
+        let root = Path::new("target/s3-test-rename-race");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+        std::fs::write(root.join("asd").join("a"), "a").unwrap();
+        std::fs::write(root.join("asd").join("c"), "c").unwrap();
+        let fs = FileSystem::new(root).unwrap();
+
+        // only one of concurrent renames to a new key succeeds
+        let (a, c) = futures::join!(
+            rename::rename_objects(&fs, "asd", "a", "b"),
+            rename::rename_objects(&fs, "asd", "c", "b"),
+        );
+        let (renamed, kept, err) = match (a, c) {
+            (Ok(_), Err(err)) => ("a", "c", err),
+            (Err(err), Ok(_)) => ("c", "a", err),
+            ret => panic!("{ret:?}"),
+        };
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        let read = |key: &str| std::fs::read_to_string(root.join("asd").join(key)).unwrap();
+        assert_eq!(read("b"), renamed);
+        assert_eq!(read(kept), kept);
+
+        // an empty directory is not replaced either
+        std::fs::create_dir_all(root.join("asd").join("d")).unwrap();
+        let err = rename::rename_objects(&fs, "asd", "b", "d")
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(read("b"), renamed);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn rename_undo() {
+        let root = Path::new("target/s3-test-rename-undo");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+        let fs = FileSystem::new(root).unwrap();
+
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            body: Some(b"Hello".to_vec().into()),
+            metadata: Some(HashMap::from([("a".into(), "b".into())])),
+            ..PutObjectRequest::default()
+        };
+        let _ = fs.put_object(input).await.unwrap();
+
+        // the checksum is moved after the metadata, and fails to replace a directory
+        let blocker = fs.get_object_json_path("asd", "b", "checksum").unwrap();
+        std::fs::create_dir_all(&blocker).unwrap();
+        assert!(rename::rename_objects(&fs, "asd", "a", "b").await.is_err());
+
+        assert_eq!(std::fs::read(root.join("asd/a")).unwrap(), b"Hello");
+        assert!(!root.join("asd/b").exists());
+        let metadata = fs.load_metadata("asd", "a").await.unwrap();
+        assert_eq!(metadata, Some(HashMap::from([("a".into(), "b".into())])));
+        assert!(fs.load_checksum("asd", "a").await.unwrap().is_some());
+        assert!(fs.get_object_manifest_path("asd", "a").unwrap().exists());
+        assert_eq!(fs.load_metadata("asd", "b").await.unwrap(), None);
+        assert!(!fs.get_object_manifest_path("asd", "b").unwrap().exists());
+
+        std::fs::remove_dir(&blocker).unwrap();
+        assert_eq!(
+            rename::rename_objects(&fs, "asd", "a", "b").await.unwrap(),
+            1
+        );
+        let metadata = fs.load_metadata("asd", "b").await.unwrap();
+        assert_eq!(metadata, Some(HashMap::from([("a".into(), "b".into())])));
+        assert!(!fs.get_object_manifest_path("asd", "a").unwrap().exists());
+    }
+
+    #[tokio::test]
+    async fn read_conditions() {
+        let root = Path::new("target/s3-test-read-conditions");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+        std::fs::write(root.join("asd").join("a"), "Hello").unwrap();
+        let fs = FileSystem::new(root).unwrap();
+
+        let e_tag = "\"8b1a9953c4611296a827abf8c47804d7\"";
+        let get = |if_match: Option<&str>, if_unmodified_since: Option<&str>| GetObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            range: Some("bytes=1-2".into()),
+            if_match: if_match.map(Into::into),
+            if_unmodified_since: if_unmodified_since.map(Into::into),
+            ..GetObjectRequest::default()
+        };
+        let is_precondition_failed = |ret: S3StorageResult<GetObjectOutput, GetObjectError>| matches!(ret, Err(S3StorageError::Other(e)) if matches!(e.code(), S3ErrorCode::PreconditionFailed));
+
+        let future = "Fri, 01 Jan 2100 00:00:00 GMT";
+        let past = "Thu, 01 Jan 1970 00:00:00 GMT";
+        for (if_match, since) in [
+            (Some(e_tag), None),
+            (Some("\"x\", *"), None),
+            (None, Some(future)),
+            (None, Some("invalid")),
+        ] {
+            let output = fs.get_object(get(if_match, since)).await.unwrap();
+            assert_eq!(output.content_range.as_deref(), Some("bytes 1-2/5"));
+        }
+        for (if_match, since) in [(Some("\"x\""), None), (None, Some(past))] {
+            assert!(is_precondition_failed(
+                fs.get_object(get(if_match, since)).await
+            ));
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn overwritten_after_open() {
+        let root = Path::new("target/s3-test-overwritten-after-open");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+        let fs = FileSystem::new(root).unwrap();
+
+        let put = |body: &str| PutObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            body: Some(body.as_bytes().to_vec().into()),
+            ..PutObjectRequest::default()
+        };
+        let _ = fs.put_object(put("Hello")).await.unwrap();
+
+        let mut file = rt::open(root.join("asd/a")).await.unwrap();
+        let opened = rt::file_metadata(&file).await.unwrap();
+        let checksum = fs.opened_checksum("asd", "a", &mut file, &opened);
+        assert_eq!(checksum.await.unwrap(), "8b1a9953c4611296a827abf8c47804d7");
+
+        // the overwrite lands between the open and the precondition check
+        let _ = fs.put_object(put("Hello World!")).await.unwrap();
+        let checksum = fs.opened_checksum("asd", "a", &mut file, &opened);
+        assert_eq!(checksum.await.unwrap(), "8b1a9953c4611296a827abf8c47804d7");
+
+        let mut content = String::new();
+        let _ = file.read_to_string(&mut content).await.unwrap();
+        assert_eq!(content, "Hello");
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn stat_object() {
+        let root = Path::new("target/s3-test-stat-object");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+        let fs = FileSystem::new(root).unwrap();
+
+        std::fs::write(root.join("asd/external"), "Hello").unwrap();
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: "written".into(),
+            body: Some(b"Hello".to_vec().into()),
+            ..PutObjectRequest::default()
+        };
+        let _ = fs.put_object(input).await.unwrap();
+
+        let head = |key: &str| HeadObjectRequest {
+            bucket: "asd".into(),
+            key: key.into(),
+            ..HeadObjectRequest::default()
+        };
+        let e_tag = "\"8b1a9953c4611296a827abf8c47804d7\"";
+
+        let output = fs.stat_object(head("written")).await.unwrap();
+        assert_eq!(output.e_tag.as_deref(), Some(e_tag));
+        assert_eq!(output.content_length, Some(5));
+
+        // the checksum of an object written outside is unknown without reading it
+        let output = fs.stat_object(head("external")).await.unwrap();
+        assert_eq!(output.e_tag, None);
+        assert_eq!(output.content_length, Some(5));
+        let output = fs.head_object(head("external")).await.unwrap();
+        assert_eq!(output.e_tag.as_deref(), Some(e_tag));
+
+        assert!(fs.stat_object(head("missing")).await.is_err());
+
+        let input = HeadObjectsRequest {
+            bucket: "asd".into(),
+            keys: vec!["written".into(), "missing".into(), "external".into()],
+        };
+        let output = fs.head_objects(input).await.unwrap();
+        let e_tags: Vec<_> = output
+            .objects
+            .iter()
+            .map(|o| o.as_ref().map(|o| o.e_tag.as_deref()))
+            .collect();
+        assert_eq!(e_tags, [Some(Some(e_tag)), None, Some(None)]);
+
+        let input = HeadObjectsRequest {
+            bucket: "missing".into(),
+            keys: vec!["written".into()],
+        };
+        assert!(fs.head_objects(input).await.is_err());
+    }
+
+    #[test]
+    fn etag_strategy() {
+        assert_eq!("md5".parse::<ETagStrategy>().unwrap(), ETagStrategy::Md5);
+        assert!("sha1".parse::<ETagStrategy>().is_err());
+        assert_eq!(
+            "blake3".parse::<ETagStrategy>().is_ok(),
+            cfg!(feature = "blake3")
+        );
+        assert_eq!(
+            ETagStrategy::of_checksum("8b1a9953c4611296a827abf8c47804d7"),
+            ETagStrategy::Md5
+        );
+    }
+
+    #[cfg(feature = "blake3")]
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn blake3_etag() {
+        let root = Path::new("target/s3-test-blake3-etag");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+
+        // an object written with md5 ETags
+        let put = |key: &str| PutObjectRequest {
+            bucket: "asd".into(),
+            key: key.into(),
+            body: Some(b"Hello".to_vec().into()),
+            ..PutObjectRequest::default()
+        };
+        let md5_fs = FileSystem::new(root).unwrap();
+        let _ = md5_fs.put_object(put("old")).await.unwrap();
+
+        let fs = FileSystem::builder(root)
+            .with_etag(ETagStrategy::Blake3)
+            .build()
+            .unwrap();
+
+        let expected = format!("\"blake3-{}\"", blake3::hash(b"Hello").to_hex());
+        let e_tag = fs.put_object(put("new")).await.unwrap().e_tag;
+        assert_eq!(e_tag.as_deref(), Some(expected.as_str()));
+
+        for key in ["old", "new"] {
+            let input = HeadObjectRequest {
+                bucket: "asd".into(),
+                key: key.into(),
+                ..HeadObjectRequest::default()
+            };
+            let output = fs.head_object(input).await.unwrap();
+            assert_eq!(output.e_tag.as_deref(), Some(expected.as_str()));
+
+            let input = GetObjectRequest {
+                bucket: "asd".into(),
+                key: key.into(),
+                ..GetObjectRequest::default()
+            };
+            let output = fs.get_object(input).await.unwrap();
+            assert_eq!(output.e_tag.as_deref(), Some(expected.as_str()));
+        }
+
+        // the md5 sum recorded before the change is still verified
+        let report = fs.scrub(None).await.unwrap();
+        assert_eq!(report.scanned, 2);
+        assert!(report.corrupted.is_empty());
+        std::fs::write(root.join("asd/old"), "World").unwrap();
+        std::fs::write(root.join("asd/new"), "World").unwrap();
+        let report = fs.scrub(None).await.unwrap();
+        assert_eq!(report.corrupted.len(), 2);
+    }
+
+    #[cfg(all(feature = "xattr", unix))]
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn xattr_metadata() {
+        let root = Path::new("target/s3-test-xattr-metadata");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+
+        let fs = FileSystem::builder(root)
+            .with_metadata_backend(MetadataBackend::Xattr)
+            .build()
+            .unwrap();
+
+        let metadata: HashMap<String, String> = [("a".to_owned(), "b".to_owned())].into();
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            body: Some(b"Hello".to_vec().into()),
+            metadata: Some(metadata.clone()),
+            ..PutObjectRequest::default()
+        };
+        let e_tag = fs.put_object(input).await.unwrap().e_tag;
+
+        let object_path = fs.get_object_path("asd", "a").unwrap();
+        if xattrs::get(&object_path, "checksum")
+            .await
+            .unwrap()
+            .is_none()
+        {
+            // the file system does not support extended attributes
+            assert!(fs
+                .get_object_json_path("asd", "a", "checksum")
+                .unwrap()
+                .exists());
+            return;
+        }
+        assert!(!fs
+            .get_object_json_path("asd", "a", "checksum")
+            .unwrap()
+            .exists());
+        assert!(!fs.get_metadata_path("asd", "a").unwrap().exists());
+
+        let input = HeadObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            ..HeadObjectRequest::default()
+        };
+        let output = fs.head_object(input).await.unwrap();
+        assert_eq!(output.metadata, Some(metadata));
+        assert_eq!(output.e_tag, e_tag);
+        let listed_e_tag = fs.load_checksum("asd", "a").await.unwrap();
+        assert_eq!(listed_e_tag.map(|s| format!("\"{s}\"")), e_tag);
+
+        // json files written by the other backend are still read
+        std::fs::write(root.join("asd/b"), "World").unwrap();
+        std::fs::write(fs.get_metadata_path("asd", "b").unwrap(), r#"{"c":"d"}"#).unwrap();
+        let output = fs.load_metadata("asd", "b").await.unwrap().unwrap();
+        assert_eq!(output["c"], "d");
+
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: "b".into(),
+            body: Some(b"World".to_vec().into()),
+            ..PutObjectRequest::default()
+        };
+        let _ = fs.put_object(input).await.unwrap();
+        assert_eq!(fs.load_metadata("asd", "b").await.unwrap(), None);
+        assert!(!fs.get_metadata_path("asd", "b").unwrap().exists());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn trash() {
+        let root = Path::new("target/s3-test-trash");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+
+        let mut fs = FileSystem::new(root).unwrap();
+        fs.set_trash_retention(Some(Duration::from_secs(3600)));
+
+        let put = |content: &'static str| PutObjectRequest {
+            bucket: "asd".into(),
+            key: "a/b".into(),
+            body: Some(content.as_bytes().to_vec().into()),
+            ..PutObjectRequest::default()
+        };
+        let delete = || DeleteObjectRequest {
+            bucket: "asd".into(),
+            key: "a/b".into(),
+            ..DeleteObjectRequest::default()
+        };
+        let undelete = || UndeleteObjectRequest {
+            bucket: "asd".into(),
+            key: "a/b".into(),
+        };
+
+        let _ = fs.put_object(put("Hello")).await.unwrap();
+        let _ = fs.delete_object(delete()).await.unwrap();
+        assert!(!root.join("asd/a/b").exists());
+
+        let _ = fs.undelete_object(undelete()).await.unwrap();
+        assert_eq!(std::fs::read(root.join("asd/a/b")).unwrap(), b"Hello");
+
+        // an existing object is never overwritten
+        let _ = fs.delete_object(delete()).await.unwrap();
+        let _ = fs.put_object(put("World")).await.unwrap();
+        assert!(fs.undelete_object(undelete()).await.is_err());
+
+        let _ = fs.delete_object(delete()).await.unwrap();
+        let later = SystemTime::now() + Duration::from_secs(3601);
+        let purged = trash::purge(&fs, later, Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert!(purged >= 1);
+        assert!(fs.undelete_object(undelete()).await.is_err());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn clock() {
+        use crate::clock::ManualClock;
+        use std::sync::Arc;
+
+        let root = Path::new("target/s3-test-clock");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let clock = Arc::new(ManualClock::new(start));
+        let mut fs = FileSystem::new(root).unwrap();
+        fs.set_clock(Arc::clone(&clock));
+        fs.set_trash_retention(Some(Duration::from_secs(3600)));
+
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            body: Some(b"Hello".to_vec().into()),
+            ..PutObjectRequest::default()
+        };
+        let _ = fs.put_object(input).await.unwrap();
+        let input = HeadObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            ..HeadObjectRequest::default()
+        };
+        let output = fs.head_object(input).await.unwrap();
+        assert_eq!(output.last_modified, Some(time::to_rfc3339(start)));
+
+        let input = DeleteObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            ..DeleteObjectRequest::default()
+        };
+        let _ = fs.delete_object(input).await.unwrap();
+        clock.advance(Duration::from_secs(3599));
+        assert_eq!(fs.purge_trash().await.unwrap(), 0);
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(fs.purge_trash().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn trash_of_deleted_bucket() {
+        let root = Path::new("target/s3-test-trash-of-deleted-bucket");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+
+        let mut fs = FileSystem::new(root).unwrap();
+        fs.set_trash_retention(Some(Duration::from_secs(3600)));
+
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            body: Some(b"Hello".to_vec().into()),
+            ..PutObjectRequest::default()
+        };
+        let _ = fs.put_object(input).await.unwrap();
+
+        let delete_bucket = || DeleteBucketRequest {
+            bucket: "asd".into(),
+            ..DeleteBucketRequest::default()
+        };
+        let undelete = || UndeleteObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+        };
+        // a non-empty bucket is not deleted with its objects
+        let err = fs.delete_bucket(delete_bucket()).await.unwrap_err();
+        assert!(
+            matches!(err, S3StorageError::Other(ref e) if matches!(e.code(), S3ErrorCode::BucketNotEmpty))
+        );
+        assert!(root.join("asd/a").exists());
+
+        let input = DeleteObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            ..DeleteObjectRequest::default()
+        };
+        let _ = fs.delete_object(input).await.unwrap();
+        let _ = fs.delete_bucket(delete_bucket()).await.unwrap();
+        assert!(!root.join("asd").exists());
+
+        // the trash is kept until it is purged
+        let err = fs.undelete_object(undelete()).await.unwrap_err();
+        assert!(
+            matches!(err, S3StorageError::Other(ref e) if matches!(e.code(), S3ErrorCode::NoSuchBucket))
+        );
+        assert!(!root.join("asd").exists());
+
+        let input = CreateBucketRequest {
+            bucket: "asd".into(),
+            ..CreateBucketRequest::default()
+        };
+        let _ = fs.create_bucket(input).await.unwrap();
+        let _ = fs.undelete_object(undelete()).await.unwrap();
+        assert_eq!(std::fs::read(root.join("asd/a")).unwrap(), b"Hello");
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn object_json_names() {
+        let root = Path::new("target/s3-test-object-json-names");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+        let fs = FileSystem::new(root).unwrap();
+
+        let put = |key: String| PutObjectRequest {
+            bucket: "asd".into(),
+            key,
+            body: Some(b"Hello".to_vec().into()),
+            metadata: Some(HashMap::from([("a".into(), "b".into())])),
+            ..PutObjectRequest::default()
         };
 
-        Ok(output)
-    }
+        // the json files of a long key are named by its hash
+        let long_key = format!("{}b", "a/".repeat(200));
+        let _ = fs.put_object(put(long_key.clone())).await.unwrap();
+        let metadata = fs.load_metadata("asd", &long_key).await.unwrap().unwrap();
+        assert_eq!(metadata["a"], "b");
+        let path = fs.get_metadata_path("asd", &long_key).unwrap();
+        assert!(path.file_name().unwrap().len() < MAX_FILE_NAME_LEN);
+        let manifest_path = fs.get_object_manifest_path("asd", &long_key).unwrap();
+        let manifest: ObjectManifest =
+            serde_json::from_slice(&std::fs::read(manifest_path).unwrap()).unwrap();
+        assert_eq!(
+            (manifest.bucket.as_str(), manifest.key.as_str()),
+            ("asd", long_key.as_str())
+        );
 
-    #[tracing::instrument]
-    async fn list_objects_v2(
-        &self,
-        input: ListObjectsV2Request,
-    ) -> S3StorageResult<ListObjectsV2Output, ListObjectsV2Error> {
-        let path = trace_try!(self.get_bucket_path(&input.bucket));
+        // a segment longer than a file name is rejected
+        let ret = fs.put_object(put(format!("c/{}", "d".repeat(256)))).await;
+        assert!(matches!(
+            ret,
+            Err(S3StorageError::Other(e)) if matches!(e.code(), S3ErrorCode::KeyTooLongError)
+        ));
 
-        let mut objects = Vec::new();
-        let mut dir_queue = VecDeque::new();
-        dir_queue.push_back(path.clone());
+        // a file named by the former encoding is read, and replaced by a save
+        let legacy_path = fs
+            .get_legacy_object_json_path("asd", "e", "metadata")
+            .unwrap()
+            .unwrap();
+        std::fs::write(&legacy_path, r#"{"c":"d"}"#).unwrap();
+        let metadata = fs.load_metadata("asd", "e").await.unwrap().unwrap();
+        assert_eq!(metadata["c"], "d");
+        let _ = fs.put_object(put("e".into())).await.unwrap();
+        assert!(!legacy_path.exists());
+        let metadata = fs.load_metadata("asd", "e").await.unwrap().unwrap();
+        assert_eq!(metadata["a"], "b");
 
-        while let Some(dir) = dir_queue.pop_front() {
-            let mut entries = trace_try!(async_fs::read_dir(dir).await);
-            while let Some(entry) = entries.next().await {
-                let entry = trace_try!(entry);
-                let file_type = trace_try!(entry.file_type().await);
-                if file_type.is_dir() {
-                    dir_queue.push_back(entry.path());
-                } else {
-                    let file_path = entry.path();
-                    let key = trace_try!(file_path.strip_prefix(&path));
-                    if let Some(ref prefix) = input.prefix {
-                        if !key.to_string_lossy().as_ref().starts_with(prefix) {
-                            continue;
-                        }
-                    }
+        // the json files and the manifest are removed with the object
+        let delete = |key: String| DeleteObjectRequest {
+            bucket: "asd".into(),
+            key,
+            ..DeleteObjectRequest::default()
+        };
+        let _ = fs.delete_object(delete("e".into())).await.unwrap();
+        assert!(!fs.get_metadata_path("asd", "e").unwrap().exists());
+        assert!(!fs.get_object_manifest_path("asd", "e").unwrap().exists());
 
-                    let metadata = trace_try!(entry.metadata().await);
-                    let last_modified = time::to_rfc3339(trace_try!(metadata.modified()));
-                    let size = metadata.len();
+        // and with the bucket
+        let _ = fs
+            .delete_bucket(DeleteBucketRequest {
+                bucket: "asd".into(),
+                ..DeleteBucketRequest::default()
+            })
+            .await
+            .unwrap();
+        assert!(!fs.get_metadata_path("asd", &long_key).unwrap().exists());
+        assert!(!fs
+            .get_object_manifest_path("asd", &long_key)
+            .unwrap()
+            .exists());
+    }
 
-                    objects.push(Object {
-                        e_tag: None,
-                        key: Some(key.to_string_lossy().into()),
-                        last_modified: Some(last_modified),
-                        owner: None,
-                        size: Some(trace_try!(size.try_into())),
-                        storage_class: None,
-                    });
-                }
-            }
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn copy_object_e_tag() {
+        let root = Path::new("target/s3-test-copy-object-e-tag");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
         }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+        std::fs::write(root.join("asd").join("unrecorded"), "Hello").unwrap();
 
-        objects.sort_by(|lhs, rhs| {
-            let lhs_key = lhs.key.as_deref().unwrap_or("");
-            let rhs_key = rhs.key.as_deref().unwrap_or("");
-            lhs_key.cmp(rhs_key)
-        });
+        let fs = FileSystem::new(root).unwrap();
 
-        // TODO: handle other fields
-        let output = ListObjectsV2Output {
-            key_count: Some(trace_try!(objects.len().try_into())),
-            contents: Some(objects),
-            delimiter: input.delimiter,
-            encoding_type: input.encoding_type,
-            name: Some(input.bucket),
-            common_prefixes: None,
-            is_truncated: None,
-            max_keys: None,
-            prefix: None,
-            continuation_token: None,
-            next_continuation_token: None,
-            start_after: None,
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            body: Some(b"Hello".to_vec().into()),
+            ..PutObjectRequest::default()
         };
+        let _ = fs.put_object(input).await.unwrap();
+        // a fake checksum shows that the source is not hashed again
+        fs.save_checksum("asd", "a", "recorded").await.unwrap();
 
-        Ok(output)
+        let copy = |src: &str, dst: &str| CopyObjectRequest {
+            bucket: "asd".into(),
+            key: dst.into(),
+            copy_source: format!("asd/{src}"),
+            ..CopyObjectRequest::default()
+        };
+        let e_tag = |output: CopyObjectOutput| output.copy_object_result.unwrap().e_tag;
+
+        let output = fs.copy_object(copy("a", "b")).await.unwrap();
+        assert_eq!(e_tag(output).as_deref(), Some("\"recorded\""));
+        assert_eq!(
+            fs.load_checksum("asd", "b").await.unwrap().as_deref(),
+            Some("recorded")
+        );
+
+        let output = fs.copy_object(copy("unrecorded", "c")).await.unwrap();
+        assert_eq!(
+            e_tag(output).as_deref(),
+            Some("\"8b1a9953c4611296a827abf8c47804d7\"")
+        );
     }
 
-    #[tracing::instrument]
-    async fn put_object(
-        &self,
-        input: PutObjectRequest,
-    ) -> S3StorageResult<PutObjectOutput, PutObjectError> {
-        if let Some(ref storage_class) = input.storage_class {
-            let is_valid = ["STANDARD", "REDUCED_REDUNDANCY"].contains(&storage_class.as_str());
-            if !is_valid {
-                let err = code_error!(
-                    InvalidStorageClass,
-                    "The storage class you specified is not valid."
-                );
-                return Err(err.into());
-            }
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn overwrite_json_files() {
+        let root = Path::new("target/s3-test-overwrite-json-files");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
         }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+        std::fs::write(root.join("asd").join("a"), "Hello").unwrap();
 
-        let PutObjectRequest {
-            body,
-            bucket,
-            key,
+        let fs = FileSystem::new(root).unwrap();
+        let record = EncodingRecord {
+            content_encoding: "gzip".into(),
+            stored_size: 5,
+            logical_size: 5,
+        };
+        fs.save_encoding("asd", "a", Some(&record)).await.unwrap();
+        fs.save_metadata("asd", "a", &HashMap::from([("a".into(), "old".into())]))
+            .await
+            .unwrap();
+
+        // a completed upload replaces all json files of the object
+        let input = CreateMultipartUploadRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            metadata: Some(HashMap::from([("a".into(), "parts".into())])),
+            ..CreateMultipartUploadRequest::default()
+        };
+        let upload_id = fs
+            .create_multipart_upload(input)
+            .await
+            .unwrap()
+            .upload_id
+            .unwrap();
+        let input = UploadPartRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            upload_id: upload_id.clone(),
+            part_number: 1,
+            body: Some(b"World".to_vec().into()),
+            ..UploadPartRequest::default()
+        };
+        let e_tag = fs.upload_part(input).await.unwrap().e_tag;
+        let input = CompleteMultipartUploadRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            upload_id,
+            multipart_upload: Some(dto::CompletedMultipartUpload {
+                parts: Some(vec![dto::CompletedPart {
+                    e_tag,
+                    part_number: Some(1),
+                }]),
+            }),
+            ..CompleteMultipartUploadRequest::default()
+        };
+        let output = fs.complete_multipart_upload(input).await.unwrap();
+        let checksum = fs.load_checksum("asd", "a").await.unwrap().unwrap();
+        assert_eq!(output.e_tag, Some(format!("\"{checksum}\"")));
+        assert_eq!(checksum, "f5a7924e621e84c9280a9a27e1bcb7f6");
+        assert!(fs.load_encoding("asd", "a").await.unwrap().is_none());
+        assert_eq!(fs.load_part_sizes("asd", "a").await.unwrap(), Some(vec![5]));
+        let metadata = fs.load_metadata("asd", "a").await.unwrap();
+        assert_eq!(
             metadata,
-            content_length,
-            ..
-        } = input;
+            Some(HashMap::from([("a".into(), "parts".into())]))
+        );
 
-        let body = body.ok_or_else(||{
-            code_error!(IncompleteBody,"You did not provide the number of bytes specified by the Content-Length HTTP header.")
-        })?;
+        // and so does a copy
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: "b".into(),
+            body: Some(b"Hello".to_vec().into()),
+            metadata: Some(HashMap::from([("a".into(), "copy".into())])),
+            ..PutObjectRequest::default()
+        };
+        let _ = fs.put_object(input).await.unwrap();
+        let input = CopyObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            copy_source: "asd/b".into(),
+            ..CopyObjectRequest::default()
+        };
+        let _ = fs.copy_object(input).await.unwrap();
+        let checksum = fs.load_checksum("asd", "a").await.unwrap();
+        assert_eq!(
+            checksum.as_deref(),
+            Some("8b1a9953c4611296a827abf8c47804d7")
+        );
+        assert!(fs.load_part_sizes("asd", "a").await.unwrap().is_none());
+        let metadata = fs.load_metadata("asd", "a").await.unwrap();
+        assert_eq!(metadata, Some(HashMap::from([("a".into(), "copy".into())])));
 
-        if key.ends_with('/') {
-            if content_length == Some(0) {
-                let object_path = trace_try!(self.get_object_path(&bucket, &key));
-                trace_try!(async_fs::create_dir_all(&object_path).await);
-                let output = PutObjectOutput::default();
-                return Ok(output);
-            }
-            let err = code_error!(
-                UnexpectedContent,
-                "Unexpected request body when creating a directory object."
-            );
-            return Err(err.into());
-        }
+        let temps = std::fs::read_dir(&fs.temp_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| name.to_string_lossy().starts_with(WRITE_PREFIX));
+        assert_eq!(temps.count(), 0);
+    }
 
-        let object_path = trace_try!(self.get_object_path(&bucket, &key));
-        if let Some(dir_path) = object_path.parent() {
-            trace_try!(async_fs::create_dir_all(&dir_path).await);
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn create_new_writes() {
+        fn is_precondition_failed<E>(err: &S3StorageError<E>) -> bool {
+            matches!(*err, S3StorageError::Other(ref e) if matches!(e.code(), S3ErrorCode::PreconditionFailed))
         }
 
-        let mut md5_hash = Md5::new();
-        let stream = body.inspect_ok(|bytes| md5_hash.update(bytes.as_ref()));
-
-        let file = trace_try!(File::create(&object_path).await);
-        let mut writer = BufWriter::new(file);
+        let root = Path::new("target/s3-test-create-new-writes");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
 
-        let (ret, duration) = time::count_duration(copy_bytes(stream, &mut writer)).await;
-        let size = trace_try!(ret);
-        let md5_sum = md5_hash.finalize().apply(crypto::to_hex_string);
+        let fs = FileSystem::new(root).unwrap();
 
-        debug!(
-            path = %object_path.display(),
-            ?size,
-            ?duration,
-            %md5_sum,
-            "PutObject: write file",
-        );
+        let put = |body: &'static [u8]| PutObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            body: Some(body.to_vec().into()),
+            ..PutObjectRequest::default()
+        };
+        let _ = fs.put_object_if_absent(put(b"Hello")).await.unwrap();
+        let err = fs.put_object_if_absent(put(b"World")).await.unwrap_err();
+        assert!(is_precondition_failed(&err));
+        assert_eq!(std::fs::read(root.join("asd/a")).unwrap(), b"Hello");
 
-        if let Some(ref metadata) = metadata {
-            trace_try!(self.save_metadata(&bucket, &key, metadata).await);
+        let copy = |src: &str, dst: &str| CopyObjectRequest {
+            bucket: "asd".into(),
+            key: dst.into(),
+            copy_source: format!("asd/{src}"),
+            ..CopyObjectRequest::default()
+        };
+        let _ = fs.copy_object_if_absent(copy("a", "b")).await.unwrap();
+        for (src, dst) in [("a", "b"), ("a", "a")] {
+            let err = fs.copy_object_if_absent(copy(src, dst)).await.unwrap_err();
+            assert!(is_precondition_failed(&err));
         }
 
-        let output = PutObjectOutput {
-            e_tag: Some(format!("\"{}\"", md5_sum)),
-            ..PutObjectOutput::default()
-        }; // TODO: handle other fields
+        let input = CreateMultipartUploadRequest {
+            bucket: "asd".into(),
+            key: "b".into(),
+            ..CreateMultipartUploadRequest::default()
+        };
+        let upload_id = fs
+            .create_multipart_upload(input)
+            .await
+            .unwrap()
+            .upload_id
+            .unwrap();
+        let input = UploadPartRequest {
+            bucket: "asd".into(),
+            key: "b".into(),
+            upload_id: upload_id.clone(),
+            part_number: 1,
+            body: Some(b"World".to_vec().into()),
+            ..UploadPartRequest::default()
+        };
+        let e_tag = fs.upload_part(input).await.unwrap().e_tag;
+        let input = CompleteMultipartUploadRequest {
+            bucket: "asd".into(),
+            key: "b".into(),
+            upload_id,
+            multipart_upload: Some(dto::CompletedMultipartUpload {
+                parts: Some(vec![dto::CompletedPart {
+                    e_tag,
+                    part_number: Some(1),
+                }]),
+            }),
+            ..CompleteMultipartUploadRequest::default()
+        };
+        let err = fs
+            .complete_multipart_upload_if_absent(input)
+            .await
+            .unwrap_err();
+        assert!(is_precondition_failed(&err));
+        assert_eq!(std::fs::read(root.join("asd/b")).unwrap(), b"Hello");
 
-        Ok(output)
+        let temps = std::fs::read_dir(&fs.temp_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| name.to_string_lossy().starts_with(WRITE_PREFIX));
+        assert_eq!(temps.count(), 0);
     }
 
-    #[tracing::instrument]
-    async fn create_multipart_upload(
-        &self,
-        input: CreateMultipartUploadRequest,
-    ) -> S3StorageResult<CreateMultipartUploadOutput, CreateMultipartUploadError> {
-        let upload_id = Uuid::new_v4().to_string();
+    #[tokio::test]
+    async fn concurrent_appends() {
+        use crate::dto::ByteStream;
+        use futures::SinkExt;
+        use std::sync::Arc;
 
-        let output = CreateMultipartUploadOutput {
-            bucket: Some(input.bucket),
-            key: Some(input.key),
-            upload_id: Some(upload_id),
-            ..CreateMultipartUploadOutput::default()
+        let root = Path::new("target/s3-test-concurrent-appends");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+        std::fs::write(root.join("asd/a"), "Hello").unwrap();
+
+        // appended chunks reach the file at once
+        let config = FileSystemConfig {
+            write_buf_size: 1,
+            ..FileSystemConfig::default()
+        };
+        let fs = Arc::new(FileSystem::new_with_config(root, config).unwrap());
+        let append = |body: ByteStream| AppendObjectRequest {
+            input: PutObjectRequest {
+                bucket: "asd".into(),
+                key: "a".into(),
+                body: Some(body),
+                ..PutObjectRequest::default()
+            },
+            write_offset_bytes: 5,
         };
 
-        Ok(output)
+        // an append which fails after writing a part of its body
+        let (mut sender, body) = futures::channel::mpsc::channel::<io::Result<Bytes>>(1);
+        let failed = tokio::spawn({
+            let fs = Arc::clone(&fs);
+            let input = append(ByteStream::new(body));
+            async move { fs.append_object(input).await.is_ok() }
+        });
+        sender.send(Ok(Bytes::from(" Wor"))).await.unwrap();
+        while std::fs::metadata(root.join("asd/a")).unwrap().len() != 9 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        // the other append waits for the first one, instead of failing with the offset
+        let appended = tokio::spawn({
+            let fs = Arc::clone(&fs);
+            let input = append(b" World".to_vec().into());
+            async move { fs.append_object(input).await.is_ok() }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        sender
+            .send(Err(io::Error::new(io::ErrorKind::Other, "aborted")))
+            .await
+            .unwrap();
+
+        assert!(!failed.await.unwrap());
+        assert!(appended.await.unwrap());
+        assert_eq!(std::fs::read(root.join("asd/a")).unwrap(), b"Hello World");
     }
 
-    #[tracing::instrument]
-    async fn upload_part(
-        &self,
-        input: UploadPartRequest,
-    ) -> S3StorageResult<UploadPartOutput, UploadPartError> {
-        let UploadPartRequest {
-            body,
-            upload_id,
-            part_number,
-            ..
-        } = input;
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn concat_parts() {
+        let root = Path::new("target/s3-test-concat-parts");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root).unwrap();
 
-        let body = body.ok_or_else(||{
-            code_error!(IncompleteBody, "You did not provide the number of bytes specified by the Content-Length HTTP header.")
-        })?;
+        let parts: Vec<PathBuf> = ["Hello", ", ", "World!"]
+            .iter()
+            .enumerate()
+            .map(|(i, content)| {
+                let path = root.join(format!("part-{i}"));
+                std::fs::write(&path, content).unwrap();
+                path
+            })
+            .collect();
 
-        let file_path_str = format!(".upload_id-{}.part-{}", upload_id, part_number);
-        let file_path = trace_try!(Path::new(&file_path_str).absolutize_virtually(&self.root));
+        let object = root.join("object");
+        let size = super::concat_parts(object.clone(), parts.clone())
+            .await
+            .unwrap();
+        assert_eq!(size, 13);
+        assert_eq!(std::fs::read_to_string(&object).unwrap(), "Hello, World!");
+        assert!(parts.iter().all(|p| !p.exists()));
 
-        let mut md5_hash = Md5::new();
-        let stream = body.inspect_ok(|bytes| md5_hash.update(bytes.as_ref()));
+        // a single part is renamed
+        std::fs::write(&parts[0], "single").unwrap();
+        let size = super::concat_parts(object.clone(), vec![parts[0].clone()])
+            .await
+            .unwrap();
+        assert_eq!(size, 6);
+        assert_eq!(std::fs::read_to_string(&object).unwrap(), "single");
+        assert!(!parts[0].exists());
+    }
 
-        let file = trace_try!(File::create(&file_path).await);
-        let mut writer = BufWriter::new(file);
+    #[cfg(feature = "listing-index")]
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn listing_index() {
+        let root = Path::new("target/s3-test-listing-index-fs");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd/a")).unwrap();
+        std::fs::write(root.join("asd/a/b"), "").unwrap();
 
-        let (ret, duration) = time::count_duration(copy_bytes(stream, &mut writer)).await;
-        let size = trace_try!(ret);
-        let md5_sum = md5_hash.finalize().apply(crypto::to_hex_string);
+        let mut fs = FileSystem::new(root).unwrap();
+        fs.enable_listing_index().unwrap();
 
-        debug!(
-            path = %file_path.display(),
-            ?size,
-            ?duration,
-            %md5_sum,
-            "UploadPart: write file",
-        );
+        let list = |max_keys: i64, continuation_token: Option<String>| ListObjectsV2Request {
+            bucket: "asd".into(),
+            max_keys: Some(max_keys),
+            continuation_token,
+            ..ListObjectsV2Request::default()
+        };
+        let keys = |output: &ListObjectsV2Output| -> Vec<String> {
+            let contents = output.contents.as_deref().unwrap_or_default();
+            contents.iter().filter_map(|o| o.key.clone()).collect()
+        };
 
-        let e_tag = format!("\"{}\"", md5_sum);
+        // the first listing walks the bucket
+        let output = fs.list_objects_v2(list(10, None)).await.unwrap();
+        assert_eq!(keys(&output), ["a/b"]);
 
-        let output = UploadPartOutput {
-            e_tag: Some(e_tag),
-            ..UploadPartOutput::default()
+        let put = |key: &str| PutObjectRequest {
+            bucket: "asd".into(),
+            key: key.into(),
+            body: Some(b"Hello".to_vec().into()),
+            ..PutObjectRequest::default()
         };
+        for key in ["c", "d"] {
+            let _ = fs.put_object(put(key)).await.unwrap();
+        }
+        let output = fs.list_objects_v2(list(2, None)).await.unwrap();
+        assert_eq!(keys(&output), ["a/b", "c"]);
+        assert_eq!(output.is_truncated, Some(true));
+        let output = fs
+            .list_objects_v2(list(2, output.next_continuation_token))
+            .await
+            .unwrap();
+        assert_eq!(keys(&output), ["d"]);
+        assert_eq!(output.is_truncated, Some(false));
 
-        Ok(output)
+        // files written by other processes are indexed by notifications
+        std::fs::write(root.join("asd/e"), "").unwrap();
+        std::fs::remove_file(root.join("asd/c")).unwrap();
+        let mut listed = Vec::new();
+        for _ in 0..50 {
+            let output = fs.list_objects_v2(list(10, None)).await.unwrap();
+            listed = keys(&output);
+            if listed == ["a/b", "d", "e"] {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(listed, ["a/b", "d", "e"]);
     }
 
-    #[tracing::instrument]
-    async fn complete_multipart_upload(
-        &self,
-        input: CompleteMultipartUploadRequest,
-    ) -> S3StorageResult<CompleteMultipartUploadOutput, CompleteMultipartUploadError> {
-        let CompleteMultipartUploadRequest {
-            multipart_upload,
-            bucket,
-            key,
-            upload_id,
-            ..
-        } = input;
+    /// a body which sends the chunks and then stalls, like a disconnected client
+    fn stalled_body(chunks: &[&'static str]) -> dto::ByteStream {
+        let chunks: Vec<io::Result<Bytes>> = chunks.iter().map(|c| Ok(Bytes::from(*c))).collect();
+        dto::ByteStream::new(futures::stream::iter(chunks).chain(futures::stream::pending()))
+    }
 
-        let multipart_upload = if let Some(multipart_upload) = multipart_upload {
-            multipart_upload
-        } else {
-            let err = code_error!(InvalidPart, "Missing multipart_upload");
-            return Err(err.into());
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn aborted_writes() {
+        let root = Path::new("target/s3-test-aborted-writes");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+        let fs = FileSystem::new(root).unwrap();
+        let timeout = Duration::from_millis(100);
+
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: "a/b".into(),
+            body: Some(stalled_body(&["Hello", "World"])),
+            ..PutObjectRequest::default()
         };
+        assert!(tokio::time::timeout(timeout, fs.put_object(input))
+            .await
+            .is_err());
+        assert!(!root.join("asd/a/b").exists());
 
-        let object_path = trace_try!(self.get_object_path(&bucket, &key));
-        let file = trace_try!(File::create(&object_path).await);
-        let mut writer = BufWriter::new(file);
+        let input = CreateMultipartUploadRequest {
+            bucket: "asd".into(),
+            key: "a/b".into(),
+            ..CreateMultipartUploadRequest::default()
+        };
+        let upload_id = fs
+            .create_multipart_upload(input)
+            .await
+            .unwrap()
+            .upload_id
+            .unwrap();
+        let input = UploadPartRequest {
+            bucket: "asd".into(),
+            key: "a/b".into(),
+            upload_id: upload_id.clone(),
+            part_number: 1,
+            body: Some(stalled_body(&["Hello"])),
+            ..UploadPartRequest::default()
+        };
+        assert!(tokio::time::timeout(timeout, fs.upload_part(input))
+            .await
+            .is_err());
+        assert!(!fs
+            .get_upload_part_path("asd", &upload_id, 1)
+            .unwrap()
+            .exists());
 
-        let mut cnt: i64 = 0;
-        for part in multipart_upload.parts.into_iter().flatten() {
-            let part_number = trace_try!(part
-                .part_number
-                .ok_or_else(|| { io::Error::new(io::ErrorKind::NotFound, "Missing part_number") }));
-            cnt = cnt.wrapping_add(1);
-            if part_number != cnt {
-                trace_try!(Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    "InvalidPartOrder"
-                )));
-            }
-            let part_path_str = format!(".upload_id-{}.part-{}", upload_id, part_number);
-            let part_path = trace_try!(Path::new(&part_path_str).absolutize_virtually(&self.root));
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: "c".into(),
+            body: Some(b"Hello".to_vec().into()),
+            ..PutObjectRequest::default()
+        };
+        let _ = fs.put_object(input).await.unwrap();
+        let input = AppendObjectRequest {
+            input: PutObjectRequest {
+                bucket: "asd".into(),
+                key: "c".into(),
+                body: Some(stalled_body(&["World"])),
+                ..PutObjectRequest::default()
+            },
+            write_offset_bytes: 5,
+        };
+        assert!(tokio::time::timeout(timeout, fs.append_object(input))
+            .await
+            .is_err());
+        assert_eq!(std::fs::read(root.join("asd/c")).unwrap(), b"Hello");
 
-            let mut reader = trace_try!(File::open(&part_path).await);
-            let (ret, duration) =
-                time::count_duration(futures::io::copy(&mut reader, &mut writer)).await;
-            let size = trace_try!(ret);
+        // an aborted overwrite keeps the previous object with its json files
+        let input = PutObjectRequest {
+            bucket: "asd".into(),
+            key: "c".into(),
+            body: Some(stalled_body(&["World"])),
+            metadata: Some(HashMap::from([("a".into(), "b".into())])),
+            ..PutObjectRequest::default()
+        };
+        assert!(tokio::time::timeout(timeout, fs.put_object(input))
+            .await
+            .is_err());
+        assert_eq!(std::fs::read(root.join("asd/c")).unwrap(), b"Hello");
+        let checksum = fs.load_checksum("asd", "c").await.unwrap();
+        assert_eq!(
+            checksum.as_deref(),
+            Some("8b1a9953c4611296a827abf8c47804d7")
+        );
+        assert_eq!(fs.load_metadata("asd", "c").await.unwrap(), None);
 
-            debug!(
-                from = %part_path.display(),
-                to = %object_path.display(),
-                ?size,
-                ?duration,
-                "CompleteMultipartUpload: write file",
-            );
-            trace_try!(async_fs::remove_file(&part_path).await);
+        // and no temp file is left
+        let is_temp = |entry: io::Result<std::fs::DirEntry>| {
+            let name = entry.unwrap().file_name();
+            name.to_string_lossy().starts_with(WRITE_PREFIX)
+        };
+        assert!(!std::fs::read_dir(root).unwrap().any(is_temp));
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn gzip_uploads() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let root = Path::new("target/s3-test-gzip-uploads");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
         }
-        drop(writer);
+        std::fs::create_dir_all(root.join("asd")).unwrap();
 
-        let file_size = trace_try!(async_fs::metadata(&object_path).await).len();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"Hello, World!").unwrap();
+        let compressed = encoder.finish().unwrap();
 
-        let (md5_sum, duration) = {
-            let (ret, duration) = time::count_duration(self.get_md5_sum(&bucket, &key)).await;
-            let md5_sum = trace_try!(ret);
-            (md5_sum, duration)
+        let put = |key: &str| PutObjectRequest {
+            bucket: "asd".into(),
+            key: key.into(),
+            body: Some(compressed.clone().into()),
+            content_encoding: Some("gzip".into()),
+            ..PutObjectRequest::default()
+        };
+        let head = |key: &str| HeadObjectRequest {
+            bucket: "asd".into(),
+            key: key.into(),
+            ..HeadObjectRequest::default()
         };
 
-        debug!(
-            sum = ?md5_sum,
-            path = %object_path.display(),
-            size = ?file_size,
-            ?duration,
-            "CompleteMultipartUpload: calculate md5 sum",
+        let mut config = FileSystemConfig {
+            content_encoding: ContentEncodingPolicy::Record,
+            ..FileSystemConfig::default()
+        };
+        let fs = FileSystem::new_with_config(root, config.clone()).unwrap();
+        let _ = fs.put_object(put("recorded")).await.unwrap();
+        let output = fs.head_object(head("recorded")).await.unwrap();
+        assert_eq!(output.content_encoding.as_deref(), Some("gzip"));
+        assert_eq!(
+            output.content_length,
+            Some(compressed.len().try_into().unwrap())
         );
+        let record = fs.load_encoding("asd", "recorded").await.unwrap().unwrap();
+        assert_eq!(record.stored_size, u64::try_from(compressed.len()).unwrap());
+        assert_eq!(record.logical_size, 13);
 
-        let e_tag = format!("\"{}\"", md5_sum);
-        let output = CompleteMultipartUploadOutput {
-            bucket: Some(bucket),
-            key: Some(key),
-            e_tag: Some(e_tag),
-            ..CompleteMultipartUploadOutput::default()
+        config.content_encoding = ContentEncodingPolicy::Decode;
+        let fs = FileSystem::new_with_config(root, config).unwrap();
+        let output = fs.put_object(put("decoded")).await.unwrap();
+        assert_eq!(
+            output.e_tag.as_deref(),
+            Some("\"65a8e27d8879283831b664bd8b7f0ad4\"")
+        );
+        let output = fs.head_object(head("decoded")).await.unwrap();
+        assert_eq!(output.content_encoding, None);
+        assert_eq!(output.content_length, Some(13));
+
+        // an object written again without the encoding forgets it
+        let input = PutObjectRequest {
+            content_encoding: None,
+            ..put("recorded")
         };
-        Ok(output)
+        let _ = fs.put_object(input).await.unwrap();
+        let output = fs.head_object(head("recorded")).await.unwrap();
+        assert_eq!(output.content_encoding, None);
+
+        let input = PutObjectRequest {
+            body: Some(b"not gzip".to_vec().into()),
+            ..put("invalid")
+        };
+        assert!(fs.put_object(input).await.is_err());
+        assert!(!root.join("asd/invalid").exists());
+    }
+
+    #[tokio::test]
+    async fn validators() {
+        fn code<T, E: fmt::Debug>(ret: S3StorageResult<T, E>) -> Option<S3ErrorCode> {
+            match ret {
+                Err(S3StorageError::Other(e)) => Some(e.code()),
+                Err(S3StorageError::Operation(e)) => panic!("{e:?}"),
+                Ok(_) => None,
+            }
+        }
+
+        let root = Path::new("target/s3-test-validators");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+
+        let put = |storage_class: &str| PutObjectRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            body: Some(b"Hello".to_vec().into()),
+            storage_class: Some(storage_class.into()),
+            ..PutObjectRequest::default()
+        };
+        let upload = |acl: &str, key: &str| CreateMultipartUploadRequest {
+            bucket: "asd".into(),
+            key: "a".into(),
+            acl: Some(acl.into()),
+            metadata: Some(HashMap::from([(key.into(), "1".into())])),
+            ..CreateMultipartUploadRequest::default()
+        };
+        let bucket = |acl: &str| CreateBucketRequest {
+            bucket: "qwe".into(),
+            acl: Some(acl.into()),
+            ..CreateBucketRequest::default()
+        };
+
+        let mut fs = FileSystem::new(root).unwrap();
+        assert!(code(fs.put_object(put("STANDARD")).await).is_none());
+        assert!(matches!(
+            code(fs.put_object(put("GLACIER")).await),
+            Some(S3ErrorCode::InvalidStorageClass)
+        ));
+        assert!(code(
+            fs.create_multipart_upload(upload("public-read", "secret"))
+                .await
+        )
+        .is_none());
+
+        fs.set_storage_class_validator(|class| class == "GLACIER");
+        fs.set_acl_validator(|acl| acl == "private");
+        fs.set_metadata_key_validator(|key| !key.starts_with("secret"));
+
+        assert!(code(fs.put_object(put("GLACIER")).await).is_none());
+        assert!(matches!(
+            code(fs.put_object(put("STANDARD")).await),
+            Some(S3ErrorCode::InvalidStorageClass)
+        ));
+        assert!(code(fs.create_multipart_upload(upload("private", "color")).await).is_none());
+        assert!(matches!(
+            code(
+                fs.create_multipart_upload(upload("public-read", "color"))
+                    .await
+            ),
+            Some(S3ErrorCode::InvalidArgument)
+        ));
+        assert!(matches!(
+            code(
+                fs.create_multipart_upload(upload("private", "secret-key"))
+                    .await
+            ),
+            Some(S3ErrorCode::InvalidArgument)
+        ));
+        assert!(matches!(
+            code(fs.create_bucket(bucket("public-read")).await),
+            Some(S3ErrorCode::InvalidArgument)
+        ));
+        assert!(!root.join("qwe").exists());
+        assert!(code(fs.create_bucket(bucket("private")).await).is_none());
     }
 }
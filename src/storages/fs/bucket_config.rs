@@ -0,0 +1,54 @@
+//! bucket configurations
+
+use super::{remove_file_if_exists, rt, FileSystem};
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use path_absolutize::Absolutize;
+use serde::{Deserialize, Serialize};
+
+/// Recorded configurations of a bucket (custom format)
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BucketConfig {
+    /// whether transfer acceleration is enabled, `None` if it has never been set
+    pub accelerate: Option<bool>,
+    /// whether the requester pays
+    pub requester_pays: bool,
+}
+
+/// resolves the path of bucket configurations under the virtual root (custom format)
+pub fn config_path(fs: &FileSystem, bucket: &str) -> io::Result<PathBuf> {
+    let encoded = base64_simd::URL_SAFE_NO_PAD.encode_to_string(bucket);
+    let file_path_str = format!(".bucket-{encoded}.config.json");
+    let ans = Path::new(&file_path_str)
+        .absolutize_virtually(&fs.root)?
+        .into();
+    Ok(ans)
+}
+
+/// loads the configurations of a bucket, returns the default if none is recorded
+pub async fn load(fs: &FileSystem, bucket: &str) -> io::Result<BucketConfig> {
+    let path = config_path(fs, bucket)?;
+    match rt::read(&path).await {
+        Ok(content) => serde_json::from_slice(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BucketConfig::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// saves the configurations of a bucket
+pub async fn save(fs: &FileSystem, bucket: &str, config: &BucketConfig) -> io::Result<()> {
+    let path = config_path(fs, bucket)?;
+    let content =
+        serde_json::to_vec(config).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    rt::write(&path, &content).await
+}
+
+/// removes the configurations when a bucket is deleted
+pub async fn remove(fs: &FileSystem, bucket: &str) -> io::Result<()> {
+    let path = config_path(fs, bucket)?;
+    remove_file_if_exists(&path).await
+}
@@ -0,0 +1,83 @@
+//! builder of [`FileSystem`]
+
+use super::{ETagStrategy, FileSystem, FileSystemConfig, FsyncPolicy, MetadataBackend};
+
+use std::io;
+use std::path::PathBuf;
+
+/// A builder of [`FileSystem`], which tunes the tradeoffs between durability and performance
+///
+/// Options which are not set keep the defaults of [`FileSystemConfig`].
+///
+/// ```no_run
+/// use s3_server::storages::fs::{FileSystem, FsyncPolicy};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let fs = FileSystem::builder("/data/s3")
+///     .with_read_chunk_size(64 * 1024)
+///     .with_temp_dir("/data/s3-uploads")
+///     .with_fsync(FsyncPolicy::Always)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileSystemBuilder {
+    /// root path
+    root: PathBuf,
+    /// options of the storage
+    config: FileSystemConfig,
+}
+
+impl FileSystemBuilder {
+    /// Constructs a builder of a file system storage located at `root`
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            config: FileSystemConfig::default(),
+        }
+    }
+
+    /// Sets where the metadata of objects is stored, json files by default
+    #[must_use]
+    pub const fn with_metadata_backend(mut self, backend: MetadataBackend) -> Self {
+        self.config.metadata = backend;
+        self
+    }
+
+    /// Sets how the `ETag` of written objects is computed, md5 by default
+    #[must_use]
+    pub const fn with_etag(mut self, strategy: ETagStrategy) -> Self {
+        self.config.etag = strategy;
+        self
+    }
+
+    /// Sets the size of the chunks read from object files, 4 KiB by default
+    #[must_use]
+    pub const fn with_read_chunk_size(mut self, size: usize) -> Self {
+        self.config.read_buf_size = size;
+        self
+    }
+
+    /// Stores the files of multipart uploads in `dir` instead of the root
+    #[must_use]
+    pub fn with_temp_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.temp_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets when written object files are synced to the disk, never by default
+    #[must_use]
+    pub const fn with_fsync(mut self, policy: FsyncPolicy) -> Self {
+        self.config.fsync = policy;
+        self
+    }
+
+    /// Constructs the storage
+    /// # Errors
+    /// Returns an `Err` if current working directory is invalid, the root or the temp dir
+    /// doesn't exist, or the read chunk size is zero
+    pub fn build(self) -> io::Result<FileSystem> {
+        FileSystem::new_with_config(self.root, self.config)
+    }
+}
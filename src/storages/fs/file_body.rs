@@ -0,0 +1,98 @@
+//! file-backed bodies of whole objects
+
+use super::{is_same_file, rt};
+
+use std::fs::{File, Metadata};
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+use futures::stream::{self, Stream};
+use hyper::body::Bytes;
+
+/// max size of a chunk of a file-backed body
+pub const CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// opens the file of `opened` again on the blocking thread pool
+///
+/// Fails if the path is not the file of `opened`, which has been replaced by an overwrite.
+pub async fn open(path: PathBuf, opened: Metadata) -> io::Result<File> {
+    rt::unblock(move || {
+        let file = File::open(&path)?;
+        if !is_same_file(&file.metadata()?, &opened) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the file has been replaced",
+            ));
+        }
+        Ok(file)
+    })
+    .await
+}
+
+/// returns a stream of the first `len` bytes of `file`
+///
+/// Each chunk is read on the blocking thread pool straight into the buffer
+/// which is handed to the connection, without zero-filling it first.
+/// A file truncated concurrently fails the stream with `UnexpectedEof`.
+pub fn stream(file: File, len: u64) -> impl Stream<Item = io::Result<Bytes>> + Send + Sync {
+    stream::try_unfold((file, len), |(file, remaining)| async move {
+        if remaining == 0 {
+            return Ok(None);
+        }
+        let chunk_len = remaining.min(CHUNK_SIZE);
+        let (file, chunk) = rt::unblock(move || read_chunk(file, chunk_len)).await?;
+        Ok(Some((chunk, (file, remaining.wrapping_sub(chunk_len)))))
+    })
+}
+
+/// reads the next `len` bytes of the file
+fn read_chunk(file: File, len: u64) -> io::Result<(File, Bytes)> {
+    let capacity =
+        usize::try_from(len).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut buf = Vec::with_capacity(capacity);
+    let nread = (&file).take(len).read_to_end(&mut buf)?;
+    if nread < capacity {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "the file has been truncated",
+        ));
+    }
+    Ok((file, buf.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::TryStreamExt;
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn file_body() {
+        let dir = Path::new("target/s3-test-file-body-stream");
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join("file");
+        std::fs::write(&path, "Hello World!").unwrap();
+        let opened = std::fs::metadata(&path).unwrap();
+
+        let read = |len| {
+            let opening = open(path.clone(), opened.clone());
+            async move {
+                stream(opening.await.unwrap(), len)
+                    .try_collect::<Vec<_>>()
+                    .await
+            }
+        };
+        assert_eq!(read(12).await.unwrap(), ["Hello World!"]);
+
+        // the file is shorter than the length of the body, as after a truncation
+        let err = read(13).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        // an overwrite replaces the file
+        let temp_path = dir.join("temp");
+        std::fs::write(&temp_path, "Hello World!").unwrap();
+        std::fs::rename(&temp_path, &path).unwrap();
+        assert!(open(path, opened).await.is_err());
+    }
+}
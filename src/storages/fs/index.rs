@@ -0,0 +1,379 @@
+//! in-memory listing index, enabled by the feature `listing-index`
+//!
+//! The index keeps the sorted keys of each bucket, so a page of `ListObjectsV2` is selected
+//! without walking the whole bucket. It is updated by the writes of the storage at once,
+//! and by file system notifications, which also catch the changes made by other processes.
+//!
+//! A bucket is cold until it has been walked once. It becomes cold again when a notification
+//! can not be applied precisely, e.g. when a directory is moved into the bucket.
+
+use super::listing::{self, Entry, PageParams};
+use super::rt;
+use super::walk::ObjectFile;
+
+use crate::path::S3Path;
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tracing::debug;
+
+/// number of keys taken from the index at a time
+const BATCH_SIZE: usize = 256;
+
+/// the indexed keys of a bucket
+#[derive(Debug, Default)]
+struct BucketIndex {
+    /// increased by every change, so a walk which races with a change is not installed
+    version: u64,
+    /// sorted keys, or `None` if the bucket is cold
+    keys: Option<BTreeSet<String>>,
+}
+
+/// the state shared with the watcher
+#[derive(Debug)]
+struct State {
+    /// root path of the storage
+    root: PathBuf,
+    /// buckets by name
+    buckets: Mutex<HashMap<String, BucketIndex>>,
+}
+
+/// An in-memory index of the keys of each bucket
+pub struct ListingIndex {
+    /// shared state
+    state: Arc<State>,
+    /// the watcher stops when it is dropped
+    _watcher: Option<RecommendedWatcher>,
+}
+
+impl fmt::Debug for ListingIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ListingIndex")
+            .field("root", &self.state.root)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ListingIndex {
+    /// starts watching the root of a storage, all buckets are cold
+    pub fn start(root: &Path) -> io::Result<Self> {
+        let state = Arc::new(State {
+            root: root.to_owned(),
+            buckets: Mutex::new(HashMap::new()),
+        });
+
+        let handler_state = Arc::clone(&state);
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+                Ok(event) => handler_state.apply_event(&event),
+                Err(e) => {
+                    debug!(error = %e, "listing index: watcher error");
+                    handler_state.invalidate_all();
+                }
+            })
+            .map_err(watcher_error)?;
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .map_err(watcher_error)?;
+
+        Ok(Self {
+            state,
+            _watcher: Some(watcher),
+        })
+    }
+
+    /// updates the index after the file at `path` is written or removed
+    pub fn update(&self, path: &Path) {
+        self.state.update(path);
+    }
+
+    /// marks a bucket cold, after a change which moves many files
+    pub fn invalidate(&self, bucket: &str) {
+        self.state.invalidate(bucket);
+    }
+
+    /// returns the version of a bucket, which must be taken before walking it
+    pub fn version(&self, bucket: &str) -> u64 {
+        self.state
+            .lock()
+            .get(bucket)
+            .map_or(0, |index| index.version)
+    }
+
+    /// installs the keys of a walked bucket unless it has changed since `version`
+    pub fn warm(&self, bucket: &str, version: u64, keys: impl IntoIterator<Item = String>) {
+        let mut buckets = self.state.lock();
+        let index = buckets.entry(bucket.to_owned()).or_default();
+        if index.version == version {
+            index.keys = Some(keys.into_iter().collect());
+        }
+        drop(buckets);
+    }
+
+    /// returns at most `limit` keys after `start` which begin with `prefix`,
+    /// or `None` if the bucket is cold
+    fn keys_after(
+        &self,
+        bucket: &str,
+        start: Option<&str>,
+        prefix: &str,
+        limit: usize,
+    ) -> Option<Vec<String>> {
+        let start = match start {
+            Some(s) if s >= prefix => Bound::Excluded(s),
+            Some(_) | None => Bound::Included(prefix),
+        };
+        let buckets = self.state.lock();
+        let batch = buckets
+            .get(bucket)?
+            .keys
+            .as_ref()?
+            .range::<str, _>((start, Bound::Unbounded))
+            .take_while(|key| key.starts_with(prefix))
+            .take(limit)
+            .cloned()
+            .collect();
+        drop(buckets);
+        Some(batch)
+    }
+}
+
+/// Returns the object files of the entries of a page and the entry after it,
+/// or `None` if the bucket is cold
+///
+/// The objects under a common prefix are skipped once one of them has been kept,
+/// so only the entries of the page are examined, besides the keys which are rolled up.
+pub async fn object_files(
+    index: &ListingIndex,
+    bucket_path: &Path,
+    bucket: &str,
+    params: &PageParams<'_>,
+    keep: impl Fn(&ObjectFile) -> bool,
+) -> io::Result<Option<Vec<ObjectFile>>> {
+    let mut files = Vec::new();
+    let mut count: usize = 0;
+    let mut last_common_prefix: Option<String> = None;
+    let mut start = params.marker.map(str::to_owned);
+
+    loop {
+        let keys = match index.keys_after(bucket, start.as_deref(), params.prefix, BATCH_SIZE) {
+            Some(keys) => keys,
+            None => return Ok(None),
+        };
+        let is_last_batch = keys.len() < BATCH_SIZE;
+        let last_key = keys.last().cloned();
+
+        for key in keys {
+            let entry = match listing::entry_of(&key, params) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            if let Entry::CommonPrefix(common_prefix) = entry {
+                if last_common_prefix.as_deref() == Some(common_prefix) {
+                    continue;
+                }
+            }
+
+            let path = bucket_path.join(&key);
+            let metadata = match rt::metadata(&path).await {
+                Ok(metadata) if metadata.is_file() => metadata,
+                Ok(_) => continue,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    index.update(&path);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let entry_common_prefix = match entry {
+                Entry::Key(_) => None,
+                Entry::CommonPrefix(common_prefix) => Some(common_prefix.to_owned()),
+            };
+            let file = ObjectFile { key, metadata };
+            if !keep(&file) {
+                continue;
+            }
+
+            // one more entry than the page tells whether the page is truncated
+            count = count.wrapping_add(1);
+            files.push(file);
+            if count > params.max_keys {
+                return Ok(Some(files));
+            }
+            if entry_common_prefix.is_some() {
+                last_common_prefix = entry_common_prefix;
+            }
+        }
+
+        if is_last_batch {
+            return Ok(Some(files));
+        }
+        start = last_key;
+    }
+}
+
+/// converts an error of the watcher
+fn watcher_error(e: notify::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e)
+}
+
+impl State {
+    /// locks the buckets
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, BucketIndex>> {
+        self.buckets.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// applies a notification
+    fn apply_event(&self, event: &Event) {
+        if event.need_rescan() {
+            self.invalidate_all();
+            return;
+        }
+        if let EventKind::Access(_) = event.kind {
+            return;
+        }
+        for path in &event.paths {
+            self.update(path);
+        }
+    }
+
+    /// splits a path under the root into a bucket name and a key, which is empty for a bucket
+    fn split_path<'p>(&self, path: &'p Path) -> Option<(&'p str, String)> {
+        let rel = path.strip_prefix(&self.root).ok()?;
+        let mut components = rel.components();
+        let bucket = components.next()?.as_os_str().to_str()?;
+        if !S3Path::check_bucket_name(bucket) {
+            return None;
+        }
+        let key = components.as_path().to_string_lossy().into_owned();
+        Some((bucket, key))
+    }
+
+    /// inserts or removes the key of a path by checking the file
+    fn update(&self, path: &Path) {
+        let (bucket, key) = match self.split_path(path) {
+            Some(split) => split,
+            None => return,
+        };
+        if key.is_empty() {
+            self.invalidate(bucket);
+            return;
+        }
+
+        let file_type = match std::fs::symlink_metadata(path) {
+            Ok(metadata) => Some(metadata.file_type()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(_) => {
+                self.invalidate(bucket);
+                return;
+            }
+        };
+        if file_type.map_or(false, |t| t.is_dir()) {
+            // the files in a directory which is moved in are not notified
+            self.invalidate(bucket);
+            return;
+        }
+
+        let mut buckets = self.lock();
+        let index = buckets.entry(bucket.to_owned()).or_default();
+        index.version = index.version.wrapping_add(1);
+        if let Some(ref mut keys) = index.keys {
+            if file_type.is_some() {
+                let _inserted = keys.insert(key);
+            } else {
+                let _removed = keys.remove(&key);
+                // a removed directory takes its keys along, which are a range of the sorted keys
+                let dir_prefix = format!("{key}/");
+                let under_dir: Vec<String> = keys
+                    .range::<str, _>((Bound::Included(dir_prefix.as_str()), Bound::Unbounded))
+                    .take_while(|k| k.starts_with(&dir_prefix))
+                    .cloned()
+                    .collect();
+                for k in &under_dir {
+                    let _removed_under_dir = keys.remove(k);
+                }
+            }
+        }
+        drop(buckets);
+    }
+
+    /// marks a bucket cold
+    fn invalidate(&self, bucket: &str) {
+        let mut buckets = self.lock();
+        let index = buckets.entry(bucket.to_owned()).or_default();
+        index.version = index.version.wrapping_add(1);
+        index.keys = None;
+        drop(buckets);
+    }
+
+    /// marks all buckets cold
+    fn invalidate_all(&self) {
+        self.lock().values_mut().for_each(|index| {
+            index.version = index.version.wrapping_add(1);
+            index.keys = None;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::shadow_unrelated)]
+    fn update() {
+        let root = Path::new("target/s3-test-listing-index");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd/a")).unwrap();
+        let root = root.canonicalize().unwrap();
+
+        // without the watcher, which would change the versions concurrently
+        let index = ListingIndex {
+            state: Arc::new(State {
+                root: root.clone(),
+                buckets: Mutex::new(HashMap::new()),
+            }),
+            _watcher: None,
+        };
+
+        let keys = |index: &ListingIndex| index.keys_after("asd", None, "", 100);
+        assert_eq!(keys(&index), None);
+
+        let version = index.version("asd");
+        index.warm("asd", version, ["a/b".to_owned(), "c".to_owned()]);
+        assert_eq!(keys(&index).unwrap(), ["a/b", "c"]);
+
+        std::fs::write(root.join("asd/d"), "").unwrap();
+        index.update(&root.join("asd/d"));
+        assert_eq!(keys(&index).unwrap(), ["a/b", "c", "d"]);
+
+        index.update(&root.join("asd/a"));
+        assert_eq!(keys(&index), None);
+
+        // a walk which races with a change is not installed
+        let version = index.version("asd");
+        index.update(&root.join("asd/c"));
+        index.warm("asd", version, ["c".to_owned()]);
+        assert_eq!(keys(&index), None);
+
+        let version = index.version("asd");
+        let warm_keys = ["a.b", "a/b", "a/c", "a0", "d"];
+        index.warm("asd", version, warm_keys.map(ToOwned::to_owned));
+        index.update(&root.join("asd/a/c"));
+        assert_eq!(keys(&index).unwrap(), ["a.b", "a/b", "a0", "d"]);
+        // only the keys under the directory are removed along with it
+        std::fs::remove_dir_all(root.join("asd/a")).unwrap();
+        index.update(&root.join("asd/a"));
+        assert_eq!(keys(&index).unwrap(), ["a.b", "a0", "d"]);
+
+        let prefixed = index.keys_after("asd", Some("a"), "d", 100);
+        assert_eq!(prefixed.unwrap(), ["d"]);
+    }
+}
@@ -0,0 +1,239 @@
+//! inventory reports
+
+use super::listing::MAX_KEYS;
+use super::{rt, walk, FileSystem};
+
+use crate::dto::HeadObjectsRequest;
+use crate::storage::S3Storage;
+use crate::utils::crypto;
+
+use std::io;
+
+use chrono::Utc;
+use md5::{Digest, Md5};
+use uuid::Uuid;
+
+/// columns of inventory data files
+const FILE_SCHEMA: &str = "Bucket, Key, Size, LastModifiedDate, ETag, StorageClass";
+
+/// The destination of inventory reports
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct InventoryConfig {
+    /// inventory id, which is a part of the report keys
+    pub id: String,
+    /// the bucket where reports are written
+    pub destination_bucket: String,
+    /// key prefix of reports in the destination bucket
+    pub prefix: String,
+}
+
+impl InventoryConfig {
+    /// Constructs a config which writes reports to the root of `destination_bucket`
+    pub fn new(id: impl Into<String>, destination_bucket: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            destination_bucket: destination_bucket.into(),
+            prefix: String::new(),
+        }
+    }
+}
+
+/// A written inventory report
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Inventory {
+    /// key of the manifest in the destination bucket
+    pub manifest_key: String,
+    /// key of the CSV data file in the destination bucket
+    pub data_key: String,
+    /// number of listed objects
+    pub object_count: u64,
+    /// total size of listed objects in bytes
+    pub total_size: u64,
+}
+
+/// writes an inventory report of `source_bucket`, see [`FileSystem::write_inventory`]
+pub async fn write(
+    fs: &FileSystem,
+    source_bucket: &str,
+    config: &InventoryConfig,
+) -> io::Result<Inventory> {
+    let report_dir = format!("{}{}/{}", config.prefix, source_bucket, config.id);
+
+    let bucket_path = fs.get_bucket_path(source_bucket)?;
+    let keys: Vec<String> = walk::object_files(&bucket_path)
+        .await?
+        .into_iter()
+        .map(|file| file.key)
+        // previous reports of the same inventory
+        .filter(|key| source_bucket != config.destination_bucket || !key.starts_with(&report_dir))
+        .collect();
+
+    let mut csv = String::new();
+    let mut object_count: u64 = 0;
+    let mut total_size: u64 = 0;
+    for chunk in keys.chunks(MAX_KEYS) {
+        let input = HeadObjectsRequest {
+            bucket: source_bucket.to_owned(),
+            keys: chunk.to_vec(),
+        };
+        let output = fs
+            .head_objects(input)
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        for (key, stat) in chunk.iter().zip(output.objects) {
+            // removed by a concurrent write
+            let stat = match stat {
+                Some(stat) => stat,
+                None => continue,
+            };
+
+            let e_tag = match stat.e_tag {
+                Some(e_tag) => e_tag.trim_matches('"').to_owned(),
+                None => fs.get_checksum(source_bucket, key, fs.config.etag).await?,
+            };
+            let size = stat
+                .content_length
+                .and_then(|n| u64::try_from(n).ok())
+                .unwrap_or_default();
+            let last_modified = stat.last_modified.unwrap_or_default();
+
+            let fields = [
+                source_bucket,
+                key,
+                &size.to_string(),
+                &last_modified,
+                &e_tag,
+                "STANDARD",
+            ];
+            write_csv_record(&mut csv, &fields);
+
+            object_count = object_count.wrapping_add(1);
+            total_size = total_size.saturating_add(size);
+        }
+    }
+
+    let data_key = format!("{}/data/{}.csv", report_dir, Uuid::new_v4());
+    let data_md5 = crypto::to_hex_string(Md5::digest(csv.as_bytes()));
+    save_report(fs, &config.destination_bucket, &data_key, csv.as_bytes()).await?;
+
+    let created_at = Utc::now();
+    let manifest = serde_json::json!({
+        "sourceBucket": source_bucket,
+        "destinationBucket": config.destination_bucket,
+        "version": "2016-11-30",
+        "creationTimestamp": created_at.timestamp_millis().to_string(),
+        "fileFormat": "CSV",
+        "fileSchema": FILE_SCHEMA,
+        "files": [{
+            "key": data_key,
+            "size": csv.len(),
+            "MD5checksum": data_md5,
+        }],
+    });
+    let manifest_key = format!(
+        "{}/{}/manifest.json",
+        report_dir,
+        created_at.format("%Y-%m-%dT%H-%MZ")
+    );
+    let manifest =
+        serde_json::to_vec(&manifest).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    save_report(fs, &config.destination_bucket, &manifest_key, &manifest).await?;
+
+    Ok(Inventory {
+        manifest_key,
+        data_key,
+        object_count,
+        total_size,
+    })
+}
+
+/// appends a CSV record whose fields are all quoted
+fn write_csv_record(buf: &mut String, fields: &[&str]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            buf.push(',');
+        }
+        buf.push('"');
+        buf.push_str(&field.replace('"', "\"\""));
+        buf.push('"');
+    }
+    buf.push('\n');
+}
+
+/// writes a report file as an object of the destination bucket
+async fn save_report(fs: &FileSystem, bucket: &str, key: &str, content: &[u8]) -> io::Result<()> {
+    let bucket_path = fs.get_bucket_path(bucket)?;
+    if !bucket_path.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "destination bucket does not exist",
+        ));
+    }
+    let object_path = fs.get_object_path(bucket, key)?;
+    if let Some(dir) = object_path.parent() {
+        rt::create_dir_all(dir).await?;
+    }
+    rt::write(&object_path, content).await?;
+
+    let mut hasher = fs.config.etag.hasher();
+    hasher.update(content);
+    let checksum = fs.config.etag.checksum(hasher.finalize());
+    fs.save_checksum(bucket, key, &checksum).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    #[test]
+    fn csv_record() {
+        let mut buf = String::new();
+        write_csv_record(&mut buf, &["a", "b\"c", "d,e"]);
+        assert_eq!(buf, "\"a\",\"b\"\"c\",\"d,e\"\n");
+    }
+
+    #[tokio::test]
+    async fn inventory() {
+        let root = Path::new("target/s3-test-inventory");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd").join("b")).unwrap();
+        std::fs::create_dir_all(root.join("reports")).unwrap();
+        std::fs::write(root.join("asd").join("a"), "Hello").unwrap();
+        std::fs::write(root.join("asd").join("b").join("c"), "World!").unwrap();
+
+        let fs = FileSystem::new(root).unwrap();
+        let mut config = InventoryConfig::new("daily", "reports");
+        config.prefix = "inventory/".into();
+
+        let inventory = fs.write_inventory("asd", &config).await.unwrap();
+        assert_eq!(inventory.object_count, 2);
+        assert_eq!(inventory.total_size, 11);
+        assert!(inventory.data_key.starts_with("inventory/asd/daily/data/"));
+        assert!(inventory.manifest_key.starts_with("inventory/asd/daily/"));
+
+        let data = std::fs::read_to_string(root.join("reports").join(&inventory.data_key)).unwrap();
+        let lines: Vec<&str> = data.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("\"asd\",\"a\",\"5\","));
+        assert!(lines[0].ends_with(",\"8b1a9953c4611296a827abf8c47804d7\",\"STANDARD\""));
+        assert!(lines[1].starts_with("\"asd\",\"b/c\",\"6\","));
+
+        let manifest = std::fs::read(root.join("reports").join(&inventory.manifest_key)).unwrap();
+        let manifest: serde_json::Value = serde_json::from_slice(&manifest).unwrap();
+        assert_eq!(manifest["sourceBucket"], "asd");
+        assert_eq!(manifest["fileFormat"], "CSV");
+        assert_eq!(manifest["files"][0]["key"], inventory.data_key.as_str());
+
+        assert!(fs
+            .write_inventory("asd", &InventoryConfig::new("x", "missing"))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//! locks of object files
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use futures::lock::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// async locks of object paths, which serialize the writes of an object
+///
+/// A lock is created on demand, and removed when the last guard of it is dropped.
+#[derive(Debug, Default)]
+pub struct KeyLocks {
+    /// locks which are held or waited for
+    locks: Mutex<HashMap<PathBuf, Arc<AsyncMutex<()>>>>,
+}
+
+/// a guard which releases the lock of a path when dropped
+#[derive(Debug)]
+pub struct KeyLockGuard<'a> {
+    /// the locks
+    locks: &'a KeyLocks,
+    /// the locked path
+    path: PathBuf,
+    /// the guard of the lock, which is `None` while waiting
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl KeyLocks {
+    /// waits for the lock of a path
+    ///
+    /// The lock is also removed if the future is dropped while waiting.
+    pub async fn lock(&self, path: &Path) -> KeyLockGuard<'_> {
+        let mut key_guard = KeyLockGuard {
+            locks: self,
+            path: path.to_owned(),
+            guard: None,
+        };
+        let mutex = {
+            let mut locks = self.locks.lock().unwrap_or_else(PoisonError::into_inner);
+            Arc::clone(locks.entry(path.to_owned()).or_default())
+        };
+        key_guard.guard = Some(mutex.lock_owned().await);
+        key_guard
+    }
+}
+
+impl Drop for KeyLockGuard<'_> {
+    fn drop(&mut self) {
+        let mut locks = self
+            .locks
+            .locks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        drop(self.guard.take());
+        // the map holds the last reference if nobody else holds or waits for the lock
+        let unused = locks
+            .get(&self.path)
+            .map_or(false, |mutex| Arc::strong_count(mutex) == 1);
+        if unused {
+            let _: Option<Arc<AsyncMutex<()>>> = locks.remove(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn lock_and_remove() {
+        let locks = KeyLocks::default();
+        let path = Path::new("a");
+
+        let guard = locks.lock(path).await;
+        let other = locks.lock(Path::new("b")).await;
+        {
+            let waiting = locks.lock(path);
+            futures::pin_mut!(waiting);
+            assert!(futures::poll!(&mut waiting).is_pending());
+            drop(guard);
+            let _guard = waiting.await;
+        }
+        drop(other);
+        assert!(locks.locks.lock().unwrap().is_empty());
+
+        // a cancelled waiter does not leave the lock behind
+        let guard = locks.lock(path).await;
+        {
+            let waiting = locks.lock(path);
+            futures::pin_mut!(waiting);
+            assert!(futures::poll!(&mut waiting).is_pending());
+            drop(guard);
+        }
+        assert!(locks.locks.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,341 @@
+//! pagination of object listings
+//!
+//! Keys are listed in the lexicographic order of their UTF-8 bytes.
+//! A page does not depend on the previous pages, but only on the last entry of the previous page,
+//! which is the marker of `ListObjects` and is embedded in the continuation token of `ListObjectsV2`.
+//! So the pages of a listing are monotonic even when objects are written between the page requests:
+//! a key which exists during the whole listing is listed exactly once,
+//! and a key which is created or deleted meanwhile is listed at most once.
+
+/// max number of keys in a page
+pub const MAX_KEYS: usize = 1000;
+
+/// prefix of continuation tokens, which distinguishes them from arbitrary strings
+const TOKEN_PREFIX: &str = "v1.";
+
+/// encodes the last entry of a page as the continuation token of the next page
+pub fn encode_continuation_token(marker: &str) -> String {
+    let mut token = String::from(TOKEN_PREFIX);
+    token.push_str(&base64_simd::URL_SAFE_NO_PAD.encode_to_string(marker));
+    token
+}
+
+/// decodes the marker embedded in a continuation token, returns `None` if the token is invalid
+pub fn decode_continuation_token(token: &str) -> Option<String> {
+    let encoded = token.strip_prefix(TOKEN_PREFIX)?;
+    let bytes = base64_simd::URL_SAFE_NO_PAD.decode_to_vec(encoded).ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// listing parameters
+#[derive(Debug, Clone, Copy)]
+pub struct PageParams<'a> {
+    /// keys must begin with the prefix
+    pub prefix: &'a str,
+    /// keys containing the delimiter after the prefix are rolled up into common prefixes
+    pub delimiter: Option<&'a str>,
+    /// listing starts after the marker
+    pub marker: Option<&'a str>,
+    /// max number of contents and common prefixes
+    pub max_keys: usize,
+}
+
+/// a page of listed objects
+#[derive(Debug)]
+pub struct Page<T> {
+    /// objects
+    pub contents: Vec<T>,
+    /// common prefixes
+    pub common_prefixes: Vec<String>,
+    /// whether there are more entries after this page
+    pub is_truncated: bool,
+    /// the last key or common prefix of a truncated page
+    pub next_marker: Option<String>,
+}
+
+/// an entry of a listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry<'k> {
+    /// an object key
+    Key(&'k str),
+    /// a common prefix which rolls up keys
+    CommonPrefix(&'k str),
+}
+
+/// Returns the entry of a key in a listing, or `None` if the key is not listed after the marker
+pub fn entry_of<'k>(key: &'k str, params: &PageParams<'_>) -> Option<Entry<'k>> {
+    if !key.starts_with(params.prefix) {
+        return None;
+    }
+    if params.marker.map_or(false, |m| key <= m) {
+        return None;
+    }
+
+    let delimiter = params.delimiter.filter(|d| !d.is_empty());
+    let common_prefix = delimiter.and_then(|d| {
+        let rest = key.get(params.prefix.len()..)?;
+        let idx = rest.find(d)?;
+        key.get(..params.prefix.len().wrapping_add(idx).wrapping_add(d.len()))
+    });
+
+    match common_prefix {
+        Some(common_prefix) if params.marker.map_or(false, |m| common_prefix <= m) => None,
+        Some(common_prefix) => Some(Entry::CommonPrefix(common_prefix)),
+        None => Some(Entry::Key(key)),
+    }
+}
+
+/// Selects a page from objects sorted by key
+///
+/// A common prefix which is not greater than the marker is skipped,
+/// so the next page can start after a common prefix.
+pub fn select_page<T>(
+    objects: impl IntoIterator<Item = T>,
+    key_of: impl Fn(&T) -> &str,
+    params: &PageParams<'_>,
+) -> Page<T> {
+    let mut page = Page {
+        contents: Vec::new(),
+        common_prefixes: Vec::new(),
+        is_truncated: false,
+        next_marker: None,
+    };
+    let mut count: usize = 0;
+    let mut last_entry: Option<String> = None;
+
+    for object in objects {
+        let key = key_of(&object);
+        let entry = match entry_of(key, params) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        if let Entry::CommonPrefix(common_prefix) = entry {
+            if page.common_prefixes.last().map(String::as_str) == Some(common_prefix) {
+                continue;
+            }
+        }
+
+        if count >= params.max_keys {
+            page.is_truncated = true;
+            break;
+        }
+        count = count.wrapping_add(1);
+
+        match entry {
+            Entry::Key(listed_key) => {
+                last_entry = Some(listed_key.to_owned());
+                page.contents.push(object);
+            }
+            Entry::CommonPrefix(common_prefix) => {
+                last_entry = Some(common_prefix.to_owned());
+                page.common_prefixes.push(common_prefix.to_owned());
+            }
+        }
+    }
+
+    if page.is_truncated {
+        page.next_marker = last_entry;
+    }
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    fn list(keys: &[&str], prefix: &str, delimiter: Option<&str>) -> Vec<String> {
+        let params = PageParams {
+            prefix,
+            delimiter,
+            marker: None,
+            max_keys: usize::MAX,
+        };
+        let page = select_page(keys.iter().copied(), |k| k, &params);
+        assert!(!page.is_truncated);
+        let mut entries: Vec<String> = page.contents.iter().map(|&k| k.to_owned()).collect();
+        entries.extend(page.common_prefixes);
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn delimiter() {
+        let keys = ["a", "a/b", "a/c/d", "a0", "b/c", "b/d"];
+        assert_eq!(list(&keys, "", Some("/")), ["a", "a/", "a0", "b/"]);
+        assert_eq!(list(&keys, "a/", Some("/")), ["a/b", "a/c/"]);
+        assert_eq!(list(&keys, "b", None), ["b/c", "b/d"]);
+    }
+
+    #[test]
+    fn marker() {
+        let keys = ["a", "a/b", "a/c", "b"];
+        let params = PageParams {
+            prefix: "",
+            delimiter: Some("/"),
+            marker: Some("a/"),
+            max_keys: 1,
+        };
+        let page = select_page(keys.iter().copied(), |k| k, &params);
+        assert_eq!(page.contents, ["b"]);
+        assert!(page.common_prefixes.is_empty());
+        assert!(!page.is_truncated);
+        assert_eq!(page.next_marker, None);
+    }
+
+    #[test]
+    fn continuation_token() {
+        for marker in ["", "a/b", "\u{e9}/ c+d=="] {
+            let token = encode_continuation_token(marker);
+            assert_eq!(decode_continuation_token(&token).as_deref(), Some(marker));
+        }
+        assert_eq!(decode_continuation_token("a/b"), None);
+        assert_eq!(decode_continuation_token("v1.!"), None);
+    }
+
+    /// xorshift64, returns a number in `0..n`
+    #[allow(
+        clippy::integer_division_remainder_used,
+        clippy::as_conversions,
+        clippy::cast_possible_truncation
+    )]
+    fn random_below(state: &mut u64, n: usize) -> usize {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        (*state % (n as u64)) as usize
+    }
+
+    /// compares paginated listings with a model which lists all entries at once
+    #[test]
+    fn model() {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let alphabet = ["a", "b", "/", "\u{e9}"];
+        let prefixes = ["", "a", "a/", "\u{e9}"];
+        let delimiters = [None, Some("/"), Some("b/"), Some("")];
+
+        for _ in 0..500 {
+            let mut keys = BTreeSet::new();
+            for _ in 0..random_below(&mut state, 20) {
+                let len = random_below(&mut state, 4) + 1;
+                let mut key = String::new();
+                for _ in 0..len {
+                    key.push_str(alphabet[random_below(&mut state, alphabet.len())]);
+                }
+                let _inserted = keys.insert(key);
+            }
+            let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+
+            let prefix = prefixes[random_below(&mut state, prefixes.len())];
+            let delimiter = delimiters[random_below(&mut state, delimiters.len())];
+            let max_keys = random_below(&mut state, 5) + 1;
+
+            let expected: Vec<String> = {
+                let mut entries = BTreeSet::new();
+                for key in keys.iter().filter(|k| k.starts_with(prefix)) {
+                    let (_, rest) = key.split_at(prefix.len());
+                    let entry = match delimiter.filter(|d| !d.is_empty()) {
+                        Some(d) => match rest.find(d) {
+                            Some(idx) => key.split_at(prefix.len() + idx + d.len()).0,
+                            None => key,
+                        },
+                        None => key,
+                    };
+                    let _inserted = entries.insert(entry.to_owned());
+                }
+                entries.into_iter().collect()
+            };
+
+            let mut actual: Vec<String> = Vec::new();
+            let mut marker: Option<String> = None;
+            loop {
+                let params = PageParams {
+                    prefix,
+                    delimiter,
+                    marker: marker.as_deref(),
+                    max_keys,
+                };
+                let page = select_page(keys.iter().copied(), |k| k, &params);
+
+                let mut entries: Vec<String> =
+                    page.contents.iter().map(|&k| k.to_owned()).collect();
+                entries.extend(page.common_prefixes);
+                entries.sort();
+                assert!(entries.len() <= max_keys);
+
+                if !page.is_truncated {
+                    assert!(page.next_marker.is_none());
+                    actual.extend(entries);
+                    break;
+                }
+                assert_eq!(entries.len(), max_keys);
+                assert_eq!(page.next_marker.as_ref(), entries.last());
+                marker = page.next_marker;
+                actual.extend(entries);
+            }
+
+            assert_eq!(actual, expected, "keys = {keys:?}, prefix = {prefix:?}, delimiter = {delimiter:?}, max_keys = {max_keys}");
+        }
+    }
+
+    /// writes random keys between the pages of listings
+    #[test]
+    fn concurrent_writes() {
+        fn random_key(state: &mut u64) -> String {
+            let alphabet = ["a", "b", "c", "/"];
+            let len = random_below(state, 4) + 1;
+            let mut key = String::new();
+            for _ in 0..len {
+                key.push_str(alphabet[random_below(state, alphabet.len())]);
+            }
+            key
+        }
+
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+
+        for _ in 0..200 {
+            let mut keys = BTreeSet::new();
+            for _ in 0..random_below(&mut state, 30) {
+                let _inserted = keys.insert(random_key(&mut state));
+            }
+            let initial = keys.clone();
+            let mut touched = BTreeSet::new();
+            let max_keys = random_below(&mut state, 4) + 1;
+
+            let mut listed: Vec<String> = Vec::new();
+            let mut marker: Option<String> = None;
+            loop {
+                let params = PageParams {
+                    prefix: "",
+                    delimiter: None,
+                    marker: marker.as_deref(),
+                    max_keys,
+                };
+                let page = select_page(keys.iter().cloned(), String::as_str, &params);
+                listed.extend(page.contents);
+                if !page.is_truncated {
+                    break;
+                }
+                marker = page.next_marker;
+
+                for _ in 0..random_below(&mut state, 3) {
+                    let key = random_key(&mut state);
+                    if !keys.remove(&key) {
+                        let _inserted = keys.insert(key.clone());
+                    }
+                    let _inserted = touched.insert(key);
+                }
+            }
+
+            // pages are monotonic, so no key is listed twice
+            let mut sorted = listed.clone();
+            sorted.sort();
+            sorted.dedup();
+            assert_eq!(listed, sorted);
+            for key in initial.difference(&touched) {
+                assert!(listed.contains(key), "{key:?} is missing in {listed:?}");
+            }
+        }
+    }
+}
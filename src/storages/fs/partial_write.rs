@@ -0,0 +1,144 @@
+//! rollback of partial writes
+
+use super::rt;
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use tracing::{debug, error};
+
+/// A guard which rolls back a write unless it is committed
+///
+/// New files are written to temp files, which are renamed into place on commit,
+/// so a failed overwrite leaves the previous files intact.
+/// The future of a request is dropped when the client disconnects in the middle of the body,
+/// so the rollback runs in `Drop` and uses blocking file operations, which are short.
+#[derive(Debug, Default)]
+pub struct PartialWrite {
+    /// temp files of the write
+    staged: Vec<Staged>,
+    /// files removed on commit, which are replaced by nothing
+    removed: Vec<PathBuf>,
+    /// a file appended by the write, with its original length
+    appended: Option<(PathBuf, u64)>,
+    /// whether the write has completed
+    committed: bool,
+}
+
+/// a temp file which is moved to its destination on commit
+#[derive(Debug)]
+struct Staged {
+    /// temp file
+    temp: PathBuf,
+    /// destination
+    dst: PathBuf,
+    /// whether the commit fails if the destination exists
+    create_new: bool,
+}
+
+impl Staged {
+    /// moves the temp file to its destination
+    ///
+    /// A new destination is created by a hard link, which fails with `AlreadyExists`
+    /// atomically if it exists, then the temp file is removed.
+    async fn commit(&self) -> io::Result<()> {
+        if !self.create_new {
+            return rt::rename(&self.temp, &self.dst).await;
+        }
+        rt::hard_link(&self.temp, &self.dst).await?;
+        if let Err(e) = rt::remove_file(&self.temp).await {
+            error!(path = %self.temp.display(), error = %e, "failed to remove temp file");
+        }
+        Ok(())
+    }
+}
+
+impl PartialWrite {
+    /// guards a write which creates or overwrites files through temp files
+    pub fn stage() -> Self {
+        Self::default()
+    }
+
+    /// guards a write which appends to a file of `len` bytes
+    pub const fn append(path: PathBuf, len: u64) -> Self {
+        Self {
+            staged: Vec::new(),
+            removed: Vec::new(),
+            appended: Some((path, len)),
+            committed: false,
+        }
+    }
+
+    /// adds a temp file, which is renamed to `dst` on commit
+    ///
+    /// Files are moved in the order they are added.
+    pub fn add(&mut self, temp: PathBuf, dst: PathBuf) {
+        self.add_with(temp, dst, false);
+    }
+
+    /// adds a temp file, which is moved to `dst` on commit
+    ///
+    /// If `create_new` is set, the commit fails with `AlreadyExists` if `dst` exists,
+    /// and the later files are not moved.
+    pub fn add_with(&mut self, temp: PathBuf, dst: PathBuf, create_new: bool) {
+        self.staged.push(Staged {
+            temp,
+            dst,
+            create_new,
+        });
+    }
+
+    /// adds a file which is removed on commit
+    pub fn remove_on_commit(&mut self, path: PathBuf) {
+        self.removed.push(path);
+    }
+
+    /// moves the temp files into place and removes the replaced files
+    ///
+    /// The temp files which are not moved are removed if it fails.
+    pub async fn commit(mut self) -> io::Result<()> {
+        while !self.staged.is_empty() {
+            let staged = self.staged.remove(0);
+            if let Err(e) = staged.commit().await {
+                self.staged.push(staged);
+                return Err(e);
+            }
+        }
+        self.committed = true;
+        for path in &self.removed {
+            match rt::remove_file(path).await {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PartialWrite {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+        for &Staged { ref temp, .. } in &self.staged {
+            match fs::remove_file(temp) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    error!(path = %temp.display(), error = %e, "failed to remove temp file");
+                }
+                _ => debug!(path = %temp.display(), "removed temp file"),
+            }
+        }
+        if let Some((ref path, len)) = self.appended {
+            let ret = fs::OpenOptions::new()
+                .write(true)
+                .open(path)
+                .and_then(|file| file.set_len(len));
+            if let Err(e) = ret {
+                error!(path = %path.display(), error = %e, "failed to truncate file");
+            } else {
+                debug!(path = %path.display(), len, "truncated partial append");
+            }
+        }
+    }
+}
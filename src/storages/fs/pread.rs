@@ -0,0 +1,75 @@
+//! positional reads of small regions
+
+use super::{is_same_file, rt};
+
+use std::fs::{File, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use hyper::body::Bytes;
+
+/// reads `len` bytes of a file from `offset` into one buffer
+///
+/// The file is opened again on the blocking thread pool, and the read fails
+/// if it is not the file of `opened`, which has been replaced by an overwrite.
+/// A file truncated concurrently fails the read with `UnexpectedEof`.
+pub async fn read(path: PathBuf, opened: Metadata, offset: u64, len: usize) -> io::Result<Bytes> {
+    rt::unblock(move || read_blocking(&path, &opened, offset, len)).await
+}
+
+/// opens the file and reads the region
+fn read_blocking(path: &Path, opened: &Metadata, offset: u64, len: usize) -> io::Result<Bytes> {
+    let file = File::open(path)?;
+    if !is_same_file(&file.metadata()?, opened) {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "the file has been replaced",
+        ));
+    }
+    let mut buf = vec![0; len];
+    read_exact_at(&file, &mut buf, offset)?;
+    Ok(buf.into())
+}
+
+/// reads the exact number of bytes to fill `buf` from `offset`
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buf, offset)
+}
+
+/// reads the exact number of bytes to fill `buf` from `offset`
+#[cfg(not(unix))]
+fn read_exact_at(mut file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::io::{Read, Seek, SeekFrom};
+    let _ = file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn pread() {
+        let dir = Path::new("target/s3-test-pread");
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join("file");
+        std::fs::write(&path, "Hello World!").unwrap();
+        let opened = std::fs::metadata(&path).unwrap();
+
+        let read = |offset, len| read(path.clone(), opened.clone(), offset, len);
+        assert_eq!(read(0, 12).await.unwrap(), "Hello World!");
+        assert_eq!(read(6, 5).await.unwrap(), "World");
+        assert!(read(6, 0).await.unwrap().is_empty());
+
+        let err = read(6, 7).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        // an overwrite replaces the file
+        let temp_path = dir.join("temp");
+        std::fs::write(&temp_path, "Hello World!").unwrap();
+        std::fs::rename(&temp_path, &path).unwrap();
+        assert!(read(0, 12).await.is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! recovery of the files left by crashes
+
+use super::{rt, FileSystem, OBJECT_JSON_KINDS, UPLOADS_PREFIX, WRITE_PREFIX};
+
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use futures::stream::StreamExt;
+use tracing::{error, info};
+
+/// prefix of the files of multipart uploads
+const UPLOAD_PREFIX: &str = ".upload_id-";
+
+/// prefix of the temporary files of scrub repairs
+const SCRUB_PREFIX: &str = ".scrub-";
+
+/// The result of [`FileSystem::recover`]
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct RecoveryReport {
+    /// number of removed multipart uploads
+    pub removed_uploads: u64,
+    /// number of kept multipart uploads, which are recent enough to be resumed
+    pub kept_uploads: u64,
+    /// number of objects whose json files were removed since the objects no longer exist
+    pub removed_orphans: u64,
+    /// removed files
+    pub removed_files: Vec<PathBuf>,
+    /// total size of the removed files, in bytes
+    pub removed_bytes: u64,
+}
+
+/// a file found under the root
+struct LeftFile {
+    /// file path
+    path: PathBuf,
+    /// file size
+    size: u64,
+    /// last modification time
+    modified: SystemTime,
+}
+
+/// lists the directories of multipart uploads
+async fn upload_dirs(fs: &FileSystem) -> io::Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    let mut entries = rt::read_dir(&fs.temp_dir).await?;
+    while let Some(entry) = entries.next().await {
+        let entry = entry?;
+        let is_upload_dir = entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(UPLOADS_PREFIX);
+        if is_upload_dir && entry.file_type().await?.is_dir() {
+            dirs.push(entry.path());
+        }
+    }
+    Ok(dirs)
+}
+
+/// lists the json files of the objects which no longer exist, found by their stale manifests
+///
+/// Trashed objects keep their metadata, so nothing is listed if the trash is enabled.
+async fn orphan_object_jsons(
+    fs: &FileSystem,
+    report: &mut RecoveryReport,
+    is_stale: impl Fn(SystemTime) -> bool,
+) -> io::Result<Vec<LeftFile>> {
+    let mut files = Vec::new();
+    if fs.trash_retention.is_some() {
+        return Ok(files);
+    }
+    for (manifest_path, modified, manifest) in fs.object_manifests().await? {
+        if !is_stale(modified) {
+            continue;
+        }
+        let (bucket, key) = (manifest.bucket.as_str(), manifest.key.as_str());
+        let object_exists = match fs.get_object_path(bucket, key) {
+            Ok(path) => rt::metadata(&path).await.is_ok(),
+            Err(_) => false,
+        };
+        if object_exists {
+            continue;
+        }
+        info!(%bucket, %key, "recover: removing json files of missing object");
+        report.removed_orphans = report.removed_orphans.wrapping_add(1);
+        for kind in OBJECT_JSON_KINDS {
+            let path = fs.get_object_json_path(bucket, key, kind)?;
+            if let Ok(metadata) = rt::metadata(&path).await {
+                files.push(LeftFile {
+                    path,
+                    size: metadata.len(),
+                    modified: metadata.modified()?,
+                });
+            }
+        }
+        // the manifest is removed last, so that a failure leaves the json files findable
+        let size = rt::metadata(&manifest_path).await.map_or(0, |m| m.len());
+        files.push(LeftFile {
+            path: manifest_path,
+            size,
+            modified,
+        });
+    }
+    Ok(files)
+}
+
+/// removes the files left before `now - max_age`, see [`FileSystem::recover`]
+pub async fn run(
+    fs: &FileSystem,
+    now: SystemTime,
+    max_age: Duration,
+) -> io::Result<RecoveryReport> {
+    let mut uploads: HashMap<String, Vec<LeftFile>> = HashMap::new();
+    let mut temp_files: Vec<LeftFile> = Vec::new();
+
+    // multipart uploads are in the directories of their buckets in the temp dir,
+    // which may be outside of the root, or in the temp dir itself if left by older versions
+    let mut dirs = vec![fs.root.clone()];
+    if fs.temp_dir != fs.root {
+        dirs.push(fs.temp_dir.clone());
+    }
+    dirs.extend(upload_dirs(fs).await?);
+    for dir in dirs {
+        let mut entries = rt::read_dir(dir).await?;
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let file = LeftFile {
+                path: entry.path(),
+                size: metadata.len(),
+                modified: metadata.modified()?,
+            };
+            if let Some(rest) = name.strip_prefix(UPLOAD_PREFIX) {
+                // `{upload_id}.part-{n}`, `{upload_id}.part-{n}.json` or `{upload_id}.json`
+                let upload_id = rest.split('.').next().unwrap_or(rest);
+                uploads.entry(upload_id.to_owned()).or_default().push(file);
+                continue;
+            }
+            if name.starts_with(SCRUB_PREFIX) || name.starts_with(WRITE_PREFIX) {
+                temp_files.push(file);
+            }
+        }
+    }
+
+    let is_stale = |modified: SystemTime| {
+        now.duration_since(modified)
+            .map_or(false, |age| age > max_age)
+    };
+
+    let mut report = RecoveryReport::default();
+
+    // an upload is removed as a whole, so a recent part keeps it resumable
+    let mut stale_files = Vec::new();
+    let mut upload_ids: Vec<String> = uploads.keys().cloned().collect();
+    upload_ids.sort();
+    for upload_id in upload_ids {
+        let files = uploads.remove(&upload_id).unwrap_or_default();
+        let last_modified = files.iter().map(|f| f.modified).max();
+        if last_modified.map_or(false, is_stale) {
+            info!(%upload_id, "recover: removing stale multipart upload");
+            report.removed_uploads = report.removed_uploads.wrapping_add(1);
+            stale_files.extend(files);
+        } else {
+            report.kept_uploads = report.kept_uploads.wrapping_add(1);
+        }
+    }
+    stale_files.extend(temp_files.into_iter().filter(|f| is_stale(f.modified)));
+    stale_files.extend(orphan_object_jsons(fs, &mut report, is_stale).await?);
+
+    for file in stale_files {
+        match rt::remove_file(&file.path).await {
+            Ok(()) => {}
+            // removed by a concurrent request
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                error!(path = %file.path.display(), error = %e, "recover: failed to remove file");
+                return Err(e);
+            }
+        }
+        report.removed_bytes = report.removed_bytes.wrapping_add(file.size);
+        report.removed_files.push(file.path);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn recover() {
+        let root = Path::new("target/s3-test-recover");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+        let fs = FileSystem::new(root).unwrap();
+
+        let upload_id = "5b3a1c2e-0000-4000-8000-000000000000";
+        std::fs::create_dir_all(fs.get_upload_dir("asd").unwrap()).unwrap();
+        std::fs::write(
+            fs.get_upload_part_path("asd", upload_id, 1).unwrap(),
+            "Hello",
+        )
+        .unwrap();
+        std::fs::write(fs.get_upload_record_path("asd", upload_id).unwrap(), "{}").unwrap();
+        std::fs::write(root.join(".scrub-tmp"), "World!").unwrap();
+        std::fs::write(root.join(".write-tmp"), "").unwrap();
+        std::fs::write(root.join("asd").join(".upload_id-object"), "").unwrap();
+
+        let report = fs.recover(Duration::from_secs(3600)).await.unwrap();
+        assert_eq!(report.removed_uploads, 0);
+        assert_eq!(report.kept_uploads, 1);
+        assert!(report.removed_files.is_empty());
+
+        let later = SystemTime::now() + Duration::from_secs(3601);
+        let report = run(&fs, later, Duration::from_secs(3600)).await.unwrap();
+        assert_eq!(report.removed_uploads, 1);
+        assert_eq!(report.kept_uploads, 0);
+        assert_eq!(report.removed_files.len(), 4);
+        assert_eq!(report.removed_bytes, 13);
+        assert!(!fs
+            .get_upload_part_path("asd", upload_id, 1)
+            .unwrap()
+            .exists());
+        assert!(!root.join(".scrub-tmp").exists());
+        assert!(!root.join(".write-tmp").exists());
+
+        // objects are never touched
+        assert!(root.join("asd").join(".upload_id-object").exists());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn orphan_object_jsons() {
+        let root = Path::new("target/s3-test-recover-orphans");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("asd")).unwrap();
+        let fs = FileSystem::new(root).unwrap();
+
+        let metadata = HashMap::from([("a".to_owned(), "b".to_owned())]);
+        for key in ["kept", "gone"] {
+            std::fs::write(fs.get_object_path("asd", key).unwrap(), "Hello").unwrap();
+            fs.save_metadata("asd", key, &metadata).await.unwrap();
+        }
+        // an object removed behind the back of the storage
+        std::fs::remove_file(fs.get_object_path("asd", "gone").unwrap()).unwrap();
+
+        let report = fs.recover(Duration::from_secs(3600)).await.unwrap();
+        assert_eq!(report.removed_orphans, 0);
+
+        let later = SystemTime::now() + Duration::from_secs(3601);
+        let report = run(&fs, later, Duration::from_secs(3600)).await.unwrap();
+        assert_eq!(report.removed_orphans, 1);
+        assert_eq!(report.removed_files.len(), 2);
+        assert!(!fs.get_metadata_path("asd", "gone").unwrap().exists());
+        assert!(!fs.get_object_manifest_path("asd", "gone").unwrap().exists());
+        assert!(fs.get_metadata_path("asd", "kept").unwrap().exists());
+        assert!(fs.get_object_manifest_path("asd", "kept").unwrap().exists());
+    }
+}
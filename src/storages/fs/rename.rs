@@ -0,0 +1,279 @@
+//! server-side renames of buckets and objects
+//!
+//! Object files are moved by renaming their files or directories,
+//! and the json files attached to each object are moved along with them,
+//! since they are named by hashes of the buckets and the keys.
+
+use super::key_locks::KeyLockGuard;
+use super::{bucket_config, remove_file_if_exists, rt, trash, walk, FileSystem, OBJECT_JSON_KINDS};
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tracing::error;
+
+/// renames a file or a directory, failing if the destination exists
+async fn rename_new(src: &Path, dst: &Path) -> io::Result<()> {
+    if let Some(parent) = dst.parent() {
+        rt::create_dir_all(parent).await?;
+    }
+    let (src, dst) = (src.to_owned(), dst.to_owned());
+    rt::unblock(move || rename_no_replace(&src, &dst)).await
+}
+
+/// renames without replacing the destination by `renameat2` with `RENAME_NOREPLACE`
+#[cfg(target_os = "linux")]
+fn rename_no_replace(src: &Path, dst: &Path) -> io::Result<()> {
+    use rustix::fs::{renameat_with, RenameFlags, CWD};
+    match renameat_with(CWD, src, CWD, dst, RenameFlags::NOREPLACE) {
+        // the file system does not support the flag
+        Err(rustix::io::Errno::INVAL) => link_new(src, dst),
+        ret => ret.map_err(Into::into),
+    }
+}
+
+/// renames without replacing the destination
+#[cfg(not(target_os = "linux"))]
+fn rename_no_replace(src: &Path, dst: &Path) -> io::Result<()> {
+    link_new(src, dst)
+}
+
+/// renames a file by linking the destination, which fails if it exists, and unlinking the source
+///
+/// A directory can not be linked, so it is renamed if the destination does not exist,
+/// which only the key locks serialize with other renames.
+fn link_new(src: &Path, dst: &Path) -> io::Result<()> {
+    if std::fs::symlink_metadata(src)?.is_dir() {
+        if std::fs::symlink_metadata(dst).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "the destination exists",
+            ));
+        }
+        return std::fs::rename(src, dst);
+    }
+    std::fs::hard_link(src, dst)?;
+    if let Err(e) = std::fs::remove_file(src) {
+        // the source is left in place, so the destination is only a second link to it
+        if std::fs::remove_file(dst).is_err() {
+            error!(dst = %dst.display(), "failed to undo a link");
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// locks the source and the destination of a rename in the order of their paths
+async fn lock_pair<'a>(
+    fs: &'a FileSystem,
+    src: &Path,
+    dst: &Path,
+) -> io::Result<[KeyLockGuard<'a>; 2]> {
+    if src == dst {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "the destination exists",
+        ));
+    }
+    let (first, second) = if src < dst { (src, dst) } else { (dst, src) };
+    Ok([
+        fs.key_locks.lock(first).await,
+        fs.key_locks.lock(second).await,
+    ])
+}
+
+/// the renames of an operation, which are undone if it fails
+struct Renames<'a> {
+    /// the storage
+    fs: &'a FileSystem,
+    /// renamed paths as `(src, dst)`
+    paths: Vec<(PathBuf, PathBuf)>,
+    /// objects whose json files are moved as `((bucket, key), (new_bucket, new_key))`
+    objects: Vec<((String, String), (String, String))>,
+}
+
+impl<'a> Renames<'a> {
+    /// constructs an empty `Renames`
+    const fn new(fs: &'a FileSystem) -> Self {
+        Self {
+            fs,
+            paths: Vec::new(),
+            objects: Vec::new(),
+        }
+    }
+
+    /// renames a file or a directory, failing if the destination exists
+    async fn rename_new(&mut self, src: &Path, dst: &Path) -> io::Result<()> {
+        rename_new(src, dst).await?;
+        self.paths.push((src.to_owned(), dst.to_owned()));
+        Ok(())
+    }
+
+    /// renames a file if it exists
+    async fn rename_if_exists(&mut self, src: &Path, dst: &Path) -> io::Result<()> {
+        match rt::rename(src, dst).await {
+            Ok(()) => {
+                self.paths.push((src.to_owned(), dst.to_owned()));
+                Ok(())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// moves the json files attached to an object
+    async fn move_object_json(
+        &mut self,
+        (bucket, key): (&str, &str),
+        (new_bucket, new_key): (&str, &str),
+    ) -> io::Result<()> {
+        let fs = self.fs;
+        self.objects.push((
+            (bucket.to_owned(), key.to_owned()),
+            (new_bucket.to_owned(), new_key.to_owned()),
+        ));
+        let mut moved = false;
+        for kind in OBJECT_JSON_KINDS {
+            let dst = fs.get_object_json_path(new_bucket, new_key, kind)?;
+            // a file named by the former encoding is moved to the hashed name,
+            // unless it is shadowed by a newer hashed file
+            if let Some(legacy) = fs.get_legacy_object_json_path(bucket, key, kind)? {
+                self.rename_if_exists(&legacy, &dst).await?;
+            }
+            let src = fs.get_object_json_path(bucket, key, kind)?;
+            self.rename_if_exists(&src, &dst).await?;
+            moved |= rt::metadata(&dst).await.is_ok();
+        }
+        if moved {
+            fs.save_object_manifest(new_bucket, new_key).await?;
+        }
+        Ok(())
+    }
+
+    /// removes the manifests of the old objects if `ret` is `Ok`, or undoes the renames
+    ///
+    /// The manifests are kept until then, since they are still needed if the renames are undone.
+    async fn finish<T>(self, ret: io::Result<T>) -> io::Result<T> {
+        if ret.is_err() {
+            self.undo().await;
+            return ret;
+        }
+        for &((ref bucket, ref key), _) in &self.objects {
+            let removed = match self.fs.get_object_manifest_path(bucket, key) {
+                Ok(path) => remove_file_if_exists(&path).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = removed {
+                error!(%bucket, %key, error = %e, "failed to remove the manifest of a renamed object");
+            }
+        }
+        ret
+    }
+
+    /// moves the renamed paths back in the reverse order
+    async fn undo(self) {
+        for &(ref src, ref dst) in self.paths.iter().rev() {
+            match rt::rename(dst, src).await {
+                // a legacy json file has been shadowed by a newer hashed file
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    error!(src = %src.display(), dst = %dst.display(), error = %e, "failed to undo a rename");
+                }
+                Ok(()) => {}
+            }
+        }
+        for &(_, (ref bucket, ref key)) in &self.objects {
+            if let Err(e) = self.fs.remove_unused_object_manifest(bucket, key).await {
+                error!(%bucket, %key, error = %e, "failed to remove the manifest of a new key");
+            }
+        }
+    }
+}
+
+/// renames a bucket with its objects, configurations and multipart uploads
+///
+/// Returns a `NotFound` error if the bucket does not exist,
+/// or an `AlreadyExists` error if the new bucket exists.
+/// The moved files are moved back if the rename fails.
+pub async fn rename_bucket(fs: &FileSystem, bucket: &str, new_bucket: &str) -> io::Result<()> {
+    let src = fs.get_bucket_path(bucket)?;
+    let dst = fs.get_bucket_path(new_bucket)?;
+    let _locks = lock_pair(fs, &src, &dst).await?;
+    let files = walk::object_files(&src).await?;
+
+    let mut renames = Renames::new(fs);
+    let ret: io::Result<()> = async {
+        renames.rename_new(&src, &dst).await?;
+        for file in &files {
+            renames
+                .move_object_json((bucket, &file.key), (new_bucket, &file.key))
+                .await?;
+        }
+        renames
+            .rename_if_exists(
+                &bucket_config::config_path(fs, bucket)?,
+                &bucket_config::config_path(fs, new_bucket)?,
+            )
+            .await?;
+        renames
+            .rename_if_exists(
+                &trash::trash_path(fs, bucket)?,
+                &trash::trash_path(fs, new_bucket)?,
+            )
+            .await?;
+        renames
+            .rename_if_exists(&fs.get_upload_dir(bucket)?, &fs.get_upload_dir(new_bucket)?)
+            .await
+    }
+    .await;
+    renames.finish(ret).await
+}
+
+/// renames an object, or all objects under a prefix if the key ends with `/`,
+/// returns the number of renamed objects
+///
+/// Returns a `NotFound` error if there is no such object or prefix,
+/// or an `AlreadyExists` error if the new key exists.
+/// The moved files are moved back if the rename fails.
+pub async fn rename_objects(
+    fs: &FileSystem,
+    bucket: &str,
+    key: &str,
+    new_key: &str,
+) -> io::Result<u64> {
+    let src = fs.get_object_path(bucket, key)?;
+    let dst = fs.get_object_path(bucket, new_key)?;
+    let _locks = lock_pair(fs, &src, &dst).await?;
+
+    let mut renames = Renames::new(fs);
+    if !key.ends_with('/') {
+        if !rt::metadata(&src).await?.is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "not an object"));
+        }
+        let ret: io::Result<()> = async {
+            renames.rename_new(&src, &dst).await?;
+            renames
+                .move_object_json((bucket, key), (bucket, new_key))
+                .await
+        }
+        .await;
+        renames.finish(ret).await?;
+        return Ok(1);
+    }
+
+    let files = walk::object_files(&src).await?;
+    let ret: io::Result<()> = async {
+        renames.rename_new(&src, &dst).await?;
+        for file in &files {
+            let old = format!("{key}{}", file.key);
+            let new = format!("{new_key}{}", file.key);
+            renames
+                .move_object_json((bucket, &old), (bucket, &new))
+                .await?;
+        }
+        Ok(())
+    }
+    .await;
+    renames.finish(ret).await?;
+    Ok(u64::try_from(files.len()).unwrap_or(u64::MAX))
+}
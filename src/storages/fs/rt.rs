@@ -0,0 +1,141 @@
+//! runtime-specific file system operations
+//!
+//! `async-fs` is used by default. `tokio::fs` is used if the feature `rt-tokio` is enabled.
+//!
+//! Files implement the runtime-agnostic io traits in `futures::io`.
+
+use std::fs::Metadata;
+use std::io;
+use std::path::Path;
+
+use futures::stream::Stream;
+
+#[cfg(not(feature = "rt-tokio"))]
+pub use async_fs::{
+    copy, create_dir, create_dir_all, hard_link, metadata, read, remove_dir, remove_dir_all,
+    remove_file, rename, write, DirEntry, File,
+};
+
+#[cfg(feature = "rt-tokio")]
+pub use tokio::fs::{
+    copy, create_dir, create_dir_all, hard_link, metadata, read, remove_dir, remove_dir_all,
+    remove_file, rename, write, DirEntry,
+};
+
+/// file
+#[cfg(feature = "rt-tokio")]
+pub type File = tokio_util::compat::Compat<tokio::fs::File>;
+
+/// open a file in read-only mode
+#[cfg(not(feature = "rt-tokio"))]
+pub async fn open(path: impl AsRef<Path>) -> io::Result<File> {
+    File::open(path).await
+}
+
+/// open a file in read-only mode
+#[cfg(feature = "rt-tokio")]
+pub async fn open(path: impl AsRef<Path>) -> io::Result<File> {
+    use tokio_util::compat::TokioAsyncReadCompatExt;
+    Ok(tokio::fs::File::open(path).await?.compat())
+}
+
+/// open a file in write-only mode
+#[cfg(not(feature = "rt-tokio"))]
+pub async fn create(path: impl AsRef<Path>) -> io::Result<File> {
+    File::create(path).await
+}
+
+/// open a file in write-only mode
+#[cfg(feature = "rt-tokio")]
+pub async fn create(path: impl AsRef<Path>) -> io::Result<File> {
+    use tokio_util::compat::TokioAsyncWriteCompatExt;
+    Ok(tokio::fs::File::create(path).await?.compat_write())
+}
+
+/// open an existing file in append mode
+#[cfg(not(feature = "rt-tokio"))]
+pub async fn append(path: impl AsRef<Path>) -> io::Result<File> {
+    async_fs::OpenOptions::new().append(true).open(path).await
+}
+
+/// open an existing file in append mode
+#[cfg(feature = "rt-tokio")]
+pub async fn append(path: impl AsRef<Path>) -> io::Result<File> {
+    use tokio_util::compat::TokioAsyncWriteCompatExt;
+    let file = tokio::fs::OpenOptions::new()
+        .append(true)
+        .open(path)
+        .await?;
+    Ok(file.compat_write())
+}
+
+/// sync all data and metadata of an opened file to the disk
+#[cfg(not(feature = "rt-tokio"))]
+pub async fn sync_all(file: &File) -> io::Result<()> {
+    file.sync_all().await
+}
+
+/// sync all data and metadata of an opened file to the disk
+#[cfg(feature = "rt-tokio")]
+pub async fn sync_all(file: &File) -> io::Result<()> {
+    file.get_ref().sync_all().await
+}
+
+/// query metadata of an opened file
+#[cfg(not(feature = "rt-tokio"))]
+pub async fn file_metadata(file: &File) -> io::Result<Metadata> {
+    file.metadata().await
+}
+
+/// query metadata of an opened file
+#[cfg(feature = "rt-tokio")]
+pub async fn file_metadata(file: &File) -> io::Result<Metadata> {
+    file.get_ref().metadata().await
+}
+
+/// returns a stream of entries in a directory
+#[cfg(not(feature = "rt-tokio"))]
+pub async fn read_dir(
+    path: impl AsRef<Path>,
+) -> io::Result<impl Stream<Item = io::Result<DirEntry>> + Send + Unpin> {
+    async_fs::read_dir(path).await
+}
+
+/// returns a stream of entries in a directory
+#[cfg(feature = "rt-tokio")]
+pub async fn read_dir(
+    path: impl AsRef<Path>,
+) -> io::Result<impl Stream<Item = io::Result<DirEntry>> + Send + Unpin> {
+    let dir = tokio::fs::read_dir(path).await?;
+    let stream = futures::stream::unfold(dir, |mut dir| async move {
+        match dir.next_entry().await {
+            Ok(Some(entry)) => Some((Ok(entry), dir)),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), dir)),
+        }
+    });
+    Ok(Box::pin(stream))
+}
+
+/// runs a blocking function on the thread pool of the runtime
+#[cfg(not(feature = "rt-tokio"))]
+pub async fn unblock<T, F>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    blocking::unblock(f).await
+}
+
+/// runs a blocking function on the thread pool of the runtime
+#[cfg(feature = "rt-tokio")]
+pub async fn unblock<T, F>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(ans) => ans,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
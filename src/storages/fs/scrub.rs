@@ -0,0 +1,287 @@
+//! object integrity scrubbing
+
+use super::{copy_file, rt, walk, ETagStrategy, FileSystem};
+
+#[cfg(all(feature = "xattr", unix))]
+use super::xattrs;
+
+use std::io;
+
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// The result of [`FileSystem::scrub`]
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct ScrubReport {
+    /// number of scanned objects
+    pub scanned: u64,
+    /// number of objects without a recorded checksum, which are not verified
+    pub unverified: u64,
+    /// objects whose content does not match the recorded checksum
+    pub corrupted: Vec<CorruptedObject>,
+}
+
+/// An object whose content does not match the recorded checksum
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct CorruptedObject {
+    /// bucket name
+    pub bucket: String,
+    /// object key
+    pub key: String,
+    /// checksum recorded when the object was written, which is an md5 sum
+    /// unless it is computed by another [`ETagStrategy`]
+    pub expected_md5: String,
+    /// checksum of the current content, or `None` if the object can not be read
+    pub actual_md5: Option<String>,
+    /// whether the object has been restored from the replica
+    pub repaired: bool,
+}
+
+impl ScrubReport {
+    /// Checks whether all corrupted objects have been repaired
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        self.corrupted.iter().all(|obj| obj.repaired)
+    }
+}
+
+/// verifies all objects of `fs`, see [`FileSystem::scrub`]
+pub async fn run(fs: &FileSystem, replica: Option<&FileSystem>) -> io::Result<ScrubReport> {
+    let mut report = ScrubReport::default();
+
+    for bucket in walk::bucket_names(&fs.root).await? {
+        let bucket_path = fs.get_bucket_path(&bucket)?;
+        for file in walk::object_files(&bucket_path).await? {
+            let key = file.key;
+            report.scanned = report.scanned.wrapping_add(1);
+
+            let checksum = fs.load_checksum(&bucket, &key).await?;
+            let expected_md5 = if let Some(md5_sum) = checksum {
+                md5_sum
+            } else {
+                report.unverified = report.unverified.wrapping_add(1);
+                continue;
+            };
+
+            // checksums recorded before a change of the `ETag` strategy are still verified
+            let strategy = ETagStrategy::of_checksum(&expected_md5);
+            let actual_md5 = match fs.get_checksum(&bucket, &key, strategy).await {
+                Ok(md5_sum) if md5_sum == expected_md5 => continue,
+                Ok(md5_sum) => Some(md5_sum),
+                // deleted during scrubbing
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    error!(%bucket, %key, error = %e, "scrub: failed to read object");
+                    None
+                }
+            };
+            warn!(%bucket, %key, %expected_md5, ?actual_md5, "scrub: corrupted object");
+
+            let corruption = (expected_md5.as_str(), actual_md5.as_deref());
+            let repaired = match replica {
+                Some(replica) => match repair(fs, replica, &bucket, &key, corruption).await {
+                    Ok(repaired) => repaired,
+                    Err(e) => {
+                        error!(%bucket, %key, error = %e, "scrub: failed to repair object");
+                        false
+                    }
+                },
+                None => false,
+            };
+            if repaired {
+                info!(%bucket, %key, "scrub: repaired object from replica");
+            }
+
+            report.corrupted.push(CorruptedObject {
+                bucket: bucket.clone(),
+                key,
+                expected_md5,
+                actual_md5,
+                repaired,
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+/// restores an object from the replica, returns `false` if the replica has no intact copy,
+/// or if the object has been written or deleted since it was found corrupted
+async fn repair(
+    fs: &FileSystem,
+    replica: &FileSystem,
+    bucket: &str,
+    key: &str,
+    (expected_md5, actual_md5): (&str, Option<&str>),
+) -> io::Result<bool> {
+    let strategy = ETagStrategy::of_checksum(expected_md5);
+    match replica.get_checksum(bucket, key, strategy).await {
+        Ok(md5_sum) if md5_sum == expected_md5 => {}
+        Ok(_) => return Ok(false),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    }
+
+    // readers never see a partially restored file
+    let src_path = replica.get_object_path(bucket, key)?;
+    let dst_path = fs.get_object_path(bucket, key)?;
+    let tmp_path = fs.root.join(format!(".scrub-{}", Uuid::new_v4()));
+    let ret = async {
+        let _: u64 = copy_file(&src_path, &tmp_path).await?;
+
+        // writes and deletes of the object hold the lock when they replace or remove it,
+        // so the object checked under the lock is the one which is replaced
+        let _lock = fs.key_locks.lock(&dst_path).await;
+        if !is_still_corrupted(fs, bucket, key, (expected_md5, actual_md5)).await? {
+            info!(%bucket, %key, "scrub: object changed during the repair");
+            return Ok(false);
+        }
+        // the attributes of the corrupted file are kept by the restored one
+        #[cfg(all(feature = "xattr", unix))]
+        xattrs::copy_all(dst_path.clone(), tmp_path.clone()).await?;
+        rt::rename(&tmp_path, &dst_path).await?;
+        Ok(true)
+    }
+    .await;
+    if !matches!(ret, Ok(true)) {
+        match rt::remove_file(&tmp_path).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                error!(path = %tmp_path.display(), error = %e, "failed to remove temp file");
+            }
+            _ => {}
+        }
+    }
+    ret
+}
+
+/// checks whether an object still has the recorded and actual checksums found by the scrub
+async fn is_still_corrupted(
+    fs: &FileSystem,
+    bucket: &str,
+    key: &str,
+    (expected_md5, actual_md5): (&str, Option<&str>),
+) -> io::Result<bool> {
+    if fs.load_checksum(bucket, key).await?.as_deref() != Some(expected_md5) {
+        return Ok(false);
+    }
+    let strategy = ETagStrategy::of_checksum(expected_md5);
+    match fs.get_checksum(bucket, key, strategy).await {
+        Ok(md5_sum) => Ok(actual_md5 == Some(md5_sum.as_str())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(_) => Ok(actual_md5.is_none()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dto::{CreateBucketRequest, PutObjectRequest};
+    use crate::storage::S3Storage;
+
+    use std::path::Path;
+
+    async fn setup(root: &Path, content: &'static str) -> FileSystem {
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root).unwrap();
+
+        let fs = FileSystem::new(root).unwrap();
+        let _ = fs
+            .create_bucket(CreateBucketRequest {
+                bucket: "asd".into(),
+                ..CreateBucketRequest::default()
+            })
+            .await
+            .unwrap();
+        let _ = fs
+            .put_object(PutObjectRequest {
+                bucket: "asd".into(),
+                key: "a/b".into(),
+                body: Some(crate::dto::ByteStream::from(content.as_bytes().to_vec())),
+                ..PutObjectRequest::default()
+            })
+            .await
+            .unwrap();
+        fs
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn scrub() {
+        let root = Path::new("target/s3-test-scrub");
+        let fs = setup(root, "Hello World!").await;
+        let replica = setup(Path::new("target/s3-test-scrub-replica"), "Hello World!").await;
+        std::fs::write(root.join("asd").join("unverified"), "").unwrap();
+
+        let report = fs.scrub(None).await.unwrap();
+        assert_eq!(report.scanned, 2);
+        assert_eq!(report.unverified, 1);
+        assert!(report.corrupted.is_empty());
+
+        let object_path = root.join("asd").join("a").join("b");
+        std::fs::write(&object_path, "Hello World?").unwrap();
+
+        let report = fs.scrub(None).await.unwrap();
+        assert_eq!(report.corrupted.len(), 1);
+        assert_eq!(report.corrupted[0].key, "a/b");
+        assert!(!report.is_healthy());
+
+        let report = fs.scrub(Some(&replica)).await.unwrap();
+        assert!(report.corrupted[0].repaired);
+        assert!(report.is_healthy());
+        assert_eq!(std::fs::read(&object_path).unwrap(), b"Hello World!");
+
+        let report = fs.scrub(None).await.unwrap();
+        assert!(report.corrupted.is_empty());
+    }
+
+    #[tokio::test]
+    async fn repair_changed_object() {
+        let root = Path::new("target/s3-test-scrub-changed");
+        let fs = setup(root, "Hello World!").await;
+        let replica = setup(
+            Path::new("target/s3-test-scrub-changed-replica"),
+            "Hello World!",
+        )
+        .await;
+
+        let object_path = root.join("asd").join("a").join("b");
+        std::fs::write(&object_path, "Hello World?").unwrap();
+        let report = fs.scrub(None).await.unwrap();
+        let corrupted = &report.corrupted[0];
+        let corruption = (
+            corrupted.expected_md5.as_str(),
+            corrupted.actual_md5.as_deref(),
+        );
+
+        // the object is overwritten after it is found corrupted
+        let _ = fs
+            .put_object(PutObjectRequest {
+                bucket: "asd".into(),
+                key: "a/b".into(),
+                body: Some(crate::dto::ByteStream::from(b"Hello".to_vec())),
+                ..PutObjectRequest::default()
+            })
+            .await
+            .unwrap();
+        let repaired = repair(&fs, &replica, "asd", "a/b", corruption)
+            .await
+            .unwrap();
+        assert!(!repaired);
+        assert_eq!(std::fs::read(&object_path).unwrap(), b"Hello");
+
+        // the temp copy of the replica is removed
+        let temps = std::fs::read_dir(root)
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with(".scrub-")
+            })
+            .count();
+        assert_eq!(temps, 0);
+    }
+}
@@ -0,0 +1,144 @@
+//! trash of deleted objects (custom format)
+//!
+//! A deleted object is moved to `.trash-{bucket}/{deleted_at}/{key}` under the root,
+//! where `deleted_at` is in milliseconds since the unix epoch.
+//! The trash is outside of the bucket directory, so trashed objects are never listed.
+
+use super::{rt, FileSystem};
+
+use crate::path::S3Path;
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use futures::stream::StreamExt;
+use path_absolutize::Absolutize;
+
+/// prefix of trash directories
+const TRASH_PREFIX: &str = ".trash-";
+
+/// resolves the trash directory of a bucket
+pub fn trash_path(fs: &FileSystem, bucket: &str) -> io::Result<PathBuf> {
+    let encoded = base64_simd::URL_SAFE_NO_PAD.encode_to_string(bucket);
+    let dir_name = format!("{TRASH_PREFIX}{encoded}");
+    let ans = Path::new(&dir_name).absolutize_virtually(&fs.root)?.into();
+    Ok(ans)
+}
+
+/// milliseconds since the unix epoch
+fn unix_millis(time: SystemTime) -> u128 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+/// lists the deletion times of a trash directory, from the latest to the earliest
+async fn list_deletions(trash: &Path) -> io::Result<Vec<u128>> {
+    let mut dir = match rt::read_dir(trash).await {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut deletions = Vec::new();
+    while let Some(entry) = dir.next().await {
+        if let Ok(t) = entry?.file_name().to_string_lossy().parse::<u128>() {
+            deletions.push(t);
+        }
+    }
+    deletions.sort_unstable_by(|lhs, rhs| rhs.cmp(lhs));
+    Ok(deletions)
+}
+
+/// moves an object file into the trash, returns `false` if the object does not exist
+pub async fn move_to_trash(
+    fs: &FileSystem,
+    bucket: &str,
+    key: &str,
+    now: SystemTime,
+) -> io::Result<bool> {
+    let src = fs.get_object_path(bucket, key)?;
+    if !matches!(rt::metadata(&src).await, Ok(m) if m.is_file()) {
+        return Ok(false);
+    }
+    let deletion = unix_millis(now).to_string();
+    let dst: PathBuf = Path::new(&deletion)
+        .join(key)
+        .absolutize_virtually(trash_path(fs, bucket)?)?
+        .into();
+    if let Some(parent) = dst.parent() {
+        rt::create_dir_all(parent).await?;
+    }
+    match rt::rename(&src, &dst).await {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// restores the latest deleted version of an object, returns `false` if there is none
+///
+/// An existing object is never overwritten.
+pub async fn restore(fs: &FileSystem, bucket: &str, key: &str) -> io::Result<bool> {
+    if !S3Path::is_safe_key(key) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "unsafe key"));
+    }
+    let trash = trash_path(fs, bucket)?;
+    let dst = fs.get_object_path(bucket, key)?;
+    if rt::metadata(&dst).await.is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "the object exists",
+        ));
+    }
+
+    for deletion in list_deletions(&trash).await? {
+        let src: PathBuf = Path::new(&deletion.to_string())
+            .join(key)
+            .absolutize_virtually(&trash)?
+            .into();
+        if !matches!(rt::metadata(&src).await, Ok(m) if m.is_file()) {
+            continue;
+        }
+        if let Some(parent) = dst.parent() {
+            rt::create_dir_all(parent).await?;
+        }
+        rt::rename(&src, &dst).await?;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// removes the trash of a deleted bucket
+pub async fn remove(fs: &FileSystem, bucket: &str) -> io::Result<()> {
+    match rt::remove_dir_all(trash_path(fs, bucket)?).await {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// permanently removes objects deleted before `now - retention`, returns the number of removed deletions
+pub async fn purge(fs: &FileSystem, now: SystemTime, retention: Duration) -> io::Result<u64> {
+    let deadline = unix_millis(now).saturating_sub(retention.as_millis());
+    let mut count: u64 = 0;
+
+    let mut root = rt::read_dir(&fs.root).await?;
+    while let Some(entry) = root.next().await {
+        let entry = entry?;
+        if !entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(TRASH_PREFIX)
+        {
+            continue;
+        }
+        let trash = entry.path();
+        for deletion in list_deletions(&trash).await? {
+            if deletion < deadline {
+                rt::remove_dir_all(trash.join(deletion.to_string())).await?;
+                count = count.saturating_add(1);
+            }
+        }
+    }
+    Ok(count)
+}
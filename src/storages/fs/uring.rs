@@ -0,0 +1,279 @@
+//! `io_uring` file I/O, enabled by the feature `rt-uring` on Linux
+//!
+//! `tokio-uring` futures are bound to a single-threaded runtime and are not `Send`.
+//! A dedicated thread drives the runtime and serves jobs sent over channels,
+//! so the storage can still be used from any executor.
+
+use std::io;
+use std::path::PathBuf;
+use std::thread;
+
+use futures::channel::{mpsc, oneshot};
+use futures::sink::SinkExt;
+use futures::stream::{Stream, StreamExt};
+use hyper::body::Bytes;
+use tracing::error;
+
+/// max number of chunks buffered between the worker and a request
+const CHANNEL_CAPACITY: usize = 4;
+
+/// max size of a chunk read by one operation
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A handle of the `io_uring` worker thread
+///
+/// The worker exits when all handles are dropped.
+#[derive(Debug, Clone)]
+pub struct Uring {
+    /// job sender
+    jobs: mpsc::UnboundedSender<Job>,
+}
+
+/// a job served by the worker
+enum Job {
+    /// streams a region of a file
+    Read {
+        /// file path
+        path: PathBuf,
+        /// offset of the first byte
+        offset: u64,
+        /// number of bytes
+        len: usize,
+        /// chunk sender
+        tx: mpsc::Sender<io::Result<Bytes>>,
+    },
+    /// writes chunks into a new file
+    Write {
+        /// file path
+        path: PathBuf,
+        /// chunk receiver, which is closed after the last chunk
+        rx: mpsc::Receiver<Bytes>,
+        /// result sender
+        done: oneshot::Sender<io::Result<usize>>,
+    },
+}
+
+impl Uring {
+    /// Starts the worker thread
+    ///
+    /// # Errors
+    /// Returns an `Err` if `io_uring` is not supported by the kernel or forbidden by the sandbox
+    pub fn start() -> io::Result<Self> {
+        let (jobs, jobs_rx) = mpsc::unbounded();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+
+        let _handle = thread::Builder::new()
+            .name("s3-server-uring".into())
+            .spawn(move || {
+                let rt = match tokio_uring::Runtime::new(&tokio_uring::builder()) {
+                    Ok(rt) => rt,
+                    Err(e) => {
+                        let _ignored = ready_tx.send(Err(e));
+                        return;
+                    }
+                };
+                let _ignored = ready_tx.send(Ok(()));
+                rt.block_on(serve(jobs_rx));
+            })?;
+
+        let ret = ready_rx
+            .recv()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        ret.map(|()| Self { jobs })
+    }
+
+    /// sends a job to the worker
+    fn send(&self, job: Job) -> io::Result<()> {
+        self.jobs
+            .unbounded_send(job)
+            .map_err(|_closed| io::Error::new(io::ErrorKind::Other, "io_uring worker exited"))
+    }
+
+    /// Returns a stream of `len` bytes of a file from `offset`
+    pub fn read(
+        &self,
+        path: PathBuf,
+        offset: u64,
+        len: usize,
+    ) -> impl Stream<Item = io::Result<Bytes>> + Send + Sync + 'static {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let job = Job::Read {
+            path,
+            offset,
+            len,
+            tx,
+        };
+        let err = self.send(job).err();
+        futures::stream::iter(err.map(Err)).chain(rx)
+    }
+
+    /// Writes a stream into a new file, returns the number of written bytes
+    ///
+    /// An error of the stream is returned as is, and the partial file is left to the caller.
+    pub async fn write<S>(&self, path: PathBuf, mut stream: S) -> io::Result<usize>
+    where
+        S: Stream<Item = io::Result<Bytes>> + Unpin,
+    {
+        let (mut tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (done, done_rx) = oneshot::channel();
+        self.send(Job::Write { path, rx, done })?;
+
+        let mut stream_err = None;
+        while let Some(bytes) = stream.next().await {
+            match bytes {
+                Ok(bytes) => {
+                    if tx.send(bytes).await.is_err() {
+                        // the worker has failed, whose error is returned below
+                        break;
+                    }
+                }
+                Err(e) => {
+                    stream_err = Some(e);
+                    break;
+                }
+            }
+        }
+        drop(tx);
+
+        let ret = done_rx
+            .await
+            .map_err(|_canceled| io::Error::new(io::ErrorKind::Other, "io_uring worker exited"));
+        match stream_err {
+            Some(e) => Err(e),
+            None => ret?,
+        }
+    }
+}
+
+/// serves jobs until all handles are dropped
+async fn serve(mut jobs: mpsc::UnboundedReceiver<Job>) {
+    while let Some(job) = jobs.next().await {
+        let _handle = tokio_uring::spawn(async move {
+            match job {
+                Job::Read {
+                    path,
+                    offset,
+                    len,
+                    mut tx,
+                } => {
+                    if let Err(e) = read_file(path, offset, len, &mut tx).await {
+                        let _ignored = tx.send(Err(e)).await;
+                    }
+                }
+                Job::Write { path, rx, done } => {
+                    let _ignored = done.send(write_file(path, rx).await);
+                }
+            }
+        });
+    }
+}
+
+/// reads a region of a file into the sender
+#[allow(clippy::future_not_send)] // runs on the worker thread
+async fn read_file(
+    path: PathBuf,
+    mut offset: u64,
+    len: usize,
+    tx: &mut mpsc::Sender<io::Result<Bytes>>,
+) -> io::Result<()> {
+    let file = tokio_uring::fs::File::open(&path).await?;
+    let mut remaining = len;
+    let ret = async {
+        while remaining > 0 {
+            let buf = Vec::with_capacity(remaining.min(READ_CHUNK_SIZE));
+            let (ret, buf) = file.read_at(buf, offset).await;
+            let n = ret?;
+            if n == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+            remaining = remaining.saturating_sub(n);
+            offset = offset.wrapping_add(to_u64(n)?);
+            if tx.send(Ok(Bytes::from(buf))).await.is_err() {
+                // the response has been dropped
+                break;
+            }
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = file.close().await {
+        error!(path = %path.display(), error = %e, "failed to close file");
+    }
+    ret
+}
+
+/// writes all received chunks into a new file
+#[allow(clippy::future_not_send)] // runs on the worker thread
+async fn write_file(path: PathBuf, mut rx: mpsc::Receiver<Bytes>) -> io::Result<usize> {
+    let file = tokio_uring::fs::File::create(&path).await?;
+    let mut nwrite: usize = 0;
+    let ret = async {
+        while let Some(mut bytes) = rx.next().await {
+            while !bytes.is_empty() {
+                let (ret, buf) = file.write_at(bytes, to_u64(nwrite)?).await;
+                let n = ret?;
+                if n == 0 {
+                    return Err(io::Error::from(io::ErrorKind::WriteZero));
+                }
+                nwrite = nwrite.wrapping_add(n);
+                bytes = buf.slice(n..);
+            }
+        }
+        Ok(nwrite)
+    }
+    .await;
+    let closed = file.close().await;
+    let size = ret?;
+    closed?;
+    Ok(size)
+}
+
+/// converts a buffer length to a file offset
+fn to_u64(n: usize) -> io::Result<u64> {
+    u64::try_from(n).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn read_write() {
+        let uring = if let Ok(uring) = Uring::start() {
+            uring
+        } else {
+            // not supported by the kernel or the sandbox
+            return;
+        };
+
+        let dir = Path::new("target/s3-test-uring");
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join("file");
+
+        let chunks = ["Hello", " ", "World!"].map(|s| Ok(Bytes::from(s)));
+        let size = uring
+            .write(path.clone(), futures::stream::iter(chunks))
+            .await
+            .unwrap();
+        assert_eq!(size, 12);
+        assert_eq!(std::fs::read(&path).unwrap(), b"Hello World!");
+
+        let chunks: Vec<Bytes> = uring
+            .read(path.clone(), 3, 7)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(chunks, ["lo Worl"]);
+
+        let err = uring.read(path.clone(), 10, 4).collect::<Vec<_>>().await;
+        assert_eq!(err.len(), 2);
+        assert!(err[1].is_err());
+
+        let chunks = [Ok(Bytes::from("a")), Err(io::ErrorKind::BrokenPipe.into())];
+        let ret = uring.write(path, futures::stream::iter(chunks)).await;
+        assert_eq!(ret.unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,81 @@
+//! pluggable validators of the values accepted by the file system storage
+
+use crate::errors::S3Result;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// `Box<dyn Fn(&str) -> bool + Send + Sync + 'static>`
+type ValidatorFn = Box<dyn Fn(&str) -> bool + Send + Sync + 'static>;
+
+/// storage classes accepted without a custom validator
+///
+/// Objects are stored in the same way whatever their class is.
+const DEFAULT_STORAGE_CLASSES: &[&str] = &["STANDARD", "REDUCED_REDUNDANCY"];
+
+/// custom validators, which replace the default checks if they are set
+#[derive(Default)]
+pub struct Validators {
+    /// validator of `x-amz-storage-class`
+    pub storage_class: Option<ValidatorFn>,
+    /// validator of `x-amz-acl`
+    pub acl: Option<ValidatorFn>,
+    /// validator of the keys of user-defined metadata
+    pub metadata_key: Option<ValidatorFn>,
+}
+
+impl fmt::Debug for Validators {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Validators")
+            .field("storage_class", &self.storage_class.is_some())
+            .field("acl", &self.acl.is_some())
+            .field("metadata_key", &self.metadata_key.is_some())
+            .finish()
+    }
+}
+
+impl Validators {
+    /// checks the storage class, the canned ACL and the metadata keys of a written object
+    pub fn check(
+        &self,
+        storage_class: Option<&str>,
+        acl: Option<&str>,
+        metadata: Option<&HashMap<String, String>>,
+    ) -> S3Result<()> {
+        if let Some(storage_class) = storage_class {
+            let is_valid = match self.storage_class {
+                Some(ref f) => f(storage_class),
+                None => DEFAULT_STORAGE_CLASSES.contains(&storage_class),
+            };
+            if !is_valid {
+                return Err(code_error!(
+                    InvalidStorageClass,
+                    "The storage class you specified is not valid."
+                ));
+            }
+        }
+        self.check_acl(acl)?;
+        if let (Some(f), Some(metadata)) = (self.metadata_key.as_ref(), metadata) {
+            if let Some(key) = metadata.keys().find(|key| !f(key)) {
+                return Err(code_error!(
+                    InvalidArgument,
+                    format!("The metadata key you specified is not valid: {key}")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// checks the canned ACL of a created bucket or a written object
+    pub fn check_acl(&self, acl: Option<&str>) -> S3Result<()> {
+        if let (Some(f), Some(acl)) = (self.acl.as_ref(), acl) {
+            if !f(acl) {
+                return Err(code_error!(
+                    InvalidArgument,
+                    "The canned ACL you specified is not supported."
+                ));
+            }
+        }
+        Ok(())
+    }
+}
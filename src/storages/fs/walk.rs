@@ -0,0 +1,87 @@
+//! walking buckets and objects
+
+use super::rt;
+
+use crate::path::S3Path;
+
+use std::collections::VecDeque;
+use std::fs::Metadata;
+use std::io;
+use std::path::Path;
+
+use futures::stream::StreamExt;
+
+/// an object file found by walking a bucket
+pub struct ObjectFile {
+    /// object key
+    pub key: String,
+    /// file metadata
+    pub metadata: Metadata,
+}
+
+/// returns the names of all buckets under the root
+pub async fn bucket_names(root: &Path) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    let mut entries = rt::read_dir(root).await?;
+    while let Some(entry) = entries.next().await {
+        let entry = entry?;
+        if entry.file_type().await?.is_dir() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if S3Path::check_bucket_name(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// returns all object files of a bucket, sorted by key
+pub async fn object_files(bucket_path: &Path) -> io::Result<Vec<ObjectFile>> {
+    object_files_with_prefix(bucket_path, "").await
+}
+
+/// returns the object files of a bucket whose keys begin with `prefix`, sorted by key
+///
+/// Files and directories which are removed by concurrent writes during the walk are skipped.
+pub async fn object_files_with_prefix(
+    bucket_path: &Path,
+    prefix: &str,
+) -> io::Result<Vec<ObjectFile>> {
+    let mut files = Vec::new();
+    let mut dir_queue = VecDeque::new();
+    dir_queue.push_back(bucket_path.to_owned());
+
+    while let Some(dir) = dir_queue.pop_front() {
+        let mut entries = match rt::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && dir != bucket_path => continue,
+            Err(e) => return Err(e),
+        };
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            if entry.file_type().await?.is_dir() {
+                dir_queue.push_back(entry.path());
+                continue;
+            }
+            let file_path = entry.path();
+            let key = file_path
+                .strip_prefix(bucket_path)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+                .to_string_lossy()
+                .into_owned();
+            if !key.starts_with(prefix) {
+                continue;
+            }
+            let metadata = match entry.metadata().await {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            files.push(ObjectFile { key, metadata });
+        }
+    }
+
+    files.sort_by(|lhs, rhs| lhs.key.cmp(&rhs.key));
+    Ok(files)
+}
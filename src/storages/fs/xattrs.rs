@@ -0,0 +1,132 @@
+//! data attached to objects in extended attributes of their files
+//!
+//! Each kind of attached data is stored as `user.s3.{kind}`,
+//! so it is renamed, trashed and removed together with the object file.
+
+use super::rt;
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tracing::debug;
+
+/// prefix of the attribute names
+const PREFIX: &str = "user.s3.";
+
+/// `ENOTSUP`, returned by file systems without extended attributes
+#[cfg(target_os = "linux")]
+const ENOTSUP: i32 = 95;
+
+/// `ENOTSUP`, returned by file systems without extended attributes
+#[cfg(not(target_os = "linux"))]
+const ENOTSUP: i32 = 45;
+
+/// checks whether an error means that extended attributes are not available
+fn is_unsupported(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::Unsupported || err.raw_os_error() == Some(ENOTSUP)
+}
+
+/// reads an attribute of a file, returns `None` if the file, the attribute
+/// or the support of extended attributes is missing
+pub async fn get(path: &Path, kind: &str) -> io::Result<Option<Vec<u8>>> {
+    let path = path.to_owned();
+    let name = format!("{PREFIX}{kind}");
+    rt::unblock(move || match xattr::get(&path, &name) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound || is_unsupported(&e) => Ok(None),
+        ret => ret,
+    })
+    .await
+}
+
+/// writes an attribute of a file, returns `false` if it can not be stored in an attribute,
+/// such as on file systems without extended attributes or for a value beyond their size limit
+pub async fn set(path: &Path, kind: &str, value: &[u8]) -> io::Result<bool> {
+    let path = path.to_owned();
+    let name = format!("{PREFIX}{kind}");
+    let value = value.to_owned();
+    rt::unblock(move || match xattr::set(&path, &name, &value) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(e),
+        Err(e) => {
+            debug!(path = %path.display(), %name, error = %e, "failed to set extended attribute");
+            Ok(false)
+        }
+    })
+    .await
+}
+
+/// removes an attribute of a file if it exists
+pub async fn remove(path: &Path, kind: &str) -> io::Result<()> {
+    let path = path.to_owned();
+    let name = format!("{PREFIX}{kind}");
+    rt::unblock(move || match xattr::get(&path, &name) {
+        Ok(Some(_)) => xattr::remove(&path, &name),
+        Err(e) if e.kind() != io::ErrorKind::NotFound && !is_unsupported(&e) => Err(e),
+        _ => Ok(()),
+    })
+    .await
+}
+
+/// copies all attributes of `src` to `dst`, which replaces `src` by a rename
+pub async fn copy_all(src: PathBuf, dst: PathBuf) -> io::Result<()> {
+    rt::unblock(move || {
+        let names = match xattr::list(&src) {
+            Ok(names) => names,
+            Err(e) if e.kind() == io::ErrorKind::NotFound || is_unsupported(&e) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for name in names {
+            if !name.to_string_lossy().starts_with(PREFIX) {
+                continue;
+            }
+            if let Some(value) = xattr::get(&src, &name)? {
+                xattr::set(&dst, &name, &value)?;
+            }
+        }
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn attributes() {
+        let root = Path::new("target/s3-test-xattrs");
+        if root.exists() {
+            std::fs::remove_dir_all(root).unwrap();
+        }
+        std::fs::create_dir_all(root).unwrap();
+        let src = root.join("src");
+        let dst = root.join("dst");
+        std::fs::write(&src, "Hello").unwrap();
+        std::fs::write(&dst, "World").unwrap();
+
+        assert_eq!(get(&src, "checksum").await.unwrap(), None);
+        if !set(&src, "checksum", b"\"abc\"").await.unwrap() {
+            // the file system does not support extended attributes
+            return;
+        }
+        assert_eq!(
+            get(&src, "checksum").await.unwrap().as_deref(),
+            Some(&b"\"abc\""[..])
+        );
+
+        copy_all(src.clone(), dst.clone()).await.unwrap();
+        assert_eq!(
+            get(&dst, "checksum").await.unwrap().as_deref(),
+            Some(&b"\"abc\""[..])
+        );
+
+        remove(&src, "checksum").await.unwrap();
+        remove(&src, "checksum").await.unwrap();
+        assert_eq!(get(&src, "checksum").await.unwrap(), None);
+
+        let missing = root.join("missing");
+        assert_eq!(get(&missing, "checksum").await.unwrap(), None);
+        remove(&missing, "checksum").await.unwrap();
+        assert!(set(&missing, "checksum", b"1").await.is_err());
+    }
+}
@@ -1,4 +1,9 @@
 //! S3 streams
 
-pub mod aws_chunked_stream;
+pub(crate) mod aws_chunked_stream;
+pub(crate) mod content_length_range_stream;
+pub(crate) mod content_sha256_stream;
+pub(crate) mod counting_stream;
+pub(crate) mod gzip_stream;
 pub mod multipart;
+pub mod tee_hash_stream;
@@ -1,15 +1,17 @@
 //! aws-chunked stream
 
 use crate::headers::AmzDate;
-use crate::signature_v4;
-use crate::utils::Apply;
+use crate::signature_v4::{self, SigningKey};
+use crate::utils::{crypto, Apply};
 
 use std::convert::TryInto;
 use std::fmt::{self, Debug};
 use std::io;
 use std::pin::Pin;
+use std::slice;
 use std::task::{Context, Poll};
 
+use bytes::BytesMut;
 use futures::future::BoxFuture;
 use futures::pin_mut;
 use futures::stream::{Stream, StreamExt};
@@ -42,8 +44,8 @@ struct SignatureCtx {
     /// region
     region: Box<str>,
 
-    /// secret_key
-    secret_key: Box<str>,
+    /// signing key derived from the secret key, the date and the region
+    signing_key: SigningKey,
 
     /// previous chunk's signature
     prev_signature: Box<str>,
@@ -113,14 +115,11 @@ fn check_signature(
         chunk_data,
     );
 
-    let chunk_signature = signature_v4::calculate_signature(
-        &string_to_sign,
-        &ctx.secret_key,
-        &ctx.amz_date,
-        &ctx.region,
-    );
+    let chunk_signature =
+        signature_v4::calculate_signature_with_key(&string_to_sign, &ctx.signing_key);
 
-    (chunk_signature.as_bytes() == expected_signature).then(|| chunk_signature.into())
+    crypto::constant_time_eq(chunk_signature.as_bytes(), expected_signature)
+        .then(|| chunk_signature.into())
 }
 
 impl AwsChunkedStream {
@@ -130,8 +129,32 @@ impl AwsChunkedStream {
         seed_signature: Box<str>,
         amz_date: AmzDate,
         region: Box<str>,
-        secret_key: Box<str>,
+        signing_key: SigningKey,
     ) -> Self
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+    {
+        let ctx = SignatureCtx {
+            amz_date,
+            region,
+            signing_key,
+            prev_signature: seed_signature,
+        };
+        Self::with_ctx(body, Some(ctx))
+    }
+
+    /// Constructs a `ChunkedStream` which decodes chunks without checking their signatures
+    ///
+    /// It is only for [`S3Service::insecure_dev`](crate::S3Service::insecure_dev).
+    pub fn new_unverified<S>(body: S) -> Self
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+    {
+        Self::with_ctx(body, None)
+    }
+
+    /// constructs a `ChunkedStream` which checks signatures if `ctx` is given
+    fn with_ctx<S>(body: S, mut ctx: Option<SignatureCtx>) -> Self
     where
         S: Stream<Item = io::Result<Bytes>> + Send + 'static,
     {
@@ -142,32 +165,37 @@ impl AwsChunkedStream {
                     Box::pin(async move {
                         pin_mut!(body);
                         let mut prev_bytes = Bytes::new();
-                        let mut buf: Vec<u8> = Vec::new();
-                        let mut ctx = SignatureCtx {
-                            amz_date,
-                            region,
-                            secret_key,
-                            prev_signature: seed_signature,
-                        };
+                        let mut buf = BytesMut::new();
 
                         loop {
-                            let meta = {
+                            let meta_bytes = {
                                 match Self::read_meta_bytes(body.as_mut(), prev_bytes, &mut buf)
                                     .await
                                 {
                                     None => break,
                                     Some(Err(e)) => return Err(AwsChunkedStreamError::Io(e)),
-                                    Some(Ok(remaining_bytes)) => prev_bytes = remaining_bytes,
-                                };
-                                if let Ok((_, meta)) = parse_chunk_meta(&buf) {
-                                    meta
-                                } else {
-                                    return Err(AwsChunkedStreamError::FormatError);
+                                    Some(Ok((meta_bytes, remaining_bytes))) => {
+                                        prev_bytes = remaining_bytes;
+                                        meta_bytes
+                                    }
                                 }
                             };
 
-                            let data: Vec<Bytes> = {
-                                match Self::read_data(body.as_mut(), prev_bytes, meta.size).await {
+                            let meta = if let Ok((_, meta)) = parse_chunk_meta(&meta_bytes) {
+                                meta
+                            } else {
+                                return Err(AwsChunkedStreamError::FormatError);
+                            };
+
+                            let data: Bytes = {
+                                match Self::read_data(
+                                    body.as_mut(),
+                                    prev_bytes,
+                                    meta.size,
+                                    &mut buf,
+                                )
+                                .await
+                                {
                                     None => return Err(AwsChunkedStreamError::Incomplete),
                                     Some(Err(e)) => return Err(e),
                                     Some(Ok((data, remaining_bytes))) => {
@@ -177,13 +205,15 @@ impl AwsChunkedStream {
                                 }
                             };
 
-                            match check_signature(&ctx, meta.signature, &data) {
-                                None => return Err(AwsChunkedStreamError::SignatureMismatch),
-                                Some(signature) => ctx.prev_signature = signature,
+                            if let Some(ref mut ctx) = ctx {
+                                match check_signature(ctx, meta.signature, slice::from_ref(&data)) {
+                                    None => return Err(AwsChunkedStreamError::SignatureMismatch),
+                                    Some(signature) => ctx.prev_signature = signature,
+                                }
                             }
 
-                            for bytes in data {
-                                y.yield_ok(bytes).await;
+                            if !data.is_empty() {
+                                y.yield_ok(data).await;
                             }
                         }
 
@@ -194,87 +224,83 @@ impl AwsChunkedStream {
         Self { inner }
     }
 
-    /// read meta bytes and return remaining bytes
+    /// read a meta line and return it with remaining bytes
+    ///
+    /// The meta line is sliced from `prev_bytes` without copying if it is complete,
+    /// otherwise it is accumulated in `buf`.
     async fn read_meta_bytes<S>(
         mut body: Pin<&mut S>,
-        prev_bytes: Bytes,
-        buf: &mut Vec<u8>,
-    ) -> Option<io::Result<Bytes>>
+        mut prev_bytes: Bytes,
+        buf: &mut BytesMut,
+    ) -> Option<io::Result<(Bytes, Bytes)>>
     where
         S: Stream<Item = io::Result<Bytes>> + Send + 'static,
     {
-        buf.clear();
-
-        let mut push_meta_bytes = |mut bytes: Bytes| {
-            if let Some(idx) = memchr(b'\n', bytes.as_ref()) {
-                let len = idx.wrapping_add(1); // NOTE: idx < bytes.len()
-                let leading = bytes.split_to(len);
-                buf.extend_from_slice(leading.as_ref());
-                return Some(bytes);
-            }
-
-            buf.extend_from_slice(bytes.as_ref());
-            None
-        };
-
-        if let Some(remaining_bytes) = push_meta_bytes(prev_bytes) {
-            return Some(Ok(remaining_bytes));
+        if let Some(idx) = memchr(b'\n', prev_bytes.as_ref()) {
+            // fast path
+            let len = idx.wrapping_add(1); // NOTE: idx < bytes.len()
+            let meta_bytes = prev_bytes.split_to(len);
+            return Some(Ok((meta_bytes, prev_bytes)));
         }
 
+        buf.clear();
+        buf.extend_from_slice(prev_bytes.as_ref());
+
         loop {
             match body.next().await? {
                 Err(e) => return Some(Err(e)),
-                Ok(bytes) => {
-                    if let Some(remaining_bytes) = push_meta_bytes(bytes) {
-                        return Some(Ok(remaining_bytes));
+                Ok(mut bytes) => {
+                    if let Some(idx) = memchr(b'\n', bytes.as_ref()) {
+                        let len = idx.wrapping_add(1); // NOTE: idx < bytes.len()
+                        buf.extend_from_slice(bytes.split_to(len).as_ref());
+                        return Some(Ok((buf.split().freeze(), bytes)));
                     }
+                    buf.extend_from_slice(bytes.as_ref());
                 }
             }
         }
     }
 
     /// read data and return remaining bytes
+    ///
+    /// The data is sliced from `prev_bytes` without copying if it is complete,
+    /// otherwise it is accumulated in `buf`.
     async fn read_data<S>(
         mut body: Pin<&mut S>,
-        prev_bytes: Bytes,
-        mut data_size: usize,
-    ) -> Option<Result<(Vec<Bytes>, Bytes), AwsChunkedStreamError>>
+        mut prev_bytes: Bytes,
+        data_size: usize,
+        buf: &mut BytesMut,
+    ) -> Option<Result<(Bytes, Bytes), AwsChunkedStreamError>>
     where
         S: Stream<Item = io::Result<Bytes>> + Send + 'static,
     {
-        let mut bytes_buffer = Vec::new();
-        let mut push_data_bytes = |mut bytes: Bytes| {
-            if data_size == 0 {
-                return Some(bytes);
-            }
-            if data_size <= bytes.len() {
-                let data = bytes.split_to(data_size);
-                bytes_buffer.push(data);
-                data_size = 0;
-                Some(bytes)
-            } else {
-                data_size = data_size.wrapping_sub(bytes.len());
-                bytes_buffer.push(bytes);
-                None
-            }
-        };
-
-        let mut remaining_bytes = 'outer: loop {
-            if let Some(remaining_bytes) = push_data_bytes(prev_bytes) {
-                break 'outer remaining_bytes;
-            }
+        let (data, mut remaining_bytes) = if data_size <= prev_bytes.len() {
+            // fast path
+            let data = prev_bytes.split_to(data_size);
+            (data, prev_bytes)
+        } else {
+            buf.clear();
+            buf.extend_from_slice(prev_bytes.as_ref());
 
-            loop {
+            let remaining_bytes = loop {
                 match body.next().await? {
                     Err(e) => return Some(Err(AwsChunkedStreamError::Io(e))),
-                    Ok(bytes) => {
-                        if let Some(remaining_bytes) = push_data_bytes(bytes) {
-                            break 'outer remaining_bytes;
+                    Ok(mut bytes) => {
+                        let needed = data_size.wrapping_sub(buf.len()); // NOTE: buf.len() < data_size
+                        if needed <= bytes.len() {
+                            buf.extend_from_slice(bytes.split_to(needed).as_ref());
+                            break bytes;
                         }
+                        buf.extend_from_slice(bytes.as_ref());
                     }
                 }
-            }
+            };
+
+            // the allocation is reclaimed by the next `buf.clear()` and `buf.extend_from_slice()`
+            // if the frozen bytes have been dropped
+            (buf.split().freeze(), remaining_bytes)
         };
+
         if remaining_bytes.starts_with(b"\r\n") {
             // fast path
             remaining_bytes.advance(2);
@@ -297,7 +323,7 @@ impl AwsChunkedStream {
             }
         }
 
-        Some(Ok((bytes_buffer, remaining_bytes)))
+        Some(Ok((data, remaining_bytes)))
     }
 }
 
@@ -314,46 +340,80 @@ mod tests {
     use super::*;
     use crate::utils::Also;
 
-    #[tokio::test]
-    async fn example_put_object_chunked_stream() {
-        let chunk1_meta = b"10000;chunk-signature=ad80c730a21e5b8d04586a2213dd63b9a0e99e0e2307b0ade35a65485a288648\r\n";
-        let chunk2_meta = b"400;chunk-signature=0055627c9e194cb4542bae2aa5492e3c1575bbb81b612b7d234b86a503ef5497\r\n";
-        let chunk3_meta = b"0;chunk-signature=b6c6ea8a5354eaf15b3cb7646744f4275b71ea724fed81ceb9323e279d449df9\r\n";
+    const CHUNK1_META: &[u8] = b"10000;chunk-signature=ad80c730a21e5b8d04586a2213dd63b9a0e99e0e2307b0ade35a65485a288648\r\n";
+    const CHUNK2_META: &[u8] =
+        b"400;chunk-signature=0055627c9e194cb4542bae2aa5492e3c1575bbb81b612b7d234b86a503ef5497\r\n";
+    const CHUNK3_META: &[u8] =
+        b"0;chunk-signature=b6c6ea8a5354eaf15b3cb7646744f4275b71ea724fed81ceb9323e279d449df9\r\n";
 
+    fn example_chunked_stream(chunk_results: Vec<io::Result<Bytes>>) -> AwsChunkedStream {
+        let seed_signature = "4f232c4386841ef735655705268965c44a0e4690baa4adea153f7db9fa80a0a9";
+        let timestamp = "20130524T000000Z";
+        let region = "us-east-1";
+        let secret_access_key = "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY";
+
+        let date = AmzDate::from_header_str(timestamp).unwrap();
+        let signing_key = signature_v4::derive_signing_key(secret_access_key, &date, region);
+
+        let stream = futures::stream::iter(chunk_results);
+        AwsChunkedStream::new(
+            stream,
+            seed_signature.into(),
+            date,
+            region.into(),
+            signing_key,
+        )
+    }
+
+    fn example_chunks() -> (Vec<u8>, Vec<u8>, Vec<Bytes>) {
         let chunk1_data = vec![b'a'; 0x10000]; // 65536
         let chunk2_data = vec![b'a'; 1024];
 
-        let chunk1 = Vec::from(chunk1_meta.as_ref())
+        let chunk1 = Vec::from(CHUNK1_META)
             .also(|b| b.extend_from_slice(&chunk1_data))
             .also(|b| b.extend_from_slice(b"\r\n"))
             .into();
 
-        let chunk2 = Vec::from(chunk2_meta.as_ref())
+        let chunk2 = Vec::from(CHUNK2_META)
             .also(|b| b.extend_from_slice(&chunk2_data))
             .also(|b| b.extend_from_slice(b"\r\n"))
             .into();
 
-        let chunk3 = Vec::from(chunk3_meta.as_ref())
+        let chunk3 = Vec::from(CHUNK3_META)
             .also(|b| b.extend_from_slice(b"\r\n"))
             .into();
 
-        let chunk_results: Vec<Result<Bytes, _>> = vec![Ok(chunk1), Ok(chunk2), Ok(chunk3)];
+        (chunk1_data, chunk2_data, vec![chunk1, chunk2, chunk3])
+    }
 
-        let seed_signature = "4f232c4386841ef735655705268965c44a0e4690baa4adea153f7db9fa80a0a9";
-        let timestamp = "20130524T000000Z";
-        let region = "us-east-1";
-        let secret_access_key = "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY";
+    /// re-splits the whole body at the given positions
+    fn resplit(chunks: &[Bytes], positions: &[usize]) -> Vec<io::Result<Bytes>> {
+        let mut whole = Bytes::from(chunks.concat());
+        let mut ans = Vec::new();
+        let mut prev = 0;
+        for &pos in positions {
+            ans.push(Ok(whole.split_to(pos - prev)));
+            prev = pos;
+        }
+        ans.push(Ok(whole));
+        ans
+    }
 
-        let date = AmzDate::from_header_str(timestamp).unwrap();
+    async fn collect_data(mut chunked_stream: AwsChunkedStream) -> Vec<u8> {
+        let mut ans = Vec::new();
+        while let Some(bytes) = chunked_stream.next().await {
+            ans.extend_from_slice(&bytes.unwrap());
+        }
+        assert!(chunked_stream.next().await.is_none());
+        ans
+    }
 
-        let stream = futures::stream::iter(chunk_results.into_iter());
-        let mut chunked_stream = AwsChunkedStream::new(
-            stream,
-            seed_signature.into(),
-            date,
-            region.into(),
-            secret_access_key.into(),
-        );
+    #[tokio::test]
+    async fn example_put_object_chunked_stream() {
+        let (chunk1_data, chunk2_data, chunks) = example_chunks();
+
+        let chunk_results: Vec<io::Result<Bytes>> = chunks.into_iter().map(Ok).collect();
+        let mut chunked_stream = example_chunked_stream(chunk_results);
 
         let ans1 = chunked_stream.next().await.unwrap();
         assert_eq!(ans1.unwrap(), chunk1_data.as_slice());
@@ -367,4 +427,67 @@ mod tests {
             assert!(chunked_stream.next().await.is_none());
         }
     }
+
+    #[tokio::test]
+    async fn split_meta_lines() {
+        let (chunk1_data, chunk2_data, chunks) = example_chunks();
+        let expected = [chunk1_data, chunk2_data].concat();
+
+        let chunk2_start = chunks[0].len();
+        let chunk3_start = chunk2_start + chunks[1].len();
+
+        let cases: &[&[usize]] = &[
+            // inside the size of the first meta line
+            &[2],
+            // inside the signature of the first meta line
+            &[40],
+            // between "\r" and "\n" of the first meta line
+            &[CHUNK1_META.len() - 1],
+            // inside the second and the third meta lines
+            &[chunk2_start + 10, chunk3_start + 30],
+            // a meta line split across three polls
+            &[chunk2_start + 1, chunk2_start + 30, chunk2_start + 60],
+            // between the data and its trailing "\r\n"
+            &[chunk2_start - 2, chunk2_start - 1],
+        ];
+
+        for positions in cases {
+            let chunk_results = resplit(&chunks, positions);
+            let data = collect_data(example_chunked_stream(chunk_results)).await;
+            assert_eq!(data, expected, "positions = {positions:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn small_packets() {
+        let (chunk1_data, chunk2_data, chunks) = example_chunks();
+        let expected = [chunk1_data, chunk2_data].concat();
+
+        for packet_size in [1, 7, 64, 1000, 4096] {
+            let whole_len: usize = chunks.iter().map(Bytes::len).sum();
+            let positions: Vec<usize> = (packet_size..whole_len).step_by(packet_size).collect();
+            let chunk_results = resplit(&chunks, &positions);
+            let data = collect_data(example_chunked_stream(chunk_results)).await;
+            assert_eq!(data, expected, "packet_size = {packet_size}");
+        }
+    }
+
+    #[tokio::test]
+    async fn split_signature_mismatch() {
+        let (_, _, mut chunks) = example_chunks();
+        let mut tampered = chunks[1].to_vec();
+        let last_data_byte = tampered.len() - 3;
+        tampered[last_data_byte] = b'b';
+        chunks[1] = tampered.into();
+
+        let chunk2_start = chunks[0].len();
+        let chunk_results = resplit(&chunks, &[chunk2_start + 5, chunk2_start + 100]);
+        let mut chunked_stream = example_chunked_stream(chunk_results);
+
+        assert!(chunked_stream.next().await.unwrap().is_ok());
+        assert!(matches!(
+            chunked_stream.next().await.unwrap(),
+            Err(AwsChunkedStreamError::SignatureMismatch)
+        ));
+    }
 }
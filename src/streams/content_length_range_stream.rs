@@ -0,0 +1,135 @@
+//! file stream of POST Object with the `content-length-range` condition of the policy
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::stream::Stream;
+use hyper::body::Bytes;
+use pin_project_lite::pin_project;
+
+pin_project! {
+    /// A stream which counts the forwarded bytes
+    /// and yields an error as soon as the size is out of the allowed range.
+    pub struct ContentLengthRangeStream<S> {
+        #[pin]
+        inner: S,
+        size: u64,
+        min: u64,
+        max: u64,
+        done: bool,
+    }
+}
+
+/// `ContentLengthRangeError`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ContentLengthRangeError {
+    /// the file is smaller than the min size
+    #[error("ContentLengthRangeError: TooSmall")]
+    TooSmall,
+    /// the file is larger than the max size
+    #[error("ContentLengthRangeError: TooLarge")]
+    TooLarge,
+}
+
+impl ContentLengthRangeError {
+    /// Returns the error which causes an io error
+    pub fn find_cause(err: &io::Error) -> Option<Self> {
+        err.get_ref()
+            .and_then(|e| e.downcast_ref::<Self>())
+            .copied()
+    }
+}
+
+impl<S> ContentLengthRangeStream<S> {
+    /// Constructs a `ContentLengthRangeStream`
+    pub const fn new(inner: S, min: u64, max: u64) -> Self {
+        Self {
+            inner,
+            size: 0,
+            min,
+            max,
+            done: false,
+        }
+    }
+}
+
+impl<S> Stream for ContentLengthRangeStream<S>
+where
+    S: Stream<Item = io::Result<Bytes>>,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        if *this.done {
+            return Poll::Ready(None);
+        }
+
+        let err = match futures::ready!(this.inner.poll_next(cx)) {
+            Some(Ok(bytes)) => {
+                let len = u64::try_from(bytes.len()).unwrap_or(u64::MAX);
+                *this.size = this.size.saturating_add(len);
+                if *this.size <= *this.max {
+                    return Poll::Ready(Some(Ok(bytes)));
+                }
+                ContentLengthRangeError::TooLarge
+            }
+            Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+            None => {
+                *this.done = true;
+                if *this.size >= *this.min {
+                    return Poll::Ready(None);
+                }
+                ContentLengthRangeError::TooSmall
+            }
+        };
+
+        *this.done = true;
+        Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::InvalidData, err))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::stream::StreamExt;
+
+    fn chunks(data: &[&'static [u8]]) -> impl Stream<Item = io::Result<Bytes>> {
+        let v: Vec<io::Result<Bytes>> = data.iter().map(|&b| Ok(Bytes::from(b))).collect();
+        futures::stream::iter(v)
+    }
+
+    async fn collect(stream: impl Stream<Item = io::Result<Bytes>>) -> io::Result<usize> {
+        let mut stream = Box::pin(stream);
+        let mut size = 0;
+        while let Some(bytes) = stream.next().await {
+            size += bytes?.len();
+        }
+        Ok(size)
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn range() {
+        let data: &[&[u8]] = &[b"Hello", b" ", b"World!"];
+
+        let stream = ContentLengthRangeStream::new(chunks(data), 12, 12);
+        assert_eq!(collect(stream).await.unwrap(), 12);
+
+        let stream = ContentLengthRangeStream::new(chunks(data), 0, 11);
+        let err = collect(stream).await.unwrap_err();
+        assert_eq!(
+            ContentLengthRangeError::find_cause(&err),
+            Some(ContentLengthRangeError::TooLarge)
+        );
+
+        let stream = ContentLengthRangeStream::new(chunks(data), 13, 100);
+        let err = collect(stream).await.unwrap_err();
+        assert_eq!(
+            ContentLengthRangeError::find_cause(&err),
+            Some(ContentLengthRangeError::TooSmall)
+        );
+    }
+}
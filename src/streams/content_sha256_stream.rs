@@ -0,0 +1,128 @@
+//! single chunk payload stream with `x-amz-content-sha256` verification
+
+use crate::utils::crypto::{self, Sha256};
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::stream::Stream;
+use hyper::body::Bytes;
+use pin_project_lite::pin_project;
+
+pin_project! {
+    /// A stream which hashes the payload while forwarding it
+    /// and yields an error at the end if the digest does not match the declared checksum.
+    pub struct ContentSha256Stream<S> {
+        #[pin]
+        inner: S,
+        hasher: Option<Sha256>,
+        expected_checksum: Box<str>,
+    }
+}
+
+/// `ContentSha256MismatchError`
+#[allow(missing_copy_implementations)] // Why? See `crate::path::ParseS3PathError`.
+#[derive(Debug, thiserror::Error)]
+#[error("ContentSha256MismatchError")]
+pub struct ContentSha256MismatchError {
+    /// priv place holder
+    _priv: (),
+}
+
+impl ContentSha256MismatchError {
+    /// Checks whether an io error is caused by a checksum mismatch
+    pub fn is_caused_by(err: &io::Error) -> bool {
+        err.get_ref()
+            .map_or(false, <dyn std::error::Error + Send + Sync>::is::<Self>)
+    }
+}
+
+impl<S> ContentSha256Stream<S> {
+    /// Constructs a `ContentSha256Stream`
+    pub fn new(inner: S, expected_checksum: Box<str>) -> Self {
+        Self {
+            inner,
+            hasher: Some(Sha256::new()),
+            expected_checksum,
+        }
+    }
+}
+
+impl<S> Stream for ContentSha256Stream<S>
+where
+    S: Stream<Item = io::Result<Bytes>>,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+
+        let hasher = match *this.hasher {
+            Some(ref mut h) => h,
+            None => return Poll::Ready(None),
+        };
+
+        let ans = match futures::ready!(this.inner.poll_next(cx)) {
+            Some(Ok(bytes)) => {
+                hasher.update(&bytes);
+                Some(Ok(bytes))
+            }
+            Some(Err(e)) => Some(Err(e)),
+            None => {
+                let checksum = this
+                    .hasher
+                    .take()
+                    .map(|h| crypto::to_hex_string(h.finalize()));
+
+                if checksum.as_deref() == Some(&**this.expected_checksum) {
+                    None
+                } else {
+                    let err = ContentSha256MismatchError { _priv: () };
+                    Some(Err(io::Error::new(io::ErrorKind::InvalidData, err)))
+                }
+            }
+        };
+
+        Poll::Ready(ans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::stream::StreamExt;
+
+    fn chunks(data: &[&'static [u8]]) -> impl Stream<Item = io::Result<Bytes>> {
+        let v: Vec<io::Result<Bytes>> = data.iter().map(|&b| Ok(Bytes::from(b))).collect();
+        futures::stream::iter(v)
+    }
+
+    #[tokio::test]
+    async fn matched() {
+        let checksum = crypto::hex_sha256(b"Welcome to Amazon S3.");
+        let mut stream =
+            ContentSha256Stream::new(chunks(&[b"Welcome to ", b"Amazon S3."]), checksum.into());
+
+        assert_eq!(stream.next().await.unwrap().unwrap(), "Welcome to ");
+        assert_eq!(stream.next().await.unwrap().unwrap(), "Amazon S3.");
+        assert!(stream.next().await.is_none());
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn mismatched() {
+        let checksum = crypto::hex_sha256(b"Welcome to Amazon S3.");
+        let mut stream =
+            ContentSha256Stream::new(chunks(&[b"Welcome to ", b"Amazon S4."]), checksum.into());
+
+        assert_eq!(stream.next().await.unwrap().unwrap(), "Welcome to ");
+        assert_eq!(stream.next().await.unwrap().unwrap(), "Amazon S4.");
+
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert!(ContentSha256MismatchError::is_caused_by(&err));
+
+        assert!(stream.next().await.is_none());
+    }
+}
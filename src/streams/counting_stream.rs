@@ -0,0 +1,60 @@
+//! stream which counts the forwarded bytes
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::stream::Stream;
+use hyper::body::Bytes;
+use pin_project_lite::pin_project;
+
+/// A receiver of byte counts
+pub trait ByteCounter {
+    /// adds `n` bytes
+    fn add(&mut self, n: u64);
+}
+
+impl ByteCounter for Arc<AtomicU64> {
+    fn add(&mut self, n: u64) {
+        let _prev = self.fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+pin_project! {
+    /// A stream which reports the size of each forwarded chunk to a counter
+    pub struct CountingStream<S, C> {
+        #[pin]
+        inner: S,
+        counter: C,
+    }
+}
+
+impl<S, C> CountingStream<S, C> {
+    /// Constructs a `CountingStream`
+    pub const fn new(inner: S, counter: C) -> Self {
+        Self { inner, counter }
+    }
+}
+
+impl<S, C, E> Stream for CountingStream<S, C>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    C: ByteCounter,
+{
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let ret = futures::ready!(this.inner.poll_next(cx));
+        if let Some(Ok(ref bytes)) = ret {
+            this.counter
+                .add(u64::try_from(bytes.len()).unwrap_or(u64::MAX));
+        }
+        Poll::Ready(ret)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,179 @@
+//! stream which decodes gzip bodies
+
+use std::io::{self, Write};
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use flate2::write::GzDecoder;
+use futures::stream::Stream;
+use hyper::body::Bytes;
+use pin_project_lite::pin_project;
+
+pin_project! {
+    /// A stream which decodes a gzip body and counts the decoded bytes
+    ///
+    /// It forwards either the decoded bytes or the original chunks,
+    /// so the logical size of a compressed body is known after it is stored as-is.
+    pub struct GzipStream<'n, S> {
+        #[pin]
+        inner: S,
+        decoder: Option<GzDecoder<Vec<u8>>>,
+        forward_decoded: bool,
+        logical_size: &'n mut u64,
+    }
+}
+
+impl<'n, S> GzipStream<'n, S> {
+    /// Constructs a `GzipStream` which forwards the decoded bytes
+    pub fn decode(inner: S, logical_size: &'n mut u64) -> Self {
+        Self::new(inner, true, logical_size)
+    }
+
+    /// Constructs a `GzipStream` which forwards the original chunks
+    pub fn inspect(inner: S, logical_size: &'n mut u64) -> Self {
+        Self::new(inner, false, logical_size)
+    }
+
+    /// Constructs a `GzipStream`
+    fn new(inner: S, forward_decoded: bool, logical_size: &'n mut u64) -> Self {
+        *logical_size = 0;
+        Self {
+            inner,
+            decoder: Some(GzDecoder::new(Vec::new())),
+            forward_decoded,
+            logical_size,
+        }
+    }
+}
+
+/// `GzipDecodeError`
+#[derive(Debug, thiserror::Error)]
+#[error("GzipDecodeError: {source}")]
+pub struct GzipDecodeError {
+    /// error of the decoder
+    source: io::Error,
+}
+
+impl GzipDecodeError {
+    /// Checks whether an io error is caused by an invalid gzip body
+    pub fn is_caused_by(err: &io::Error) -> bool {
+        err.get_ref()
+            .map_or(false, <dyn std::error::Error + Send + Sync>::is::<Self>)
+    }
+}
+
+/// converts a decoding error
+fn invalid_data(source: io::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, GzipDecodeError { source })
+}
+
+impl<S> Stream for GzipStream<'_, S>
+where
+    S: Stream<Item = io::Result<Bytes>>,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            let decoder = match *this.decoder {
+                Some(ref mut decoder) => decoder,
+                None => return Poll::Ready(None),
+            };
+            let chunk = match futures::ready!(this.inner.as_mut().poll_next(cx)) {
+                Some(Ok(chunk)) => Some(chunk),
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => None,
+            };
+
+            let ret = match chunk {
+                Some(ref chunk) => decoder.write_all(chunk),
+                None => decoder.try_finish(),
+            };
+            if let Err(e) = ret {
+                *this.decoder = None;
+                return Poll::Ready(Some(Err(invalid_data(e))));
+            }
+            let output = mem::take(decoder.get_mut());
+            let len = u64::try_from(output.len()).unwrap_or(u64::MAX);
+            **this.logical_size = this.logical_size.saturating_add(len);
+
+            let forwarded = match chunk {
+                Some(chunk) if !*this.forward_decoded => chunk,
+                Some(_) => Bytes::from(output),
+                None => {
+                    *this.decoder = None;
+                    if *this.forward_decoded && !output.is_empty() {
+                        return Poll::Ready(Some(Ok(Bytes::from(output))));
+                    }
+                    return Poll::Ready(None);
+                }
+            };
+            if !forwarded.is_empty() {
+                return Poll::Ready(Some(Ok(forwarded)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use futures::stream::TryStreamExt;
+
+    /// compresses `data` and splits it into small chunks
+    fn gzip_chunks(data: &[u8]) -> Vec<io::Result<Bytes>> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        let compressed = encoder.finish().unwrap();
+        compressed
+            .chunks(7)
+            .map(|c| Ok(Bytes::copy_from_slice(c)))
+            .collect()
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn decode_and_inspect() {
+        let data = b"Hello, World! Hello, World! Hello, World!";
+        let chunks = gzip_chunks(data);
+        let compressed: Vec<u8> = chunks
+            .iter()
+            .flat_map(|c| c.as_ref().unwrap().to_vec())
+            .collect();
+
+        let mut logical_size = 0;
+        let stream =
+            GzipStream::decode(futures::stream::iter(gzip_chunks(data)), &mut logical_size);
+        let decoded: Vec<Bytes> = stream.try_collect().await.unwrap();
+        assert_eq!(decoded.concat(), data);
+        assert_eq!(logical_size, 41);
+
+        let mut logical_size = 0;
+        let stream = GzipStream::inspect(futures::stream::iter(chunks), &mut logical_size);
+        let forwarded: Vec<Bytes> = stream.try_collect().await.unwrap();
+        assert_eq!(forwarded.concat(), compressed);
+        assert_eq!(logical_size, 41);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn invalid() {
+        let mut logical_size = 0;
+        let chunks: Vec<io::Result<Bytes>> = vec![Ok(Bytes::from_static(b"not gzip"))];
+        let stream = GzipStream::decode(futures::stream::iter(chunks), &mut logical_size);
+        let err = stream.try_collect::<Vec<Bytes>>().await.unwrap_err();
+        assert!(GzipDecodeError::is_caused_by(&err));
+
+        // a truncated body
+        let mut chunks = gzip_chunks(b"Hello, World!");
+        let _ = chunks.pop();
+        let stream = GzipStream::decode(futures::stream::iter(chunks), &mut logical_size);
+        let err = stream.try_collect::<Vec<Bytes>>().await.unwrap_err();
+        assert!(GzipDecodeError::is_caused_by(&err));
+    }
+}
@@ -2,6 +2,33 @@
 //!
 //! See <https://docs.aws.amazon.com/AmazonS3/latest/API/RESTObjectPOST.html>
 //!
+//! [`transform_multipart`] parses the fields preceding the file,
+//! then the file content can be read from [`FileStream`] without buffering.
+//!
+//! ```no_run
+//! use s3_server::streams::multipart::{transform_multipart, MultipartLimits};
+//!
+//! use futures::stream::StreamExt;
+//! use hyper::body::Bytes;
+//!
+//! async fn upload(
+//!     body: impl futures::Stream<Item = std::io::Result<Bytes>> + Send + Sync + 'static,
+//!     boundary: &str,
+//! ) -> Result<(), Box<dyn std::error::Error>> {
+//!     let multipart =
+//!         transform_multipart(body, boundary.as_bytes(), MultipartLimits::default()).await?;
+//!
+//!     let key = multipart.find_field_value("key");
+//!     println!("key = {:?}, file name = {}", key, multipart.file.name);
+//!
+//!     let mut stream = multipart.file.stream;
+//!     while let Some(bytes) = stream.next().await {
+//!         let bytes = bytes?;
+//!         println!("received {} bytes", bytes.len());
+//!     }
+//!     Ok(())
+//! }
+//! ```
 
 use crate::utils::Also;
 
@@ -18,6 +45,7 @@ use transform_stream::{AsyncTryStream, Yielder};
 
 /// Form file
 #[derive(Debug)]
+#[non_exhaustive]
 pub struct File {
     /// name
     pub name: String,
@@ -29,21 +57,24 @@ pub struct File {
 
 /// multipart/form-data for POST Object
 #[derive(Debug)]
+#[non_exhaustive]
 pub struct Multipart {
-    /// fields
+    /// fields preceding the file, in order of appearance
     pub fields: Vec<(String, String)>,
     /// file
     pub file: File,
 }
 
 impl Multipart {
-    /// Finds field value
+    /// Finds field value by case-insensitive name
+    ///
+    /// The last one wins if the name occurs more than once.
     #[must_use]
     pub fn find_field_value<'a>(&'a self, name: &str) -> Option<&'a str> {
         self.fields
             .iter()
             .rev()
-            .find_map(|&(ref n, ref v)| n.eq_ignore_ascii_case(name).then(|| v.as_str()))
+            .find_map(|&(ref n, ref v)| n.eq_ignore_ascii_case(name).then_some(v.as_str()))
     }
 
     // /// assign from optional field
@@ -66,18 +97,64 @@ impl Multipart {
     }
 }
 
-/// generate format error
-fn generate_format_error() -> io::Error {
-    io::Error::new(
-        io::ErrorKind::InvalidData,
-        "multipart/form-data format error",
-    )
+/// limits of multipart/form-data parsing
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct MultipartLimits {
+    /// max number of fields, excluding the file
+    pub max_fields: usize,
+    /// max size of a field value
+    pub max_field_size: usize,
+    /// max total bytes preceding the file content
+    pub max_header_bytes: usize,
+}
+
+impl Default for MultipartLimits {
+    fn default() -> Self {
+        Self {
+            max_fields: 128,
+            max_field_size: 20 * 1024,
+            max_header_bytes: 64 * 1024,
+        }
+    }
+}
+
+/// A limit of multipart/form-data parsing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MultipartLimit {
+    /// [`MultipartLimits::max_fields`]
+    Fields,
+    /// [`MultipartLimits::max_field_size`]
+    FieldSize,
+    /// [`MultipartLimits::max_header_bytes`]
+    HeaderBytes,
+}
+
+/// multipart/form-data parsing error
+#[allow(variant_size_differences)] // `io::Error` is only a pointer
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum MultipartError {
+    /// IO error
+    #[error("MultipartError: IO: {}",.0)]
+    Io(io::Error),
+    /// Format error
+    #[error("MultipartError: Format")]
+    Format,
+    /// A limit is exceeded
+    #[error("MultipartError: LimitExceeded: {:?}",.0)]
+    LimitExceeded(MultipartLimit),
 }
 
 /// transform multipart
 /// # Errors
-/// Returns an `Err` if the format is invalid
-pub async fn transform_multipart<S>(body_stream: S, boundary: &'_ [u8]) -> io::Result<Multipart>
+/// Returns an `Err` if the format is invalid or any limit is exceeded
+pub async fn transform_multipart<S>(
+    body_stream: S,
+    boundary: &'_ [u8],
+    limits: MultipartLimits,
+) -> Result<Multipart, MultipartError>
 where
     S: Stream<Item = io::Result<Bytes>> + Send + Sync + 'static,
 {
@@ -96,31 +173,40 @@ where
     loop {
         // copy bytes to buf
         match body.as_mut().next().await {
-            None => return Err(generate_format_error()),
-            Some(Err(e)) => return Err(e),
+            None => return Err(MultipartError::Format),
+            Some(Err(e)) => return Err(MultipartError::Io(e)),
             Some(Ok(bytes)) => buf.extend_from_slice(&bytes),
-        };
+        }
 
         // try to parse
-        match try_parse(body, pat, &buf, &mut fields, boundary) {
+        match try_parse(body, pat, &buf, &mut fields, boundary, limits) {
             Err((b, p)) => {
                 body = b;
                 pat = p;
             }
             Ok(ans) => return ans,
         }
+
+        // all bytes in buf precede the file content
+        if buf.len() > limits.max_header_bytes {
+            return Err(MultipartError::LimitExceeded(MultipartLimit::HeaderBytes));
+        }
     }
 }
 
 /// try to parse data buffer, pat: b"--{boundary}\r\n"
-#[allow(clippy::type_complexity)]
+#[allow(
+    clippy::type_complexity,
+    clippy::needless_continue, // skipping other headers explicitly
+)]
 fn try_parse<S>(
     body: Pin<Box<S>>,
     pat: Box<[u8]>,
     buf: &'_ [u8],
     fields: &'_ mut Vec<(String, String)>,
     boundary: &'_ [u8],
-) -> Result<io::Result<Multipart>, (Pin<Box<S>>, Box<[u8]>)>
+    limits: MultipartLimits,
+) -> Result<Result<Multipart, MultipartError>, (Pin<Box<S>>, Box<[u8]>)>
 where
     S: Stream<Item = io::Result<Bytes>> + Send + Sync + 'static,
 {
@@ -131,33 +217,34 @@ where
 
     let mut lines = CrlfLines { slice: buf };
 
-    // first line
-    match lines.next_line() {
+    // the last line may be incomplete
+    let is_incomplete_boundary = |line: &[u8], rest: &CrlfLines<'_>| {
+        rest.slice.is_empty() && pat_without_crlf.starts_with(line)
+    };
+
+    // first boundary, which may be preceded by an empty line
+    let first_boundary = match lines.next_line() {
+        Some(&[]) => lines.next_line(),
+        other => other,
+    };
+    match first_boundary {
         None => return Err((body, pat)),
-        Some(&[]) => {
-            // first boundary
-            match lines.next_line() {
-                None => return Err((body, pat)),
-                Some(line) => {
-                    if line != pat_without_crlf {
-                        return Ok(Err(generate_format_error()));
-                    };
-                }
-            }
-        }
         Some(line) => {
+            if is_incomplete_boundary(line, &lines) {
+                return Err((body, pat));
+            }
             if line != pat_without_crlf {
-                return Ok(Err(generate_format_error()));
+                return Ok(Err(MultipartError::Format));
             }
         }
-    };
+    }
 
     let mut headers = [httparse::EMPTY_HEADER; 2];
     loop {
         let (idx, parsed_headers) = match httparse::parse_headers(lines.slice, &mut headers) {
             Ok(httparse::Status::Complete(ans)) => ans,
             Ok(_) => return Err((body, pat)),
-            Err(_) => return Ok(Err(generate_format_error())),
+            Err(_) => return Ok(Err(MultipartError::Format)),
         };
         lines.slice = lines.slice.split_at(idx).1;
 
@@ -175,7 +262,7 @@ where
 
         let content_disposition = match content_disposition_bytes.map(parse_content_disposition) {
             None => return Err((body, pat)),
-            Some(Err(_)) => return Ok(Err(generate_format_error())),
+            Some(Err(_)) => return Ok(Err(MultipartError::Format)),
             Some(Ok((_, c))) => c,
         };
         match content_disposition.filename {
@@ -187,18 +274,27 @@ where
                         let b = &b[..b.len().saturating_sub(2)];
 
                         match std::str::from_utf8(b) {
-                            Err(_) => return Ok(Err(generate_format_error())),
+                            Err(_) => return Ok(Err(MultipartError::Format)),
                             Ok(s) => s,
                         }
                     }
                 };
 
+                if fields.len() >= limits.max_fields {
+                    return Ok(Err(MultipartError::LimitExceeded(MultipartLimit::Fields)));
+                }
+                if value.len() > limits.max_field_size {
+                    return Ok(Err(MultipartError::LimitExceeded(
+                        MultipartLimit::FieldSize,
+                    )));
+                }
+
                 fields.push((content_disposition.name.to_owned(), value.to_owned()));
             }
             Some(filename) => {
                 let content_type = match content_type_bytes.map(std::str::from_utf8) {
                     None => return Err((body, pat)),
-                    Some(Err(_)) => return Ok(Err(generate_format_error())),
+                    Some(Err(_)) => return Ok(Err(MultipartError::Format)),
                     Some(Ok(s)) => s,
                 };
                 let remaining_bytes = if lines.slice.is_empty() {
@@ -214,7 +310,7 @@ where
                 };
 
                 return Ok(Ok(Multipart {
-                    fields: fields.drain(..).collect(),
+                    fields: mem::take(fields),
                     file,
                 }));
             }
@@ -222,8 +318,9 @@ where
     }
 }
 
-#[derive(Debug, thiserror::Error)]
 /// File stream error
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum FileStreamError {
     /// Incomplete error
     #[error("FileStreamError: Incomplete")]
@@ -256,6 +353,7 @@ impl FileStream {
         S: Stream<Item = io::Result<Bytes>> + Send + Sync + 'static,
     {
         /// internal async generator
+        #[allow(clippy::needless_continue)] // every state of the dfa ends with a jump
         async fn gen<S>(
             mut y: Yielder<Result<Bytes, FileStreamError>>,
             mut body: Pin<Box<S>>,
@@ -285,7 +383,7 @@ impl FileStream {
                             None => return Err(FileStreamError::Incomplete),
                             Some(Err(e)) => return Err(FileStreamError::Io(e)),
                             Some(Ok(b)) => bytes = b,
-                        };
+                        }
                         state = 2;
                         continue 'dfa;
                     }
@@ -323,7 +421,7 @@ impl FileStream {
                             None => return Err(FileStreamError::Incomplete),
                             Some(Err(e)) => return Err(FileStreamError::Io(e)),
                             Some(Ok(b)) => buf.extend_from_slice(&b),
-                        };
+                        }
                         bytes = Bytes::from(mem::take(&mut buf));
                         state = 2;
                         continue 'dfa;
@@ -592,7 +690,7 @@ mod tests {
 
         let body_stream = futures::stream::iter(body_bytes);
 
-        let ans = transform_multipart(body_stream, boundary.as_bytes())
+        let ans = transform_multipart(body_stream, boundary.as_bytes(), MultipartLimits::default())
             .await
             .unwrap();
 
@@ -648,7 +746,7 @@ mod tests {
         let body_stream = futures::stream::iter(body_bytes);
         let boundary = "------------------------c634190ccaebbc34";
 
-        let ans = transform_multipart(body_stream, boundary.as_bytes())
+        let ans = transform_multipart(body_stream, boundary.as_bytes(), MultipartLimits::default())
             .await
             .unwrap();
 
@@ -706,4 +804,139 @@ mod tests {
             assert_eq!(file_bytes, file_content);
         }
     }
+
+    fn form_body(boundary: &str, fields: &[(&str, &str)], file_content: &str) -> Vec<u8> {
+        let mut parts: Vec<String> = fields
+            .iter()
+            .map(|&(n, v)| {
+                format!(
+                    "--{boundary}\r\nContent-Disposition: form-data; name=\"{n}\"\r\n\r\n{v}\r\n"
+                )
+            })
+            .collect();
+        parts.push(format!(
+            concat!(
+                "--{}\r\n",
+                "Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n",
+                "Content-Type: text/plain\r\n",
+                "\r\n",
+                "{}\r\n",
+                "--{}--\r\n",
+            ),
+            boundary, file_content, boundary
+        ));
+        parts.concat().into_bytes()
+    }
+
+    fn split_body(body: &[u8], chunk_size: usize) -> impl Stream<Item = io::Result<Bytes>> {
+        let chunks: Vec<io::Result<Bytes>> = body
+            .chunks(chunk_size)
+            .map(|c| Ok(Bytes::copy_from_slice(c)))
+            .collect();
+        futures::stream::iter(chunks)
+    }
+
+    #[tokio::test]
+    async fn limits() {
+        let boundary = "9431149156168";
+        let limits = MultipartLimits {
+            max_fields: 2,
+            max_field_size: 8,
+            max_header_bytes: 256,
+        };
+
+        let check = |ret: Result<Multipart, MultipartError>, limit: MultipartLimit| match ret {
+            Err(MultipartError::LimitExceeded(l)) => assert_eq!(l, limit),
+            _ => panic!("limit is not checked"),
+        };
+
+        {
+            let fields = [("a", "1"), ("b", "2")];
+            let body = form_body(boundary, &fields, "content");
+            let ans = transform_multipart(split_body(&body, 7), boundary.as_bytes(), limits)
+                .await
+                .unwrap();
+            assert_eq!(ans.fields.len(), 2);
+        }
+        {
+            let fields = [("a", "1"), ("b", "2"), ("c", "3")];
+            let body = form_body(boundary, &fields, "content");
+            check(
+                transform_multipart(split_body(&body, 7), boundary.as_bytes(), limits).await,
+                MultipartLimit::Fields,
+            );
+        }
+        {
+            let fields = [("a", "123456789")];
+            let body = form_body(boundary, &fields, "content");
+            check(
+                transform_multipart(split_body(&body, 7), boundary.as_bytes(), limits).await,
+                MultipartLimit::FieldSize,
+            );
+        }
+        {
+            let long_name = "n".repeat(300);
+            let fields = [(long_name.as_str(), "1")];
+            let body = form_body(boundary, &fields, "content");
+            check(
+                transform_multipart(split_body(&body, 7), boundary.as_bytes(), limits).await,
+                MultipartLimit::HeaderBytes,
+            );
+        }
+    }
+
+    /// xorshift64, returns a number in `0..n`
+    #[allow(
+        clippy::integer_division_remainder_used,
+        clippy::as_conversions,
+        clippy::cast_possible_truncation
+    )]
+    fn random_below(state: &mut u64, n: usize) -> usize {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        (*state % (n as u64)) as usize
+    }
+
+    #[tokio::test]
+    async fn fuzz() {
+        let boundary = "9431149156168";
+        let fields = [("key", "acl"), ("policy", "encoded_policy"), ("x", "")];
+        let body = form_body(boundary, &fields, "file\r\n--content");
+
+        let limits = MultipartLimits {
+            max_fields: 4,
+            max_field_size: 64,
+            max_header_bytes: 512,
+        };
+
+        let special_bytes = b"\r\n-\";=";
+
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..2000 {
+            let mut mutated = body.clone();
+
+            for _ in 0..random_below(&mut state, 4) {
+                let pos = random_below(&mut state, mutated.len());
+                match random_below(&mut state, 4) {
+                    0 => mutated[pos] = b'a',
+                    1 => {
+                        let idx = random_below(&mut state, special_bytes.len());
+                        mutated[pos] = special_bytes[idx];
+                    }
+                    2 => mutated.truncate(pos.max(1)),
+                    _ => drop(mutated.remove(pos)),
+                }
+            }
+
+            let chunk_size = random_below(&mut state, 16) + 1;
+            let stream = split_body(&mutated, chunk_size);
+
+            // must not panic or hang
+            if let Ok(multipart) = transform_multipart(stream, boundary.as_bytes(), limits).await {
+                assert!(multipart.fields.len() <= limits.max_fields);
+                drop(aggregate_file_stream(multipart.file.stream).await);
+            }
+        }
+    }
 }
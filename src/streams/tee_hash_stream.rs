@@ -0,0 +1,198 @@
+//! stream which computes several digests of the forwarded bytes in one pass
+
+use crate::utils::crypto::{self, Sha256};
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::stream::Stream;
+use hyper::body::Bytes;
+use md5::{Digest, Md5};
+use pin_project_lite::pin_project;
+
+/// Hashers of the selected digest algorithms
+#[derive(Debug, Default)]
+pub struct MultiHasher {
+    /// MD5
+    md5: Option<Md5>,
+    /// SHA-256
+    sha256: Option<Sha256>,
+    /// CRC32C
+    crc32c: Option<u32>,
+    /// BLAKE3
+    #[cfg(feature = "blake3")]
+    blake3: Option<Box<blake3::Hasher>>,
+}
+
+/// Digests computed by [`MultiHasher`], `None` for the algorithms which are not selected
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Checksums {
+    /// hex-encoded MD5
+    pub md5: Option<String>,
+    /// hex-encoded SHA-256
+    pub sha256: Option<String>,
+    /// base64-encoded big-endian CRC32C, as in `x-amz-checksum-crc32c`
+    pub crc32c: Option<String>,
+    /// hex-encoded BLAKE3
+    #[cfg(feature = "blake3")]
+    pub blake3: Option<String>,
+}
+
+impl MultiHasher {
+    /// Constructs a hasher which selects no algorithm
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Selects MD5
+    #[must_use]
+    pub fn with_md5(mut self) -> Self {
+        self.md5 = Some(Md5::new());
+        self
+    }
+
+    /// Selects SHA-256
+    #[must_use]
+    pub fn with_sha256(mut self) -> Self {
+        self.sha256 = Some(Sha256::new());
+        self
+    }
+
+    /// Selects CRC32C
+    #[must_use]
+    pub const fn with_crc32c(mut self) -> Self {
+        self.crc32c = Some(0);
+        self
+    }
+
+    /// Selects BLAKE3
+    #[cfg(feature = "blake3")]
+    #[must_use]
+    pub fn with_blake3(mut self) -> Self {
+        self.blake3 = Some(Box::new(blake3::Hasher::new()));
+        self
+    }
+
+    /// Feeds data to all selected hashers
+    pub fn update(&mut self, data: &[u8]) {
+        if let Some(ref mut md5) = self.md5 {
+            md5.update(data);
+        }
+        if let Some(ref mut sha256) = self.sha256 {
+            sha256.update(data);
+        }
+        if let Some(ref mut crc) = self.crc32c {
+            *crc = crc32c::crc32c_append(*crc, data);
+        }
+        #[cfg(feature = "blake3")]
+        if let Some(ref mut blake3) = self.blake3 {
+            #[allow(clippy::let_underscore_untyped)] // returns the hasher itself
+            let _ = blake3.update(data);
+        }
+    }
+
+    /// Returns the digests
+    #[allow(clippy::big_endian_bytes)] // S3 checksums are big-endian
+    #[must_use]
+    pub fn finalize(self) -> Checksums {
+        Checksums {
+            md5: self.md5.map(|h| crypto::to_hex_string(h.finalize())),
+            sha256: self.sha256.map(|h| crypto::to_hex_string(h.finalize())),
+            crc32c: self
+                .crc32c
+                .map(|crc| base64_simd::STANDARD.encode_to_string(crc.to_be_bytes())),
+            #[cfg(feature = "blake3")]
+            blake3: self.blake3.map(|h| h.finalize().to_hex().to_string()),
+        }
+    }
+}
+
+pin_project! {
+    /// A stream which feeds each forwarded chunk to a [`MultiHasher`]
+    ///
+    /// The hasher is borrowed, so the digests can be taken
+    /// after the stream is consumed by a writer.
+    pub struct TeeHashStream<'h, S> {
+        #[pin]
+        inner: S,
+        hasher: &'h mut MultiHasher,
+    }
+}
+
+impl<'h, S> TeeHashStream<'h, S> {
+    /// Constructs a `TeeHashStream`
+    pub fn new(inner: S, hasher: &'h mut MultiHasher) -> Self {
+        Self { inner, hasher }
+    }
+}
+
+impl<S, E> Stream for TeeHashStream<'_, S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+{
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let ret = futures::ready!(this.inner.poll_next(cx));
+        if let Some(Ok(ref bytes)) = ret {
+            this.hasher.update(bytes);
+        }
+        Poll::Ready(ret)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::stream::TryStreamExt;
+
+    #[tokio::test]
+    #[allow(clippy::big_endian_bytes)]
+    async fn digests() {
+        let chunks: Vec<Result<Bytes, ()>> =
+            vec![Ok("Welcome to ".into()), Ok("Amazon S3.".into())];
+
+        let mut hasher = MultiHasher::new().with_md5().with_sha256().with_crc32c();
+        let stream = TeeHashStream::new(futures::stream::iter(chunks), &mut hasher);
+        let forwarded: Vec<Bytes> = stream.try_collect().await.unwrap();
+        assert_eq!(forwarded.concat(), b"Welcome to Amazon S3.");
+
+        let checksums = hasher.finalize();
+        let content = b"Welcome to Amazon S3.";
+        assert_eq!(
+            checksums.md5.unwrap(),
+            crypto::to_hex_string(Md5::digest(content))
+        );
+        assert_eq!(checksums.sha256.unwrap(), crypto::hex_sha256(content));
+        let crc = crc32c::crc32c(content).to_be_bytes();
+        assert_eq!(
+            checksums.crc32c.unwrap(),
+            base64_simd::STANDARD.encode_to_string(crc)
+        );
+
+        let md5_only = MultiHasher::new().with_md5().finalize();
+        assert!(md5_only.sha256.is_none() && md5_only.crc32c.is_none());
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn blake3() {
+        let mut hasher = MultiHasher::new().with_blake3();
+        hasher.update(b"Welcome to ");
+        hasher.update(b"Amazon S3.");
+        let checksums = hasher.finalize();
+        assert_eq!(
+            checksums.blake3.unwrap(),
+            blake3::hash(b"Welcome to Amazon S3.").to_hex().as_str()
+        );
+        assert!(checksums.md5.is_none());
+    }
+}
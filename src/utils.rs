@@ -3,13 +3,17 @@
 mod also;
 mod apply;
 mod response;
+#[macro_use]
 mod xml;
 
 pub use self::also::Also;
 pub use self::apply::Apply;
-pub use self::response::ResponseExt;
-pub use self::xml::XmlWriterExt;
+pub use self::response::{ResponseExt, XmlBody};
+pub use self::xml::{is_xml_char, pretty_print, XmlElement, XmlWriterExt};
 
 pub mod body;
+pub mod copy;
 pub mod crypto;
+#[cfg(any(feature = "jwt", feature = "vault"))]
+pub mod http_client;
 pub mod time;
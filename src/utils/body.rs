@@ -12,16 +12,19 @@ use serde::de::DeserializeOwned;
 
 /// deserialize xml body
 pub async fn deserialize_xml_body<T: DeserializeOwned>(body: Body) -> Result<T, BoxStdError> {
-    let bytes = hyper::body::to_bytes(body).await?;
+    let bytes = body.into_bytes().await?;
     let ans: T = quick_xml::de::from_reader(&*bytes)?;
     Ok(ans)
 }
 
 /// transform `Body` into `ByteStream`
+///
+/// io errors from wrapped streams are passed through
 pub fn transform_body_stream(body: Body) -> ByteStream {
     body.map(|try_chunk| {
-        try_chunk.map_err(|e| {
-            io::Error::new(io::ErrorKind::Other, format!("Error obtaining chunk: {e}"))
+        try_chunk.map_err(|e| match e.downcast::<io::Error>() {
+            Ok(io_err) => *io_err,
+            Err(e) => io::Error::new(io::ErrorKind::Other, format!("Error obtaining chunk: {e}")),
         })
     })
     .apply(ByteStream::new)
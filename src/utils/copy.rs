@@ -0,0 +1,108 @@
+//! copying byte streams to writers
+
+use crate::streams::content_length_range_stream::ContentLengthRangeError;
+
+use std::io;
+
+use futures::io::{AsyncWrite, AsyncWriteExt};
+use futures::stream::{Stream, StreamExt};
+use hyper::body::Bytes;
+
+/// A copier of byte streams to writers, shared by the writes of object bodies
+///
+/// Each chunk is written completely before the next one is polled,
+/// so a slow writer holds back the stream instead of buffering it in memory.
+pub struct StreamCopier<'p> {
+    /// max number of bytes to copy
+    max_size: Option<u64>,
+    /// called with the total number of copied bytes after each chunk
+    progress: Option<Box<dyn FnMut(u64) + Send + 'p>>,
+}
+
+impl<'p> StreamCopier<'p> {
+    /// Constructs a copier without limits
+    pub fn new() -> Self {
+        Self {
+            max_size: None,
+            progress: None,
+        }
+    }
+
+    /// Fails the copy with [`ContentLengthRangeError::TooLarge`] before more than `max_size`
+    /// bytes are written
+    pub const fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Reports the total number of copied bytes after each chunk
+    pub fn with_progress(mut self, f: impl FnMut(u64) + Send + 'p) -> Self {
+        self.progress = Some(Box::new(f));
+        self
+    }
+
+    /// Copies all bytes of a stream to a writer and flushes it, returns the number of bytes
+    pub async fn copy<S, W>(mut self, mut stream: S, writer: &mut W) -> io::Result<u64>
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + Unpin,
+        W: AsyncWrite + Send + Unpin,
+    {
+        let mut nwrite: u64 = 0;
+        while let Some(bytes) = stream.next().await {
+            let bytes = bytes?;
+            let len = u64::try_from(bytes.len()).unwrap_or(u64::MAX);
+            nwrite = nwrite.saturating_add(len);
+            if self.max_size.map_or(false, |max| nwrite > max) {
+                let err = ContentLengthRangeError::TooLarge;
+                return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+            }
+
+            writer.write_all(&bytes).await?;
+            if let Some(ref mut f) = self.progress {
+                f(nwrite);
+            }
+        }
+        writer.flush().await?;
+        Ok(nwrite)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks(data: &[&'static [u8]]) -> impl Stream<Item = io::Result<Bytes>> + Send + Unpin {
+        let v: Vec<io::Result<Bytes>> = data.iter().map(|&b| Ok(Bytes::from(b))).collect();
+        futures::stream::iter(v)
+    }
+
+    #[tokio::test]
+    #[allow(clippy::shadow_unrelated)]
+    async fn copy() {
+        let mut progress = Vec::new();
+        let mut buf = Vec::new();
+        let copier = StreamCopier::new()
+            .with_max_size(11)
+            .with_progress(|n| progress.push(n));
+        let nwrite = copier
+            .copy(chunks(&[b"Hello", b", ", b"", b"rust"]), &mut buf)
+            .await
+            .unwrap();
+        assert_eq!(nwrite, 11);
+        assert_eq!(buf, b"Hello, rust");
+        assert_eq!(progress, [5, 7, 7, 11]);
+
+        let mut buf = Vec::new();
+        let err = StreamCopier::new()
+            .with_max_size(10)
+            .copy(chunks(&[b"Hello", b", ", b"rust"]), &mut buf)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            ContentLengthRangeError::find_cause(&err),
+            Some(ContentLengthRangeError::TooLarge)
+        );
+        // the chunk beyond the limit is not written
+        assert_eq!(buf, b"Hello, ");
+    }
+}
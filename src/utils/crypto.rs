@@ -1,10 +1,95 @@
 //! crypto utils
+//!
+//! SHA-256 and HMAC-SHA256 are provided by a backend selected by features:
+//!
+//! + `openssl`: `OpenSSL`
+//! + `aws-lc-rs`: `AWS-LC`
+//! + `ring`: `ring`
+//! + default: pure-Rust implementations
+//!
+//! If several of them are enabled, the first one in the list is used.
+
+#[cfg(all(feature = "aws-lc-rs", not(feature = "openssl")))]
+mod aws_lc_crypto;
+#[cfg(feature = "openssl")]
+mod openssl_crypto;
+#[cfg(all(feature = "ring", not(any(feature = "openssl", feature = "aws-lc-rs"))))]
+mod ring_crypto;
+#[cfg(not(any(feature = "openssl", feature = "aws-lc-rs", feature = "ring")))]
+mod rust_crypto;
 
 use crate::utils::Also;
 
-use hmac::{Hmac, Mac};
+use std::fmt::{self, Debug};
+
 use hyper::body::Bytes;
-use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+/// incremental SHA-256 hasher
+pub trait Sha256Hasher: Send + Sized {
+    /// Constructs a hasher
+    fn new() -> Self;
+    /// feeds data
+    fn update(&mut self, data: &[u8]);
+    /// returns the digest
+    fn finalize(self) -> [u8; 32];
+}
+
+/// crypto backend
+pub trait CryptoBackend {
+    /// SHA-256 hasher
+    type Sha256: Sha256Hasher;
+
+    /// `hmac_sha256(key, data)`
+    fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32];
+}
+
+/// selected crypto backend
+#[cfg(feature = "openssl")]
+pub type Backend = openssl_crypto::OpenSsl;
+
+/// selected crypto backend
+#[cfg(all(feature = "aws-lc-rs", not(feature = "openssl")))]
+pub type Backend = aws_lc_crypto::AwsLc;
+
+/// selected crypto backend
+#[cfg(all(feature = "ring", not(any(feature = "openssl", feature = "aws-lc-rs"))))]
+pub type Backend = ring_crypto::Ring;
+
+/// selected crypto backend
+#[cfg(not(any(feature = "openssl", feature = "aws-lc-rs", feature = "ring")))]
+pub type Backend = rust_crypto::RustCrypto;
+
+/// SHA-256 hasher of the selected backend
+pub struct Sha256 {
+    /// inner hasher
+    inner: <Backend as CryptoBackend>::Sha256,
+}
+
+impl Debug for Sha256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Sha256 {{...}}")
+    }
+}
+
+impl Sha256 {
+    /// Constructs a hasher
+    pub fn new() -> Self {
+        Self {
+            inner: Sha256Hasher::new(),
+        }
+    }
+
+    /// feeds data
+    pub fn update(&mut self, data: &[u8]) {
+        Sha256Hasher::update(&mut self.inner, data);
+    }
+
+    /// returns the digest
+    pub fn finalize(self) -> [u8; 32] {
+        Sha256Hasher::finalize(self.inner)
+    }
+}
 
 /// convert bytes to hex string
 pub fn to_hex_string(src: impl AsRef<[u8]>) -> String {
@@ -19,11 +104,7 @@ pub fn is_sha256_checksum(s: &str) -> bool {
 
 /// `hex(sha256(data))`
 pub fn hex_sha256(data: &[u8]) -> String {
-    let src = Sha256::digest(data);
-
-    #[cfg(test)]
-    debug_assert!(src.as_slice().len() == 32);
-
+    let src = Sha256::new().also(|h| h.update(data)).finalize();
     to_hex_string(src)
 }
 
@@ -32,32 +113,63 @@ pub fn hex_sha256_chunk(chunk_data: &[Bytes]) -> String {
     let src = Sha256::new()
         .also(|h| chunk_data.iter().for_each(|data| h.update(data)))
         .finalize();
-
-    #[cfg(test)]
-    debug_assert!(src.as_slice().len() == 32);
-
     to_hex_string(src)
 }
 
 /// `hmac_sha256(key, data)`
-pub fn hmac_sha256(key: &[u8], data: &[u8]) -> impl AsRef<[u8]> {
-    let mut m = <Hmac<Sha256>>::new_from_slice(key)
-        .unwrap_or_else(|_| panic!("HMAC can take key of any size"));
-    m.update(data.as_ref());
-    m.finalize().into_bytes()
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    Backend::hmac_sha256(key, data)
 }
 
 /// `hex(hmac_sha256(key, data))`
 pub fn hex_hmac_sha256(key: &[u8], data: &[u8]) -> String {
-    let src = hmac_sha256(key, data);
-
-    #[cfg(test)]
-    debug_assert!(src.as_ref().len() == 32);
+    to_hex_string(hmac_sha256(key, data))
+}
 
-    to_hex_string(src)
+/// compares two byte strings in constant time
+///
+/// The time only depends on the lengths, so it is suitable for comparing signatures.
+pub fn constant_time_eq(lhs: &[u8], rhs: &[u8]) -> bool {
+    lhs.ct_eq(rhs).into()
 }
 
 /// is base64 encoded
 pub fn is_base64_encoded(bytes: &[u8]) -> bool {
     base64_simd::STANDARD.check(bytes).is_ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha256_and_hmac() {
+        assert_eq!(
+            hex_sha256(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex_sha256_chunk(&[Bytes::from_static(b"a"), Bytes::from_static(b"bc")]),
+            hex_sha256(b"abc")
+        );
+        assert_eq!(
+            hex_hmac_sha256(b"key", b"The quick brown fox jumps over the lazy dog"),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn constant_time_comparison() {
+        let signature = "aeeed9bbccd4d02ee5c0109b86d86835f995330da4c265957d157751f604d404";
+        assert!(constant_time_eq(signature.as_bytes(), signature.as_bytes()));
+        assert!(!constant_time_eq(
+            signature.as_bytes(),
+            &signature.as_bytes()[1..]
+        ));
+        assert!(!constant_time_eq(
+            signature.as_bytes(),
+            signature.replace('a', "b").as_bytes()
+        ));
+        assert!(constant_time_eq(b"", b""));
+    }
+}
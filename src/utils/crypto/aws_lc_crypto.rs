@@ -0,0 +1,39 @@
+//! `AWS-LC` crypto backend
+
+use super::{CryptoBackend, Sha256Hasher};
+
+use aws_lc_rs::digest::{Context, SHA256};
+use aws_lc_rs::hmac::{self, Key, HMAC_SHA256};
+
+/// crypto backend powered by `AWS-LC`
+#[derive(Debug, Clone, Copy)]
+pub struct AwsLc;
+
+impl Sha256Hasher for Context {
+    fn new() -> Self {
+        Self::new(&SHA256)
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        Self::update(self, data);
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        self.finish()
+            .as_ref()
+            .try_into()
+            .unwrap_or_else(|_| panic!("SHA-256 digest is 32 bytes"))
+    }
+}
+
+impl CryptoBackend for AwsLc {
+    type Sha256 = Context;
+
+    fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+        let key = Key::new(HMAC_SHA256, key);
+        hmac::sign(&key, data)
+            .as_ref()
+            .try_into()
+            .unwrap_or_else(|_| panic!("HMAC-SHA256 tag is 32 bytes"))
+    }
+}
@@ -0,0 +1,42 @@
+//! `OpenSSL` crypto backend
+
+use super::{CryptoBackend, Sha256Hasher};
+
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sha::Sha256;
+use openssl::sign::Signer;
+
+/// crypto backend powered by `OpenSSL`
+#[derive(Debug, Clone, Copy)]
+pub struct OpenSsl;
+
+impl Sha256Hasher for Sha256 {
+    fn new() -> Self {
+        Self::new()
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        Self::update(self, data);
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        self.finish()
+    }
+}
+
+impl CryptoBackend for OpenSsl {
+    type Sha256 = Sha256;
+
+    fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+        let sign = || {
+            let pkey = PKey::hmac(key)?;
+            let mut signer = Signer::new(MessageDigest::sha256(), &pkey)?;
+            signer.update(data)?;
+            let mut ans = [0_u8; 32];
+            let _: usize = signer.sign(&mut ans)?;
+            Ok::<_, openssl::error::ErrorStack>(ans)
+        };
+        sign().unwrap_or_else(|err| panic!("OpenSSL HMAC-SHA256 failed: {err}"))
+    }
+}
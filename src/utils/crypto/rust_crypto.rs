@@ -0,0 +1,35 @@
+//! pure-Rust crypto backend
+
+use super::{CryptoBackend, Sha256Hasher};
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+/// crypto backend powered by `RustCrypto`
+#[derive(Debug, Clone, Copy)]
+pub struct RustCrypto;
+
+impl Sha256Hasher for Sha256 {
+    fn new() -> Self {
+        Digest::new()
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(self, data);
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        Digest::finalize(self).into()
+    }
+}
+
+impl CryptoBackend for RustCrypto {
+    type Sha256 = Sha256;
+
+    fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+        let mut m = <Hmac<Sha256>>::new_from_slice(key)
+            .unwrap_or_else(|_| panic!("HMAC can take key of any size"));
+        m.update(data);
+        m.finalize().into_bytes().into()
+    }
+}
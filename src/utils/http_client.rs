@@ -0,0 +1,48 @@
+//! a minimal http client of rare requests to internal endpoints
+
+use std::io;
+
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper::client::conn::http1;
+use hyper::header::{HeaderName, HOST};
+use hyper::{Request, StatusCode, Uri};
+use hyper_util::rt::TokioIo;
+use tracing::debug;
+
+/// sends `GET` to a `http` endpoint by a new connection, returns the status and the whole body
+pub async fn get(uri: &Uri, headers: &[(HeaderName, &str)]) -> io::Result<(StatusCode, Bytes)> {
+    let to_io = |e: hyper::Error| io::Error::new(io::ErrorKind::Other, e);
+    let invalid_input = |msg: &str| io::Error::new(io::ErrorKind::InvalidInput, msg);
+
+    if uri.scheme_str() != Some("http") {
+        return Err(invalid_input("endpoint must be http"));
+    }
+    let authority = uri
+        .authority()
+        .ok_or_else(|| invalid_input("endpoint has no host"))?;
+    let port = authority.port_u16().unwrap_or(80);
+    let stream = tokio::net::TcpStream::connect((authority.host(), port)).await?;
+
+    let (mut sender, conn) = http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(to_io)?;
+    drop(tokio::spawn(async move {
+        if let Err(err) = conn.await {
+            debug!(%err, "http client connection failed");
+        }
+    }));
+
+    let path = uri.path_and_query().map_or("/", |p| p.as_str());
+    let mut req = Request::get(path).header(HOST, authority.as_str());
+    for &(ref name, value) in headers {
+        req = req.header(name, value);
+    }
+    let req = req
+        .body(Empty::<Bytes>::new())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let res = sender.send_request(req).await.map_err(to_io)?;
+    let status = res.status();
+    let body = res.into_body().collect().await.map_err(to_io)?.to_bytes();
+    Ok((status, body))
+}
@@ -7,6 +7,11 @@ use std::{collections::HashMap, convert::TryFrom};
 use hyper::header::{self, HeaderName, HeaderValue, InvalidHeaderValue};
 use xml::{common::XmlVersion, writer::XmlEvent, EventWriter};
 
+/// The extension of responses whose body is written by [`ResponseExt::set_xml_body`],
+/// which tells them from objects of `text/xml`
+#[derive(Debug, Clone, Copy)]
+pub struct XmlBody;
+
 /// `ResponseExt`
 pub trait ResponseExt {
     /// create response with body and status
@@ -73,7 +78,7 @@ impl ResponseExt for Response {
     {
         let mut body = Vec::with_capacity(cap);
         {
-            let mut w = EventWriter::new(&mut body);
+            let mut w = super::xml::new_writer(&mut body);
             w.write(XmlEvent::StartDocument {
                 version: XmlVersion::Version10,
                 encoding: Some("UTF-8"),
@@ -85,6 +90,7 @@ impl ResponseExt for Response {
 
         *self.body_mut() = Body::from(body);
         self.set_mime(&mime::TEXT_XML)?;
+        let _prev = self.extensions_mut().insert(XmlBody);
         Ok(())
     }
 
@@ -93,6 +99,7 @@ impl ResponseExt for Response {
         metadata: &HashMap<String, String>,
     ) -> Result<(), BoxStdError> {
         let headers = self.headers_mut();
+        #[allow(clippy::iter_over_hash_type)] // the order of headers does not matter
         for (name, value) in metadata {
             let header_name = HeaderName::from_bytes(format!("x-amz-meta-{name}").as_bytes())?;
             let header_value = HeaderValue::from_bytes(value.as_bytes())?;
@@ -19,12 +19,26 @@ pub fn to_rfc3339(time: SystemTime) -> String {
     time.to_rfc3339()
 }
 
+/// parse rfc3339 as `SystemTime`
+#[cfg(feature = "extensions")]
+pub fn parse_rfc3339(s: &str) -> Result<SystemTime, chrono::ParseError> {
+    let time: DateTime<Utc> = DateTime::parse_from_rfc3339(s)?.into();
+    Ok(time.into())
+}
+
 /// convert rfc3339 to `last_modified`
 pub fn rfc3339_to_last_modified(s: &str) -> Result<String, chrono::ParseError> {
     let time: DateTime<Utc> = DateTime::parse_from_rfc3339(s)?.into();
     time.format(LAST_MODIFIED_TIME_FORMAT).to_string().apply(Ok)
 }
 
+/// checks whether `time` is later than an http date at the precision of seconds
+pub fn is_modified_since(time: SystemTime, http_date: &str) -> Result<bool, chrono::ParseError> {
+    let date = DateTime::parse_from_rfc2822(http_date)?;
+    let time: DateTime<Utc> = time.into();
+    Ok(time.timestamp() > date.timestamp())
+}
+
 /// convert optional rfc3339 to optional `last_modified`
 pub fn map_opt_rfc3339_to_last_modified(
     s: Option<&str>,
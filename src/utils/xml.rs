@@ -1,14 +1,101 @@
 //! helper trait for writing xml
 
+use std::borrow::Cow;
+use std::fmt::Write;
 use std::io;
-use std::ops::Deref;
-use xml::writer::{events::XmlEvent, EventWriter, Result};
+use xml::reader::ParserConfig;
+use xml::writer::{events::XmlEvent, EmitterConfig, EventWriter, Result};
+
+/// The namespace of S3 responses
+///
+/// It is declared on the root element of result bodies, but not on error bodies,
+/// which are not namespaced by AWS either.
+pub const S3_NAMESPACE: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+/// Creates a xml writer
+///
+/// The writer does not escape text by itself.
+/// Text must be written by [`XmlWriterExt`], which escapes it by [`escape_text`].
+pub fn new_writer<W: io::Write>(sink: W) -> EventWriter<W> {
+    let mut config = EmitterConfig::new();
+    config.perform_escaping = false;
+    config.create_writer(sink)
+}
+
+/// Re-indents a xml document for human inspection
+///
+/// Text is escaped again by the writer, so control characters are written as raw characters.
+pub fn pretty_print(document: &[u8]) -> std::result::Result<Vec<u8>, crate::BoxStdError> {
+    let reader = ParserConfig::new()
+        .whitespace_to_characters(true)
+        .create_reader(document);
+    let mut buf = Vec::with_capacity(document.len().saturating_mul(2));
+    {
+        let mut w = EmitterConfig::new()
+            .perform_indent(true)
+            .create_writer(&mut buf);
+        for event in reader {
+            if let Some(event) = event?.as_writer_event() {
+                w.write(event)?;
+            }
+        }
+    }
+    buf.push(b'\n');
+    Ok(buf)
+}
+
+/// Whether a char is allowed in xml 1.0 documents, either raw or as a character reference
+pub const fn is_xml_char(c: char) -> bool {
+    !matches!(
+        c,
+        '\u{0}'..='\u{8}' | '\u{b}' | '\u{c}' | '\u{e}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}'
+    )
+}
+
+/// Escapes xml text
+///
+/// `&`, `<` and `>` are replaced by entities.
+/// `\r` and `DEL` are replaced by character references,
+/// so that they are not normalized or rejected as raw characters by parsers.
+/// Chars which are not allowed in xml 1.0 even as references are replaced by `U+FFFD`,
+/// so listings reject keys with them unless `encoding-type=url` is requested.
+pub fn escape_text(s: &str) -> Cow<'_, str> {
+    /// whether a char needs escaping
+    const fn needs_escape(c: char) -> bool {
+        matches!(c, '&' | '<' | '>')
+            || !is_xml_char(c)
+            || (c.is_ascii_control() && c != '\t' && c != '\n')
+    }
+
+    if !s.chars().any(needs_escape) {
+        return Cow::Borrowed(s);
+    }
+
+    let mut ans = String::with_capacity(s.len().saturating_add(16));
+    for c in s.chars() {
+        match c {
+            '&' => ans.push_str("&amp;"),
+            '<' => ans.push_str("&lt;"),
+            '>' => ans.push_str("&gt;"),
+            _ if !is_xml_char(c) => ans.push(char::REPLACEMENT_CHARACTER),
+            _ if needs_escape(c) => {
+                write!(ans, "&#x{:X};", u32::from(c))
+                    .unwrap_or_else(|_| unreachable!("writing to a string never fails"));
+            }
+            _ => ans.push(c),
+        }
+    }
+    Cow::Owned(ans)
+}
 
 /// helper trait for writing xml
 pub trait XmlWriterExt {
     /// write xml stack
     fn stack(&mut self, name: &str, f: impl FnOnce(&mut Self) -> Result<()>) -> Result<()>;
 
+    /// write the root element of a result body, in the S3 namespace
+    fn root(&mut self, name: &str, f: impl FnOnce(&mut Self) -> Result<()>) -> Result<()>;
+
     /// write xml optional stack
     fn opt_stack<T>(
         &mut self,
@@ -20,8 +107,8 @@ pub trait XmlWriterExt {
     /// write xml element
     fn element(&mut self, name: &str, data: &str) -> Result<()>;
 
-    /// write xml optional element
-    fn opt_element(&mut self, name: &str, data: Option<impl Deref<Target = str>>) -> Result<()>;
+    /// write xml text
+    fn text(&mut self, data: &str) -> Result<()>;
 
     /// write xml by an iterator
     fn iter_element<T>(
@@ -38,6 +125,12 @@ impl<W: io::Write> XmlWriterExt for EventWriter<W> {
         self.write(XmlEvent::end_element())
     }
 
+    fn root(&mut self, name: &str, f: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        self.write(XmlEvent::start_element(name).default_ns(S3_NAMESPACE))?;
+        f(self)?;
+        self.write(XmlEvent::end_element())
+    }
+
     fn opt_stack<T>(
         &mut self,
         name: &str,
@@ -54,17 +147,12 @@ impl<W: io::Write> XmlWriterExt for EventWriter<W> {
 
     fn element(&mut self, name: &str, data: &str) -> Result<()> {
         self.write(XmlEvent::start_element(name))?;
-        self.write(XmlEvent::characters(data))?;
+        self.text(data)?;
         self.write(XmlEvent::end_element())
     }
 
-    fn opt_element(&mut self, name: &str, data: Option<impl Deref<Target = str>>) -> Result<()> {
-        if let Some(data) = data {
-            self.write(XmlEvent::start_element(name))?;
-            self.write(XmlEvent::characters(&data))?;
-            self.write(XmlEvent::end_element())?;
-        }
-        Ok(())
+    fn text(&mut self, data: &str) -> Result<()> {
+        self.write(XmlEvent::characters(&escape_text(data)))
     }
 
     fn iter_element<T>(
@@ -78,3 +166,302 @@ impl<W: io::Write> XmlWriterExt for EventWriter<W> {
         Ok(())
     }
 }
+
+/// Values which are written as xml elements
+///
+/// Optional values are skipped when they are absent,
+/// and lists are written as repeated elements with the same name.
+pub trait XmlElement {
+    /// write the value as elements named `name`
+    fn write_element<W: io::Write>(self, w: &mut EventWriter<W>, name: &str) -> Result<()>;
+}
+
+impl XmlElement for &str {
+    fn write_element<W: io::Write>(self, w: &mut EventWriter<W>, name: &str) -> Result<()> {
+        w.element(name, self)
+    }
+}
+
+impl XmlElement for String {
+    fn write_element<W: io::Write>(self, w: &mut EventWriter<W>, name: &str) -> Result<()> {
+        w.element(name, &self)
+    }
+}
+
+/// implements `XmlElement` for types written by `to_string`
+macro_rules! impl_xml_element_by_display {
+    ($($ty:ty),+) => {$(
+        impl XmlElement for $ty {
+            fn write_element<W: io::Write>(self, w: &mut EventWriter<W>, name: &str) -> Result<()> {
+                w.element(name, &self.to_string())
+            }
+        }
+    )+};
+}
+
+impl_xml_element_by_display!(bool, i64, u64);
+
+impl<T: XmlElement> XmlElement for Option<T> {
+    fn write_element<W: io::Write>(self, w: &mut EventWriter<W>, name: &str) -> Result<()> {
+        match self {
+            Some(value) => value.write_element(w, name),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: XmlElement> XmlElement for Vec<T> {
+    fn write_element<W: io::Write>(self, w: &mut EventWriter<W>, name: &str) -> Result<()> {
+        w.iter_element(self.into_iter(), |w, value| value.write_element(w, name))
+    }
+}
+
+/// writes a value as an xml element whose children are mapped from its fields
+///
+/// A field is listed as `"ElementName" => field`.
+/// A list which is wrapped in an element is listed as `"Wrapper" / "Item" => field`,
+/// and the wrapper is skipped when the list is absent.
+/// Fields which are not listed are not written.
+/// The root element of a result body is written by `root "Name"`, which declares [`S3_NAMESPACE`].
+///
+/// ```text
+/// xml_element!(w, root "ListAllMyBucketsResult", self {
+///     "Buckets" / "Bucket" => buckets,
+///     "Owner" => owner,
+/// })
+/// ```
+macro_rules! xml_element {
+    ($w:expr, root $name:expr, $value:ident { $($fields:tt)* }) => {
+        $crate::utils::XmlWriterExt::root($w, $name, |w| {
+            xml_element!(@fields w, $value, $($fields)*);
+            Ok(())
+        })
+    };
+    ($w:expr, $name:expr, $value:ident { $($fields:tt)* }) => {
+        $crate::utils::XmlWriterExt::stack($w, $name, |w| {
+            xml_element!(@fields w, $value, $($fields)*);
+            Ok(())
+        })
+    };
+
+    (@fields $w:ident, $value:ident, $(,)?) => {};
+    (@fields $w:ident, $value:ident,
+        $name:literal / $item:literal => $field:ident $(, $($rest:tt)*)?
+    ) => {
+        $crate::utils::XmlWriterExt::opt_stack($w, $name, $value.$field, |w, items| {
+            $crate::utils::XmlElement::write_element(items, w, $item)
+        })?;
+        xml_element!(@fields $w, $value, $($($rest)*)?);
+    };
+    (@fields $w:ident, $value:ident, $name:literal => $field:ident $(, $($rest:tt)*)?) => {
+        $crate::utils::XmlElement::write_element($value.$field, $w, $name)?;
+        xml_element!(@fields $w, $value, $($($rest)*)?);
+    };
+}
+
+/// implements [`XmlElement`] for structs by [`xml_element!`]
+///
+/// ```text
+/// impl_xml_element! {
+///     Owner { "DisplayName" => display_name, "ID" => id }
+/// }
+/// ```
+macro_rules! impl_xml_element {
+    ($($ty:ty { $($fields:tt)* })+) => {$(
+        impl $crate::utils::XmlElement for $ty {
+            fn write_element<W: std::io::Write>(
+                self,
+                w: &mut ::xml::writer::EventWriter<W>,
+                name: &str,
+            ) -> ::xml::writer::Result<()> {
+                xml_element!(w, name, self { $($fields)* })
+            }
+        }
+    )+};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use xml::reader::{EventReader, XmlEvent as ReaderEvent};
+
+    #[test]
+    fn escape() {
+        assert!(matches!(
+            escape_text("plain text"),
+            Cow::Borrowed("plain text")
+        ));
+        assert_eq!(escape_text("a&b<c>d"), "a&amp;b&lt;c&gt;d");
+        assert_eq!(escape_text("]]>"), "]]&gt;");
+        assert_eq!(escape_text("\t\n"), "\t\n");
+        assert_eq!(escape_text("a\rb\u{1}c\u{7f}"), "a&#xD;b\u{fffd}c&#x7F;");
+        assert_eq!(escape_text("\u{fffe}\u{ffff}"), "\u{fffd}\u{fffd}");
+    }
+
+    #[test]
+    fn pretty() {
+        let document = concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>",
+            "<ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">",
+            "<Name>asd</Name><Contents><Key>a&amp;b </Key></Contents>",
+            "</ListBucketResult>",
+        );
+        let ans = pretty_print(document.as_bytes()).unwrap();
+        let expected = concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\n",
+            "  <Name>asd</Name>\n",
+            "  <Contents>\n",
+            "    <Key>a&amp;b </Key>\n",
+            "  </Contents>\n",
+            "</ListBucketResult>\n",
+        );
+        assert_eq!(String::from_utf8(ans).unwrap(), expected);
+
+        assert!(pretty_print(b"<a>").is_err());
+    }
+
+    #[test]
+    fn hostile_text() {
+        let texts = [
+            "a&b",
+            "<Key>",
+            "]]>",
+            "&amp;",
+            "cr\r\nlf",
+            "tab\t",
+            "'\"",
+            "\u{4e2d}",
+            "del\u{7f}",
+            "nul\u{0}",
+            "soh\u{1}",
+            "esc\u{1b}",
+            "\u{fffe}\u{ffff}",
+        ];
+
+        for text in texts {
+            let mut buf = Vec::new();
+            {
+                let mut w = new_writer(&mut buf);
+                w.element("Key", text).unwrap();
+            }
+
+            let mut parsed = String::new();
+            for event in EventReader::new(buf.as_slice()) {
+                if let ReaderEvent::Characters(s) | ReaderEvent::Whitespace(s) = event.unwrap() {
+                    parsed.push_str(&s);
+                }
+            }
+            // chars which are not allowed in xml 1.0 are replaced, so that strict parsers accept it
+            let expected: String = text
+                .chars()
+                .map(|c| {
+                    if is_xml_char(c) {
+                        c
+                    } else {
+                        char::REPLACEMENT_CHARACTER
+                    }
+                })
+                .collect();
+            assert_eq!(parsed, expected);
+        }
+    }
+
+    struct Item {
+        name: Option<String>,
+        size: i64,
+    }
+
+    struct Listing {
+        is_truncated: Option<bool>,
+        items: Option<Vec<Item>>,
+        wrapped: Option<Vec<Item>>,
+        marker: Option<String>,
+    }
+
+    impl_xml_element! {
+        Item {
+            "Name" => name,
+            "Size" => size,
+        }
+    }
+
+    #[test]
+    #[allow(clippy::shadow_unrelated)]
+    fn element_macros() {
+        let write = |listing: Listing| {
+            let mut buf = Vec::new();
+            {
+                let mut w = new_writer(&mut buf);
+                xml_element!(&mut w, "Listing", listing {
+                    "IsTruncated" => is_truncated,
+                    "Item" => items,
+                    "Items" / "Item" => wrapped,
+                    "Marker" => marker,
+                })
+                .unwrap();
+            }
+            let xml = String::from_utf8(buf).unwrap();
+            let declaration = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+            xml.strip_prefix(declaration).unwrap().to_owned()
+        };
+
+        let items = || {
+            vec![
+                Item {
+                    name: Some("a&b".into()),
+                    size: 1,
+                },
+                Item {
+                    name: None,
+                    size: 2,
+                },
+            ]
+        };
+        let listing = Listing {
+            is_truncated: Some(false),
+            items: Some(items()),
+            wrapped: Some(items()),
+            marker: None,
+        };
+        assert_eq!(
+            write(listing),
+            concat!(
+                "<Listing><IsTruncated>false</IsTruncated>",
+                "<Item><Name>a&amp;b</Name><Size>1</Size></Item><Item><Size>2</Size></Item>",
+                "<Items><Item><Name>a&amp;b</Name><Size>1</Size></Item><Item><Size>2</Size></Item></Items>",
+                "</Listing>"
+            )
+        );
+
+        let listing = Listing {
+            is_truncated: None,
+            items: None,
+            wrapped: None,
+            marker: Some("m".into()),
+        };
+        assert_eq!(write(listing), "<Listing><Marker>m</Marker></Listing>");
+    }
+
+    #[test]
+    fn root_namespace() {
+        let mut buf = Vec::new();
+        {
+            let mut w = new_writer(&mut buf);
+            let item = Item {
+                name: Some("a".into()),
+                size: 1,
+            };
+            xml_element!(&mut w, root "Result", item {
+                "Item" => name,
+            })
+            .unwrap();
+        }
+        let xml = String::from_utf8(buf).unwrap();
+        assert!(xml.ends_with(concat!(
+            r#"<Result xmlns="http://s3.amazonaws.com/doc/2006-03-01/">"#,
+            "<Item>a</Item></Result>"
+        )));
+    }
+}
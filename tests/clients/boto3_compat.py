@@ -0,0 +1,70 @@
+"""boto3 compatibility checks, driven by `tests/clients.rs`
+
+Usage: python3 boto3_compat.py <endpoint> <access_key> <secret_key>
+"""
+
+import sys
+import urllib.request
+
+import boto3
+from botocore.config import Config
+
+
+def main(endpoint, access_key, secret_key):
+    s3 = boto3.client(
+        "s3",
+        endpoint_url=endpoint,
+        aws_access_key_id=access_key,
+        aws_secret_access_key=secret_key,
+        region_name="us-east-1",
+        config=Config(signature_version="s3v4", s3={"addressing_style": "path"}),
+    )
+
+    bucket = "asd"
+    s3.create_bucket(Bucket=bucket)
+
+    keys = ["a", "b/c", "d e", "f+g", "h~i", "j=k&l", "中文.txt"]
+    for key in keys:
+        ret = s3.put_object(Bucket=bucket, Key=key, Body=b"Hello World!")
+        assert ret["ETag"] == '"ed076287532e86365e841e92bfc50d8c"', ret["ETag"]
+
+    for key in keys:
+        body = s3.get_object(Bucket=bucket, Key=key)["Body"].read()
+        assert body == b"Hello World!", (key, body)
+
+    listed = [o["Key"] for o in s3.list_objects_v2(Bucket=bucket)["Contents"]]
+    assert listed == sorted(keys), listed
+
+    listed = [o["Key"] for o in s3.list_objects(Bucket=bucket)["Contents"]]
+    assert listed == sorted(keys), listed
+
+    key = "multi part"
+    upload_id = s3.create_multipart_upload(Bucket=bucket, Key=key)["UploadId"]
+    parts = []
+    for number, content in [(1, b"Hello "), (2, b"World!")]:
+        ret = s3.upload_part(
+            Bucket=bucket, Key=key, UploadId=upload_id, PartNumber=number, Body=content
+        )
+        parts.append({"PartNumber": number, "ETag": ret["ETag"]})
+    s3.complete_multipart_upload(
+        Bucket=bucket, Key=key, UploadId=upload_id, MultipartUpload={"Parts": parts}
+    )
+    body = s3.get_object(Bucket=bucket, Key=key)["Body"].read()
+    assert body == b"Hello World!", body
+
+    url = s3.generate_presigned_url(
+        "get_object", Params={"Bucket": bucket, "Key": "d e"}, ExpiresIn=60
+    )
+    with urllib.request.urlopen(url) as res:
+        assert res.read() == b"Hello World!"
+
+    s3.delete_object(Bucket=bucket, Key="a")
+    try:
+        s3.head_object(Bucket=bucket, Key="a")
+        raise AssertionError("HeadObject succeeded on a deleted key")
+    except s3.exceptions.ClientError as err:
+        assert err.response["Error"]["Code"] == "404", err.response
+
+
+if __name__ == "__main__":
+    main(*sys.argv[1:])
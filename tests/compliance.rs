@@ -0,0 +1,23 @@
+use s3_server::storages::fs::{FileSystem, FileSystemConfig};
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+
+#[tokio::test]
+async fn file_system() -> Result<()> {
+    let root = Path::new("target/s3-test-compliance");
+    if root.exists() {
+        fs::remove_dir_all(root)?;
+    }
+    fs::create_dir_all(root)?;
+
+    let mut config = FileSystemConfig::default();
+    config.limits.min_part_size = 0;
+    let storage = FileSystem::new_with_config(root, config)?;
+    let report = s3_server::compliance::run(&storage).await;
+    assert!(report.is_success(), "{}", report);
+
+    Ok(())
+}
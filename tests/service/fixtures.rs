@@ -0,0 +1,298 @@
+//! golden fixtures of request/response exchanges
+//!
+//! Each file `fixtures/<operation>/<case>.http` is a transcript of exchanges with a fresh service.
+//! Request lines start with `>>>` and response lines start with `<<<`.
+//! A message is a start line, header lines, an empty line and the body.
+//! Exchanges are separated by blank lines,
+//! and lines starting with `#` before the first exchange are comments.
+//!
+//! ```text
+//! # creates a bucket and lists it
+//! >>> PUT /asd
+//!
+//! <<< 200 OK
+//! <<< x-amz-request-id: {request-id}
+//!
+//! >>> GET /
+//! <<< 200 OK
+//! <<< content-type: text/xml
+//! <<< x-amz-request-id: {request-id}
+//! <<<
+//! <<< <?xml version="1.0" encoding="UTF-8"?><ListAllMyBucketsResult>...</ListAllMyBucketsResult>
+//! ```
+//!
+//! `x-amz-content-sha256: UNSIGNED-PAYLOAD` is added to requests without it.
+//! All response headers are compared in name order.
+//! Request ids and timestamps are replaced by `{request-id}`, `{timestamp}` and `{http-date}`.
+//!
+//! Run with `S3_UPDATE_FIXTURES=1` to rewrite the responses of all fixtures from the actual ones.
+
+use super::setup_tracing;
+use super::utils::{recv_body_string, request_id, Request, Response};
+
+use s3_server::headers::X_AMZ_CONTENT_SHA256;
+use s3_server::storages::fs::{FileSystem, FileSystemConfig};
+use s3_server::{AnonymousPolicy, Body, S3Service};
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::Method;
+use regex::Regex;
+
+/// the prefix of request lines
+const REQUEST: &str = ">>>";
+/// the prefix of response lines
+const RESPONSE: &str = "<<<";
+
+/// a parsed fixture file
+struct Fixture {
+    /// leading comment lines
+    comments: Vec<String>,
+    /// exchanges in order
+    exchanges: Vec<Exchange>,
+}
+
+/// a request and its expected response, without line prefixes
+struct Exchange {
+    request: Vec<String>,
+    response: Vec<String>,
+}
+
+fn parse_fixture(text: &str) -> Result<Fixture> {
+    let mut comments = Vec::new();
+    let mut exchanges: Vec<Exchange> = Vec::new();
+
+    // whether the next request line starts a new exchange
+    let mut separated = true;
+
+    for (i, line) in text.lines().enumerate() {
+        let strip = |prefix: &str| {
+            line.strip_prefix(prefix)
+                .map(|rest| rest.strip_prefix(' ').unwrap_or(rest).to_owned())
+        };
+
+        if let Some(rest) = strip(REQUEST) {
+            match exchanges.last_mut() {
+                Some(last) if !separated && last.response.is_empty() => last.request.push(rest),
+                _ => exchanges.push(Exchange {
+                    request: vec![rest],
+                    response: Vec::new(),
+                }),
+            }
+            separated = false;
+        } else if let Some(rest) = strip(RESPONSE) {
+            match exchanges.last_mut() {
+                Some(last) if !separated => last.response.push(rest),
+                _ => bail!("line {}: response without a request", i + 1),
+            }
+        } else if line.trim().is_empty() {
+            separated = true;
+        } else if line.starts_with('#') && exchanges.is_empty() {
+            comments.push(line.to_owned());
+        } else {
+            bail!("line {}: unexpected line: {:?}", i + 1, line);
+        }
+    }
+
+    Ok(Fixture {
+        comments,
+        exchanges,
+    })
+}
+
+fn render_fixture(fixture: &Fixture) -> String {
+    let mut text = String::new();
+    for line in &fixture.comments {
+        text.push_str(line);
+        text.push('\n');
+    }
+    for exchange in &fixture.exchanges {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        for (prefix, lines) in [(REQUEST, &exchange.request), (RESPONSE, &exchange.response)] {
+            for line in lines {
+                if line.is_empty() {
+                    writeln!(text, "{}", prefix).unwrap();
+                } else {
+                    writeln!(text, "{} {}", prefix, line).unwrap();
+                }
+            }
+        }
+    }
+    text
+}
+
+/// splits a message into its start line, header lines and body
+fn split_message(lines: &[String]) -> Result<(&str, &[String], String)> {
+    let (start, rest) = lines
+        .split_first()
+        .ok_or_else(|| anyhow!("empty message"))?;
+    let (headers, body) = match rest.iter().position(String::is_empty) {
+        Some(pos) => (&rest[..pos], rest[pos + 1..].join("\n")),
+        None => (rest, String::new()),
+    };
+    Ok((start, headers, body))
+}
+
+fn build_request(lines: &[String]) -> Result<Request> {
+    let (start, headers, body) = split_message(lines)?;
+    let (method, path) = start
+        .split_once(' ')
+        .ok_or_else(|| anyhow!("invalid request line: {:?}", start))?;
+
+    let mut req = Request::new(Body::from(body));
+    *req.method_mut() = method.parse::<Method>()?;
+    *req.uri_mut() = format!("http://localhost{}", path).parse()?;
+    for line in headers {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("invalid header line: {:?}", line))?;
+        let name = name.trim().parse::<HeaderName>()?;
+        let value = HeaderValue::from_str(value.trim())?;
+        let _ = req.headers_mut().append(name, value);
+    }
+    let _ = req
+        .headers_mut()
+        .entry(X_AMZ_CONTENT_SHA256)
+        .or_insert(HeaderValue::from_static("UNSIGNED-PAYLOAD"));
+    Ok(req)
+}
+
+/// replaces the values which differ between runs
+fn normalize(s: &str, request_id: &str) -> String {
+    let timestamp =
+        Regex::new(r"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:\d{2})").unwrap();
+    let http_date =
+        Regex::new(r"[A-Z][a-z]{2}, \d{2} [A-Z][a-z]{2} \d{4} \d{2}:\d{2}:\d{2} GMT").unwrap();
+
+    let s = if request_id.is_empty() {
+        s.to_owned()
+    } else {
+        s.replace(request_id, "{request-id}")
+    };
+    let s = timestamp.replace_all(&s, "{timestamp}");
+    http_date.replace_all(&s, "{http-date}").into_owned()
+}
+
+async fn render_response(mut res: Response) -> Result<Vec<String>> {
+    let request_id = request_id(&res).to_owned();
+    let body = recv_body_string(&mut res).await?;
+
+    let status = res.status();
+    let mut lines = vec![format!(
+        "{} {}",
+        status.as_str(),
+        status.canonical_reason().unwrap_or_default()
+    )];
+
+    let mut headers: Vec<(&str, &str)> = res
+        .headers()
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.to_str().unwrap_or("<binary>")))
+        .collect();
+    headers.sort_unstable();
+    for (name, value) in headers {
+        lines.push(normalize(&format!("{}: {}", name, value), &request_id));
+    }
+
+    if !body.is_empty() {
+        lines.push(String::new());
+        lines.extend(normalize(&body, &request_id).lines().map(ToOwned::to_owned));
+    }
+    Ok(lines)
+}
+
+fn setup_service(root: &Path) -> Result<S3Service> {
+    if root.exists() {
+        fs::remove_dir_all(root)?;
+    }
+    fs::create_dir_all(root)?;
+
+    let fs = FileSystem::new_with_config(root, FileSystemConfig::default())?;
+    let mut service = S3Service::new(fs);
+    service.set_anonymous_policy(AnonymousPolicy::AllowAll);
+    Ok(service)
+}
+
+/// runs a fixture, returns a description of each mismatched response
+async fn run_fixture(fixture: &mut Fixture, root: &Path, update: bool) -> Result<Vec<String>> {
+    let service = setup_service(root)?;
+
+    let mut mismatches = Vec::new();
+    for (i, exchange) in fixture.exchanges.iter_mut().enumerate() {
+        let req = build_request(&exchange.request)?;
+        let res = service.hyper_call(req).await.map_err(|e| anyhow!(e))?;
+        let actual = render_response(res).await?;
+
+        if actual != exchange.response {
+            mismatches.push(format!(
+                "exchange {} ({}):\n--- expected\n{}\n--- actual\n{}",
+                i + 1,
+                exchange.request[0],
+                exchange.response.join("\n"),
+                actual.join("\n"),
+            ));
+            if update {
+                exchange.response = actual;
+            }
+        }
+    }
+    Ok(mismatches)
+}
+
+fn fixture_paths(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            paths.extend(fixture_paths(&path)?);
+        } else if path.extension().map_or(false, |ext| ext == "http") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+#[tokio::test]
+async fn golden_fixtures() {
+    setup_tracing();
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/service/fixtures");
+    let root = PathBuf::from("target/s3-fixtures");
+    let update = env::var("S3_UPDATE_FIXTURES").map_or(false, |v| v == "1");
+
+    let mut failures = Vec::new();
+    for path in fixture_paths(&dir).unwrap() {
+        let name = path.strip_prefix(&dir).unwrap().with_extension("");
+        let text = fs::read_to_string(&path).unwrap();
+        let mut fixture = parse_fixture(&text)
+            .with_context(|| format!("invalid fixture {}", name.display()))
+            .unwrap();
+
+        let mismatches = run_fixture(&mut fixture, &root.join(&name), update)
+            .await
+            .with_context(|| format!("failed to run fixture {}", name.display()))
+            .unwrap();
+
+        if update {
+            fs::write(&path, render_fixture(&fixture)).unwrap();
+        } else {
+            for mismatch in mismatches {
+                failures.push(format!("{}: {}", name.display(), mismatch));
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "golden fixtures mismatched, run with S3_UPDATE_FIXTURES=1 to update them\n\n{}",
+        failures.join("\n\n")
+    );
+}
@@ -5,12 +5,13 @@ use std::io;
 use std::mem;
 use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use hyper::header;
 use mime::Mime;
+use s3_server::Body;
 
-pub type Request = hyper::Request<hyper::Body>;
-pub type Response = hyper::Response<hyper::Body>;
+pub type Request = hyper::Request<Body>;
+pub type Response = hyper::Response<Body>;
 
 pub trait ResultExt<T, E> {
     /// See <https://github.com/rust-lang/rust/issues/91345>
@@ -33,6 +34,13 @@ pub fn parse_mime(res: &Response) -> Result<Mime> {
     }
 }
 
+pub fn request_id(res: &Response) -> &str {
+    res.headers()
+        .get("x-amz-request-id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
+
 pub fn generate_path(root: impl AsRef<Path>, path: S3Path) -> PathBuf {
     match path {
         S3Path::Root => root.as_ref().to_owned(),
@@ -43,7 +51,7 @@ pub fn generate_path(root: impl AsRef<Path>, path: S3Path) -> PathBuf {
 
 pub async fn recv_body_string(res: &mut Response) -> Result<String> {
     let body = mem::take(res.body_mut());
-    let bytes = hyper::body::to_bytes(body).await?;
+    let bytes = body.into_bytes().await.map_err(|e| anyhow!(e))?;
     let ans = String::from_utf8(bytes.to_vec())?;
     Ok(ans)
 }